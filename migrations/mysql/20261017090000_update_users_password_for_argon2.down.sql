@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE `users` MODIFY `password` VARCHAR(191) NOT NULL;
+
+ALTER TABLE `users` ADD KEY `idx_users_password` (`password`);
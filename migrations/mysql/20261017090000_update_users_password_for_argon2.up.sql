@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE `users` DROP INDEX `idx_users_password`;
+
+ALTER TABLE `users` MODIFY `password` VARCHAR(255) NOT NULL;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `refresh_tokens` (
+        `id` varchar(36) NOT NULL,
+        `family_id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `token_hash` varchar(128) NOT NULL,
+        `expired_at` datetime(3) NOT NULL,
+        `used_at` datetime(3) DEFAULT NULL,
+        `revoked_at` datetime(3) DEFAULT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_refresh_tokens_token_hash` (`token_hash`),
+        KEY `idx_refresh_tokens_family_id` (`family_id`),
+        KEY `idx_refresh_tokens_expired_at` (`expired_at`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `refresh_tokens`
+ADD
+    CONSTRAINT `fk_refresh_tokens_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+ALTER TABLE
+    `revoked_user_tokens` DROP FOREIGN KEY `fk_revoked_user_tokens_user_id`;
+
+DROP TABLE IF EXISTS `revoked_user_tokens`;
+
+DROP TABLE IF EXISTS `revoked_tokens`;
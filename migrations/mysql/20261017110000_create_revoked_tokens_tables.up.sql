@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `revoked_tokens` (
+        `jti` varchar(36) NOT NULL,
+        `expired_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`jti`),
+        KEY `idx_revoked_tokens_expired_at` (`expired_at`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE
+    IF NOT EXISTS `revoked_user_tokens` (
+        `user_id` varchar(36) NOT NULL,
+        `revoked_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`user_id`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `revoked_user_tokens`
+ADD
+    CONSTRAINT `fk_revoked_user_tokens_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `email_outbox` (
+        `id` varchar(36) NOT NULL,
+        `from_email` varchar(255) NOT NULL,
+        `to_list` text NOT NULL,
+        `subject` varchar(255) NOT NULL,
+        `text_body` mediumtext NOT NULL,
+        `html_body` mediumtext NOT NULL,
+        `status` varchar(16) NOT NULL,
+        `attempts` int unsigned NOT NULL DEFAULT 0,
+        `last_error` text DEFAULT NULL,
+        `next_attempt_at` datetime(3) NOT NULL,
+        `sent_at` datetime(3) DEFAULT NULL,
+        `created_at` datetime(3) NOT NULL,
+        `updated_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        KEY `idx_email_outbox_status_next_attempt_at` (`status`, `next_attempt_at`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE `users` DROP INDEX `idx_users_unlock_token`, DROP COLUMN `unlock_token`, DROP COLUMN `locked_until`;
+
+DROP TABLE IF EXISTS `login_attempts`;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `login_attempts` (
+        `id` varchar(36) NOT NULL,
+        `username` varchar(255) NOT NULL,
+        `ip` varchar(45) NOT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        KEY `idx_login_attempts_username_created_at` (`username`, `created_at`),
+        KEY `idx_login_attempts_ip_created_at` (`ip`, `created_at`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `users`
+ADD
+    COLUMN `locked_until` datetime(3) DEFAULT NULL,
+ADD
+    COLUMN `unlock_token` varchar(36) DEFAULT NULL,
+ADD
+    UNIQUE KEY `idx_users_unlock_token` (`unlock_token`);
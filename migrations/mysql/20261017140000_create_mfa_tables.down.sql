@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS `mfa_challenges`;
+
+DROP TABLE IF EXISTS `user_recovery_codes`;
+
+ALTER TABLE `users` DROP COLUMN `totp_last_used_step`, DROP COLUMN `totp_enabled_at`, DROP COLUMN `totp_secret`;
@@ -0,0 +1,41 @@
+-- Add up migration script here
+
+ALTER TABLE `users`
+ADD
+    COLUMN `totp_secret` varchar(64) DEFAULT NULL,
+ADD
+    COLUMN `totp_enabled_at` datetime(3) DEFAULT NULL,
+ADD
+    COLUMN `totp_last_used_step` bigint DEFAULT NULL;
+
+CREATE TABLE
+    IF NOT EXISTS `user_recovery_codes` (
+        `id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `code_hash` varchar(128) NOT NULL,
+        `used_at` datetime(3) DEFAULT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        KEY `idx_user_recovery_codes_user_id_code_hash` (`user_id`, `code_hash`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `user_recovery_codes`
+ADD
+    CONSTRAINT `fk_user_recovery_codes_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
+
+CREATE TABLE
+    IF NOT EXISTS `mfa_challenges` (
+        `id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `token_hash` varchar(128) NOT NULL,
+        `attempts` int unsigned NOT NULL DEFAULT 0,
+        `expired_at` datetime(3) NOT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_mfa_challenges_token_hash` (`token_hash`),
+        KEY `idx_mfa_challenges_expired_at` (`expired_at`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `mfa_challenges`
+ADD
+    CONSTRAINT `fk_mfa_challenges_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
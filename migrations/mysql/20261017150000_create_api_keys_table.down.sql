@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+ALTER TABLE
+    `api_keys` DROP FOREIGN KEY `fk_api_keys_user_id`;
+
+DROP TABLE IF EXISTS `api_keys`;
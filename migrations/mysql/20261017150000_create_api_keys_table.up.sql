@@ -0,0 +1,24 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `api_keys` (
+        `id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `name` varchar(100) NOT NULL,
+        `prefix` varchar(16) NOT NULL,
+        `key_hash` varchar(128) NOT NULL,
+        `roles` varchar(255) NOT NULL,
+        `routes` varchar(1000) DEFAULT NULL,
+        `rate_limit` int NOT NULL,
+        `expired_at` datetime(3) DEFAULT NULL,
+        `last_used_at` datetime(3) DEFAULT NULL,
+        `created_at` datetime(3) NOT NULL,
+        `updated_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_api_keys_key_hash` (`key_hash`),
+        KEY `idx_api_keys_user_id` (`user_id`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `api_keys`
+ADD
+    CONSTRAINT `fk_api_keys_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS `user_identities`;
+
+DROP TABLE IF EXISTS `oidc_authorizations`;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `oidc_authorizations` (
+        `id` varchar(36) NOT NULL,
+        `state_hash` varchar(128) NOT NULL,
+        `nonce` varchar(64) NOT NULL,
+        `code_verifier` varchar(128) NOT NULL,
+        `expired_at` datetime(3) NOT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_oidc_authorizations_state_hash` (`state_hash`),
+        KEY `idx_oidc_authorizations_expired_at` (`expired_at`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE
+    IF NOT EXISTS `user_identities` (
+        `id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `issuer` varchar(255) NOT NULL,
+        `subject` varchar(255) NOT NULL,
+        `email` varchar(255) NOT NULL,
+        `last_login_at` datetime(3) DEFAULT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_user_identities_issuer_subject` (`issuer`, `subject`),
+        UNIQUE KEY `idx_user_identities_user_id_issuer` (`user_id`, `issuer`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `user_identities`
+ADD
+    CONSTRAINT `fk_user_identities_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
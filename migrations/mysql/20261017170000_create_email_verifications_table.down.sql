@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS `email_verifications`;
+
+ALTER TABLE `users` DROP COLUMN `email_verified_at`;
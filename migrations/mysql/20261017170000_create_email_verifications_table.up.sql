@@ -0,0 +1,24 @@
+-- Add up migration script here
+
+-- Existing users (and users created by an administrator) are considered verified
+ALTER TABLE `users`
+ADD
+    COLUMN `email_verified_at` datetime(3) NULL DEFAULT CURRENT_TIMESTAMP(3);
+
+UPDATE `users` SET `email_verified_at` = `created_at`;
+
+CREATE TABLE
+    IF NOT EXISTS `email_verifications` (
+        `id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `token_hash` varchar(128) NOT NULL,
+        `expired_at` datetime(3) NOT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_email_verifications_token_hash` (`token_hash`),
+        KEY `idx_email_verifications_user_id` (`user_id`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `email_verifications`
+ADD
+    CONSTRAINT `fk_email_verifications_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
@@ -0,0 +1,18 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `email_changes` (
+        `id` varchar(36) NOT NULL,
+        `user_id` varchar(36) NOT NULL,
+        `new_email` varchar(127) NOT NULL,
+        `token_hash` varchar(128) NOT NULL,
+        `expired_at` datetime(3) NOT NULL,
+        `created_at` datetime(3) NOT NULL,
+        PRIMARY KEY (`id`),
+        UNIQUE KEY `idx_email_changes_token_hash` (`token_hash`),
+        KEY `idx_email_changes_user_id` (`user_id`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `email_changes`
+ADD
+    CONSTRAINT `fk_email_changes_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+DELETE FROM `password_resets`;
+
+ALTER TABLE `password_resets`
+    DROP INDEX `idx_password_resets_token_hash`,
+    DROP COLUMN `created_at`,
+    CHANGE COLUMN `token_hash` `token` varchar(36) NOT NULL;
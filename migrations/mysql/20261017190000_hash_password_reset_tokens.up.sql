@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+-- Pending resets were stored in clear and are invalidated
+DELETE FROM `password_resets`;
+
+ALTER TABLE `password_resets`
+    CHANGE COLUMN `token` `token_hash` varchar(128) NOT NULL,
+ADD
+    COLUMN `created_at` datetime(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+ADD
+    UNIQUE KEY `idx_password_resets_token_hash` (`token_hash`);
@@ -0,0 +1,17 @@
+-- Add down migration script here
+
+ALTER TABLE `users`
+ADD
+    COLUMN `roles` VARCHAR(63) AFTER `firstname`,
+ADD
+    KEY `idx_users_roles` (`roles`);
+
+UPDATE `users` u
+SET u.`roles` = (
+    SELECT GROUP_CONCAT(ur.`role` SEPARATOR ',')
+    FROM `user_roles` ur
+    WHERE ur.`user_id` = u.`id`
+);
+
+DROP TABLE IF EXISTS `user_roles`;
+DROP TABLE IF EXISTS `roles`;
@@ -0,0 +1,31 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS `roles` (
+        `name` varchar(63) NOT NULL,
+        PRIMARY KEY (`name`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+INSERT INTO `roles` (`name`) VALUES ('USER'), ('MANAGER'), ('ADMIN');
+
+CREATE TABLE
+    IF NOT EXISTS `user_roles` (
+        `user_id` varchar(36) NOT NULL,
+        `role` varchar(63) NOT NULL,
+        PRIMARY KEY (`user_id`, `role`),
+        KEY `idx_user_roles_role` (`role`)
+    ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+ALTER TABLE `user_roles`
+ADD
+    CONSTRAINT `fk_user_roles_user_id` FOREIGN KEY (`user_id`) REFERENCES `users`(`id`),
+ADD
+    CONSTRAINT `fk_user_roles_role` FOREIGN KEY (`role`) REFERENCES `roles`(`name`);
+
+-- Roles of the comma-separated `users.roles` column (unknown roles are dropped)
+INSERT INTO `user_roles` (`user_id`, `role`)
+SELECT u.`id`, r.`name`
+FROM `users` u
+    INNER JOIN `roles` r ON FIND_IN_SET(r.`name`, REPLACE(u.`roles`, ' ', '')) > 0;
+
+ALTER TABLE `users` DROP INDEX `idx_users_roles`, DROP COLUMN `roles`;
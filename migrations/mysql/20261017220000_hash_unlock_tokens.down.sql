@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+UPDATE `users` SET `unlock_token_hash` = NULL;
+
+ALTER TABLE `users`
+    DROP INDEX `idx_users_unlock_token_hash`,
+    CHANGE COLUMN `unlock_token_hash` `unlock_token` varchar(36) DEFAULT NULL,
+ADD
+    UNIQUE KEY `idx_users_unlock_token` (`unlock_token`);
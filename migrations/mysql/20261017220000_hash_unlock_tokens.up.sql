@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+-- Pending unlock tokens were stored in clear and are invalidated (lockouts end at `locked_until`)
+ALTER TABLE `users`
+    DROP INDEX `idx_users_unlock_token`,
+    CHANGE COLUMN `unlock_token` `unlock_token_hash` varchar(128) DEFAULT NULL,
+ADD
+    UNIQUE KEY `idx_users_unlock_token_hash` (`unlock_token_hash`);
+
+UPDATE `users` SET `unlock_token_hash` = NULL;
@@ -0,0 +1,16 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS email_changes;
+DROP TABLE IF EXISTS email_verifications;
+DROP TABLE IF EXISTS user_identities;
+DROP TABLE IF EXISTS oidc_authorizations;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS mfa_challenges;
+DROP TABLE IF EXISTS user_recovery_codes;
+DROP TABLE IF EXISTS login_attempts;
+DROP TABLE IF EXISTS email_outbox;
+DROP TABLE IF EXISTS revoked_user_tokens;
+DROP TABLE IF EXISTS revoked_tokens;
+DROP TABLE IF EXISTS refresh_tokens;
+DROP TABLE IF EXISTS password_resets;
+DROP TABLE IF EXISTS users;
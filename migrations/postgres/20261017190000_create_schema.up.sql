@@ -0,0 +1,203 @@
+-- Add up migration script here
+
+-- Schema of the MySQL migrations up to 20261017190000_hash_password_reset_tokens
+
+CREATE TABLE IF NOT EXISTS users (
+    id VARCHAR(36) NOT NULL,
+    username VARCHAR(127) NOT NULL,
+    password VARCHAR(255) NOT NULL,
+    lastname VARCHAR(63) NOT NULL,
+    firstname VARCHAR(63) NOT NULL,
+    roles VARCHAR(63) DEFAULT NULL,
+    rate_limit INTEGER NOT NULL DEFAULT 30,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    deleted_at TIMESTAMPTZ DEFAULT NULL,
+    locked_until TIMESTAMPTZ DEFAULT NULL,
+    unlock_token VARCHAR(36) DEFAULT NULL,
+    totp_secret VARCHAR(64) DEFAULT NULL,
+    totp_enabled_at TIMESTAMPTZ DEFAULT NULL,
+    totp_last_used_step BIGINT DEFAULT NULL,
+    email_verified_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_users_username UNIQUE (username),
+    CONSTRAINT idx_users_unlock_token UNIQUE (unlock_token)
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_roles ON users (roles);
+CREATE INDEX IF NOT EXISTS idx_users_deleted_at ON users (deleted_at);
+
+CREATE TABLE IF NOT EXISTS password_resets (
+    user_id VARCHAR(36) NOT NULL,
+    token_hash VARCHAR(128) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id),
+    CONSTRAINT idx_password_resets_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_password_resets_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_resets_expired_at ON password_resets (expired_at);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id VARCHAR(36) NOT NULL,
+    family_id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    token_hash VARCHAR(128) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ DEFAULT NULL,
+    revoked_at TIMESTAMPTZ DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_refresh_tokens_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_refresh_tokens_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_expired_at ON refresh_tokens (expired_at);
+
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti VARCHAR(36) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (jti)
+);
+
+CREATE INDEX IF NOT EXISTS idx_revoked_tokens_expired_at ON revoked_tokens (expired_at);
+
+CREATE TABLE IF NOT EXISTS revoked_user_tokens (
+    user_id VARCHAR(36) NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (user_id),
+    CONSTRAINT fk_revoked_user_tokens_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+-- Unsigned integers are stored in BIGINT columns
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id VARCHAR(36) NOT NULL,
+    from_email VARCHAR(255) NOT NULL,
+    to_list TEXT NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    text_body TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT DEFAULT NULL,
+    next_attempt_at TIMESTAMPTZ NOT NULL,
+    sent_at TIMESTAMPTZ DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_status_next_attempt_at ON email_outbox (status, next_attempt_at);
+
+CREATE TABLE IF NOT EXISTS login_attempts (
+    id VARCHAR(36) NOT NULL,
+    username VARCHAR(255) NOT NULL,
+    ip VARCHAR(45) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_username_created_at ON login_attempts (username, created_at);
+CREATE INDEX IF NOT EXISTS idx_login_attempts_ip_created_at ON login_attempts (ip, created_at);
+
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    code_hash VARCHAR(128) NOT NULL,
+    used_at TIMESTAMPTZ DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT fk_user_recovery_codes_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_recovery_codes_user_id_code_hash ON user_recovery_codes (user_id, code_hash);
+
+CREATE TABLE IF NOT EXISTS mfa_challenges (
+    id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    token_hash VARCHAR(128) NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_mfa_challenges_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_mfa_challenges_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mfa_challenges_expired_at ON mfa_challenges (expired_at);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    prefix VARCHAR(16) NOT NULL,
+    key_hash VARCHAR(128) NOT NULL,
+    roles VARCHAR(255) NOT NULL,
+    routes VARCHAR(1000) DEFAULT NULL,
+    rate_limit INTEGER NOT NULL,
+    expired_at TIMESTAMPTZ DEFAULT NULL,
+    last_used_at TIMESTAMPTZ DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_api_keys_key_hash UNIQUE (key_hash),
+    CONSTRAINT fk_api_keys_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_user_id ON api_keys (user_id);
+
+CREATE TABLE IF NOT EXISTS oidc_authorizations (
+    id VARCHAR(36) NOT NULL,
+    state_hash VARCHAR(128) NOT NULL,
+    nonce VARCHAR(64) NOT NULL,
+    code_verifier VARCHAR(128) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_oidc_authorizations_state_hash UNIQUE (state_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_oidc_authorizations_expired_at ON oidc_authorizations (expired_at);
+
+CREATE TABLE IF NOT EXISTS user_identities (
+    id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    last_login_at TIMESTAMPTZ DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_user_identities_issuer_subject UNIQUE (issuer, subject),
+    CONSTRAINT idx_user_identities_user_id_issuer UNIQUE (user_id, issuer),
+    CONSTRAINT fk_user_identities_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE TABLE IF NOT EXISTS email_verifications (
+    id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    token_hash VARCHAR(128) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_email_verifications_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_email_verifications_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_verifications_user_id ON email_verifications (user_id);
+
+CREATE TABLE IF NOT EXISTS email_changes (
+    id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    new_email VARCHAR(127) NOT NULL,
+    token_hash VARCHAR(128) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_email_changes_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_email_changes_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_changes_user_id ON email_changes (user_id);
@@ -0,0 +1,27 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS roles (
+    name VARCHAR(63) NOT NULL,
+    PRIMARY KEY (name)
+);
+
+INSERT INTO roles (name) VALUES ('USER'), ('MANAGER'), ('ADMIN');
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id VARCHAR(36) NOT NULL,
+    role VARCHAR(63) NOT NULL,
+    PRIMARY KEY (user_id, role),
+    CONSTRAINT fk_user_roles_user_id FOREIGN KEY (user_id) REFERENCES users (id),
+    CONSTRAINT fk_user_roles_role FOREIGN KEY (role) REFERENCES roles (name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_roles_role ON user_roles (role);
+
+-- Roles of the comma-separated `users.roles` column (unknown roles are dropped)
+INSERT INTO user_roles (user_id, role)
+SELECT u.id, r.name
+FROM users u
+    INNER JOIN roles r ON r.name = ANY(string_to_array(replace(u.roles, ' ', ''), ','));
+
+DROP INDEX IF EXISTS idx_users_roles;
+ALTER TABLE users DROP COLUMN roles;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+UPDATE users SET unlock_token_hash = NULL;
+
+ALTER TABLE users RENAME CONSTRAINT idx_users_unlock_token_hash TO idx_users_unlock_token;
+ALTER TABLE users ALTER COLUMN unlock_token_hash TYPE VARCHAR(36);
+ALTER TABLE users RENAME COLUMN unlock_token_hash TO unlock_token;
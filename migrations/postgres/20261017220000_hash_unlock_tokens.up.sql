@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- Pending unlock tokens were stored in clear and are invalidated (lockouts end at `locked_until`)
+UPDATE users SET unlock_token = NULL;
+
+ALTER TABLE users RENAME COLUMN unlock_token TO unlock_token_hash;
+ALTER TABLE users ALTER COLUMN unlock_token_hash TYPE VARCHAR(128);
+ALTER TABLE users RENAME CONSTRAINT idx_users_unlock_token TO idx_users_unlock_token_hash;
@@ -0,0 +1,204 @@
+-- Add up migration script here
+
+-- Schema of the MySQL migrations up to 20261017190000_hash_password_reset_tokens
+-- (dates are stored as RFC 3339 strings, usernames are case insensitive as with MySQL)
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT NOT NULL,
+    username TEXT NOT NULL COLLATE NOCASE,
+    password TEXT NOT NULL,
+    lastname TEXT NOT NULL,
+    firstname TEXT NOT NULL,
+    roles TEXT DEFAULT NULL,
+    rate_limit INTEGER NOT NULL DEFAULT 30,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    deleted_at DATETIME DEFAULT NULL,
+    locked_until DATETIME DEFAULT NULL,
+    unlock_token TEXT DEFAULT NULL,
+    totp_secret TEXT DEFAULT NULL,
+    totp_enabled_at DATETIME DEFAULT NULL,
+    totp_last_used_step INTEGER DEFAULT NULL,
+    email_verified_at DATETIME DEFAULT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_users_username UNIQUE (username),
+    CONSTRAINT idx_users_unlock_token UNIQUE (unlock_token)
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_roles ON users (roles);
+CREATE INDEX IF NOT EXISTS idx_users_deleted_at ON users (deleted_at);
+
+CREATE TABLE IF NOT EXISTS password_resets (
+    user_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    expired_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (user_id),
+    CONSTRAINT idx_password_resets_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_password_resets_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_resets_expired_at ON password_resets (expired_at);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id TEXT NOT NULL,
+    family_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    expired_at DATETIME NOT NULL,
+    used_at DATETIME DEFAULT NULL,
+    revoked_at DATETIME DEFAULT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_refresh_tokens_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_refresh_tokens_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_expired_at ON refresh_tokens (expired_at);
+
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti TEXT NOT NULL,
+    expired_at DATETIME NOT NULL,
+    PRIMARY KEY (jti)
+);
+
+CREATE INDEX IF NOT EXISTS idx_revoked_tokens_expired_at ON revoked_tokens (expired_at);
+
+CREATE TABLE IF NOT EXISTS revoked_user_tokens (
+    user_id TEXT NOT NULL,
+    revoked_at DATETIME NOT NULL,
+    PRIMARY KEY (user_id),
+    CONSTRAINT fk_revoked_user_tokens_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+-- Unsigned integers are stored in INTEGER columns
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id TEXT NOT NULL,
+    from_email TEXT NOT NULL,
+    to_list TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT DEFAULT NULL,
+    next_attempt_at DATETIME NOT NULL,
+    sent_at DATETIME DEFAULT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_status_next_attempt_at ON email_outbox (status, next_attempt_at);
+
+CREATE TABLE IF NOT EXISTS login_attempts (
+    id TEXT NOT NULL,
+    username TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_username_created_at ON login_attempts (username, created_at);
+CREATE INDEX IF NOT EXISTS idx_login_attempts_ip_created_at ON login_attempts (ip, created_at);
+
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    used_at DATETIME DEFAULT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT fk_user_recovery_codes_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_recovery_codes_user_id_code_hash ON user_recovery_codes (user_id, code_hash);
+
+CREATE TABLE IF NOT EXISTS mfa_challenges (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    expired_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_mfa_challenges_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_mfa_challenges_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mfa_challenges_expired_at ON mfa_challenges (expired_at);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    roles TEXT NOT NULL,
+    routes TEXT DEFAULT NULL,
+    rate_limit INTEGER NOT NULL,
+    expired_at DATETIME DEFAULT NULL,
+    last_used_at DATETIME DEFAULT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_api_keys_key_hash UNIQUE (key_hash),
+    CONSTRAINT fk_api_keys_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_user_id ON api_keys (user_id);
+
+CREATE TABLE IF NOT EXISTS oidc_authorizations (
+    id TEXT NOT NULL,
+    state_hash TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    expired_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_oidc_authorizations_state_hash UNIQUE (state_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_oidc_authorizations_expired_at ON oidc_authorizations (expired_at);
+
+CREATE TABLE IF NOT EXISTS user_identities (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT NOT NULL,
+    last_login_at DATETIME DEFAULT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_user_identities_issuer_subject UNIQUE (issuer, subject),
+    CONSTRAINT idx_user_identities_user_id_issuer UNIQUE (user_id, issuer),
+    CONSTRAINT fk_user_identities_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE TABLE IF NOT EXISTS email_verifications (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    expired_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_email_verifications_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_email_verifications_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_verifications_user_id ON email_verifications (user_id);
+
+CREATE TABLE IF NOT EXISTS email_changes (
+    id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    new_email TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    expired_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (id),
+    CONSTRAINT idx_email_changes_token_hash UNIQUE (token_hash),
+    CONSTRAINT fk_email_changes_user_id FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_changes_user_id ON email_changes (user_id);
@@ -0,0 +1,15 @@
+-- Add down migration script here
+
+ALTER TABLE users ADD COLUMN roles TEXT DEFAULT NULL;
+
+UPDATE users
+SET roles = (
+    SELECT group_concat(user_roles.role, ',')
+    FROM user_roles
+    WHERE user_roles.user_id = users.id
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_roles ON users (roles);
+
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS roles;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission),
+    CONSTRAINT fk_role_permissions_role FOREIGN KEY (role) REFERENCES roles (name)
+);
+
+INSERT INTO role_permissions (role, permission)
+VALUES
+    ('MANAGER', 'users:read'),
+    ('ADMIN', 'users:read'),
+    ('ADMIN', 'users:write'),
+    ('ADMIN', 'users:delete'),
+    ('ADMIN', 'roles:manage'),
+    ('ADMIN', 'api-keys:manage');
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+UPDATE users SET unlock_token_hash = NULL;
+
+ALTER TABLE users RENAME COLUMN unlock_token_hash TO unlock_token;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- Pending unlock tokens were stored in clear and are invalidated (lockouts end at `locked_until`)
+UPDATE users SET unlock_token = NULL;
+
+ALTER TABLE users RENAME COLUMN unlock_token TO unlock_token_hash;
@@ -2,9 +2,13 @@
 
 use super::databases;
 use crate::config::Config;
-use crate::models::user::{PasswordScorer, PasswordStrength, User, UserCreation};
-use crate::repositories::user::UserRepository;
+use crate::models::email_outbox::EmailOutboxStatus;
+use crate::models::user::{PasswordScorer, PasswordStrength, Role, User, UserCreation};
+use crate::repositories::email_outbox::EmailOutboxRepository;
+use crate::repositories::login_attempt::LoginAttemptRepository;
+use crate::repositories::user::UserStore;
 use crate::utils::errors::{CliError, CliResult};
+use crate::utils::password::PasswordHasher;
 use clap::{Parser, Subcommand};
 use std::io::{self, Write};
 
@@ -73,6 +77,49 @@ enum Commands {
         )]
         password: String,
     },
+
+    /// Unlock user
+    #[clap(about = "Unlock a user locked after too many failed logins", long_about = None)]
+    Unlock {
+        /// User username (email)
+        #[clap(
+            required = true,
+            short = 'u',
+            long,
+            value_name = "Email",
+            num_args = 1,
+            help = "Username (email)"
+        )]
+        username: String,
+    },
+
+    /// Email outbox
+    #[clap(about = "Inspect and replay emails in the dead letter state", long_about = None)]
+    EmailOutbox {
+        #[clap(subcommand)]
+        command: EmailOutboxCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum EmailOutboxCommands {
+    /// List dead emails
+    #[clap(about = "List emails whose maximum number of attempts has been reached", long_about = None)]
+    List,
+
+    /// Replay dead emails
+    #[clap(about = "Send again all the dead emails or only one", long_about = None)]
+    Replay {
+        /// Email ID
+        #[clap(
+            short = 'i',
+            long,
+            value_name = "ID",
+            num_args = 1,
+            help = "Email ID (all dead emails if absent)"
+        )]
+        id: Option<String>,
+    },
 }
 
 /// Start CLI
@@ -88,6 +135,8 @@ pub async fn start() -> CliResult<()> {
             username,
             password,
         } => register(lastname, firstname, username, password).await,
+        Commands::Unlock { username } => unlock(username).await,
+        Commands::EmailOutbox { command } => email_outbox(command).await,
     }
 }
 
@@ -97,9 +146,9 @@ async fn register(lastname: &str, firstname: &str, username: &str, password: &st
     // ------------------
     let config = Config::from_env().map_err(|err| CliError::ConfigError(err.to_string()))?;
 
-    // MySQL pool creation
-    // -------------------
-    let pool = databases::init_mysql(&config).await?;
+    // Database pool creation
+    // ----------------------
+    let pool = databases::init_database(&config).await?;
 
     let lastname = lastname.trim();
     let firstname = firstname.trim();
@@ -145,13 +194,88 @@ async fn register(lastname: &str, firstname: &str, username: &str, password: &st
         firstname: firstname.to_string(),
         username: username.to_string(),
         password: password.to_string(),
-        roles: Some(String::from("ADMIN")),
+        roles: vec![Role::Admin.to_string()],
         rate_limit: -1,
     };
+    let hasher = PasswordHasher::new(
+        config.password_hash_memory_cost,
+        config.password_hash_time_cost,
+        config.password_hash_parallelism,
+    );
     let mut user = User::new(user);
-    UserRepository::create(&pool, &mut user)
+    UserStore::create(&pool, &hasher, &mut user)
         .await
         .map_err(|err| CliError::DatabaseError(err.to_string()))?;
 
     Ok(())
 }
+
+/// Command that unlocks a user and forgets its failed logins
+async fn unlock(username: &str) -> CliResult<()> {
+    // Load configuration
+    // ------------------
+    let config = Config::from_env().map_err(|err| CliError::ConfigError(err.to_string()))?;
+
+    // Database pool creation
+    // ----------------------
+    let pool = databases::init_database(&config).await?;
+
+    let user = UserStore::get_by_email(&pool, username.trim().to_string())
+        .await
+        .map_err(|err| CliError::DatabaseError(err.to_string()))?
+        .ok_or_else(|| CliError::Error(String::from("no user found")))?;
+
+    UserStore::unlock(&pool, &user.id)
+        .await
+        .map_err(|err| CliError::DatabaseError(err.to_string()))?;
+    LoginAttemptRepository::delete_by_username(&pool, &user.username)
+        .await
+        .map_err(|err| CliError::DatabaseError(err.to_string()))?;
+
+    println!("User {} unlocked", user.username);
+
+    Ok(())
+}
+
+/// Command that lists or replays dead emails of the outbox
+async fn email_outbox(command: &EmailOutboxCommands) -> CliResult<()> {
+    // Load configuration
+    // ------------------
+    let config = Config::from_env().map_err(|err| CliError::ConfigError(err.to_string()))?;
+
+    // Database pool creation
+    // ----------------------
+    let pool = databases::init_database(&config).await?;
+
+    match command {
+        EmailOutboxCommands::List => {
+            let emails = EmailOutboxRepository::get_by_status(&pool, EmailOutboxStatus::Dead)
+                .await
+                .map_err(|err| CliError::DatabaseError(err.to_string()))?;
+
+            if emails.is_empty() {
+                println!("No dead email");
+            }
+            for email in emails {
+                println!(
+                    "{} | {} | {} | {} attempts | {} | {}",
+                    email.id,
+                    email.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    email.to_list.join(", "),
+                    email.attempts,
+                    email.subject,
+                    email.last_error.unwrap_or_default()
+                );
+            }
+        }
+        EmailOutboxCommands::Replay { id } => {
+            let replayed = EmailOutboxRepository::replay(&pool, id.as_deref())
+                .await
+                .map_err(|err| CliError::DatabaseError(err.to_string()))?;
+
+            println!("{replayed} email(s) will be sent again");
+        }
+    }
+
+    Ok(())
+}
@@ -1,40 +1,52 @@
 //! Database module
 
 use crate::config::Config;
+use crate::database::{Database, DatabaseBackend};
 use crate::utils::errors::{CliError, CliResult};
-use sqlx::mysql::MySqlPoolOptions;
-use sqlx::{MySql, Pool};
+use sqlx::pool::PoolOptions;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::str::FromStr;
 use std::time::Duration;
 
-/// Initialize MySQL connection pool
-pub async fn init_mysql(settings: &Config) -> CliResult<Pool<MySql>> {
+/// Initialize the connection pool of the database (the backend is selected by the scheme of the URL)
+pub async fn init_database(settings: &Config) -> CliResult<Database> {
     let url = &settings.database_url;
-    let max_connections = settings.database_max_connections;
-    let min_connections = settings.database_min_connections;
-    let max_lifetime = settings.database_max_lifetime;
-    let connect_timeout = settings.database_connect_timeout;
-    let idle_timeout = settings.database_idle_timeout;
-
-    let pool = MySqlPoolOptions::new()
-        .max_connections(max_connections)
-        .min_connections(min_connections)
-        .max_lifetime(Some(Duration::from_secs(max_lifetime)))
-        .acquire_timeout(Duration::from_secs(connect_timeout))
-        .idle_timeout(Duration::from_secs(idle_timeout))
-        .test_before_acquire(true)
-        .connect(url)
-        .await
-        .map_err(|err| CliError::DatabaseError(err.to_string()))?;
+    let backend = DatabaseBackend::from_url(url)
+        .ok_or_else(|| CliError::DatabaseError(String::from("unsupported database URL scheme")))?;
+
+    let database = match backend {
+        DatabaseBackend::MySql => pool_options(settings).connect(url).await.map(Database::MySql),
+        DatabaseBackend::Postgres => pool_options(settings).connect(url).await.map(Database::Postgres),
+        DatabaseBackend::Sqlite => {
+            // The database file is created on the first run
+            let options = SqliteConnectOptions::from_str(url)
+                .map_err(|err| CliError::DatabaseError(err.to_string()))?
+                .create_if_missing(true);
+            pool_options(settings).connect_with(options).await.map(Database::Sqlite)
+        }
+    }
+    .map_err(|err| CliError::DatabaseError(err.to_string()))?;
 
     if settings.database_auto_migration {
         info!("Run database migrations");
-        sqlx::migrate!("./migrations")
-            .run(&pool)
+        database
+            .migrate()
             .await
             .map_err(|err| CliError::DatabaseError(format!("failed to run database migrations: {err}")))?
     }
 
-    Ok(pool)
+    Ok(database)
+}
+
+/// Connection pool options shared by all the backends
+fn pool_options<DB: sqlx::Database>(settings: &Config) -> PoolOptions<DB> {
+    PoolOptions::new()
+        .max_connections(settings.database_max_connections)
+        .min_connections(settings.database_min_connections)
+        .max_lifetime(Some(Duration::from_secs(settings.database_max_lifetime)))
+        .acquire_timeout(Duration::from_secs(settings.database_connect_timeout))
+        .idle_timeout(Duration::from_secs(settings.database_idle_timeout))
+        .test_before_acquire(true)
 }
 
 /// Initialize database connection pool for Redis
@@ -0,0 +1,403 @@
+//! Queries run on the pool or in a transaction of any backend
+
+use super::{Database, DatabaseBackend};
+use chrono::{DateTime, Utc};
+use sqlx::database::HasArguments;
+use sqlx::mysql::MySqlRow;
+use sqlx::postgres::PgRow;
+use sqlx::query::Query;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Encode, MySql, Postgres, Row, Sqlite, Transaction, Type};
+
+/// Value bound to a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbValue {
+    Bool(Option<bool>),
+    Int(Option<i32>),
+    BigInt(Option<i64>),
+    Text(Option<String>),
+    DateTime(Option<DateTime<Utc>>),
+}
+
+macro_rules! impl_from_for_db_value {
+    ($($variant:ident: $t:ty),*) => {
+        $(
+            impl From<$t> for DbValue {
+                fn from(value: $t) -> Self {
+                    Self::$variant(Some(value.into()))
+                }
+            }
+
+            impl From<Option<$t>> for DbValue {
+                fn from(value: Option<$t>) -> Self {
+                    Self::$variant(value.map(Into::into))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_db_value!(
+    Bool: bool,
+    Int: i32,
+    BigInt: i64,
+    BigInt: u32,
+    Text: String,
+    Text: &String,
+    Text: &str,
+    DateTime: DateTime<Utc>
+);
+
+impl From<&Option<String>> for DbValue {
+    fn from(value: &Option<String>) -> Self {
+        Self::Text(value.clone())
+    }
+}
+
+/// Query with `?` placeholders, converted for the backend when it is run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbQuery {
+    sql: String,
+    values: Vec<DbValue>,
+}
+
+/// Create a query
+pub fn query(sql: &str) -> DbQuery {
+    DbQuery {
+        sql: sql.to_owned(),
+        values: vec![],
+    }
+}
+
+/// Result of a query which does not return rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbQueryResult {
+    rows_affected: u64,
+}
+
+impl DbQueryResult {
+    /// Number of inserted, updated or deleted rows
+    pub fn rows_affected(&self) -> u64 {
+        self.rows_affected
+    }
+}
+
+/// Pool or transaction used to run a query
+pub enum DbExecutor<'e> {
+    Database(&'e Database),
+    Transaction(&'e mut DbTransaction),
+}
+
+impl<'e> DbExecutor<'e> {
+    fn backend(&self) -> DatabaseBackend {
+        match self {
+            Self::Database(database) => database.backend(),
+            Self::Transaction(transaction) => transaction.backend(),
+        }
+    }
+}
+
+impl<'e> From<&'e Database> for DbExecutor<'e> {
+    fn from(database: &'e Database) -> Self {
+        Self::Database(database)
+    }
+}
+
+impl<'e> From<&'e mut DbTransaction> for DbExecutor<'e> {
+    fn from(transaction: &'e mut DbTransaction) -> Self {
+        Self::Transaction(transaction)
+    }
+}
+
+/// Transaction of any backend
+pub enum DbTransaction {
+    MySql(Transaction<'static, MySql>),
+    Postgres(Transaction<'static, Postgres>),
+    Sqlite(Transaction<'static, Sqlite>),
+}
+
+impl DbTransaction {
+    /// Backend of the transaction
+    pub fn backend(&self) -> DatabaseBackend {
+        match self {
+            Self::MySql(_) => DatabaseBackend::MySql,
+            Self::Postgres(_) => DatabaseBackend::Postgres,
+            Self::Sqlite(_) => DatabaseBackend::Sqlite,
+        }
+    }
+
+    /// Commit the transaction
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            Self::MySql(transaction) => transaction.commit().await,
+            Self::Postgres(transaction) => transaction.commit().await,
+            Self::Sqlite(transaction) => transaction.commit().await,
+        }
+    }
+
+    /// Roll back the transaction
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        match self {
+            Self::MySql(transaction) => transaction.rollback().await,
+            Self::Postgres(transaction) => transaction.rollback().await,
+            Self::Sqlite(transaction) => transaction.rollback().await,
+        }
+    }
+}
+
+/// Build the sqlx query of a backend
+fn build<'q, DB>(sql: &'q str, values: &'q [DbValue]) -> Query<'q, DB, <DB as HasArguments<'q>>::Arguments>
+where
+    DB: sqlx::Database,
+    Option<bool>: Encode<'q, DB> + Type<DB>,
+    Option<i32>: Encode<'q, DB> + Type<DB>,
+    Option<i64>: Encode<'q, DB> + Type<DB>,
+    Option<&'q str>: Encode<'q, DB> + Type<DB>,
+    Option<DateTime<Utc>>: Encode<'q, DB> + Type<DB>,
+{
+    values.iter().fold(sqlx::query(sql), |query, value| match value {
+        DbValue::Bool(value) => query.bind(*value),
+        DbValue::Int(value) => query.bind(*value),
+        DbValue::BigInt(value) => query.bind(*value),
+        DbValue::Text(value) => query.bind(value.as_deref()),
+        DbValue::DateTime(value) => query.bind(*value),
+    })
+}
+
+/// Run a query with the executor of its backend
+macro_rules! run {
+    ($executor:expr, $sql:expr, $values:expr, |$query:ident, $conn:ident| $body:expr) => {
+        match $executor {
+            DbExecutor::Database(Database::MySql($conn)) => {
+                let $query = build::<MySql>($sql, $values);
+                $body
+            }
+            DbExecutor::Database(Database::Postgres($conn)) => {
+                let $query = build::<Postgres>($sql, $values);
+                $body
+            }
+            DbExecutor::Database(Database::Sqlite($conn)) => {
+                let $query = build::<Sqlite>($sql, $values);
+                $body
+            }
+            DbExecutor::Transaction(DbTransaction::MySql(transaction)) => {
+                let $conn = &mut **transaction;
+                let $query = build::<MySql>($sql, $values);
+                $body
+            }
+            DbExecutor::Transaction(DbTransaction::Postgres(transaction)) => {
+                let $conn = &mut **transaction;
+                let $query = build::<Postgres>($sql, $values);
+                $body
+            }
+            DbExecutor::Transaction(DbTransaction::Sqlite(transaction)) => {
+                let $conn = &mut **transaction;
+                let $query = build::<Sqlite>($sql, $values);
+                $body
+            }
+        }
+    };
+}
+
+impl DbQuery {
+    /// Bind a value to the next placeholder
+    pub fn bind(mut self, value: impl Into<DbValue>) -> Self {
+        self.values.push(value.into());
+        self
+    }
+
+    /// Run a query which does not return rows
+    pub async fn execute<'e>(self, executor: impl Into<DbExecutor<'e>>) -> Result<DbQueryResult, sqlx::Error> {
+        let executor = executor.into();
+        let sql = executor.backend().sql(&self.sql).into_owned();
+
+        let rows_affected = run!(executor, &sql, &self.values, |query, conn| query
+            .execute(conn)
+            .await?
+            .rows_affected());
+
+        Ok(DbQueryResult { rows_affected })
+    }
+
+    /// Returns all the rows
+    pub async fn fetch_all<'e>(self, executor: impl Into<DbExecutor<'e>>) -> Result<Vec<DbRow>, sqlx::Error> {
+        let executor = executor.into();
+        let sql = executor.backend().sql(&self.sql).into_owned();
+
+        Ok(run!(executor, &sql, &self.values, |query, conn| query
+            .fetch_all(conn)
+            .await?
+            .into_iter()
+            .map(DbRow::from)
+            .collect()))
+    }
+
+    /// Returns the first row if any
+    pub async fn fetch_optional<'e>(self, executor: impl Into<DbExecutor<'e>>) -> Result<Option<DbRow>, sqlx::Error> {
+        let executor = executor.into();
+        let sql = executor.backend().sql(&self.sql).into_owned();
+
+        Ok(run!(executor, &sql, &self.values, |query, conn| query
+            .fetch_optional(conn)
+            .await?
+            .map(DbRow::from)))
+    }
+
+    /// Returns the first row (`sqlx::Error::RowNotFound` if there is no row)
+    pub async fn fetch_one<'e>(self, executor: impl Into<DbExecutor<'e>>) -> Result<DbRow, sqlx::Error> {
+        self.fetch_optional(executor).await?.ok_or(sqlx::Error::RowNotFound)
+    }
+}
+
+/// Row of any backend
+pub enum DbRow {
+    MySql(MySqlRow),
+    Postgres(PgRow),
+    Sqlite(SqliteRow),
+}
+
+impl From<MySqlRow> for DbRow {
+    fn from(row: MySqlRow) -> Self {
+        Self::MySql(row)
+    }
+}
+
+impl From<PgRow> for DbRow {
+    fn from(row: PgRow) -> Self {
+        Self::Postgres(row)
+    }
+}
+
+impl From<SqliteRow> for DbRow {
+    fn from(row: SqliteRow) -> Self {
+        Self::Sqlite(row)
+    }
+}
+
+impl DbRow {
+    /// Decode a column
+    pub fn try_get<T: DbDecode>(&self, column: &str) -> Result<T, sqlx::Error> {
+        T::decode(self, column)
+    }
+}
+
+/// Type which can be decoded from a column of all the backends
+pub trait DbDecode: Sized {
+    fn decode(row: &DbRow, column: &str) -> Result<Self, sqlx::Error>;
+}
+
+macro_rules! impl_db_decode {
+    ($($t:ty),*) => {
+        $(
+            impl DbDecode for $t {
+                fn decode(row: &DbRow, column: &str) -> Result<Self, sqlx::Error> {
+                    match row {
+                        DbRow::MySql(row) => row.try_get(column),
+                        DbRow::Postgres(row) => row.try_get(column),
+                        DbRow::Sqlite(row) => row.try_get(column),
+                    }
+                }
+            }
+
+            impl DbDecode for Option<$t> {
+                fn decode(row: &DbRow, column: &str) -> Result<Self, sqlx::Error> {
+                    match row {
+                        DbRow::MySql(row) => row.try_get(column),
+                        DbRow::Postgres(row) => row.try_get(column),
+                        DbRow::Sqlite(row) => row.try_get(column),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_db_decode!(bool, i32, i64, String, DateTime<Utc>);
+
+/// Unsigned integers are stored in `BIGINT` columns with PostgreSQL and SQLite
+impl DbDecode for u32 {
+    fn decode(row: &DbRow, column: &str) -> Result<Self, sqlx::Error> {
+        match row {
+            DbRow::MySql(row) => row.try_get(column),
+            _ => u32::try_from(i64::decode(row, column)?).map_err(|err| sqlx::Error::ColumnDecode {
+                index: column.to_owned(),
+                source: Box::new(err),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_query_bind() {
+        let now = Utc::now();
+        let lastname: Option<String> = None;
+        let query = query("UPDATE users SET lastname = ?, rate_limit = ?, updated_at = ? WHERE id = ?")
+            .bind(&lastname)
+            .bind(10)
+            .bind(now)
+            .bind("id");
+
+        assert_eq!(
+            query.values,
+            vec![
+                DbValue::Text(None),
+                DbValue::Int(Some(10)),
+                DbValue::DateTime(Some(now)),
+                DbValue::Text(Some(String::from("id"))),
+            ]
+        );
+        assert_eq!(DbValue::from(3_u32), DbValue::BigInt(Some(3)));
+    }
+
+    #[tokio::test]
+    async fn test_db_query_sqlite() {
+        let database = Database::Sqlite(sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap());
+        query("CREATE TABLE t (id TEXT NOT NULL, n INTEGER NOT NULL, created_at DATETIME NULL)")
+            .execute(&database)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let mut transaction = database.begin().await.unwrap();
+        let result = query("INSERT INTO t (id, n, created_at) VALUES (?, ?, ?), (?, ?, ?)")
+            .bind("a")
+            .bind(1_u32)
+            .bind(now)
+            .bind("b")
+            .bind(2_u32)
+            .bind(None::<DateTime<Utc>>)
+            .execute(&mut transaction)
+            .await
+            .unwrap();
+        assert_eq!(result.rows_affected(), 2);
+        transaction.commit().await.unwrap();
+
+        let row = query("SELECT * FROM t WHERE id = ?")
+            .bind("a")
+            .fetch_one(&database)
+            .await
+            .unwrap();
+        assert_eq!(row.try_get::<String>("id").unwrap(), "a");
+        assert_eq!(row.try_get::<u32>("n").unwrap(), 1);
+        assert_eq!(row.try_get::<Option<DateTime<Utc>>>("created_at").unwrap(), Some(now));
+
+        let rows = query("SELECT * FROM t WHERE created_at IS NULL OR created_at < ?")
+            .bind(now)
+            .fetch_all(&database)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].try_get::<String>("id").unwrap(), "b");
+
+        assert!(query("SELECT * FROM t WHERE id = ?")
+            .bind("c")
+            .fetch_optional(&database)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,29 @@
+//! Account locked email module
+
+use super::template::{build_link, Email};
+use crate::utils::errors::AppResult;
+use serde::Serialize;
+
+/// Email sent when an account is temporarily locked after too many failed logins
+#[derive(Debug, Serialize)]
+pub struct AccountLockedEmail {
+    firstname: String,
+    /// Lockout duration (in minute)
+    duration: i64,
+    link: String,
+}
+
+impl AccountLockedEmail {
+    /// New `AccountLockedEmail`
+    pub fn new(firstname: &str, duration: i64, base_url: &str, token: &str) -> AppResult<Self> {
+        Ok(Self {
+            firstname: firstname.to_owned(),
+            duration,
+            link: build_link(base_url, token)?,
+        })
+    }
+}
+
+impl Email for AccountLockedEmail {
+    const TEMPLATE: &'static str = "account_locked";
+}
@@ -0,0 +1,26 @@
+//! Email change verification email module
+
+use super::template::{build_link, Email};
+use crate::utils::errors::AppResult;
+use serde::Serialize;
+
+/// Email sent to a new address to verify it before changing the user email
+#[derive(Debug, Serialize)]
+pub struct EmailChangeVerificationEmail {
+    firstname: String,
+    link: String,
+}
+
+impl EmailChangeVerificationEmail {
+    /// New `EmailChangeVerificationEmail`
+    pub fn new(firstname: &str, base_url: &str, token: &str) -> AppResult<Self> {
+        Ok(Self {
+            firstname: firstname.to_owned(),
+            link: build_link(base_url, token)?,
+        })
+    }
+}
+
+impl Email for EmailChangeVerificationEmail {
+    const TEMPLATE: &'static str = "email_change";
+}
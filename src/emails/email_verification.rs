@@ -0,0 +1,29 @@
+//! Email verification email module
+
+use super::template::{build_link, Email};
+use crate::utils::errors::AppResult;
+use serde::Serialize;
+
+/// Email sent after a registration to verify the address of the new account
+#[derive(Debug, Serialize)]
+pub struct EmailVerificationEmail {
+    firstname: String,
+    /// Link lifetime (in hour)
+    lifetime: i64,
+    link: String,
+}
+
+impl EmailVerificationEmail {
+    /// New `EmailVerificationEmail`
+    pub fn new(firstname: &str, lifetime: i64, base_url: &str, token: &str) -> AppResult<Self> {
+        Ok(Self {
+            firstname: firstname.to_owned(),
+            lifetime,
+            link: build_link(base_url, token)?,
+        })
+    }
+}
+
+impl Email for EmailVerificationEmail {
+    const TEMPLATE: &'static str = "email_verification";
+}
@@ -1,13 +1,21 @@
 //! Email helper module
 
+pub mod account_locked;
+pub mod email_change;
+pub mod email_verification;
 pub mod forgotten_password;
+pub mod password_changed;
+pub mod template;
+pub mod transport;
+pub mod welcome;
+pub mod worker;
 
 use crate::app_error;
+use crate::config::Config;
 use crate::utils::errors::{AppError, AppErrorCode, AppResult};
 use lettre::message::{header, MultiPart, SinglePart};
-use lettre::{SmtpTransport, Transport};
-use std::time::Duration;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Message {
     pub from: String,
     pub to_list: Vec<String>,
@@ -16,37 +24,70 @@ pub struct Message {
     pub html_body: String,
 }
 
+/// SMTP connection security
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// No encryption (local development server only)
+    None,
+
+    /// Plain connection upgraded with the `STARTTLS` command (port 587)
+    StartTls,
+
+    /// Implicit TLS (port 465)
+    Tls,
+}
+
+impl SmtpTls {
+    /// Parse the `SMTP_TLS` configuration value (`none`, `starttls` or `tls`)
+    pub fn try_from_str(tls: &str) -> Option<Self> {
+        match tls.to_lowercase().as_str() {
+            "" | "none" => Some(Self::None),
+            "starttls" => Some(Self::StartTls),
+            "tls" => Some(Self::Tls),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SmtpConfig {
     pub host: String,
     pub port: u16,
     pub timeout: u64,
+    pub tls: SmtpTls,
+    /// Empty if no authentication
+    pub username: String,
+    pub password: String,
 }
 
-/// Initialize SMTP client
-fn init(config: &SmtpConfig) -> SmtpTransport {
-    let host = &config.host[..];
-    let port = config.port;
-    let timeout = match config.timeout {
-        0 => None,
-        t => Some(Duration::from_secs(t)),
-    };
-
-    SmtpTransport::builder_dangerous(host)
-        .port(port)
-        .timeout(timeout)
-        .build()
-}
+impl TryFrom<&Config> for SmtpConfig {
+    type Error = AppError;
 
-/// Sends an email
-pub fn send(config: &SmtpConfig, message: Message) -> AppResult<()> {
-    let mailer = init(config);
+    fn try_from(config: &Config) -> Result<Self, Self::Error> {
+        Ok(Self {
+            host: config.smtp_host.clone(),
+            port: config.smtp_port,
+            timeout: config.smtp_timeout,
+            tls: SmtpTls::try_from_str(&config.smtp_tls).ok_or_else(|| {
+                app_error!(
+                    AppErrorCode::InternalError,
+                    format!("invalid SMTP TLS mode: {}", config.smtp_tls)
+                )
+            })?,
+            username: config.smtp_username.clone(),
+            password: config.smtp_password.clone(),
+        })
+    }
+}
 
+/// Build the email to send
+fn build(message: Message) -> AppResult<lettre::Message> {
     let mut email_builder = lettre::Message::builder()
         .subject(message.subject)
         .from(message.from.parse().map_err(|_| {
             app_error!(
                 AppErrorCode::InternalError,
-                "cannot send password reset email because: invalid from email".to_string()
+                "cannot send email because: invalid from email".to_string()
             )
         })?);
 
@@ -55,12 +96,12 @@ pub fn send(config: &SmtpConfig, message: Message) -> AppResult<()> {
         email_builder = email_builder.to(to.parse().map_err(|_| {
             app_error!(
                 AppErrorCode::InternalError,
-                "cannot send password reset email because: invalid to email".to_string()
+                "cannot send email because: invalid to email".to_string()
             )
         })?)
     }
 
-    let email = email_builder
+    email_builder
         .multipart(
             MultiPart::alternative()
                 .singlepart(
@@ -74,19 +115,37 @@ pub fn send(config: &SmtpConfig, message: Message) -> AppResult<()> {
                         .body(message.html_body),
                 ),
         )
-        .map_err(|err| {
-            app_error!(
-                AppErrorCode::InternalError,
-                format!("cannot send password reset email because: {err}")
-            )
-        })?;
+        .map_err(|err| app_error!(AppErrorCode::InternalError, format!("cannot send email because: {err}")))
+}
 
-    mailer.send(&email).map_err(|err| {
-        app_error!(
-            AppErrorCode::InternalError,
-            format!("SMTP Error when sending password reset email: {err}")
-        )
-    })?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smtp_tls_try_from_str() {
+        assert_eq!(SmtpTls::try_from_str(""), Some(SmtpTls::None));
+        assert_eq!(SmtpTls::try_from_str("none"), Some(SmtpTls::None));
+        assert_eq!(SmtpTls::try_from_str("STARTTLS"), Some(SmtpTls::StartTls));
+        assert_eq!(SmtpTls::try_from_str("tls"), Some(SmtpTls::Tls));
+        assert_eq!(SmtpTls::try_from_str("ssl"), None);
+    }
 
-    Ok(())
+    #[test]
+    fn test_build_message() {
+        let message = Message {
+            from: String::from("contact@test.com"),
+            to_list: vec![String::from("user@test.com")],
+            subject: String::from("Subject"),
+            text_body: String::from("Text"),
+            html_body: String::from("<p>HTML</p>"),
+        };
+        assert!(build(message.clone()).is_ok());
+
+        let message = Message {
+            from: String::from("invalid"),
+            ..message
+        };
+        assert!(build(message).is_err());
+    }
 }
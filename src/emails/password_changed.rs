@@ -0,0 +1,23 @@
+//! Password changed email module
+
+use super::template::Email;
+use serde::Serialize;
+
+/// Email confirming a password change
+#[derive(Debug, Serialize)]
+pub struct PasswordChangedEmail {
+    firstname: String,
+}
+
+impl PasswordChangedEmail {
+    /// New `PasswordChangedEmail`
+    pub fn new(firstname: &str) -> Self {
+        Self {
+            firstname: firstname.to_owned(),
+        }
+    }
+}
+
+impl Email for PasswordChangedEmail {
+    const TEMPLATE: &'static str = "password_changed";
+}
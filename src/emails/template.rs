@@ -0,0 +1,164 @@
+//! Templated email module
+//!
+//! An email is rendered from three Tera templates in `templates/email/<locale>/`:
+//! `<name>.subject`, `<name>.html` and `<name>.txt`.
+//! Templates extend the locale layout (`templates/email/<locale>/layout.*`)
+//! which extends the global layout (`templates/email/layout.*`).
+
+use super::Message;
+use crate::utils::errors::{AppError, AppErrorCode, AppResult};
+use crate::{app_error, APP_NAME, TEMPLATES};
+use serde::Serialize;
+use tera::{Context, Tera};
+
+/// Locale used when a template does not exist in the requested locale
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Transactional email: a template name and a serializable context
+pub trait Email: Serialize {
+    /// Template name (without locale and extension)
+    const TEMPLATE: &'static str;
+
+    /// Render the email in `locale` (or in the nearest available locale)
+    fn message(&self, locale: &str, from: &str, to: &str) -> AppResult<Message> {
+        let tera = TEMPLATES
+            .as_ref()
+            .map_err(|err| app_error!(AppErrorCode::InternalError, err, "error during template render"))?;
+        let locale = resolve_locale(tera, locale, Self::TEMPLATE);
+
+        let mut context = Context::from_serialize(self).map_err(|err| template_error(Self::TEMPLATE, err))?;
+        context.insert("app_name", APP_NAME);
+        context.insert("locale", &locale);
+
+        let render = |extension: &str| {
+            tera.render(&format!("email/{locale}/{}.{extension}", Self::TEMPLATE), &context)
+                .map_err(|err| template_error(Self::TEMPLATE, err))
+        };
+
+        Ok(Message {
+            from: from.to_owned(),
+            to_list: vec![to.to_owned()],
+            subject: render("subject")?.trim().to_owned(),
+            text_body: render("txt")?,
+            html_body: render("html")?,
+        })
+    }
+}
+
+/// Build a link from a base URL and a token
+pub(super) fn build_link(base_url: &str, token: &str) -> AppResult<String> {
+    let link = format!("{base_url}/{token}");
+
+    match validator::validate_url(&link) {
+        true => Ok(link),
+        false => Err(app_error!(
+            AppErrorCode::InternalError,
+            "cannot build email because: invalid link"
+        )),
+    }
+}
+
+/// Find the locale to use for a template: `fr-FR`, then `fr`, then [`DEFAULT_LOCALE`]
+fn resolve_locale(tera: &Tera, locale: &str, template: &str) -> String {
+    let locale = locale.trim().to_lowercase().replace('_', "-");
+    let language = locale.split('-').next().unwrap_or_default().to_owned();
+
+    [locale, language]
+        .into_iter()
+        .find(|locale| {
+            let name = format!("email/{locale}/{template}.html");
+            !locale.is_empty() && tera.get_template_names().any(|n| n == name)
+        })
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_owned())
+}
+
+/// Convert a Tera error to an `AppError`
+fn template_error(template: &str, err: tera::Error) -> AppError {
+    app_error!(
+        AppErrorCode::InternalError,
+        format!("error when rendering {template} email"),
+        format!("error when rendering {template} email: {err:?}")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emails::{
+        account_locked::AccountLockedEmail, email_change::EmailChangeVerificationEmail,
+        email_verification::EmailVerificationEmail, forgotten_password::ForgottenPasswordEmail,
+        password_changed::PasswordChangedEmail, welcome::WelcomeEmail,
+    };
+
+    #[test]
+    fn test_resolve_locale() {
+        let tera = TEMPLATES.as_ref().unwrap();
+
+        assert_eq!(resolve_locale(tera, "fr", WelcomeEmail::TEMPLATE), "fr");
+        assert_eq!(resolve_locale(tera, "fr-FR", WelcomeEmail::TEMPLATE), "fr");
+        assert_eq!(resolve_locale(tera, "fr_FR", WelcomeEmail::TEMPLATE), "fr");
+        assert_eq!(resolve_locale(tera, "de", WelcomeEmail::TEMPLATE), DEFAULT_LOCALE);
+        assert_eq!(resolve_locale(tera, "", WelcomeEmail::TEMPLATE), DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn test_build_link() {
+        assert_eq!(
+            build_link("http://localhost", "token").unwrap(),
+            String::from("http://localhost/token")
+        );
+        assert!(build_link("", "token").is_err());
+    }
+
+    #[test]
+    fn test_email_message() {
+        let email = WelcomeEmail::new("Doe", "John", "john@test.com");
+        let message = email.message("en", "contact@test.com", "john@test.com").unwrap();
+
+        assert_eq!(message.from, "contact@test.com");
+        assert_eq!(message.to_list, vec![String::from("john@test.com")]);
+        assert_eq!(message.subject, format!("[{APP_NAME}] Welcome"));
+        assert!(message.text_body.contains("John"));
+        assert!(message.html_body.contains("<html"));
+
+        let message = email.message("fr", "contact@test.com", "john@test.com").unwrap();
+        assert_eq!(message.subject, format!("[{APP_NAME}] Bienvenue"));
+    }
+
+    #[test]
+    fn test_all_emails_are_rendered_in_all_locales() {
+        let link = "http://localhost/token";
+        for locale in ["en", "fr"] {
+            assert!(ForgottenPasswordEmail::new("http://localhost", "token")
+                .unwrap()
+                .message(locale, "a@test.com", "b@test.com")
+                .unwrap()
+                .text_body
+                .contains(link));
+            assert!(WelcomeEmail::new("Doe", "John", "john@test.com")
+                .message(locale, "a@test.com", "b@test.com")
+                .is_ok());
+            assert!(PasswordChangedEmail::new("John")
+                .message(locale, "a@test.com", "b@test.com")
+                .is_ok());
+            assert!(EmailChangeVerificationEmail::new("John", "http://localhost", "token")
+                .unwrap()
+                .message(locale, "a@test.com", "b@test.com")
+                .unwrap()
+                .text_body
+                .contains(link));
+            assert!(EmailVerificationEmail::new("John", 24, "http://localhost", "token")
+                .unwrap()
+                .message(locale, "a@test.com", "b@test.com")
+                .unwrap()
+                .text_body
+                .contains(link));
+            assert!(AccountLockedEmail::new("John", 15, "http://localhost", "token")
+                .unwrap()
+                .message(locale, "a@test.com", "b@test.com")
+                .unwrap()
+                .text_body
+                .contains(link));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Email transports module
+
+use super::{build, Message, SmtpConfig, SmtpTls};
+use crate::app_error;
+use crate::config::Config;
+use crate::utils::errors::{AppError, AppErrorCode, AppResult};
+use futures::future::BoxFuture;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Transport used to deliver emails
+pub trait EmailTransport: Send + Sync {
+    /// Sends an email
+    // `BoxFuture` is a type alias for `Pin<Box<dyn Future + Send + 'a>>`
+    fn send<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, AppResult<()>>;
+}
+
+/// Initialize the transport chosen in configuration (`EMAIL_TRANSPORT`): `smtp`, `file` or `stdout`
+pub fn init(config: &Config) -> AppResult<Arc<dyn EmailTransport>> {
+    match config.email_transport.to_lowercase().as_str() {
+        "" | "smtp" => Ok(Arc::new(SmtpTransport::new(&SmtpConfig::try_from(config)?)?)),
+        "file" => Ok(Arc::new(FileTransport::new(&config.email_file_path)?)),
+        "stdout" => Ok(Arc::new(StdoutTransport)),
+        transport => Err(app_error!(
+            AppErrorCode::InternalError,
+            format!("invalid email transport: {transport}")
+        )),
+    }
+}
+
+/// SMTP transport with optional TLS and authentication
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    /// Initialize SMTP client
+    pub fn new(config: &SmtpConfig) -> AppResult<Self> {
+        let host = &config.host[..];
+        let timeout = match config.timeout {
+            0 => None,
+            t => Some(Duration::from_secs(t)),
+        };
+
+        let builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(smtp_error)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(smtp_error)?,
+        };
+        let mut builder = builder.port(config.port).timeout(timeout);
+
+        if !config.username.is_empty() {
+            builder = builder.credentials(Credentials::new(config.username.clone(), config.password.clone()));
+        }
+
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+impl EmailTransport for SmtpTransport {
+    fn send<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let email = build(message.clone())?;
+            self.mailer.send(email).await.map_err(smtp_error)?;
+
+            Ok(())
+        })
+    }
+}
+
+/// File transport writing each email in a `.eml` file (development)
+pub struct FileTransport {
+    mailer: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileTransport {
+    /// Create the transport, `path` is created if it does not exist
+    pub fn new(path: &str) -> AppResult<Self> {
+        std::fs::create_dir_all(path).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                format!("cannot create email directory {path}: {err}")
+            )
+        })?;
+
+        Ok(Self {
+            mailer: AsyncFileTransport::new(path),
+        })
+    }
+}
+
+impl EmailTransport for FileTransport {
+    fn send<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let email = build(message.clone())?;
+            self.mailer.send(email).await.map_err(|err| {
+                app_error!(
+                    AppErrorCode::InternalError,
+                    format!("error when writing email file: {err}")
+                )
+            })?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Transport printing emails on the standard output (development)
+pub struct StdoutTransport;
+
+impl EmailTransport for StdoutTransport {
+    fn send<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let email = build(message.clone())?;
+            println!("{}", String::from_utf8_lossy(&email.formatted()));
+
+            Ok(())
+        })
+    }
+}
+
+/// In-memory transport keeping sent emails (tests)
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    emails: Arc<Mutex<Vec<Message>>>,
+}
+
+impl MemoryTransport {
+    /// Create an empty transport
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns sent emails
+    pub fn emails(&self) -> Vec<Message> {
+        self.emails.lock().map(|emails| emails.clone()).unwrap_or_default()
+    }
+}
+
+impl EmailTransport for MemoryTransport {
+    fn send<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            // Invalid emails are rejected like the other transports
+            build(message.clone())?;
+
+            self.emails
+                .lock()
+                .map_err(|err| app_error!(AppErrorCode::InternalError, format!("memory transport error: {err}")))?
+                .push(message.clone());
+
+            Ok(())
+        })
+    }
+}
+
+/// Convert a SMTP error to an `AppError`
+fn smtp_error(err: lettre::transport::smtp::Error) -> AppError {
+    app_error!(
+        AppErrorCode::InternalError,
+        format!("SMTP Error when sending email: {err}")
+    )
+}
@@ -0,0 +1,27 @@
+//! Welcome email module
+
+use super::template::Email;
+use serde::Serialize;
+
+/// Email sent when a user account is created
+#[derive(Debug, Serialize)]
+pub struct WelcomeEmail {
+    lastname: String,
+    firstname: String,
+    username: String,
+}
+
+impl WelcomeEmail {
+    /// New `WelcomeEmail`
+    pub fn new(lastname: &str, firstname: &str, username: &str) -> Self {
+        Self {
+            lastname: lastname.to_owned(),
+            firstname: firstname.to_owned(),
+            username: username.to_owned(),
+        }
+    }
+}
+
+impl Email for WelcomeEmail {
+    const TEMPLATE: &'static str = "welcome";
+}
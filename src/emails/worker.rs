@@ -0,0 +1,104 @@
+//! Email outbox worker module
+//!
+//! Emails are not sent during HTTP requests but persisted in the `email_outbox` table.
+//! This worker periodically sends ready emails, retries failures with an exponential backoff
+//! and moves emails to the dead letter state once the maximum number of attempts is reached.
+
+use super::transport::EmailTransport;
+use crate::config::Config;
+use crate::models::email_outbox::OutboxEmail;
+use crate::repositories::email_outbox::EmailOutboxRepository;
+use crate::utils::errors::AppResult;
+use chrono::{Duration, Utc};
+use metrics::counter;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// Duration during which an email being sent is locked for other workers (in second)
+const LOCK_DURATION_IN_SECONDS: i64 = 300;
+
+pub struct EmailWorker {
+    outbox: Arc<dyn EmailOutboxRepository>,
+    transport: Arc<dyn EmailTransport>,
+    /// Interval between two outbox polls (in second)
+    poll_interval: u64,
+    /// Maximum number of emails sent by poll
+    batch_size: u32,
+    /// Maximum number of attempts before moving an email to the dead letter state
+    max_attempts: u32,
+    /// Delay before the first retry (in second)
+    retry_delay: i64,
+}
+
+impl EmailWorker {
+    /// Create a new worker
+    pub fn new(outbox: Arc<dyn EmailOutboxRepository>, transport: Arc<dyn EmailTransport>, config: &Config) -> Self {
+        Self {
+            outbox,
+            transport,
+            poll_interval: config.email_outbox_poll_interval.max(1),
+            batch_size: config.email_outbox_batch_size.max(1),
+            max_attempts: config.email_outbox_max_attempts.max(1),
+            retry_delay: config.email_outbox_retry_delay.max(1),
+        }
+    }
+
+    /// Start the worker in a background task
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(self.poll_interval));
+
+            loop {
+                interval.tick().await;
+
+                if let Err(err) = self.process().await {
+                    error!("email outbox worker error: {err}");
+                }
+            }
+        })
+    }
+
+    /// Send a batch of ready emails and returns the number of processed emails
+    #[instrument(skip(self))]
+    pub async fn process(&self) -> AppResult<usize> {
+        let emails = self.outbox.get_ready(self.batch_size).await?;
+        let mut processed = 0;
+
+        for email in emails {
+            // Another worker may already be sending this email
+            let locked_until = Utc::now() + Duration::seconds(LOCK_DURATION_IN_SECONDS);
+            if !self.outbox.lock(&email.id, locked_until).await? {
+                continue;
+            }
+
+            let attempts = email.attempts + 1;
+            match self.transport.send(&email.message()).await {
+                Ok(_) => {
+                    self.outbox.mark_as_sent(&email.id, attempts).await?;
+                    counter!("emails_sent_total").increment(1);
+                }
+                Err(err) => {
+                    let next_attempt_at = match attempts >= self.max_attempts {
+                        true => {
+                            counter!("emails_dead_total").increment(1);
+                            warn!(
+                                "email {} moved to dead letter after {attempts} attempts: {err}",
+                                email.id
+                            );
+                            None
+                        }
+                        false => Some(Utc::now() + OutboxEmail::retry_delay(attempts, self.retry_delay)),
+                    };
+                    counter!("emails_failed_total").increment(1);
+
+                    self.outbox
+                        .mark_as_failed(&email.id, attempts, &err.to_string(), next_attempt_at)
+                        .await?;
+                }
+            }
+            processed += 1;
+        }
+
+        Ok(processed)
+    }
+}
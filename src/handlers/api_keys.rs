@@ -0,0 +1,268 @@
+//! API keys handlers
+//!
+//! API keys are managed with a JWT only: a key cannot be used to create or change keys.
+//! Users manage their own keys under `/api/v1/api-keys`, the keys of any user (e.g. service accounts)
+//! are managed under `/api/v1/users/:id/api-keys` with the `api-keys:manage` permission.
+
+use crate::{
+    app_error,
+    layers::SharedState,
+    models::{
+        api_key::{ApiKey, ApiKeyCreated, ApiKeyCreation, ApiKeyUpdate},
+        user::User,
+    },
+    utils::{
+        errors::{AppError, AppErrorCode, AppResult},
+        extractors::{ExtractRequestId, JwtAuthUser, Path},
+        validation::validate_request_data,
+    },
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+// Route: POST /api/v1/api-keys
+#[instrument(skip(claims, state))]
+pub async fn create(
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<ApiKeyCreation>,
+) -> AppResult<Json<ApiKeyCreated>> {
+    Ok(Json(create_api_key(&state, &claims.user_id, payload, None).await?))
+}
+
+// Route: GET /api/v1/api-keys
+#[instrument(skip(claims, state))]
+pub async fn get_all(
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<Vec<ApiKey>>> {
+    Ok(Json(state.stores.api_keys.get_all_by_user(&claims.user_id).await?))
+}
+
+// Route: GET /api/v1/api-keys/:id
+#[instrument(skip(claims, state))]
+pub async fn get_by_id(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<ApiKey>> {
+    Ok(Json(get_api_key(&state, &claims.user_id, id).await?))
+}
+
+// Route: PUT /api/v1/api-keys/:id
+#[instrument(skip(claims, state))]
+pub async fn update(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<ApiKeyUpdate>,
+) -> AppResult<Json<ApiKey>> {
+    Ok(Json(update_api_key(&state, &claims.user_id, id, payload, None).await?))
+}
+
+// Route: DELETE /api/v1/api-keys/:id
+#[instrument(skip(claims, state))]
+pub async fn delete(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    delete_api_key(&state, &claims.user_id, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Route: POST /api/v1/users/:id/api-keys
+#[instrument(skip(claims, state))]
+pub async fn create_for_user(
+    Path(user_id): Path<Uuid>,
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<ApiKeyCreation>,
+) -> AppResult<Json<ApiKeyCreated>> {
+    Ok(Json(
+        create_api_key(&state, &user_id.to_string(), payload, Some(claims.user_rate_limit)).await?,
+    ))
+}
+
+// Route: GET /api/v1/users/:id/api-keys
+#[instrument(skip(state))]
+pub async fn get_all_for_user(
+    Path(user_id): Path<Uuid>,
+    State(state): State<SharedState>,
+    JwtAuthUser(_claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<Vec<ApiKey>>> {
+    let owner = get_owner(&state, &user_id.to_string()).await?;
+
+    Ok(Json(state.stores.api_keys.get_all_by_user(&owner.id).await?))
+}
+
+// Route: GET /api/v1/users/:id/api-keys/:key_id
+#[instrument(skip(state))]
+pub async fn get_by_id_for_user(
+    Path((user_id, id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+    JwtAuthUser(_claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<ApiKey>> {
+    Ok(Json(get_api_key(&state, &user_id.to_string(), id).await?))
+}
+
+// Route: PUT /api/v1/users/:id/api-keys/:key_id
+#[instrument(skip(claims, state))]
+pub async fn update_for_user(
+    Path((user_id, id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<ApiKeyUpdate>,
+) -> AppResult<Json<ApiKey>> {
+    Ok(Json(
+        update_api_key(&state, &user_id.to_string(), id, payload, Some(claims.user_rate_limit)).await?,
+    ))
+}
+
+// Route: DELETE /api/v1/users/:id/api-keys/:key_id
+#[instrument(skip(state))]
+pub async fn delete_for_user(
+    Path((user_id, id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+    JwtAuthUser(_claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    delete_api_key(&state, &user_id.to_string(), id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Create a key of a user (its rate limit is bounded by the manager one for managers of API keys)
+async fn create_api_key(
+    state: &SharedState,
+    owner_id: &str,
+    payload: ApiKeyCreation,
+    manager_rate_limit: Option<i32>,
+) -> AppResult<ApiKeyCreated> {
+    validate_request_data(&payload)?;
+    validate_expired_at(payload.expired_at)?;
+
+    let owner = get_owner(state, owner_id).await?;
+    let roles = get_roles(&owner, payload.roles.as_deref())?;
+    let routes = get_routes(payload.routes.as_deref())?;
+    let rate_limit = get_rate_limit(
+        &owner,
+        payload.rate_limit.unwrap_or(owner.rate_limit),
+        manager_rate_limit,
+    )?;
+
+    let (mut api_key, key) = ApiKey::new(owner.id, payload.name, roles, routes, rate_limit);
+    api_key.expired_at = payload.expired_at;
+    state.stores.api_keys.create(&api_key).await?;
+
+    Ok(ApiKeyCreated { api_key, key })
+}
+
+/// Update a key of a user (its rate limit is bounded by the manager one for managers of API keys)
+async fn update_api_key(
+    state: &SharedState,
+    owner_id: &str,
+    id: Uuid,
+    payload: ApiKeyUpdate,
+    manager_rate_limit: Option<i32>,
+) -> AppResult<ApiKey> {
+    validate_request_data(&payload)?;
+    validate_expired_at(payload.expired_at)?;
+
+    let mut api_key = get_api_key(state, owner_id, id).await?;
+    let owner = get_owner(state, &api_key.user_id).await?;
+
+    api_key.name = payload.name;
+    api_key.roles = get_roles(&owner, payload.roles.as_deref())?;
+    api_key.routes = get_routes(payload.routes.as_deref())?;
+    api_key.rate_limit = get_rate_limit(&owner, payload.rate_limit, manager_rate_limit)?;
+    api_key.expired_at = payload.expired_at;
+    state.stores.api_keys.update(&api_key).await?;
+
+    get_api_key(state, owner_id, id).await
+}
+
+/// Delete a key of a user
+async fn delete_api_key(state: &SharedState, owner_id: &str, id: Uuid) -> AppResult<()> {
+    let api_key = get_api_key(state, owner_id, id).await?;
+
+    state.stores.api_keys.delete(&api_key.id).await?;
+
+    Ok(())
+}
+
+/// Returns the owner of keys
+async fn get_owner(state: &SharedState, owner_id: &str) -> AppResult<User> {
+    state
+        .stores
+        .users
+        .get_by_id(owner_id.to_owned())
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "no user found"))
+}
+
+/// Returns a key of a user
+async fn get_api_key(state: &SharedState, owner_id: &str, id: Uuid) -> AppResult<ApiKey> {
+    state
+        .stores
+        .api_keys
+        .get_by_id(&id.to_string())
+        .await?
+        .filter(|api_key| api_key.user_id == owner_id)
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "no API key found"))
+}
+
+/// Check the roles of a key
+fn get_roles(owner: &User, roles: Option<&str>) -> AppResult<String> {
+    ApiKey::roles_for(owner, roles).ok_or_else(|| {
+        app_error!(
+            AppErrorCode::BadRequest,
+            "roles must be a non empty subset of the owner roles"
+        )
+    })
+}
+
+/// Check the rate limit of a key: it cannot exceed the owner one, or the manager one for managers of API keys
+fn get_rate_limit(owner: &User, rate_limit: i32, manager_rate_limit: Option<i32>) -> AppResult<i32> {
+    match manager_rate_limit {
+        None if !ApiKey::is_rate_limit_allowed(owner.rate_limit, rate_limit) => Err(app_error!(
+            AppErrorCode::BadRequest,
+            "rate limit cannot exceed the owner rate limit"
+        )),
+        Some(manager_rate_limit) if !ApiKey::is_rate_limit_allowed(manager_rate_limit, rate_limit) => Err(app_error!(
+            AppErrorCode::BadRequest,
+            "rate limit cannot exceed your rate limit"
+        )),
+        _ => Ok(rate_limit),
+    }
+}
+
+/// Check the routes of a key
+fn get_routes(routes: Option<&[String]>) -> AppResult<Option<String>> {
+    ApiKey::routes_from(routes).ok_or_else(|| app_error!(AppErrorCode::BadRequest, "routes must start with '/'"))
+}
+
+/// Check that the expiration date is in the future
+fn validate_expired_at(expired_at: Option<DateTime<Utc>>) -> AppResult<()> {
+    match expired_at {
+        Some(expired_at) if expired_at <= Utc::now() => Err(app_error!(
+            AppErrorCode::BadRequest,
+            "expiration date must be in the future"
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,171 @@
+//! API handlers of the authenticated user account
+
+use super::users::{queue_email, revoke_user_tokens, send_password_changed_email};
+use crate::{
+    app_error,
+    emails::{email_change::EmailChangeVerificationEmail, template::Email},
+    layers::SharedState,
+    models::user::{
+        EmailChange, PasswordScorer, PasswordStrength, User, UserChangeEmail, UserChangePassword, UserProfileUpdate,
+    },
+    repositories::Stores,
+    utils::{
+        errors::{AppError, AppErrorCode, AppResult},
+        extractors::{AuthUser, ExtractRequestId, Path},
+        password::PasswordHasher,
+        validation::validate_request_data,
+    },
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+};
+
+// Route: GET /api/v1/me
+#[instrument(skip(state))]
+pub async fn get(
+    AuthUser(claims): AuthUser,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<User>> {
+    Ok(Json(get_user(&state.stores, &claims.user_id).await?))
+}
+
+// Route: PATCH /api/v1/me
+#[instrument(skip(state))]
+pub async fn update(
+    AuthUser(claims): AuthUser,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<UserProfileUpdate>,
+) -> AppResult<Json<User>> {
+    validate_request_data(&payload)?;
+
+    let user = get_user(&state.stores, &claims.user_id).await?;
+    state.stores.users.update_profile(&user.id, &payload).await?;
+
+    Ok(Json(get_user(&state.stores, &user.id).await?))
+}
+
+// Route: POST /api/v1/me/password
+#[instrument(skip(state, payload))]
+pub async fn change_password(
+    AuthUser(claims): AuthUser,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<UserChangePassword>,
+) -> AppResult<StatusCode> {
+    validate_request_data(&payload)?;
+
+    let user = get_user(&state.stores, &claims.user_id).await?;
+    check_password(&state.config.password_hasher, &user, &payload.current_password).await?;
+
+    if !PasswordScorer::valid(&payload.new_password, PasswordStrength::Good) {
+        return Err(app_error!(AppErrorCode::BadRequest, "password is not strong enough"));
+    }
+
+    state
+        .stores
+        .users
+        .update_password(
+            &state.config.password_hasher,
+            user.id.clone(),
+            user.password.clone(),
+            payload.new_password,
+        )
+        .await?;
+
+    // All the sessions must log in again with the new password
+    revoke_user_tokens(&state, &user.id).await?;
+    send_password_changed_email(&state, &user).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Route: POST /api/v1/me/email
+#[instrument(skip(state, payload))]
+pub async fn change_email(
+    AuthUser(claims): AuthUser,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<UserChangeEmail>,
+) -> AppResult<StatusCode> {
+    validate_request_data(&payload)?;
+
+    let user = get_user(&state.stores, &claims.user_id).await?;
+    check_password(&state.config.password_hasher, &user, &payload.password).await?;
+
+    if !mailchecker::is_valid(&payload.email) {
+        return Err(app_error!(AppErrorCode::BadRequest, "invalid email"));
+    }
+    if payload.email == user.username {
+        return Err(app_error!(
+            AppErrorCode::BadRequest,
+            "new email cannot be the same as the current one"
+        ));
+    }
+
+    // Same answer for a used email to not leak registered users
+    if state.stores.users.is_username_used(&payload.email).await? {
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    let (change, token) = EmailChange::new(
+        user.id.clone(),
+        payload.email.clone(),
+        state.config.email_verification_lifetime,
+    );
+    state.stores.email_changes.create(&change).await?;
+
+    // The link is sent to the new email
+    let message = EmailChangeVerificationEmail::new(&user.firstname, &state.config.email_change_base_url, &token)?
+        .message(&state.config.email_locale, &state.config.email_from, &payload.email)?;
+    queue_email(&state.stores, message).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Route: GET|POST "/api/v1/verify-email-change/:token"
+#[instrument(skip(state, token))]
+pub async fn verify_email_change(
+    Path(token): Path<String>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    let change = state
+        .stores
+        .email_changes
+        .get_by_hash(&EmailChange::hash(&token))
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "no email change found"))?;
+
+    if !change.is_valid() {
+        return Err(app_error!(AppErrorCode::BadRequest, "email change link is expired"));
+    }
+
+    // The email may have been used since the request
+    if state.stores.users.is_username_used(&change.new_email).await? {
+        return Err(app_error!(AppErrorCode::BadRequest, "email is already used"));
+    }
+
+    state.stores.email_changes.confirm(&change).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Returns the authenticated user (a deleted user is not authorized anymore)
+async fn get_user(stores: &Stores, user_id: &str) -> AppResult<User> {
+    stores
+        .users
+        .get_by_id(user_id.to_owned())
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))
+}
+
+/// Check the current password before a sensitive change
+async fn check_password(hasher: &PasswordHasher, user: &User, password: &str) -> AppResult<()> {
+    match hasher.verify_async(password, &user.password).await?.is_valid() {
+        true => Ok(()),
+        false => Err(app_error!(AppErrorCode::BadRequest, "invalid current password")),
+    }
+}
@@ -0,0 +1,298 @@
+//! API two-factor authentication handlers
+
+use super::users::generate_tokens;
+use crate::{
+    app_error,
+    layers::SharedState,
+    models::{
+        auth::Claims,
+        mfa::{
+            MfaChallenge, MfaChallengeResponse, MfaCode, MfaLogin, MfaLoginEnrollment, RecoveryCode, RecoveryCodes,
+            TotpEnrollment, UserTotp, CHALLENGE_MAX_ATTEMPTS,
+        },
+        user::{LoginResponse, Role, User},
+    },
+    repositories::Stores,
+    utils::{
+        errors::{AppError, AppErrorCode, AppResult},
+        extractors::{ExtractRequestId, JwtAuthUser},
+        totp::Totp,
+        validation::validate_request_data,
+    },
+    APP_NAME,
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+};
+use chrono::{SecondsFormat, Utc};
+
+// Route: POST /api/v1/login/mfa
+#[instrument(name = "MFA login handler", skip(state, payload), level = "warn")]
+pub async fn login(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<MfaLogin>,
+) -> AppResult<Json<LoginResponse>> {
+    validate_request_data(&payload)?;
+
+    let challenge = get_challenge(&state.stores, &payload.mfa_token).await?;
+    let user = state
+        .stores
+        .users
+        .get_by_id(challenge.user_id.clone())
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))?;
+    let totp = state.stores.mfa.get_totp(&user.id).await?.unwrap_or_default();
+
+    let recovery_codes = match (totp.is_enabled(), totp.secret.as_deref()) {
+        // Enrolled user: TOTP or recovery code
+        (true, _) => {
+            if !verify_code(&state.stores, &user.id, &totp, &payload.code).await? {
+                return wrong_code(&state.stores, &challenge).await;
+            }
+            None
+        }
+        // Enrollment started during the login: the first code confirms it
+        (false, Some(secret)) => match verify_totp(secret, &payload.code) {
+            Some(step) => {
+                state.stores.mfa.enable_totp(&user.id, step).await?;
+                Some(replace_recovery_codes(&state.stores, &user.id).await?)
+            }
+            None => return wrong_code(&state.stores, &challenge).await,
+        },
+        (false, None) => {
+            return Err(app_error!(
+                AppErrorCode::BadRequest,
+                "TOTP enrollment must be started before logging in"
+            ))
+        }
+    };
+
+    // A challenge can only be used once
+    if !state.stores.mfa.delete_challenge(&challenge.id).await? {
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    let mut response = generate_tokens(&state, user, None).await?;
+    response.recovery_codes = recovery_codes;
+
+    Ok(Json(response))
+}
+
+// Route: POST /api/v1/login/mfa/enroll
+#[instrument(skip(state, payload))]
+pub async fn login_enroll(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<MfaLoginEnrollment>,
+) -> AppResult<Json<TotpEnrollment>> {
+    validate_request_data(&payload)?;
+
+    let challenge = get_challenge(&state.stores, &payload.mfa_token).await?;
+    let user = state
+        .stores
+        .users
+        .get_by_id(challenge.user_id)
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))?;
+
+    Ok(Json(start_enrollment(&state.stores, &user).await?))
+}
+
+// Route: POST /api/v1/mfa/totp
+#[instrument(skip(claims, state))]
+pub async fn enroll(
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<TotpEnrollment>> {
+    let user = get_authenticated_user(&state.stores, &claims).await?;
+
+    Ok(Json(start_enrollment(&state.stores, &user).await?))
+}
+
+// Route: POST /api/v1/mfa/totp/confirm
+#[instrument(skip(claims, state, payload))]
+pub async fn confirm(
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<MfaCode>,
+) -> AppResult<Json<RecoveryCodes>> {
+    validate_request_data(&payload)?;
+
+    let user = get_authenticated_user(&state.stores, &claims).await?;
+    let totp = state.stores.mfa.get_totp(&user.id).await?.unwrap_or_default();
+
+    let secret = match (totp.is_enabled(), totp.secret) {
+        (false, Some(secret)) => secret,
+        _ => return Err(app_error!(AppErrorCode::BadRequest, "no pending TOTP enrollment")),
+    };
+    let step =
+        verify_totp(&secret, &payload.code).ok_or_else(|| app_error!(AppErrorCode::BadRequest, "invalid code"))?;
+
+    state.stores.mfa.enable_totp(&user.id, step).await?;
+
+    Ok(Json(RecoveryCodes {
+        recovery_codes: replace_recovery_codes(&state.stores, &user.id).await?,
+    }))
+}
+
+// Route: DELETE /api/v1/mfa/totp
+#[instrument(skip(claims, state, payload))]
+pub async fn disable(
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<MfaCode>,
+) -> AppResult<StatusCode> {
+    validate_request_data(&payload)?;
+
+    let user = get_authenticated_user(&state.stores, &claims).await?;
+    if is_mfa_required(&state, &user) {
+        return Err(app_error!(AppErrorCode::Forbidden));
+    }
+
+    let totp = get_enabled_totp(&state.stores, &user).await?;
+    if !verify_code(&state.stores, &user.id, &totp, &payload.code).await? {
+        return Err(app_error!(AppErrorCode::BadRequest, "invalid code"));
+    }
+
+    state.stores.mfa.disable_totp(&user.id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Route: POST /api/v1/mfa/recovery-codes
+#[instrument(skip(claims, state, payload))]
+pub async fn regenerate_recovery_codes(
+    State(state): State<SharedState>,
+    JwtAuthUser(claims): JwtAuthUser,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<MfaCode>,
+) -> AppResult<Json<RecoveryCodes>> {
+    validate_request_data(&payload)?;
+
+    let user = get_authenticated_user(&state.stores, &claims).await?;
+    let totp = get_enabled_totp(&state.stores, &user).await?;
+    if !verify_code(&state.stores, &user.id, &totp, &payload.code).await? {
+        return Err(app_error!(AppErrorCode::BadRequest, "invalid code"));
+    }
+
+    Ok(Json(RecoveryCodes {
+        recovery_codes: replace_recovery_codes(&state.stores, &user.id).await?,
+    }))
+}
+
+/// Create a MFA challenge if the user has enrolled TOTP or must enroll it.
+///
+/// Returns `None` if the user can log in with its password only.
+pub(super) async fn create_challenge(state: &SharedState, user: &User) -> AppResult<Option<MfaChallengeResponse>> {
+    let totp = state.stores.mfa.get_totp(&user.id).await?.unwrap_or_default();
+    let enrollment_required = !totp.is_enabled() && is_mfa_required(state, user);
+
+    if !totp.is_enabled() && !enrollment_required {
+        return Ok(None);
+    }
+
+    state.stores.mfa.delete_expired_challenges().await?;
+
+    let (challenge, token) = MfaChallenge::new(user.id.clone(), state.config.mfa_challenge_lifetime);
+    state.stores.mfa.create_challenge(&challenge).await?;
+
+    Ok(Some(MfaChallengeResponse {
+        mfa_token: token,
+        expires_at: challenge.expired_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        enrollment_required,
+    }))
+}
+
+/// Is the user forced to use two-factor authentication?
+fn is_mfa_required(state: &SharedState, user: &User) -> bool {
+    state.config.mfa_required_for_admin && user.roles.contains(&Role::Admin)
+}
+
+/// Returns a valid challenge from its token
+async fn get_challenge(stores: &Stores, token: &str) -> AppResult<MfaChallenge> {
+    let challenge = stores
+        .mfa
+        .get_challenge_by_hash(&MfaChallenge::hash(token))
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))?;
+
+    if !challenge.is_valid() {
+        stores.mfa.delete_challenge(&challenge.id).await?;
+
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    Ok(challenge)
+}
+
+/// Count a wrong code and delete the challenge after too many attempts
+async fn wrong_code<T>(stores: &Stores, challenge: &MfaChallenge) -> AppResult<T> {
+    if challenge.attempts + 1 >= CHALLENGE_MAX_ATTEMPTS {
+        stores.mfa.delete_challenge(&challenge.id).await?;
+    } else {
+        stores.mfa.increment_challenge_attempts(&challenge.id).await?;
+    }
+
+    Err(app_error!(AppErrorCode::Unauthorized))
+}
+
+/// Returns the user of the access token
+async fn get_authenticated_user(stores: &Stores, claims: &Claims) -> AppResult<User> {
+    stores
+        .users
+        .get_by_id(claims.user_id.clone())
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))
+}
+
+/// Returns the TOTP settings of a user which has enrolled TOTP
+async fn get_enabled_totp(stores: &Stores, user: &User) -> AppResult<UserTotp> {
+    stores
+        .mfa
+        .get_totp(&user.id)
+        .await?
+        .filter(UserTotp::is_enabled)
+        .ok_or_else(|| app_error!(AppErrorCode::BadRequest, "TOTP is not enabled"))
+}
+
+/// Generate and save a new TOTP secret (a confirmed enrollment cannot be restarted)
+async fn start_enrollment(stores: &Stores, user: &User) -> AppResult<TotpEnrollment> {
+    let totp = stores.mfa.get_totp(&user.id).await?.unwrap_or_default();
+    if totp.is_enabled() {
+        return Err(app_error!(AppErrorCode::BadRequest, "TOTP is already enabled"));
+    }
+
+    let totp = Totp::generate();
+    stores.mfa.set_totp_secret(&user.id, &totp.secret()).await?;
+
+    Ok(TotpEnrollment {
+        secret: totp.secret(),
+        provisioning_uri: totp.provisioning_uri(APP_NAME, &user.username),
+    })
+}
+
+/// Verify a TOTP code with a secret and returns the matching time step
+fn verify_totp(secret: &str, code: &str) -> Option<i64> {
+    Totp::from_base32(secret).and_then(|totp| totp.verify(code, Utc::now().timestamp()))
+}
+
+/// Verify a TOTP code (not already used) or a recovery code (used once)
+async fn verify_code(stores: &Stores, user_id: &str, totp: &UserTotp, code: &str) -> AppResult<bool> {
+    match totp.secret.as_deref().and_then(|secret| verify_totp(secret, code)) {
+        Some(step) => stores.mfa.use_totp_step(user_id, step).await,
+        None => stores.mfa.use_recovery_code(user_id, &RecoveryCode::hash(code)).await,
+    }
+}
+
+/// Replace the recovery codes of a user and returns the new ones
+async fn replace_recovery_codes(stores: &Stores, user_id: &str) -> AppResult<Vec<String>> {
+    let (recovery_codes, codes) = RecoveryCode::generate(user_id);
+    stores.mfa.replace_recovery_codes(user_id, &recovery_codes).await?;
+
+    Ok(codes)
+}
@@ -1,5 +1,10 @@
 //! Handlers module
 
+pub mod api_keys;
+pub mod me;
+pub mod mfa;
+pub mod oidc;
+pub mod roles;
 pub mod users;
 pub mod web;
 pub mod ws;
@@ -0,0 +1,148 @@
+//! API OpenID Connect login handlers
+
+use super::users::generate_tokens;
+use crate::{
+    app_error,
+    layers::SharedState,
+    models::{
+        mfa::LoginResult,
+        oidc::{OidcAuthorization, OidcCallback, UserIdentity},
+        user::User,
+    },
+    repositories::Stores,
+    utils::{
+        errors::{AppError, AppErrorCode, AppResult},
+        extractors::{ExtractRequestId, Query},
+        oidc::{IdTokenClaims, OidcClient, Pkce},
+    },
+};
+use axum::{
+    extract::{Json, State},
+    response::Redirect,
+};
+
+// Route: GET /api/v1/oidc/authorize
+#[instrument(skip(state))]
+pub async fn authorize(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Redirect> {
+    let client = get_client(&state)?;
+
+    state.stores.oidc.delete_expired_authorizations().await?;
+
+    let pkce = Pkce::generate();
+    let (authorization, oidc_state) = OidcAuthorization::new(pkce.verifier, state.config.oidc_authorization_lifetime);
+    let url = client
+        .authorization_url(&oidc_state, &authorization.nonce, &pkce.challenge)
+        .await?;
+    state.stores.oidc.create_authorization(&authorization).await?;
+
+    Ok(Redirect::to(&url))
+}
+
+// Route: GET /api/v1/oidc/callback
+#[instrument(name = "OIDC callback handler", skip(state, callback), level = "warn")]
+pub async fn callback(
+    Query(callback): Query<OidcCallback>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<LoginResult>> {
+    let client = get_client(&state)?;
+
+    // The state is deleted, so it cannot be used twice
+    let authorization = state
+        .stores
+        .oidc
+        .take_authorization(&OidcAuthorization::hash(&callback.state))
+        .await?
+        .filter(OidcAuthorization::is_valid)
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))?;
+
+    let code = match (callback.code, callback.error) {
+        (Some(code), None) => code,
+        (_, error) => {
+            warn!("OIDC login refused by the provider: {error:?}");
+            return Err(app_error!(AppErrorCode::Unauthorized));
+        }
+    };
+
+    let id_token = client.exchange_code(&code, &authorization.code_verifier).await?;
+    let claims = client.verify_id_token(&id_token, &authorization.nonce).await?;
+    let user = get_user(&state.stores, &claims).await?;
+
+    // A locked user cannot log in, even with an external provider
+    if state.stores.users.get_locked_until(&user.id).await?.is_some() {
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    // Second step with a TOTP code if two-factor authentication is enabled or required
+    match super::mfa::create_challenge(&state, &user).await? {
+        Some(challenge) => Ok(Json(LoginResult::MfaRequired(challenge))),
+        None => Ok(Json(LoginResult::Authenticated(
+            generate_tokens(&state, user, None).await?,
+        ))),
+    }
+}
+
+/// Returns the OIDC client
+fn get_client(state: &SharedState) -> AppResult<&OidcClient> {
+    state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "OIDC login is not enabled"))
+}
+
+/// Returns the user of an external identity.
+///
+/// At the first login, the identity is linked to the user with the same verified email
+/// (verified both by the provider and by the application).
+async fn get_user(stores: &Stores, claims: &IdTokenClaims) -> AppResult<User> {
+    let users = &stores.users;
+
+    if let Some(identity) = stores.oidc.get_identity(&claims.iss, &claims.sub).await? {
+        stores.oidc.touch_identity(&identity.id).await?;
+
+        return users
+            .get_by_id(identity.user_id)
+            .await?
+            .ok_or_else(|| app_error!(AppErrorCode::Unauthorized));
+    }
+
+    let email = claims.verified_email().ok_or_else(|| {
+        warn!("OIDC login without verified email for subject {}", claims.sub);
+        app_error!(AppErrorCode::Unauthorized)
+    })?;
+    let user = users
+        .get_by_email(email.to_owned())
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))?;
+
+    // An unverified account may have been registered by someone else with this email
+    if !users.is_email_verified(&user.id).await? {
+        warn!(
+            "OIDC identity {} not linked to user {}: email not verified",
+            claims.sub, user.id
+        );
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    // Another identity of the provider is already linked (e.g. an email address reassigned by the provider)
+    if stores.oidc.has_identity(&user.id, &claims.iss).await? {
+        warn!(
+            "OIDC identity {} not linked to user {}: another identity is linked",
+            claims.sub, user.id
+        );
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    let identity = UserIdentity::new(
+        user.id.clone(),
+        claims.iss.clone(),
+        claims.sub.clone(),
+        email.to_owned(),
+    );
+    stores.oidc.create_identity(&identity).await?;
+
+    Ok(user)
+}
@@ -0,0 +1,177 @@
+//! Roles handlers
+
+use super::users::revoke_user_tokens;
+use crate::{
+    app_error,
+    layers::SharedState,
+    models::{
+        role::{Permission, RoleCreation, RolePermissions, RoleUpdate},
+        user::{Role, User},
+    },
+    repositories::Stores,
+    utils::{
+        errors::{AppError, AppErrorCode, AppResult},
+        extractors::{ExtractRequestId, JwtAuthUser, Path},
+        validation::validate_request_data,
+    },
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+};
+use uuid::Uuid;
+
+// Route: GET /api/v1/roles
+#[instrument(skip(state))]
+pub async fn get_all(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<Vec<RolePermissions>>> {
+    Ok(Json(state.stores.roles.get_all().await?))
+}
+
+// Route: GET /api/v1/roles/:name
+#[instrument(skip(state))]
+pub async fn get(
+    Path(name): Path<String>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<RolePermissions>> {
+    let role = get_role(&name)?;
+
+    Ok(Json(get_role_permissions(&state.stores, &role).await?))
+}
+
+// Route: POST /api/v1/roles
+#[instrument(skip(state))]
+pub async fn create(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<RoleCreation>,
+) -> AppResult<(StatusCode, Json<RolePermissions>)> {
+    validate_request_data(&payload)?;
+
+    let role = RolePermissions::from(payload);
+    if state.stores.roles.get(&role.name).await?.is_some() {
+        return Err(app_error!(AppErrorCode::BadRequest, "role already exists"));
+    }
+
+    state.stores.roles.create(&role).await?;
+
+    Ok((StatusCode::CREATED, Json(role)))
+}
+
+// Route: PUT /api/v1/roles/:name
+#[instrument(skip(state))]
+pub async fn update(
+    Path(name): Path<String>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<RoleUpdate>,
+) -> AppResult<Json<RolePermissions>> {
+    validate_request_data(&payload)?;
+
+    let role = get_custom_role(&state.stores, &name).await?;
+    let role = RolePermissions {
+        name: role.name,
+        permissions: Permission::from_names(payload.permissions),
+    };
+
+    // Current tokens keep their permissions until they expire
+    state.stores.roles.update(&role).await?;
+
+    Ok(Json(role))
+}
+
+// Route: DELETE /api/v1/roles/:name
+#[instrument(skip(state))]
+pub async fn delete(
+    Path(name): Path<String>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    let role = get_custom_role(&state.stores, &name).await?;
+
+    if state.stores.roles.is_granted(&role.name).await? {
+        return Err(app_error!(AppErrorCode::BadRequest, "role is granted to users"));
+    }
+
+    state.stores.roles.delete(&role.name).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Route: PUT "/api/v1/users/:id/roles/:role"
+#[instrument(skip(state))]
+pub async fn grant(
+    Path((id, role)): Path<(Uuid, String)>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<User>> {
+    let user = get_user(&state.stores, id).await?;
+    let role = get_role_permissions(&state.stores, &get_role(&role)?).await?.name;
+
+    state.stores.roles.grant(&user.id, &role).await?;
+
+    Ok(Json(get_user(&state.stores, id).await?))
+}
+
+// Route: DELETE "/api/v1/users/:id/roles/:role"
+#[instrument(skip(claims, state))]
+pub async fn revoke(
+    Path((id, role)): Path<(Uuid, String)>,
+    JwtAuthUser(claims): JwtAuthUser,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<Json<User>> {
+    let user = get_user(&state.stores, id).await?;
+    let role = get_role(&role)?;
+
+    // Administrators cannot lock themselves out of the administration
+    if role == Role::Admin && user.id == claims.user_id {
+        return Err(app_error!(
+            AppErrorCode::BadRequest,
+            "you cannot revoke your own ADMIN role"
+        ));
+    }
+
+    // Tokens hold the roles of the user, so they are revoked with the role
+    if state.stores.roles.revoke(&user.id, &role).await? {
+        revoke_user_tokens(&state, &user.id).await?;
+    }
+
+    Ok(Json(get_user(&state.stores, id).await?))
+}
+
+/// Returns a not deleted user
+async fn get_user(stores: &Stores, id: Uuid) -> AppResult<User> {
+    stores
+        .users
+        .get_by_id(id.to_string())
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "no user found"))
+}
+
+/// Returns a role from its name
+fn get_role(name: &str) -> AppResult<Role> {
+    Role::try_from_str(name).ok_or_else(|| app_error!(AppErrorCode::NotFound, "no role found"))
+}
+
+/// Returns an existing role with its permissions
+async fn get_role_permissions(stores: &Stores, role: &Role) -> AppResult<RolePermissions> {
+    stores
+        .roles
+        .get(role)
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "no role found"))
+}
+
+/// Returns an existing custom role (built-in roles cannot be changed)
+async fn get_custom_role(stores: &Stores, name: &str) -> AppResult<RolePermissions> {
+    let role = get_role(name)?;
+    if role.is_built_in() {
+        return Err(app_error!(AppErrorCode::BadRequest, "built-in roles cannot be changed"));
+    }
+
+    get_role_permissions(stores, &role).await
+}
@@ -2,131 +2,536 @@
 
 use crate::{
     app_error,
-    emails::{forgotten_password::ForgottenPasswordEmail, SmtpConfig},
+    emails::{
+        account_locked::AccountLockedEmail, email_verification::EmailVerificationEmail,
+        forgotten_password::ForgottenPasswordEmail, password_changed::PasswordChangedEmail, template::Email,
+        welcome::WelcomeEmail, Message,
+    },
     layers::SharedState,
     models::{
-        auth::Jwt,
-        user::{Login, LoginResponse, PasswordReset, User, UserCreation, UserUpdatePassword},
+        auth::{Claims, Jwt, RefreshToken, RefreshTokenRequest},
+        email_outbox::OutboxEmail,
+        login_attempt::LoginAttempt,
+        mfa::LoginResult,
+        role::Permission,
+        user::{
+            EmailVerification, Login, LoginResponse, PasswordReset, PasswordScorer, PasswordStrength, Role, User,
+            UserCreation, UserRegistration, UserUpdatePassword,
+        },
+    },
+    repositories::{
+        user::{USER_CURSOR_SORT_FIELDS, USER_FILTER_FIELDS},
+        Stores,
     },
-    repositories::user::{PasswordResetRepository, UserRepository},
     utils::{
         errors::{AppError, AppErrorCode, AppResult},
-        extractors::{ExtractRequestId, Path, Query},
-        query::{PaginateResponse, PaginateSort, PaginateSortQuery},
+        extractors::{AuthUser, ExtractRequestId, JwtAuthUser, Path, Query},
+        query::{
+            CursorQuery, FilterQuery, Filters, KeysetPagination, PaginateResponse, PaginateSort, PaginateSortQuery,
+        },
         validation::validate_request_data,
     },
 };
 use axum::{
-    extract::{Extension, Json, State},
+    extract::{ConnectInfo, Json, State},
     http::StatusCode,
 };
-use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
-use sqlx::{MySql, Pool};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use std::net::SocketAddr;
+use std::time::Duration as StdDuration;
+use tokio::time::Instant;
 use uuid::Uuid;
 
+/// Minimum response time of a forgotten password request, so that it does not reveal if the email exists
+pub const FORGOTTEN_PASSWORD_MIN_RESPONSE_TIME: StdDuration = StdDuration::from_millis(300);
+
 // Route: POST /api/v1/login
-#[instrument(name = "Login handler", skip(pool, state), level = "warn")]
+#[instrument(name = "Login handler", skip(state, addr), level = "warn")]
 pub async fn login(
-    Extension(pool): Extension<Pool<MySql>>,
     State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
+    addr: Option<ConnectInfo<SocketAddr>>,
     Json(payload): Json<Login>,
-) -> AppResult<Json<LoginResponse>> {
+) -> AppResult<Json<LoginResult>> {
     // warn!("In Login handler");
 
     validate_request_data(&payload)?;
 
+    let ip = addr.map(|ConnectInfo(addr)| addr.ip().to_string()).unwrap_or_default();
+    let username = payload.username.clone();
+    let since = Utc::now() - Duration::seconds(state.config.login_failures_window);
+
+    // Too many failed logins from this IP address
+    if !ip.is_empty()
+        && state.stores.login_attempts.count_by_ip(&ip, since).await? >= state.config.login_ip_max_failures
+    {
+        return Err(app_error!(AppErrorCode::TooManyRequests));
+    }
+
     // Search user in database and return `LoginResponse`
-    let user = UserRepository::login(&pool, payload).await?;
+    let user = state.stores.users.login(&state.config.password_hasher, payload).await?;
+
+    // A locked user cannot log in, even with the right password
+    let user = match user {
+        Some(user) if state.stores.users.get_locked_until(&user.id).await?.is_none() => Some(user),
+        _ => None,
+    };
+
     match user {
-        None => Err(app_error!(AppErrorCode::Unauthorized)),
+        None => {
+            login_failed(&state, &username, &ip, since).await?;
+
+            // Same error for unknown, locked and wrong credentials to not leak existing usernames
+            Err(app_error!(AppErrorCode::Unauthorized))
+        }
         Some(user) => {
-            // Token generation
-            let encoding_key = &state.config.jwt_encoding_key.clone();
-            let jwt_lifetime = state.config.jwt_lifetime;
-            let roles = match user.roles {
-                Some(roles) => roles,
-                None => String::new(),
-            };
-            let token = Jwt::generate(
-                user.id.to_owned(),
-                user.rate_limit,
-                roles.clone(),
-                encoding_key,
-                jwt_lifetime,
-            );
-
-            match token {
-                Ok(token) => match NaiveDateTime::from_timestamp_opt(token.1, 0) {
-                    Some(expires_at) => {
-                        let expires_at: DateTime<Utc> = DateTime::from_naive_utc_and_offset(expires_at, Utc);
-
-                        Ok(Json(LoginResponse {
-                            id: user.id.to_owned(),
-                            lastname: user.lastname.to_owned(),
-                            firstname: user.firstname.to_owned(),
-                            username: user.username,
-                            roles,
-                            token: token.0,
-                            expires_at: expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
-                        }))
-                    }
-                    None => Err(app_error!(
-                        AppErrorCode::InternalError,
-                        "error during JWT generation",
-                        format!(
-                            "error during JWT generation: invalid 'expired_at' field in JWT claims ({})",
-                            token.1
-                        )
-                    )),
-                },
-                Err(err) => Err(app_error!(
-                    AppErrorCode::InternalError,
-                    "error during JWT generation",
-                    format!("error during JWT generation: {err}")
-                )),
+            state.stores.login_attempts.delete_by_username(&user.username).await?;
+
+            // A registered user must verify its email before logging in
+            if !state.stores.users.is_email_verified(&user.id).await? {
+                return Err(app_error!(AppErrorCode::Forbidden));
+            }
+
+            // Second step with a TOTP code if two-factor authentication is enabled or required
+            match super::mfa::create_challenge(&state, &user).await? {
+                Some(challenge) => Ok(Json(LoginResult::MfaRequired(challenge))),
+                None => Ok(Json(LoginResult::Authenticated(
+                    generate_tokens(&state, user, None).await?,
+                ))),
             }
         }
     }
 }
 
+// Route: POST /api/v1/token/refresh
+#[instrument(skip(state, payload))]
+pub async fn refresh_token(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> AppResult<Json<LoginResponse>> {
+    validate_request_data(&payload)?;
+
+    let token_hash = RefreshToken::hash(&payload.refresh_token);
+    let refresh_token = state
+        .stores
+        .refresh_tokens
+        .get_by_hash(&token_hash)
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))?;
+
+    if !refresh_token.is_valid() {
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    // Reuse detection: an already used token means that it has been stolen,
+    // so the whole family is revoked
+    if refresh_token.used_at.is_some() || !state.stores.refresh_tokens.use_token(&refresh_token.id).await? {
+        warn!(
+            "refresh token reuse detected for user {}, revoking token family {}",
+            refresh_token.user_id, refresh_token.family_id
+        );
+        state
+            .stores
+            .refresh_tokens
+            .revoke_family(&refresh_token.family_id)
+            .await?;
+
+        return Err(app_error!(AppErrorCode::Unauthorized));
+    }
+
+    match state.stores.users.get_by_id(refresh_token.user_id).await? {
+        None => Err(app_error!(AppErrorCode::Unauthorized)),
+        Some(user) => Ok(Json(
+            generate_tokens(&state, user, Some(refresh_token.family_id)).await?,
+        )),
+    }
+}
+
+// Route: POST /api/v1/logout
+#[instrument(skip(claims, state, payload))]
+pub async fn logout(
+    JwtAuthUser(claims): JwtAuthUser,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    payload: Option<Json<RefreshTokenRequest>>,
+) -> AppResult<StatusCode> {
+    // Access token revocation
+    state.revoked_tokens.revoke(&claims.jti, claims.exp).await?;
+
+    // Refresh token family revocation (optional)
+    if let Some(Json(payload)) = payload {
+        validate_request_data(&payload)?;
+
+        let token_hash = RefreshToken::hash(&payload.refresh_token);
+        if let Some(refresh_token) = state.stores.refresh_tokens.get_by_hash(&token_hash).await? {
+            if refresh_token.user_id == claims.user_id {
+                state
+                    .stores
+                    .refresh_tokens
+                    .revoke_family(&refresh_token.family_id)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Revoke all the access and refresh tokens of a user
+pub(super) async fn revoke_user_tokens(state: &SharedState, user_id: &str) -> AppResult<()> {
+    state
+        .revoked_tokens
+        .revoke_user(user_id, state.config.jwt_lifetime)
+        .await?;
+    state.stores.refresh_tokens.revoke_user(user_id).await?;
+
+    Ok(())
+}
+
+/// Add an email in the outbox (sent by the email worker)
+pub(super) async fn queue_email(stores: &Stores, message: Message) -> AppResult<()> {
+    stores.email_outbox.create(&OutboxEmail::new(message)).await
+}
+
+/// Record a failed login, lock the account after too many failures and slow down the answer
+async fn login_failed(state: &SharedState, username: &str, ip: &str, since: DateTime<Utc>) -> AppResult<()> {
+    state
+        .stores
+        .login_attempts
+        .create(&LoginAttempt::new(username, ip))
+        .await?;
+    let failures = state.stores.login_attempts.count_by_username(username, since).await?;
+
+    if failures >= state.config.login_max_failures {
+        lock_user(state, username).await?;
+    }
+
+    // Failures are counted by username, so the delay is the same for unknown usernames
+    tokio::time::sleep(LoginAttempt::delay(failures, state.config.login_delay)).await;
+
+    Ok(())
+}
+
+/// Lock a user (if it exists and is not already locked) and send an unlock link by email
+async fn lock_user(state: &SharedState, username: &str) -> AppResult<()> {
+    let user = match state.stores.users.get_by_email(username.to_owned()).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    if state.stores.users.get_locked_until(&user.id).await?.is_some() {
+        return Ok(());
+    }
+
+    let locked_until = Utc::now() + Duration::seconds(state.config.login_lockout_duration);
+    let (token, token_hash) = LoginAttempt::unlock_token();
+    state.stores.users.lock(&user.id, locked_until, &token_hash).await?;
+    warn!(
+        "user {} locked until {locked_until} after too many failed logins",
+        user.id
+    );
+
+    // An email error must not change the answer of the login request
+    let message = AccountLockedEmail::new(
+        &user.firstname,
+        state.config.login_lockout_duration / 60,
+        &state.config.login_unlock_base_url,
+        &token,
+    )
+    .and_then(|email| email.message(&state.config.email_locale, &state.config.email_from, &user.username));
+    match message {
+        Ok(message) => queue_email(&state.stores, message).await,
+        Err(err) => {
+            error!("cannot send account locked email to user {}: {err}", user.id);
+            Ok(())
+        }
+    }
+}
+
+/// Unlock a user and forget its failed logins
+async fn unlock_user(stores: &Stores, user: &User) -> AppResult<()> {
+    stores.users.unlock(&user.id).await?;
+    stores.login_attempts.delete_by_username(&user.username).await?;
+
+    Ok(())
+}
+
+/// Confirm a password change by email
+pub(super) async fn send_password_changed_email(state: &SharedState, user: &User) -> AppResult<()> {
+    let message = PasswordChangedEmail::new(&user.firstname).message(
+        &state.config.email_locale,
+        &state.config.email_from,
+        &user.username,
+    )?;
+
+    queue_email(&state.stores, message).await
+}
+
+/// Create a new email verification for a user and send the verification link by email
+async fn send_verification_email(state: &SharedState, user: &User) -> AppResult<()> {
+    let (verification, token) = EmailVerification::new(user.id.clone(), state.config.email_verification_lifetime);
+    state.stores.email_verifications.create(&verification).await?;
+
+    queue_verification_email(state, user, &token).await
+}
+
+/// Queue the email with the verification link
+async fn queue_verification_email(state: &SharedState, user: &User, token: &str) -> AppResult<()> {
+    let message = EmailVerificationEmail::new(
+        &user.firstname,
+        state.config.email_verification_lifetime,
+        &state.config.email_verification_base_url,
+        token,
+    )?
+    .message(&state.config.email_locale, &state.config.email_from, &user.username)?;
+
+    queue_email(&state.stores, message).await
+}
+
+/// Check that all the roles exist (custom roles can be deleted)
+async fn check_roles_exist(stores: &Stores, roles: &[Role]) -> AppResult<()> {
+    for role in roles.iter().filter(|role| !role.is_built_in()) {
+        if stores.roles.get(role).await?.is_none() {
+            return Err(app_error!(AppErrorCode::BadRequest, format!("unknown role: {role}")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Only users with the `roles:manage` permission can choose the roles of a user
+fn check_roles_manage(claims: &Claims) -> AppResult<()> {
+    match claims.has_permission(Permission::RolesManage) {
+        true => Ok(()),
+        false => Err(app_error!(AppErrorCode::Forbidden)),
+    }
+}
+
+/// A user can only be changed by a user with all its permissions (or with the `roles:manage` permission),
+/// so that the password of a more privileged user cannot be changed
+async fn check_user_manage(stores: &Stores, claims: &Claims, user: &User) -> AppResult<()> {
+    let permissions = stores.roles.get_permissions(&user.roles).await?;
+
+    match claims.has_permission(Permission::RolesManage)
+        || permissions.iter().all(|permission| claims.has_permission(*permission))
+    {
+        true => Ok(()),
+        false => Err(app_error!(AppErrorCode::Forbidden)),
+    }
+}
+
+/// Generate access and refresh tokens for a user.
+///
+/// The refresh token starts a new family if `family_id` is `None`.
+pub(super) async fn generate_tokens(
+    state: &SharedState,
+    user: User,
+    family_id: Option<String>,
+) -> AppResult<LoginResponse> {
+    // Permissions are resolved once and stored in the access token
+    let permissions = state.stores.roles.get_permissions(&user.roles).await?;
+
+    // Access token generation
+    let (token, expires_at) = Jwt::generate(
+        user.id.to_owned(),
+        user.rate_limit,
+        user.roles.clone(),
+        permissions,
+        &state.config.jwt_keys,
+        state.config.jwt_lifetime,
+    )?;
+    let expires_at = DateTime::<Utc>::from_timestamp(expires_at, 0).ok_or_else(|| {
+        app_error!(
+            AppErrorCode::InternalError,
+            "error during JWT generation",
+            format!("error during JWT generation: invalid 'expired_at' field in JWT claims ({expires_at})")
+        )
+    })?;
+
+    // Refresh token generation
+    let (refresh_token, refresh_token_value) =
+        RefreshToken::new(user.id.clone(), family_id, state.config.jwt_refresh_lifetime);
+    state.stores.refresh_tokens.create(&refresh_token).await?;
+
+    Ok(LoginResponse {
+        id: user.id,
+        lastname: user.lastname,
+        firstname: user.firstname,
+        username: user.username,
+        roles: user.roles,
+        token,
+        expires_at: expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        refresh_token: refresh_token_value,
+        refresh_token_expires_at: refresh_token.expired_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        recovery_codes: None,
+    })
+}
+
 // Route: POST /api/v1/users
-#[instrument(skip(pool))]
+#[instrument(skip(claims, state))]
 pub async fn create(
-    Extension(pool): Extension<Pool<MySql>>,
+    AuthUser(claims): AuthUser,
+    State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
     Json(payload): Json<UserCreation>,
 ) -> AppResult<Json<User>> {
     validate_request_data(&payload)?;
 
+    let roles = payload.roles();
+    check_roles_exist(&state.stores, &roles).await?;
+    if roles.iter().any(|role| *role != Role::User) {
+        check_roles_manage(&claims)?;
+    }
+
     let mut user = User::new(payload);
-    UserRepository::create(&pool, &mut user).await?;
+    state
+        .stores
+        .users
+        .create(&state.config.password_hasher, &mut user)
+        .await?;
+
+    let message = WelcomeEmail::new(&user.lastname, &user.firstname, &user.username).message(
+        &state.config.email_locale,
+        &state.config.email_from,
+        &user.username,
+    )?;
+    queue_email(&state.stores, message).await?;
 
     Ok(Json(user))
 }
 
+// Route: POST /api/v1/register
+#[instrument(skip(state, payload))]
+pub async fn register(
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+    Json(payload): Json<UserRegistration>,
+) -> AppResult<StatusCode> {
+    if !state.config.registration_enabled {
+        return Err(app_error!(AppErrorCode::NotFound, "registration is not enabled"));
+    }
+
+    validate_request_data(&payload)?;
+
+    if !mailchecker::is_valid(&payload.username) {
+        return Err(app_error!(AppErrorCode::BadRequest, "invalid email"));
+    }
+    if !PasswordScorer::valid(&payload.password, PasswordStrength::Good) {
+        return Err(app_error!(AppErrorCode::BadRequest, "password is not strong enough"));
+    }
+
+    // Same answer for an existing email to not leak registered users
+    match state.stores.users.get_by_email(payload.username.clone()).await? {
+        Some(user) => {
+            // The verification link is sent again if the email is not verified yet
+            if !state.stores.users.is_email_verified(&user.id).await? {
+                state.stores.email_verifications.delete_by_user(&user.id).await?;
+                send_verification_email(&state, &user).await?;
+            }
+        }
+        None => {
+            let mut user = User::new(UserCreation {
+                lastname: payload.lastname,
+                firstname: payload.firstname,
+                username: payload.username,
+                password: payload.password,
+                roles: vec![Role::User.to_string()],
+                rate_limit: state.config.registration_rate_limit,
+            });
+            let (verification, token) =
+                EmailVerification::new(user.id.clone(), state.config.email_verification_lifetime);
+            state
+                .stores
+                .users
+                .register(&state.config.password_hasher, &mut user, &verification)
+                .await?;
+
+            queue_verification_email(&state, &user, &token).await?;
+        }
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Route: GET|POST "/api/v1/verify-email/:token"
+#[instrument(skip(state, token))]
+pub async fn verify_email(
+    Path(token): Path<String>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    let verification = state
+        .stores
+        .email_verifications
+        .get_by_hash(&EmailVerification::hash(&token))
+        .await?
+        .ok_or_else(|| app_error!(AppErrorCode::NotFound, "no email verification found"))?;
+
+    if !verification.is_valid() {
+        return Err(app_error!(
+            AppErrorCode::BadRequest,
+            "email verification link is expired"
+        ));
+    }
+
+    state.stores.users.verify_email(&verification.user_id).await?;
+
+    if let Some(user) = state.stores.users.get_by_id(verification.user_id).await? {
+        let message = WelcomeEmail::new(&user.lastname, &user.firstname, &user.username).message(
+            &state.config.email_locale,
+            &state.config.email_from,
+            &user.username,
+        )?;
+        queue_email(&state.stores, message).await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 // Route: GET /api/v1/users
-// TODO: Add filter
-#[instrument(skip(pool))]
+#[instrument(skip(state))]
 pub async fn get_all(
     Query(pagination): Query<PaginateSortQuery>,
-    Extension(pool): Extension<Pool<MySql>>,
+    Query(filter): Query<FilterQuery>,
+    Query(cursor): Query<CursorQuery>,
+    State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
 ) -> AppResult<Json<PaginateResponse<Vec<User>>>> {
     let paginate_sort = PaginateSort::from(pagination);
-    let users = UserRepository::get_all(&pool, &paginate_sort).await?;
+    let filters = Filters::parse(&filter.filter.unwrap_or_default(), USER_FILTER_FIELDS)?;
+    let with_total = cursor.total.unwrap_or(true);
+
+    let users = match cursor.cursor {
+        // Cursor pagination
+        Some(cursor) => {
+            let pagination = KeysetPagination::new(
+                &paginate_sort,
+                USER_CURSOR_SORT_FIELDS,
+                "id",
+                &cursor,
+                &state.config.cursor_secret_key,
+            )?;
+            state
+                .stores
+                .users
+                .get_all_by_cursor(&pagination, &filters, with_total, &state.config.cursor_secret_key)
+                .await?
+        }
+        // Offset pagination
+        None => state.stores.users.get_all(&paginate_sort, &filters, with_total).await?,
+    };
 
     Ok(Json(users))
 }
 
 // Route: GET "/api/v1/users/:id"
-#[instrument(skip(pool))]
+#[instrument(skip(state))]
 pub async fn get_by_id(
     Path(id): Path<Uuid>,
-    Extension(pool): Extension<Pool<MySql>>,
+    State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
 ) -> AppResult<Json<User>> {
-    let user = UserRepository::get_by_id(&pool, id.to_string()).await?;
+    let user = state.stores.users.get_by_id(id.to_string()).await?;
     match user {
         Some(user) => Ok(Json(user)),
         _ => Err(app_error!(AppErrorCode::NotFound, "no user found")),
@@ -134,15 +539,19 @@ pub async fn get_by_id(
 }
 
 // Route: DELETE "/api/v1/users/:id"
-#[instrument(skip(pool))]
+#[instrument(skip(state))]
 pub async fn delete(
     Path(id): Path<Uuid>,
-    Extension(pool): Extension<Pool<MySql>>,
+    State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
 ) -> AppResult<StatusCode> {
-    let result = UserRepository::delete(&pool, id.to_string()).await?;
+    let result = state.stores.users.delete(id.to_string()).await?;
     match result {
-        1 => Ok(StatusCode::NO_CONTENT),
+        1 => {
+            revoke_user_tokens(&state, &id.to_string()).await?;
+
+            Ok(StatusCode::NO_CONTENT)
+        }
         _ => Err(app_error!(
             AppErrorCode::InternalError,
             "no user or user already deleted"
@@ -151,80 +560,183 @@ pub async fn delete(
 }
 
 // Route: PUT "/api/v1/users/:id"
-#[instrument(skip(pool))]
+#[instrument(skip(claims, state))]
 pub async fn update(
     Path(id): Path<Uuid>,
-    Extension(pool): Extension<Pool<MySql>>,
+    AuthUser(claims): AuthUser,
+    State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
     Json(payload): Json<UserCreation>,
 ) -> AppResult<Json<User>> {
     validate_request_data(&payload)?;
 
-    UserRepository::update(&pool, id.to_string(), &payload).await?;
+    let roles = payload.roles();
+    check_roles_exist(&state.stores, &roles).await?;
 
-    let user = UserRepository::get_by_id(&pool, id.to_string()).await?;
+    // Tokens must be revoked only if the password has changed
+    let password_changed = match state.stores.users.get_by_id(id.to_string()).await? {
+        Some(user) => {
+            check_user_manage(&state.stores, &claims, &user).await?;
+            if roles != user.roles {
+                check_roles_manage(&claims)?;
+            }
+
+            !state
+                .config
+                .password_hasher
+                .verify_async(&payload.password, &user.password)
+                .await?
+                .is_valid()
+        }
+        None => false,
+    };
+
+    state
+        .stores
+        .users
+        .update(&state.config.password_hasher, id.to_string(), &payload)
+        .await?;
+
+    let user = state.stores.users.get_by_id(id.to_string()).await?;
     match user {
-        Some(user) => Ok(Json(user)),
+        Some(user) => {
+            if password_changed {
+                revoke_user_tokens(&state, &user.id).await?;
+                send_password_changed_email(&state, &user).await?;
+            }
+
+            Ok(Json(user))
+        }
         _ => Err(app_error!(AppErrorCode::NotFound, "no user found")),
     }
 }
 
+// Route: POST "/api/v1/users/:id/unlock"
+#[instrument(skip(state))]
+pub async fn unlock(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    match state.stores.users.get_by_id(id.to_string()).await? {
+        None => Err(app_error!(AppErrorCode::NotFound, "no user found")),
+        Some(user) => {
+            unlock_user(&state.stores, &user).await?;
+
+            Ok(StatusCode::NO_CONTENT)
+        }
+    }
+}
+
+// Route: POST "/api/v1/unlock/:token"
+#[instrument(skip(state, token))]
+pub async fn unlock_account(
+    Path(token): Path<String>,
+    State(state): State<SharedState>,
+    ExtractRequestId(request_id): ExtractRequestId,
+) -> AppResult<StatusCode> {
+    let token_hash = LoginAttempt::hash_unlock_token(&token);
+
+    // The token is cleared by the unlock, and is no longer valid once the lockout is over
+    match state.stores.users.get_by_unlock_token(&token_hash).await? {
+        None => Err(app_error!(AppErrorCode::NotFound, "no user found")),
+        Some(user) => {
+            unlock_user(&state.stores, &user).await?;
+
+            Ok(StatusCode::NO_CONTENT)
+        }
+    }
+}
+
 // Route: POST "/api/v1/forgotten-password/:email"
-#[instrument(skip(pool, state))]
+#[instrument(skip(state))]
 pub async fn forgotten_password(
     Path(email): Path<String>,
     State(state): State<SharedState>,
-    Extension(pool): Extension<Pool<MySql>>,
     ExtractRequestId(request_id): ExtractRequestId,
-) -> AppResult<Json<PasswordReset>> {
-    match UserRepository::get_by_email(&pool, email.clone()).await? {
-        None => Err(app_error!(AppErrorCode::NotFound, "no user found")),
-        Some(user) => {
-            let mut password_reset = PasswordReset::new(user.id, state.config.forgotten_password_expiration_duration);
-
-            // Save in database
-            PasswordResetRepository::create_or_update(&pool, &mut password_reset).await?;
-
-            // Send email
-            ForgottenPasswordEmail::send(
-                &SmtpConfig {
-                    host: state.config.smtp_host.clone(),
-                    port: state.config.smtp_port,
-                    timeout: state.config.smtp_timeout,
-                },
-                state.config.forgotten_password_base_url.clone(),
-                state.config.forgotten_password_email_from.clone(),
-                email,
-                password_reset.token.clone(),
-            )?;
+) -> AppResult<StatusCode> {
+    let deadline = Instant::now() + FORGOTTEN_PASSWORD_MIN_RESPONSE_TIME;
+    let result = send_password_reset(&state, &email).await;
+
+    // The answer is the same for unknown emails, and is sent after the same delay
+    tokio::time::sleep_until(deadline).await;
+
+    result.map(|_| StatusCode::ACCEPTED)
+}
+
+/// Create a password reset and send its link by email (nothing is done for an unknown email or during the cooldown)
+async fn send_password_reset(state: &SharedState, email: &str) -> AppResult<()> {
+    let Some(user) = state.stores.users.get_by_email(email.to_owned()).await? else {
+        return Ok(());
+    };
 
-            Ok(Json(password_reset))
+    // No new email during the cooldown
+    if let Some(password_reset) = state.stores.password_resets.get_by_user(&user.id).await? {
+        if !password_reset.can_be_renewed(state.config.forgotten_password_cooldown) {
+            return Ok(());
         }
     }
+
+    // Save in database (the previous token is no longer valid)
+    let (password_reset, token) = PasswordReset::new(user.id, state.config.forgotten_password_expiration_duration);
+    state.stores.password_resets.create_or_update(&password_reset).await?;
+
+    // Send email
+    let message = ForgottenPasswordEmail::new(&state.config.forgotten_password_base_url, &token)?.message(
+        &state.config.email_locale,
+        &state.config.forgotten_password_email_from,
+        email,
+    )?;
+
+    queue_email(&state.stores, message).await
 }
 
 // Route: PATCH "/api/v1/update-password/:token"
-#[instrument(skip(pool))]
+#[instrument(skip(state, token))]
 pub async fn update_password(
-    Path(token): Path<Uuid>,
-    Extension(pool): Extension<Pool<MySql>>,
+    Path(token): Path<String>,
+    State(state): State<SharedState>,
     ExtractRequestId(request_id): ExtractRequestId,
     Json(payload): Json<UserUpdatePassword>,
 ) -> AppResult<StatusCode> {
     validate_request_data(&payload)?;
 
-    let result = PasswordResetRepository::get_user_id_from_token(&pool, token.to_string()).await?;
-    match result {
-        Some((user_id, current_password)) => {
-            // Update user password
-            let password = payload.password;
-            UserRepository::update_password(&pool, user_id.clone(), current_password, password).await?;
+    let token_hash = PasswordReset::hash(&token);
+    let Some((user_id, current_password)) = state.stores.password_resets.get_user_id_from_hash(&token_hash).await?
+    else {
+        return Err(app_error!(AppErrorCode::NotFound, "no user found"));
+    };
 
-            // Delete password reset entry
-            PasswordResetRepository::delete(&pool, user_id).await?;
+    let hasher = &state.config.password_hasher;
+    if hasher
+        .verify_async(&payload.password, &current_password)
+        .await?
+        .is_valid()
+    {
+        return Err(app_error!(
+            AppErrorCode::BadRequest,
+            "new password cannot be the same as the current one"
+        ));
+    }
 
-            Ok(StatusCode::OK)
-        }
-        _ => Err(app_error!(AppErrorCode::NotFound, "no user found")),
+    // Update user password and delete the password reset (a token is only used once)
+    let password = hasher.hash_async(&payload.password).await?;
+    if !state
+        .stores
+        .password_resets
+        .reset_password(&user_id, &token_hash, &password)
+        .await?
+    {
+        return Err(app_error!(AppErrorCode::NotFound, "no user found"));
     }
+
+    // Revoke user tokens
+    revoke_user_tokens(&state, &user_id).await?;
+
+    // Send confirmation email
+    if let Some(user) = state.stores.users.get_by_id(user_id).await? {
+        send_password_changed_email(&state, &user).await?;
+    }
+
+    Ok(StatusCode::OK)
 }
@@ -1,14 +1,17 @@
 //! Web handlers
 
+use crate::layers::SharedState;
 use crate::utils::errors::{AppError, AppErrorCode, AppResult};
 use crate::{app_error, TEMPLATES};
 use axum::{
     body::Body,
+    extract::State,
     http::header::CONTENT_TYPE,
     response::{AppendHeaders, Html, IntoResponse},
     Json,
 };
 use bytes::{Bytes, BytesMut};
+use jsonwebtoken::jwk::JwkSet;
 use serde::Serialize;
 use std::time::Duration;
 use tera::Context;
@@ -19,6 +22,11 @@ pub async fn health_check<'a>() -> &'a str {
     "OK"
 }
 
+// Route: GET "/.well-known/jwks.json"
+pub async fn jwks(State(state): State<SharedState>) -> Json<JwkSet> {
+    Json(state.config.jwt_keys.jwks().clone())
+}
+
 // Route: GET "/doc/api-v1"
 pub async fn doc_api_v1() -> AppResult<Html<String>> {
     Ok(Html(
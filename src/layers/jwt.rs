@@ -1,9 +1,19 @@
 //! JWT layer
+//!
+//! Requests are authenticated with a JWT (`Authorization: Bearer <token>`) or an API key (`X-API-Key: <key>`).
+//! The claims, the roles and the permissions of the authenticated request are added to the request extensions
+//! (see `AuthUser`, `OptionalAuthUser` and `RequireRole` extractors, and `RequirePermissionLayer`).
 
-use super::{body_from_parts, SharedState};
-use crate::models::auth::Claims;
+use super::{
+    body_from_parts, error_response, rate_limiter::set_headers, rate_limiter::RateLimiterFailureMode, SharedState,
+};
+use crate::models::{
+    api_key::{ApiKey, API_KEY_HEADER},
+    auth::{Claims, UserPermissions, UserRoles},
+};
 use axum::{
     body::Body,
+    extract::OriginalUri,
     http::{Request, StatusCode},
     response::Response,
 };
@@ -11,6 +21,9 @@ use futures::future::BoxFuture;
 use std::task::{Context, Poll};
 use tower::{Layer, Service};
 
+/// Namespace of the API keys rate limits
+const API_KEY_RATE_LIMITER_NAME: &str = "api_key";
+
 #[derive(Clone)]
 pub struct JwtLayer {
     pub state: SharedState,
@@ -35,7 +48,7 @@ pub struct JwtMiddleware<S> {
 
 impl<S> Service<Request<Body>> for JwtMiddleware<S>
 where
-    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
     S::Future: Send + 'static,
 {
     type Response = S::Response;
@@ -48,26 +61,117 @@ where
     }
 
     fn call(&mut self, request: Request<Body>) -> Self::Future {
-        let is_authorized =
-            match Claims::extract_from_request(request.headers(), &self.state.config.jwt_decoding_key.clone()) {
-                Some(claims) => claims.is_ok(),
-                _ => false,
-            };
+        // The JWT may already have been decoded by the rate limiter
+        let (mut parts, body) = request.into_parts();
+        let claims = Claims::extract_from_parts(&mut parts, &self.state.config.jwt_keys);
+        let api_key = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim().to_owned());
+
+        // The path of a nested router does not contain the prefix
+        let path = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map(|uri| uri.path().to_owned())
+            .unwrap_or_else(|| parts.uri.path().to_owned());
+        let mut request = Request::from_parts(parts, body);
+        let state = self.state.clone();
+
+        // The ready service is used and replaced by a clone
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
 
-        let future = self.inner.call(request);
         Box::pin(async move {
-            let mut response = Response::default();
-
-            response = match is_authorized {
-                true => future.await?,
-                false => {
-                    let (mut parts, _body) = response.into_parts();
-                    let msg = body_from_parts(&mut parts, StatusCode::UNAUTHORIZED, "Unauthorized", None);
-                    Response::from_parts(parts, Body::from(msg))
-                }
+            let result = match (claims, api_key) {
+                // Revoked tokens are rejected
+                (Some(Ok(claims)), _) => match state.revoked_tokens.is_revoked(&claims).await {
+                    Ok(false) => Ok(claims),
+                    Ok(true) => Err(error_response(StatusCode::UNAUTHORIZED, "Unauthorized")),
+                    Err(err) => {
+                        error!("error during revoked token check: {err}");
+                        Err(error_response(StatusCode::UNAUTHORIZED, "Unauthorized"))
+                    }
+                },
+                (None, Some(key)) => authenticate_api_key(&state, &path, &key).await,
+                _ => Err(error_response(StatusCode::UNAUTHORIZED, "Unauthorized")),
             };
 
-            Ok(response)
+            match result {
+                Ok(claims) => {
+                    request.extensions_mut().insert(UserRoles(claims.roles()));
+                    request
+                        .extensions_mut()
+                        .insert(UserPermissions(claims.user_permissions.iter().copied().collect()));
+                    request.extensions_mut().insert(claims);
+                    inner.call(request).await
+                }
+                Err(response) => Ok(response),
+            }
         })
     }
 }
+
+/// Check an API key (expiration, routes and rate limit) and returns the claims of the request
+async fn authenticate_api_key(state: &SharedState, path: &str, key: &str) -> Result<Claims, Response> {
+    let unauthorized = || error_response(StatusCode::UNAUTHORIZED, "Unauthorized");
+
+    if !ApiKey::is_well_formed(key) {
+        return Err(unauthorized());
+    }
+
+    let api_key = match state.stores.api_keys.get_by_hash(&ApiKey::hash(key)).await {
+        Ok(Some(api_key)) if api_key.is_valid() => api_key,
+        Ok(_) => return Err(unauthorized()),
+        Err(err) => {
+            error!("error during API key check: {err}");
+            return Err(unauthorized());
+        }
+    };
+
+    if !api_key.allows_route(path) {
+        return Err(error_response(StatusCode::FORBIDDEN, "Forbidden"));
+    }
+
+    // Each key has its own rate limit
+    if let (Some(limiter), true) = (state.rate_limiter.as_ref(), api_key.rate_limit >= 0) {
+        match limiter
+            .check(API_KEY_RATE_LIMITER_NAME, &api_key.id, api_key.rate_limit as i64)
+            .await
+        {
+            Ok(result) if result.remaining < 0 => {
+                let (mut parts, _body) = Response::new(Body::empty()).into_parts();
+                set_headers(&mut parts, api_key.rate_limit, result.remaining, result.reset);
+
+                let msg = body_from_parts(&mut parts, StatusCode::TOO_MANY_REQUESTS, "Too Many Requests", None);
+                return Err(Response::from_parts(parts, Body::from(msg)));
+            }
+            Ok(_) => (),
+            Err(err) => match limiter.config.failure_mode {
+                RateLimiterFailureMode::Open => {
+                    warn!("rate limiter store unavailable, request accepted without limit: {err}")
+                }
+                RateLimiterFailureMode::Closed => {
+                    return Err(error_response(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable"))
+                }
+            },
+        }
+    }
+
+    if let Err(err) = state.stores.api_keys.touch(&api_key.id).await {
+        error!("error during API key last use update: {err}");
+    }
+
+    // Permissions are not stored with the key, they follow the changes of its roles
+    let mut claims = api_key.claims();
+    claims.user_permissions = match state.stores.roles.get_permissions(&claims.user_roles).await {
+        Ok(permissions) => permissions,
+        Err(err) => {
+            error!("error during API key permissions resolution: {err}");
+            return Err(unauthorized());
+        }
+    };
+
+    Ok(claims)
+}
@@ -3,12 +3,21 @@
 pub mod basic_auth;
 pub mod jwt;
 pub mod logger;
+pub mod permissions;
 pub mod prometheus;
 pub mod rate_limiter;
+pub mod roles;
 
 use crate::app_error;
 use crate::config::Config;
-use crate::utils::errors::{AppError, AppErrorCode, AppErrorMessage};
+use crate::layers::rate_limiter::RateLimiter;
+use crate::models::api_key::API_KEY_HEADER;
+use crate::repositories::revoked_token::RevokedTokenStore;
+use crate::repositories::Stores;
+use crate::utils::errors::{AppError, AppErrorCode, AppErrorMessage, AppResult};
+use crate::utils::jwt::JwtKeys;
+use crate::utils::oidc::OidcClient;
+use crate::utils::password::PasswordHasher;
 use axum::body::Body;
 use axum::http::{
     header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, ORIGIN},
@@ -20,7 +29,6 @@ use axum::response::{IntoResponse, Response};
 use bytes::Bytes;
 use http_body_util::BodyExt;
 use hyper::StatusCode;
-use jsonwebtoken::{DecodingKey, EncodingKey};
 use std::collections::HashSet;
 use std::str::from_utf8;
 use std::sync::{Arc, Mutex};
@@ -58,6 +66,14 @@ pub fn body_from_parts(
     Bytes::from(msg.to_string())
 }
 
+/// Build an error response with a JSON body
+pub(crate) fn error_response(status_code: StatusCode, message: &str) -> Response {
+    let (mut parts, _body) = Response::new(Body::empty()).into_parts();
+    let msg = body_from_parts(&mut parts, status_code, message, None);
+
+    Response::from_parts(parts, Body::from(msg))
+}
+
 // ================ Request ID ================
 
 /// Request ID middleware
@@ -82,44 +98,104 @@ pub type SharedState = Arc<State>;
 // #[derive(Default, Debug)]
 pub struct State {
     pub config: ConfigState,
+    /// Stores of the users and their accounts
+    pub stores: Stores,
+    pub revoked_tokens: RevokedTokenStore,
+    /// Store used by the route rate limit policies (`None` if the rate limiter is disabled)
+    pub rate_limiter: Option<RateLimiter>,
+    /// OpenID Connect client (`None` if OIDC login is disabled)
+    pub oidc: Option<OidcClient>,
 }
 
 impl State {
-    /// Initialize `State` with configuration data (`.env`)
-    pub fn init(config: &Config) -> Self {
+    /// Initialize `State` with configuration data (`.env`), stores, revoked tokens storage, rate limiter and OIDC client
+    pub fn init(
+        config: &Config,
+        stores: Stores,
+        revoked_tokens: RevokedTokenStore,
+        rate_limiter: Option<RateLimiter>,
+    ) -> AppResult<Self> {
         info!("Init app state");
-        Self {
-            config: config.clone().into(),
-        }
+        Ok(Self {
+            config: config.clone().try_into()?,
+            stores,
+            revoked_tokens,
+            rate_limiter,
+            oidc: OidcClient::from_config(config)?,
+        })
     }
 }
 
 // #[derive(Default, Debug)]
 pub struct ConfigState {
-    pub jwt_encoding_key: EncodingKey,
-    pub jwt_decoding_key: DecodingKey,
+    pub jwt_keys: JwtKeys,
     pub jwt_lifetime: i64,
+    pub jwt_refresh_lifetime: i64,
+    pub password_hasher: PasswordHasher,
+    pub cursor_secret_key: String,
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_timeout: u64,
     pub forgotten_password_expiration_duration: i64,
     pub forgotten_password_base_url: String,
     pub forgotten_password_email_from: String,
+    pub forgotten_password_cooldown: i64,
+    pub email_from: String,
+    pub email_locale: String,
+    pub registration_enabled: bool,
+    pub registration_rate_limit: i32,
+    pub email_verification_base_url: String,
+    pub email_verification_lifetime: i64,
+    pub email_change_base_url: String,
+    pub login_max_failures: u32,
+    pub login_ip_max_failures: u32,
+    pub login_failures_window: i64,
+    pub login_lockout_duration: i64,
+    pub login_delay: u64,
+    pub login_unlock_base_url: String,
+    pub mfa_required_for_admin: bool,
+    pub mfa_challenge_lifetime: i64,
+    pub oidc_authorization_lifetime: i64,
 }
 
-impl From<Config> for ConfigState {
-    fn from(config: Config) -> Self {
-        Self {
-            jwt_encoding_key: EncodingKey::from_secret(config.jwt_secret_key.clone().as_bytes()),
-            jwt_decoding_key: DecodingKey::from_secret(config.jwt_secret_key.as_bytes()),
+impl TryFrom<Config> for ConfigState {
+    type Error = AppError;
+
+    fn try_from(config: Config) -> AppResult<Self> {
+        Ok(Self {
+            jwt_keys: JwtKeys::from_config(&config)?,
             jwt_lifetime: config.jwt_lifetime,
+            jwt_refresh_lifetime: config.jwt_refresh_lifetime,
+            password_hasher: PasswordHasher::new(
+                config.password_hash_memory_cost,
+                config.password_hash_time_cost,
+                config.password_hash_parallelism,
+            ),
+            cursor_secret_key: config.cursor_secret_key.clone(),
             smtp_host: config.smtp_host.clone(),
             smtp_port: config.smtp_port,
             smtp_timeout: config.smtp_timeout,
             forgotten_password_expiration_duration: config.forgotten_password_expiration_duration,
             forgotten_password_base_url: config.forgotten_password_base_url.clone(),
             forgotten_password_email_from: config.forgotten_password_email_from,
-        }
+            forgotten_password_cooldown: config.forgotten_password_cooldown,
+            email_from: config.email_from,
+            email_locale: config.email_locale,
+            registration_enabled: config.registration_enabled,
+            registration_rate_limit: config.registration_rate_limit,
+            email_verification_base_url: config.email_verification_base_url,
+            email_verification_lifetime: config.email_verification_lifetime,
+            email_change_base_url: config.email_change_base_url,
+            login_max_failures: config.login_max_failures,
+            login_ip_max_failures: config.login_ip_max_failures,
+            login_failures_window: config.login_failures_window,
+            login_lockout_duration: config.login_lockout_duration,
+            login_delay: config.login_delay,
+            login_unlock_base_url: config.login_unlock_base_url,
+            mfa_required_for_admin: config.mfa_required_for_admin,
+            mfa_challenge_lifetime: config.mfa_challenge_lifetime,
+            oidc_authorization_lifetime: config.oidc_authorization_lifetime,
+        })
     }
 }
 
@@ -139,7 +215,13 @@ pub fn cors(config: &Config) -> CorsLayer {
 
     let layer = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
-        .allow_headers([AUTHORIZATION, ACCEPT, ORIGIN, CONTENT_TYPE]);
+        .allow_headers([
+            AUTHORIZATION,
+            ACCEPT,
+            ORIGIN,
+            CONTENT_TYPE,
+            HeaderName::from_static(API_KEY_HEADER),
+        ]);
 
     if allow_origin == "*" {
         layer.allow_origin(Any)
@@ -0,0 +1,103 @@
+//! Permissions layer
+
+use super::body_from_parts;
+use crate::models::{auth::UserPermissions, role::Permission};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use futures::future::BoxFuture;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Return a `RequirePermissionLayer` from a permission name (e.g. `require_permission("users:delete")`).
+///
+/// # Panics
+///
+/// Panics if the permission does not exist, so that routes cannot be declared with a typo.
+pub fn require_permission(permission: &str) -> RequirePermissionLayer {
+    match Permission::try_from_str(permission) {
+        Some(permission) => RequirePermissionLayer::new(permission),
+        None => panic!("unknown permission: {permission}"),
+    }
+}
+
+/// Layer which only lets requests through if the JWT (or the API key) holds the required permission.
+///
+/// It must be used behind `JwtLayer`, which adds the permissions of the request to its extensions.
+#[derive(Clone)]
+pub struct RequirePermissionLayer {
+    pub permission: Permission,
+}
+
+impl RequirePermissionLayer {
+    /// Create a new `RequirePermissionLayer`
+    pub fn new(permission: Permission) -> Self {
+        Self { permission }
+    }
+}
+
+impl<S> Layer<S> for RequirePermissionLayer {
+    type Service = RequirePermissionMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermissionMiddleware {
+            inner,
+            permission: self.permission,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequirePermissionMiddleware<S> {
+    inner: S,
+    permission: Permission,
+}
+
+impl<S> Service<Request<Body>> for RequirePermissionMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    // `BoxFuture` is a type alias for `Pin<Box<dyn Future + Send + 'a>>`
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // Permissions added by `JwtLayer` (JWT or API key)
+        let status = match request.extensions().get::<UserPermissions>() {
+            Some(permissions) => match permissions.has(self.permission) {
+                true => StatusCode::OK,
+                false => StatusCode::FORBIDDEN,
+            },
+            None => StatusCode::UNAUTHORIZED,
+        };
+
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = Response::default();
+
+            response = match status {
+                StatusCode::OK => future.await?,
+                StatusCode::FORBIDDEN => {
+                    let (mut parts, _body) = response.into_parts();
+                    let msg = body_from_parts(&mut parts, StatusCode::FORBIDDEN, "Forbidden", None);
+                    Response::from_parts(parts, Body::from(msg))
+                }
+                _ => {
+                    let (mut parts, _body) = response.into_parts();
+                    let msg = body_from_parts(&mut parts, StatusCode::UNAUTHORIZED, "Unauthorized", None);
+                    Response::from_parts(parts, Body::from(msg))
+                }
+            };
+
+            Ok(response)
+        })
+    }
+}
@@ -1,9 +1,14 @@
 //! Rate limiter middleware
 
+pub mod policy;
+pub mod store;
+pub mod strategy;
+
 use super::{body_from_parts, SharedState};
 use crate::{
+    config::Config,
     models::auth::{self, Claims},
-    utils::errors::AppResult,
+    utils::errors::{AppResult, CliError},
 };
 use axum::{
     body::Body,
@@ -11,16 +16,15 @@ use axum::{
     http::{response::Parts, HeaderValue, Request, StatusCode},
     response::Response,
 };
-use chrono::Utc;
 use derive_more::{Display, Error};
 use futures::future::BoxFuture;
-use r2d2::Pool;
-use redis::{Client, Commands};
 use std::{
-    collections::HashMap,
     net::SocketAddr,
+    sync::Arc,
     task::{Context, Poll},
 };
+use store::RateLimiterStore;
+use strategy::{RateLimitResult, RateLimitStrategy};
 use tower::{Layer, Service};
 
 const RATE_LIMITER_PREFIX: &str = "rl_";
@@ -29,36 +33,121 @@ const REMAINING_HEADER: &str = "x-ratelimit-remaining";
 const RESET_HEADER: &str = "x-ratelimit-reset";
 const RETRY_AFTER_HEADER: &str = "retry-after";
 
-#[derive(Clone)]
-pub struct RateLimiterLayer {
-    pub state: SharedState,
-    pub pool: Pool<Client>,
-    pub redis_prefix: String,
+/// Behavior when the store is unavailable (Redis errors)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimiterFailureMode {
+    /// Requests are accepted without limit
+    Open,
+
+    /// Requests are rejected (503 Service Unavailable)
+    Closed,
+}
+
+impl RateLimiterFailureMode {
+    /// Parse the `LIMITER_FAILURE_MODE` configuration value (`open` or `closed`)
+    pub fn try_from_str(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "" | "open" => Some(Self::Open),
+            "closed" => Some(Self::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// Rate limiter configuration
+#[derive(Debug, Clone)]
+pub struct RateLimiterConfig {
+    /// Keys prefix
+    pub prefix: String,
+    pub strategy: RateLimitStrategy,
+    pub failure_mode: RateLimiterFailureMode,
+    /// Default number of requests by window (-1 for no limit)
     pub requests_by_second: i32,
+    /// Window duration (in second)
     pub expire_in_seconds: i64,
+    /// IP addresses without limit (delimited by a comma)
     pub white_list: String,
 }
 
+impl TryFrom<&Config> for RateLimiterConfig {
+    type Error = CliError;
+
+    fn try_from(config: &Config) -> Result<Self, Self::Error> {
+        Ok(Self {
+            prefix: config.redis_prefix.clone(),
+            strategy: RateLimitStrategy::try_from_str(&config.limiter_strategy).ok_or_else(|| {
+                CliError::ConfigError(format!("invalid rate limiter strategy: {}", config.limiter_strategy))
+            })?,
+            failure_mode: RateLimiterFailureMode::try_from_str(&config.limiter_failure_mode).ok_or_else(|| {
+                CliError::ConfigError(format!(
+                    "invalid rate limiter failure mode: {}",
+                    config.limiter_failure_mode
+                ))
+            })?,
+            requests_by_second: config.limiter_requests_by_second,
+            expire_in_seconds: config.limiter_expire_in_seconds,
+            white_list: config.limiter_white_list.clone(),
+        })
+    }
+}
+
+/// Store and configuration shared by the global rate limiter and the route policies
+#[derive(Clone)]
+pub struct RateLimiter {
+    pub store: Arc<dyn RateLimiterStore>,
+    pub config: RateLimiterConfig,
+}
+
+impl RateLimiter {
+    /// Consume a request of a consumer with its own limit (e.g. an API key) in the `name` namespace,
+    /// with the strategy and the window of the global rate limiter
+    pub async fn check(&self, name: &str, consumer: &str, limit: i64) -> Result<RateLimitResult, RateLimiterError> {
+        let key = format!(
+            "{}{RATE_LIMITER_PREFIX}{name}_{}{consumer}",
+            self.config.prefix,
+            self.config.strategy.key_prefix()
+        );
+
+        check_store(
+            self.store.clone(),
+            self.config.strategy,
+            key,
+            limit,
+            self.config.expire_in_seconds,
+        )
+        .await
+    }
+}
+
+/// Consume a request on the blocking thread pool (the Redis store uses synchronous connections)
+pub(crate) async fn check_store(
+    store: Arc<dyn RateLimiterStore>,
+    strategy: RateLimitStrategy,
+    key: String,
+    limit: i64,
+    window_in_seconds: i64,
+) -> Result<RateLimitResult, RateLimiterError> {
+    tokio::task::spawn_blocking(move || store.check(strategy, &key, limit, window_in_seconds))
+        .await
+        .map_err(|err| RateLimiterError::Store {
+            message: format!("rate limiter task error: {err}"),
+        })?
+}
+
+#[derive(Clone)]
+pub struct RateLimiterLayer {
+    pub state: SharedState,
+    pub store: Arc<dyn RateLimiterStore>,
+    pub config: RateLimiterConfig,
+}
+
 impl RateLimiterLayer {
-    pub fn new(
-        state: SharedState,
-        pool: Pool<Client>,
-        redis_prefix: String,
-        requests_by_second: i32,
-        expire_in_seconds: i64,
-        white_list: String,
-    ) -> Self {
-        let mut redis_prefix = redis_prefix;
-        redis_prefix.push_str(RATE_LIMITER_PREFIX);
+    pub fn new(state: SharedState, store: Arc<dyn RateLimiterStore>, config: RateLimiterConfig) -> Self {
+        let mut config = config;
+        config.prefix.push_str(RATE_LIMITER_PREFIX);
+        config.prefix.push_str(config.strategy.key_prefix());
 
-        Self {
-            state,
-            pool,
-            redis_prefix,
-            requests_by_second,
-            expire_in_seconds,
-            white_list,
-        }
+        Self { state, store, config }
     }
 }
 
@@ -66,15 +155,13 @@ impl<S> Layer<S> for RateLimiterLayer {
     type Service = RateLimiterMiddleware<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        let white_list = self.white_list.split(',').map(|s| s.to_string()).collect();
+        let white_list = self.config.white_list.split(',').map(|s| s.to_string()).collect();
 
         RateLimiterMiddleware {
             inner,
             state: self.state.clone(),
-            pool: self.pool.clone(),
-            redis_prefix: self.redis_prefix.clone(),
-            requests_by_second: self.requests_by_second,
-            expire_in_seconds: self.expire_in_seconds,
+            store: self.store.clone(),
+            config: self.config.clone(),
             white_list,
         }
     }
@@ -84,10 +171,8 @@ impl<S> Layer<S> for RateLimiterLayer {
 pub struct RateLimiterMiddleware<S> {
     inner: S,
     state: SharedState,
-    pool: Pool<Client>,
-    redis_prefix: String,
-    requests_by_second: i32,
-    expire_in_seconds: i64,
+    store: Arc<dyn RateLimiterStore>,
+    config: RateLimiterConfig,
     white_list: Vec<String>,
 }
 
@@ -106,11 +191,10 @@ where
     }
 
     fn call(&mut self, request: Request<Body>) -> Self::Future {
-        // Redis connection
-        let pool = self.pool.clone();
-
-        // Check JWT claims
-        let claims = auth::Claims::extract_from_request(request.headers(), &self.state.config.jwt_decoding_key.clone());
+        // Check JWT claims (the decoded JWT is cached in the request extensions for the next layers)
+        let (mut parts, body) = request.into_parts();
+        let claims = auth::Claims::extract_from_parts(&mut parts, &self.state.config.jwt_keys);
+        let request = Request::from_parts(parts, body);
 
         // Get socket address
         let addr = request.extensions().get::<ConnectInfo<SocketAddr>>();
@@ -120,13 +204,17 @@ where
             claims,
             addr,
             &self.white_list,
-            &self.redis_prefix,
-            self.requests_by_second,
+            &self.config.prefix,
+            self.config.requests_by_second,
         );
-        let check_result = check.process(&pool, self.expire_in_seconds);
+        let store = self.store.clone();
+        let strategy = self.config.strategy;
+        let expire_in_seconds = self.config.expire_in_seconds;
+        let failure_mode = self.config.failure_mode;
 
         let future = self.inner.call(request);
         Box::pin(async move {
+            let check_result = check.process(store, strategy, expire_in_seconds).await;
             let mut response = Response::default();
 
             response = match check_result {
@@ -157,6 +245,16 @@ where
                         let msg = body_from_parts(&mut parts, StatusCode::UNAUTHORIZED, "Unauthorized", None);
                         Response::from_parts(parts, Body::from(msg))
                     }
+                    RateLimiterError::Store { .. } if failure_mode == RateLimiterFailureMode::Open => {
+                        warn!("rate limiter store unavailable, request accepted without limit: {err}");
+                        future.await?
+                    }
+                    RateLimiterError::Store { .. } => {
+                        let (mut parts, _body) = response.into_parts();
+                        let msg =
+                            body_from_parts(&mut parts, StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable", None);
+                        Response::from_parts(parts, Body::from(msg))
+                    }
                     _ => {
                         let (mut parts, _body) = response.into_parts();
                         let msg =
@@ -172,7 +270,7 @@ where
 }
 
 /// Set middleware specific headers
-fn set_headers(parts: &mut Parts, limit: i32, remaining: i64, reset: i64) {
+pub(crate) fn set_headers(parts: &mut Parts, limit: i32, remaining: i64, reset: i64) {
     if remaining >= 0 {
         // Limit OK
         if let Ok(limit) = HeaderValue::from_str(limit.to_string().as_str()) {
@@ -195,12 +293,12 @@ fn set_headers(parts: &mut Parts, limit: i32, remaining: i64, reset: i64) {
 }
 
 #[derive(Display, Debug, Error, Clone, PartialEq)]
-enum RateLimiterError {
+pub enum RateLimiterError {
     Ip,
     JwtDecoding,
 
     #[display(fmt = "{message}")]
-    Redis {
+    Store {
         message: String,
     },
 }
@@ -209,7 +307,7 @@ impl From<redis::RedisError> for RateLimiterError {
     fn from(error: redis::RedisError) -> Self {
         error!("Redis database error from Rate Limiter middleware: {:?}", error);
 
-        Self::Redis {
+        Self::Store {
             message: error.to_string(),
         }
     }
@@ -219,7 +317,7 @@ impl From<r2d2::Error> for RateLimiterError {
     fn from(error: r2d2::Error) -> Self {
         error!("Redis r2d2 pool error from Rate Limiter middleware: {:?}", error);
 
-        Self::Redis {
+        Self::Store {
             message: error.to_string(),
         }
     }
@@ -303,49 +401,24 @@ impl RateLimiterCheck {
         }
     }
 
-    /// Check limit, update Redis and returns information for headers
-    fn process(&self, pool: &Pool<Client>, expire_in_seconds: i64) -> Result<(i32, i64, i64), RateLimiterError> {
+    /// Check limit, update the store and returns information for headers
+    async fn process(
+        &self,
+        store: Arc<dyn RateLimiterStore>,
+        strategy: RateLimitStrategy,
+        expire_in_seconds: i64,
+    ) -> Result<(i32, i64, i64), RateLimiterError> {
         if let Some(err) = &self.error {
             Err(err.clone())
-        } else if self.limit == -1 {
-            Ok((self.limit, 0, 0))
+        } else if self.limit == -1 || expire_in_seconds <= 0 {
+            Ok((-1, 0, 0))
         } else {
-            let mut conn = pool.get()?;
-            let now = Utc::now().timestamp();
-            let mut remaining = self.limit as i64 - 1;
-            let mut reset = expire_in_seconds;
-            let mut expired_at = now + expire_in_seconds;
-
-            let result: HashMap<String, i64> = conn.hgetall(&self.key)?;
-
-            if !result.is_empty() {
-                expired_at = *result.get("expiredAt").ok_or(RateLimiterError::Redis {
-                    message: "Redis key not found in Rate Limiter middleware".to_owned(),
-                })?;
-                reset = expired_at - now;
-
-                if reset <= 0 {
-                    // Expired cache
-                    // -------------
-                    expired_at = now + expire_in_seconds;
-                    reset = expire_in_seconds;
-                } else {
-                    // Valid cache
-                    // -----------
-                    remaining = *result.get("remaining").ok_or(RateLimiterError::Redis {
-                        message: "Redis key not found in Rate Limiter middleware".to_owned(),
-                    })?;
-
-                    if remaining >= 0 {
-                        remaining -= 1;
-                    }
-                }
-            }
-
-            conn.hset(&self.key, "remaining", remaining)?;
-            conn.hset(&self.key, "expiredAt", expired_at)?;
+            let key = self.key.as_ref().ok_or(RateLimiterError::Store {
+                message: "Rate limiter key not found".to_owned(),
+            })?;
+            let result = check_store(store, strategy, key.clone(), self.limit as i64, expire_in_seconds).await?;
 
-            Ok((self.limit, remaining, reset))
+            Ok((self.limit, result.remaining, result.reset))
         }
     }
 }
@@ -354,6 +427,7 @@ impl RateLimiterCheck {
 mod tests {
     use crate::{
         app_error,
+        models::user::Role,
         utils::errors::{AppError, AppErrorCode},
     };
 
@@ -395,8 +469,11 @@ mod tests {
             exp: 123456789,
             iat: 123456789,
             nbf: 123456789,
+            iat_ms: 123456789000,
+            jti: String::from("jti"),
             user_id: user_id.clone(),
-            user_roles: String::from("ADMIN"),
+            user_roles: vec![Role::Admin],
+            user_permissions: vec![],
             user_rate_limit: 25,
         }));
         let addr = None;
@@ -0,0 +1,284 @@
+//! Rate limit policies attached to individual routes
+//!
+//! A policy has its own limit, window and key (e.g. `login: 5/min per IP+username`)
+//! and its own key namespace: `<redis_prefix>rl_<policy name>_<strategy prefix><key>`.
+
+use super::{
+    check_store, set_headers, strategy::RateLimitStrategy, RateLimiter, RateLimiterError, RateLimiterFailureMode,
+    RATE_LIMITER_PREFIX,
+};
+use crate::{
+    layers::{body_from_parts, error_response, SharedState},
+    models::auth::Claims,
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path},
+    http::{request, Request, StatusCode},
+    response::Response,
+    RequestPartsExt,
+};
+use futures::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Maximum size of a body read to build a key (in byte)
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Part of the request used to build the key of a policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// Client IP address
+    Ip,
+
+    /// Authenticated user ID (client IP address for anonymous requests)
+    User,
+
+    /// Path parameter (e.g. `email` for `/forgotten-password/:email`)
+    PathParam(&'static str),
+
+    /// Field of a JSON body (e.g. `username` for `/login`)
+    BodyField(&'static str),
+}
+
+/// Named rate limit policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitPolicy {
+    /// Policy name, used in the keys namespace
+    pub name: &'static str,
+    pub strategy: RateLimitStrategy,
+    /// Number of requests by window
+    pub limit: i64,
+    /// Window duration (in second)
+    pub window_in_seconds: i64,
+    /// Parts of the request identifying a consumer
+    pub keys: &'static [RateLimitKey],
+}
+
+impl RateLimitPolicy {
+    /// Does the policy need to read the request body?
+    fn reads_body(&self) -> bool {
+        self.keys.iter().any(|key| matches!(key, RateLimitKey::BodyField(_)))
+    }
+
+    /// Build the store key of a consumer from the values of the policy keys
+    fn key(&self, prefix: &str, values: &[String]) -> String {
+        format!(
+            "{prefix}{RATE_LIMITER_PREFIX}{}_{}{}",
+            self.name,
+            self.strategy.key_prefix(),
+            values.join(":")
+        )
+    }
+
+    /// Extract the values of the policy keys from the request
+    fn values(
+        &self,
+        parts: &mut request::Parts,
+        path_params: &HashMap<String, String>,
+        body: &serde_json::Value,
+        state: &SharedState,
+    ) -> Result<Vec<String>, RateLimiterError> {
+        // Claims of the authenticated request (added by `JwtLayer`) or decoded JWT of a public route
+        let claims = match self.keys.contains(&RateLimitKey::User) {
+            true => match parts.extensions.get::<Claims>() {
+                Some(claims) => Some(Ok(claims.clone())),
+                None => Claims::extract_from_parts(parts, &state.config.jwt_keys),
+            },
+            false => None,
+        };
+
+        let ip = || {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|addr| addr.0.ip().to_string())
+                .ok_or(RateLimiterError::Ip)
+        };
+
+        self.keys
+            .iter()
+            .map(|key| match key {
+                RateLimitKey::Ip => ip(),
+                RateLimitKey::User => match &claims {
+                    Some(Ok(claims)) => Ok(claims.user_id.clone()),
+                    Some(Err(_)) => Err(RateLimiterError::JwtDecoding),
+                    None => ip(),
+                },
+                RateLimitKey::PathParam(name) => Ok(normalize(path_params.get(*name).map(String::as_str))),
+                RateLimitKey::BodyField(name) => Ok(normalize(body.get(name).and_then(|value| value.as_str()))),
+            })
+            .collect()
+    }
+}
+
+/// Lowercase and trim a key value (emails and usernames are case insensitive)
+fn normalize(value: Option<&str>) -> String {
+    value.unwrap_or_default().trim().to_lowercase()
+}
+
+#[derive(Clone)]
+pub struct RateLimitPolicyLayer {
+    pub state: SharedState,
+    pub policy: RateLimitPolicy,
+}
+
+impl RateLimitPolicyLayer {
+    pub fn new(state: SharedState, policy: RateLimitPolicy) -> Self {
+        Self { state, policy }
+    }
+}
+
+impl<S> Layer<S> for RateLimitPolicyLayer {
+    type Service = RateLimitPolicyMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitPolicyMiddleware {
+            inner,
+            state: self.state.clone(),
+            policy: self.policy,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitPolicyMiddleware<S> {
+    inner: S,
+    state: SharedState,
+    policy: RateLimitPolicy,
+}
+
+impl<S> Service<Request<Body>> for RateLimitPolicyMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    // `BoxFuture` is a type alias for `Pin<Box<dyn Future + Send + 'a>>`
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let state = self.state.clone();
+        let policy = self.policy;
+
+        // The ready service is used and replaced by a clone
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            // Policies are disabled with the rate limiter
+            let Some(limiter) = state.rate_limiter.clone() else {
+                return inner.call(request).await;
+            };
+
+            let (mut parts, body) = request.into_parts();
+            let path_params = parts
+                .extract::<Path<HashMap<String, String>>>()
+                .await
+                .map(|Path(params)| params)
+                .unwrap_or_default();
+
+            // The body is read to get the key and given back to the handler
+            let (body, json) = match policy.reads_body() {
+                true => match axum::body::to_bytes(body, MAX_BODY_SIZE).await {
+                    Ok(bytes) => {
+                        let json = serde_json::from_slice(&bytes).unwrap_or_default();
+                        (Body::from(bytes), json)
+                    }
+                    Err(_) => return Ok(error_response(StatusCode::PAYLOAD_TOO_LARGE, "Payload Too Large")),
+                },
+                false => (body, serde_json::Value::Null),
+            };
+
+            let result = match policy.values(&mut parts, &path_params, &json, &state) {
+                Ok(values) => check(&limiter, &policy, policy.key(&limiter.config.prefix, &values)).await,
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok((remaining, _reset)) if remaining >= 0 => inner.call(Request::from_parts(parts, body)).await,
+                Ok((remaining, reset)) => {
+                    let (mut parts, _body) = Response::new(Body::empty()).into_parts();
+                    set_headers(&mut parts, policy.limit as i32, remaining, reset);
+
+                    let msg = body_from_parts(&mut parts, StatusCode::TOO_MANY_REQUESTS, "Too Many Requests", None);
+                    Ok(Response::from_parts(parts, Body::from(msg)))
+                }
+                Err(RateLimiterError::JwtDecoding) => Ok(error_response(StatusCode::UNAUTHORIZED, "Unauthorized")),
+                Err(err @ RateLimiterError::Store { .. }) => match limiter.config.failure_mode {
+                    RateLimiterFailureMode::Open => {
+                        warn!("rate limiter store unavailable, request accepted without limit: {err}");
+                        inner.call(Request::from_parts(parts, body)).await
+                    }
+                    RateLimiterFailureMode::Closed => {
+                        Ok(error_response(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable"))
+                    }
+                },
+                Err(err) => Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string())),
+            }
+        })
+    }
+}
+
+/// Consume a request of the policy and returns remaining requests and reset
+async fn check(limiter: &RateLimiter, policy: &RateLimitPolicy, key: String) -> Result<(i64, i64), RateLimiterError> {
+    let result = check_store(
+        limiter.store.clone(),
+        policy.strategy,
+        key,
+        policy.limit,
+        policy.window_in_seconds,
+    )
+    .await?;
+
+    Ok((result.remaining, result.reset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: RateLimitPolicy = RateLimitPolicy {
+        name: "login",
+        strategy: RateLimitStrategy::SlidingWindow,
+        limit: 5,
+        window_in_seconds: 60,
+        keys: &[RateLimitKey::Ip, RateLimitKey::BodyField("username")],
+    };
+
+    #[test]
+    fn test_policy_key() {
+        let values = vec!["127.0.0.1".to_owned(), "john@test.com".to_owned()];
+
+        assert_eq!(
+            POLICY.key("axum_", &values),
+            String::from("axum_rl_login_sw_127.0.0.1:john@test.com")
+        );
+    }
+
+    #[test]
+    fn test_policy_reads_body() {
+        assert!(POLICY.reads_body());
+
+        let policy = RateLimitPolicy {
+            keys: &[RateLimitKey::PathParam("email")],
+            ..POLICY
+        };
+        assert!(!policy.reads_body());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Some(" John@Test.com ")), String::from("john@test.com"));
+        assert_eq!(normalize(None), String::new());
+    }
+}
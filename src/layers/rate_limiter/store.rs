@@ -0,0 +1,164 @@
+//! Rate limiter stores (Redis or in-memory)
+
+use super::{
+    strategy::{MemoryEntry, RateLimitResult, RateLimitStrategy},
+    RateLimiterError,
+};
+use chrono::Utc;
+use r2d2::Pool;
+use redis::Client;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+/// Default number of shards of the in-memory store
+const MEMORY_STORE_SHARDS: usize = 16;
+
+/// Interval between two sweeps of the expired entries of a shard (in millisecond)
+const MEMORY_STORE_SWEEP_INTERVAL: i64 = 60_000;
+
+/// Storage of the rate limiter counters
+pub trait RateLimiterStore: Send + Sync {
+    /// Consume a request for `key` (`limit` requests every `window_in_seconds`)
+    fn check(
+        &self,
+        strategy: RateLimitStrategy,
+        key: &str,
+        limit: i64,
+        window_in_seconds: i64,
+    ) -> Result<RateLimitResult, RateLimiterError>;
+}
+
+/// Redis store shared by all the server instances
+pub struct RedisStore {
+    pool: Pool<Client>,
+}
+
+impl RedisStore {
+    /// Create a Redis store
+    pub fn new(pool: Pool<Client>) -> Self {
+        Self { pool }
+    }
+}
+
+impl RateLimiterStore for RedisStore {
+    fn check(
+        &self,
+        strategy: RateLimitStrategy,
+        key: &str,
+        limit: i64,
+        window_in_seconds: i64,
+    ) -> Result<RateLimitResult, RateLimiterError> {
+        let mut conn = self.pool.get()?;
+
+        Ok(strategy.check(&mut *conn, key, limit, window_in_seconds)?)
+    }
+}
+
+#[derive(Default)]
+struct MemoryShard {
+    entries: HashMap<String, MemoryEntry>,
+    next_sweep_at: i64,
+}
+
+/// In-process store for single instance deployments and tests.
+///
+/// Keys are distributed in several shards to reduce lock contention
+/// and expired entries are removed from a shard at most every minute.
+pub struct MemoryStore {
+    shards: Vec<Mutex<MemoryShard>>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(MEMORY_STORE_SHARDS)
+    }
+}
+
+impl MemoryStore {
+    /// Create an in-memory store with `shards` shards
+    pub fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Mutex::new(MemoryShard::default())).collect(),
+        }
+    }
+
+    /// Number of keys in the store
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().map(|shard| shard.entries.len()).unwrap_or_default())
+            .sum()
+    }
+
+    /// Is the store empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove expired entries of all the shards
+    pub fn sweep(&self, now: i64) {
+        for shard in &self.shards {
+            if let Ok(mut shard) = shard.lock() {
+                shard.entries.retain(|_, entry| entry.expired_at > now);
+                shard.next_sweep_at = now + MEMORY_STORE_SWEEP_INTERVAL;
+            }
+        }
+    }
+
+    /// Shard of a key
+    fn shard(&self, key: &str) -> &Mutex<MemoryShard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+impl RateLimiterStore for MemoryStore {
+    fn check(
+        &self,
+        strategy: RateLimitStrategy,
+        key: &str,
+        limit: i64,
+        window_in_seconds: i64,
+    ) -> Result<RateLimitResult, RateLimiterError> {
+        let now = Utc::now().timestamp_millis();
+        let mut shard = self.shard(key).lock().map_err(|err| RateLimiterError::Store {
+            message: format!("in-memory store lock error: {err}"),
+        })?;
+
+        if shard.next_sweep_at <= now {
+            shard.entries.retain(|_, entry| entry.expired_at > now);
+            shard.next_sweep_at = now + MEMORY_STORE_SWEEP_INTERVAL;
+        }
+
+        let entry = shard.entries.entry(key.to_owned()).or_default();
+
+        Ok(strategy.check_in_memory(entry, now, limit, window_in_seconds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store_sweep() {
+        let store = MemoryStore::new(4);
+        for i in 0..10 {
+            store
+                .check(RateLimitStrategy::FixedWindow, &format!("key_{i}"), 5, 60)
+                .unwrap();
+        }
+        assert_eq!(store.len(), 10);
+
+        store.sweep(Utc::now().timestamp_millis());
+        assert_eq!(store.len(), 10);
+
+        store.sweep(Utc::now().timestamp_millis() + 61_000);
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+//! Rate limiting algorithms
+//!
+//! Each algorithm is executed atomically in Redis with a Lua script, so concurrent requests
+//! cannot read and write the same counter at the same time.
+//! The current time is read from Redis (`TIME`) to avoid clock differences between servers.
+//!
+//! The same algorithms are implemented in Rust for the in-memory store.
+
+use redis::{ConnectionLike, RedisResult, Script};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+/// Fixed window: a counter reset at the end of each window
+const FIXED_WINDOW_SCRIPT: &str = r#"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+
+local count = redis.call('INCR', KEYS[1])
+local ttl = redis.call('PTTL', KEYS[1])
+if ttl < 0 then
+    redis.call('PEXPIRE', KEYS[1], window)
+    ttl = window
+end
+
+if count > limit then
+    return {-1, ttl}
+end
+return {limit - count, ttl}
+"#;
+
+/// Sliding window log: timestamps of the requests of the last window in a sorted set
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+local count = redis.call('ZCARD', KEYS[1])
+local remaining = -1
+if count < limit then
+    redis.call('ZADD', KEYS[1], now, ARGV[3])
+    redis.call('PEXPIRE', KEYS[1], window)
+    remaining = limit - count - 1
+end
+
+-- Time until the oldest request leaves the window
+local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+local reset = window
+if oldest[2] then
+    reset = tonumber(oldest[2]) + window - now
+end
+return {remaining, reset}
+"#;
+
+/// Token bucket implemented with GCRA (Generic Cell Rate Algorithm):
+/// only the theoretical arrival time (TAT) of the next request is stored (in microsecond).
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2]) * 1000
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000000 + tonumber(time[2])
+local interval = math.max(math.floor(window / limit), 1)
+
+local tat = tonumber(redis.call('GET', KEYS[1])) or now
+if tat < now then
+    tat = now
+end
+local new_tat = tat + interval
+local allow_at = new_tat - window
+
+if now < allow_at then
+    return {-1, math.ceil((allow_at - now) / 1000)}
+end
+
+redis.call('SET', KEYS[1], string.format('%.0f', new_tat), 'PX', math.ceil((new_tat - now) / 1000))
+return {math.min(math.floor((now - allow_at) / interval), limit - 1), math.ceil((new_tat - now) / 1000)}
+"#;
+
+lazy_static! {
+    static ref FIXED_WINDOW: Script = Script::new(FIXED_WINDOW_SCRIPT);
+    static ref SLIDING_WINDOW: Script = Script::new(SLIDING_WINDOW_SCRIPT);
+    static ref TOKEN_BUCKET: Script = Script::new(TOKEN_BUCKET_SCRIPT);
+}
+
+/// Rate limiting algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitStrategy {
+    /// `limit` requests per window, bursts of `2 * limit` are possible around the end of a window
+    FixedWindow,
+
+    /// At most `limit` requests in any window (one sorted set entry by request)
+    SlidingWindow,
+
+    /// Bucket of `limit` tokens refilled continuously (one token every `window / limit`)
+    TokenBucket,
+}
+
+/// Result of a rate limit check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitResult {
+    /// Remaining requests (`-1` if the request is rejected)
+    pub remaining: i64,
+
+    /// Number of seconds before the limit is reset (retry delay if the request is rejected)
+    pub reset: i64,
+}
+
+/// Rate limit state of a key in the in-memory store
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryEntry {
+    /// Number of requests in the current window (fixed window)
+    count: i64,
+
+    /// Timestamps of the requests of the last window (sliding window)
+    requests: VecDeque<i64>,
+
+    /// Theoretical arrival time of the next request in microsecond (token bucket)
+    tat: i64,
+
+    /// Expiration timestamp (in millisecond)
+    pub expired_at: i64,
+}
+
+impl RateLimitStrategy {
+    /// Parse the `LIMITER_STRATEGY` configuration value
+    pub fn try_from_str(strategy: &str) -> Option<Self> {
+        match strategy.to_lowercase().as_str() {
+            "" | "fixed_window" => Some(Self::FixedWindow),
+            "sliding_window" => Some(Self::SlidingWindow),
+            "token_bucket" => Some(Self::TokenBucket),
+            _ => None,
+        }
+    }
+
+    /// Redis keys prefix (each algorithm uses a different data type)
+    pub fn key_prefix(&self) -> &'static str {
+        match self {
+            Self::FixedWindow => "fw_",
+            Self::SlidingWindow => "sw_",
+            Self::TokenBucket => "tb_",
+        }
+    }
+
+    /// Consume a request for `key` (`limit` requests every `window_in_seconds`)
+    pub fn check<C: ConnectionLike>(
+        &self,
+        conn: &mut C,
+        key: &str,
+        limit: i64,
+        window_in_seconds: i64,
+    ) -> RedisResult<RateLimitResult> {
+        let window = window_in_seconds.max(1) * 1000;
+        let limit = limit.max(1);
+
+        let (remaining, reset): (i64, i64) = match self {
+            Self::FixedWindow => FIXED_WINDOW.key(key).arg(limit).arg(window).invoke(conn)?,
+            Self::SlidingWindow => SLIDING_WINDOW
+                .key(key)
+                .arg(limit)
+                .arg(window)
+                .arg(Uuid::new_v4().to_string())
+                .invoke(conn)?,
+            Self::TokenBucket => TOKEN_BUCKET.key(key).arg(limit).arg(window).invoke(conn)?,
+        };
+
+        Ok(RateLimitResult {
+            remaining,
+            reset: Self::milliseconds_to_seconds(reset),
+        })
+    }
+
+    /// Consume a request for an in-memory entry (`now` in millisecond)
+    pub fn check_in_memory(
+        &self,
+        entry: &mut MemoryEntry,
+        now: i64,
+        limit: i64,
+        window_in_seconds: i64,
+    ) -> RateLimitResult {
+        let window = window_in_seconds.max(1) * 1000;
+        let limit = limit.max(1);
+
+        if entry.expired_at <= now {
+            *entry = MemoryEntry::default();
+        }
+
+        let (remaining, reset) = match self {
+            Self::FixedWindow => {
+                if entry.count == 0 {
+                    entry.expired_at = now + window;
+                }
+                entry.count += 1;
+
+                let remaining = match entry.count > limit {
+                    true => -1,
+                    false => limit - entry.count,
+                };
+                (remaining, entry.expired_at - now)
+            }
+            Self::SlidingWindow => {
+                while matches!(entry.requests.front(), Some(&t) if t <= now - window) {
+                    entry.requests.pop_front();
+                }
+
+                let count = entry.requests.len() as i64;
+                let mut remaining = -1;
+                if count < limit {
+                    entry.requests.push_back(now);
+                    entry.expired_at = now + window;
+                    remaining = limit - count - 1;
+                }
+
+                // Time until the oldest request leaves the window
+                let reset = entry.requests.front().map(|t| t + window - now).unwrap_or(window);
+                (remaining, reset)
+            }
+            Self::TokenBucket => {
+                let now = now * 1000;
+                let window = window * 1000;
+                let interval = (window / limit).max(1);
+                let new_tat = entry.tat.max(now) + interval;
+                let allow_at = new_tat - window;
+
+                if now < allow_at {
+                    (-1, Self::microseconds_to_milliseconds(allow_at - now))
+                } else {
+                    entry.tat = new_tat;
+                    entry.expired_at = Self::microseconds_to_milliseconds(new_tat);
+                    (
+                        ((now - allow_at) / interval).min(limit - 1),
+                        Self::microseconds_to_milliseconds(new_tat - now),
+                    )
+                }
+            }
+        };
+
+        RateLimitResult {
+            remaining,
+            reset: Self::milliseconds_to_seconds(reset),
+        }
+    }
+
+    /// Convert a delay in millisecond to second (rounded up)
+    fn milliseconds_to_seconds(delay: i64) -> i64 {
+        (delay.max(0) + 999) / 1000
+    }
+
+    /// Convert a time in microsecond to millisecond (rounded up)
+    fn microseconds_to_milliseconds(time: i64) -> i64 {
+        (time + 999).div_euclid(1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_strategy_try_from_str() {
+        assert_eq!(
+            RateLimitStrategy::try_from_str(""),
+            Some(RateLimitStrategy::FixedWindow)
+        );
+        assert_eq!(
+            RateLimitStrategy::try_from_str("fixed_window"),
+            Some(RateLimitStrategy::FixedWindow)
+        );
+        assert_eq!(
+            RateLimitStrategy::try_from_str("Sliding_Window"),
+            Some(RateLimitStrategy::SlidingWindow)
+        );
+        assert_eq!(
+            RateLimitStrategy::try_from_str("token_bucket"),
+            Some(RateLimitStrategy::TokenBucket)
+        );
+        assert_eq!(RateLimitStrategy::try_from_str("leaky_bucket"), None);
+    }
+
+    /// Send `n` requests at `now` and returns the remaining requests of each one
+    fn check_n(strategy: RateLimitStrategy, entry: &mut MemoryEntry, n: usize, now: i64) -> Vec<i64> {
+        (0..n)
+            .map(|_| strategy.check_in_memory(entry, now, 3, 10).remaining)
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_window_in_memory() {
+        let strategy = RateLimitStrategy::FixedWindow;
+        let mut entry = MemoryEntry::default();
+
+        assert_eq!(check_n(strategy, &mut entry, 4, 1_000), vec![2, 1, 0, -1]);
+        assert_eq!(strategy.check_in_memory(&mut entry, 5_000, 3, 10).reset, 6);
+
+        // New window
+        assert_eq!(check_n(strategy, &mut entry, 1, 11_000), vec![2]);
+    }
+
+    #[test]
+    fn test_sliding_window_in_memory() {
+        let strategy = RateLimitStrategy::SlidingWindow;
+        let mut entry = MemoryEntry::default();
+
+        assert_eq!(check_n(strategy, &mut entry, 2, 1_000), vec![2, 1]);
+        assert_eq!(check_n(strategy, &mut entry, 2, 6_000), vec![0, -1]);
+
+        // The 2 first requests have left the window
+        let result = strategy.check_in_memory(&mut entry, 11_000, 3, 10);
+        assert_eq!(result.remaining, 1);
+        assert_eq!(result.reset, 5);
+        assert_eq!(check_n(strategy, &mut entry, 2, 11_000), vec![0, -1]);
+    }
+
+    #[test]
+    fn test_token_bucket_in_memory() {
+        let strategy = RateLimitStrategy::TokenBucket;
+        let mut entry = MemoryEntry::default();
+
+        // One token every 3.33s
+        assert_eq!(check_n(strategy, &mut entry, 4, 1_000), vec![2, 1, 0, -1]);
+        assert_eq!(strategy.check_in_memory(&mut entry, 1_000, 3, 10).reset, 4);
+        assert_eq!(check_n(strategy, &mut entry, 2, 4_400), vec![0, -1]);
+
+        // Full bucket
+        assert_eq!(check_n(strategy, &mut entry, 1, 60_000), vec![2]);
+    }
+
+    #[test]
+    fn test_milliseconds_to_seconds() {
+        assert_eq!(RateLimitStrategy::milliseconds_to_seconds(-10), 0);
+        assert_eq!(RateLimitStrategy::milliseconds_to_seconds(0), 0);
+        assert_eq!(RateLimitStrategy::milliseconds_to_seconds(1), 1);
+        assert_eq!(RateLimitStrategy::milliseconds_to_seconds(1000), 1);
+        assert_eq!(RateLimitStrategy::milliseconds_to_seconds(1001), 2);
+    }
+}
@@ -0,0 +1,94 @@
+//! Roles layer
+
+use super::body_from_parts;
+use crate::models::{auth::UserRoles, user::Role};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use futures::future::BoxFuture;
+use std::collections::HashSet;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Layer which only lets requests through if the JWT (or the API key) holds at least one of the required roles.
+///
+/// It must be used behind `JwtLayer`, which adds the roles of the request to its extensions.
+#[derive(Clone)]
+pub struct RequireRolesLayer {
+    pub roles: HashSet<Role>,
+}
+
+impl RequireRolesLayer {
+    /// Create a new `RequireRolesLayer`
+    pub fn new(roles: &[Role]) -> Self {
+        Self {
+            roles: roles.iter().cloned().collect(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequireRolesLayer {
+    type Service = RequireRolesMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireRolesMiddleware {
+            inner,
+            roles: self.roles.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequireRolesMiddleware<S> {
+    inner: S,
+    roles: HashSet<Role>,
+}
+
+impl<S> Service<Request<Body>> for RequireRolesMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    // `BoxFuture` is a type alias for `Pin<Box<dyn Future + Send + 'a>>`
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // Roles added by `JwtLayer` (JWT or API key)
+        let status = match request.extensions().get::<UserRoles>() {
+            Some(roles) => match roles.has_one_of(&self.roles) {
+                true => StatusCode::OK,
+                false => StatusCode::FORBIDDEN,
+            },
+            None => StatusCode::UNAUTHORIZED,
+        };
+
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = Response::default();
+
+            response = match status {
+                StatusCode::OK => future.await?,
+                StatusCode::FORBIDDEN => {
+                    let (mut parts, _body) = response.into_parts();
+                    let msg = body_from_parts(&mut parts, StatusCode::FORBIDDEN, "Forbidden", None);
+                    Response::from_parts(parts, Body::from(msg))
+                }
+                _ => {
+                    let (mut parts, _body) = response.into_parts();
+                    let msg = body_from_parts(&mut parts, StatusCode::UNAUTHORIZED, "Unauthorized", None);
+                    Response::from_parts(parts, Body::from(msg))
+                }
+            };
+
+            Ok(response)
+        })
+    }
+}
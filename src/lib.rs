@@ -1,4 +1,5 @@
 pub mod config;
+pub mod database;
 pub mod emails;
 pub mod handlers;
 pub mod layers;
@@ -25,7 +26,7 @@ pub const APP_NAME: &str = "Axum Boilerplate";
 lazy_static! {
     pub static ref TEMPLATES: Result<Tera, tera::Error> = {
         let mut tera = Tera::new("templates/**/*")?;
-        tera.autoescape_on(vec![".html", ".txt"]);
+        tera.autoescape_on(vec![".html"]);
         Ok(tera)
     };
 }
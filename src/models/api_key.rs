@@ -0,0 +1,332 @@
+//! API key model module
+//!
+//! API keys authenticate machine-to-machine requests with the `X-API-Key` header.
+//! A key looks like `ak_<prefix>_<secret>`: the prefix is stored in clear to identify the key in lists,
+//! only a hash of the whole key is stored.
+
+use super::{
+    auth::Claims,
+    user::{Role, User},
+};
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::collections::HashSet;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Header of the API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// First part of all the API keys
+const API_KEY_TAG: &str = "ak";
+
+/// Length of the visible prefix
+const API_KEY_PREFIX_LENGTH: usize = 8;
+
+/// Length of the secret part
+const API_KEY_SECRET_LENGTH: usize = 40;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// Visible part of the key
+    pub prefix: String,
+    #[serde(skip_serializing, default)]
+    pub key_hash: String,
+    /// Roles of the key (subset of the owner roles)
+    pub roles: String,
+    /// Path prefixes the key can access, delimited by a comma (all the routes if `None`)
+    pub routes: Option<String>,
+    /// Max number of requests by window (-1: unlimited)
+    pub rate_limit: i32,
+    pub expired_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    /// Create a new API key and return it with its clear value
+    pub fn new(
+        user_id: String,
+        name: String,
+        roles: String,
+        routes: Option<String>,
+        rate_limit: i32,
+    ) -> (Self, String) {
+        let now = Utc::now();
+        let mut rng = rand::thread_rng();
+        let prefix = Alphanumeric.sample_string(&mut rng, API_KEY_PREFIX_LENGTH);
+        let secret = Alphanumeric.sample_string(&mut rng, API_KEY_SECRET_LENGTH);
+        let key = format!("{API_KEY_TAG}_{prefix}_{secret}");
+
+        (
+            Self {
+                id: Uuid::new_v4().to_string(),
+                user_id,
+                name,
+                prefix,
+                key_hash: Self::hash(&key),
+                roles,
+                routes,
+                rate_limit,
+                expired_at: None,
+                last_used_at: None,
+                created_at: now,
+                updated_at: now,
+            },
+            key,
+        )
+    }
+
+    /// Hash an API key
+    pub fn hash(key: &str) -> String {
+        format!("{:x}", Sha512::digest(key.as_bytes()))
+    }
+
+    /// Check if the key looks like an API key (avoid useless database queries)
+    pub fn is_well_formed(key: &str) -> bool {
+        let parts = key.split('_').collect::<Vec<_>>();
+
+        matches!(parts.as_slice(), [tag, prefix, secret]
+            if *tag == API_KEY_TAG
+                && prefix.len() == API_KEY_PREFIX_LENGTH
+                && secret.len() == API_KEY_SECRET_LENGTH)
+    }
+
+    /// Check if the key is expired
+    pub fn is_valid(&self) -> bool {
+        !matches!(self.expired_at, Some(expired_at) if expired_at <= Utc::now())
+    }
+
+    /// Check if the key can access a path
+    pub fn allows_route(&self, path: &str) -> bool {
+        match self.routes.as_deref() {
+            None | Some("") => true,
+            Some(routes) => routes.split(',').map(str::trim).any(|route| {
+                let route = route.trim_end_matches('/');
+                path == route || path.starts_with(&format!("{route}/"))
+            }),
+        }
+    }
+
+    /// Claims used by the layers and handlers for a request authenticated with the key
+    /// (the permissions of its roles are resolved by `JwtLayer`)
+    pub fn claims(&self) -> Claims {
+        let now_ms = Utc::now().timestamp_millis();
+        let now = now_ms / 1000;
+
+        Claims {
+            sub: self.user_id.clone(),
+            exp: self.expired_at.map_or(i64::MAX, |expired_at| expired_at.timestamp()),
+            iat: now,
+            nbf: now,
+            iat_ms: now_ms,
+            jti: self.id.clone(),
+            user_id: self.user_id.clone(),
+            user_roles: Role::from_names(self.roles.split(',')),
+            user_permissions: vec![],
+            user_rate_limit: self.rate_limit,
+        }
+    }
+
+    /// Check and normalize the roles of a key: a subset of the owner roles (all of them by default)
+    pub fn roles_for(owner: &User, roles: Option<&str>) -> Option<String> {
+        let owner_roles = owner.roles.iter().cloned().collect::<HashSet<_>>();
+        let roles = match roles {
+            None => owner_roles.clone(),
+            Some(roles) => Role::get_list(roles),
+        };
+
+        if roles.is_empty() || !roles.is_subset(&owner_roles) {
+            return None;
+        }
+
+        let mut roles = roles.iter().map(Role::to_string).collect::<Vec<_>>();
+        roles.sort();
+
+        Some(roles.join(","))
+    }
+
+    /// Keep only the roles still held by the owner (roles can be revoked after the key creation)
+    pub fn restrict_roles(&mut self, owner_roles: &[Role]) {
+        let mut roles = Role::from_names(self.roles.split(','))
+            .into_iter()
+            .filter(|role| owner_roles.contains(role))
+            .map(|role| role.to_string())
+            .collect::<Vec<_>>();
+        roles.sort();
+
+        self.roles = roles.join(",");
+    }
+
+    /// Check that a rate limit does not exceed a maximum one, e.g. the owner one (-1: unlimited)
+    pub fn is_rate_limit_allowed(max_rate_limit: i32, rate_limit: i32) -> bool {
+        match (max_rate_limit, rate_limit) {
+            (-1, _) => true,
+            (_, -1) => false,
+            (max_rate_limit, rate_limit) => rate_limit <= max_rate_limit,
+        }
+    }
+
+    /// Check and normalize the routes of a key (path prefixes starting with `/`)
+    pub fn routes_from(routes: Option<&[String]>) -> Option<Option<String>> {
+        match routes {
+            None => Some(None),
+            Some(routes) => {
+                let routes = routes
+                    .iter()
+                    .map(|route| route.trim().to_owned())
+                    .collect::<HashSet<_>>();
+                if routes
+                    .iter()
+                    .any(|route| !route.starts_with('/') || route.contains(','))
+                {
+                    return None;
+                }
+
+                let mut routes = routes.into_iter().collect::<Vec<_>>();
+                routes.sort();
+
+                Some(Some(routes.join(",")))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct ApiKeyCreation {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// Roles delimited by a comma, all the owner roles by default
+    pub roles: Option<String>,
+    /// Path prefixes (e.g. `/api/v1/users`), all the routes by default
+    pub routes: Option<Vec<String>>,
+    /// Owner rate limit by default
+    #[validate(range(min = -1))]
+    pub rate_limit: Option<i32>,
+    pub expired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct ApiKeyUpdate {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    pub roles: Option<String>,
+    pub routes: Option<Vec<String>>,
+    #[validate(range(min = -1))]
+    pub rate_limit: i32,
+    pub expired_at: Option<DateTime<Utc>>,
+}
+
+/// Created API key, the clear key is only returned once
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ApiKeyCreated {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(roles: &str) -> User {
+        User {
+            id: Uuid::new_v4().to_string(),
+            lastname: String::from("Doe"),
+            firstname: String::from("John"),
+            username: String::from("john@test.com"),
+            password: String::new(),
+            roles: Role::from_names(roles.split(',')),
+            rate_limit: 10,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_api_key_new() {
+        let (api_key, key) = ApiKey::new(String::from("user"), String::from("CI"), String::from("USER"), None, 10);
+        assert!(ApiKey::is_well_formed(&key));
+        assert!(key.starts_with(&format!("ak_{}_", api_key.prefix)));
+        assert_eq!(api_key.key_hash, ApiKey::hash(&key));
+        assert!(api_key.is_valid());
+    }
+
+    #[test]
+    fn test_api_key_is_well_formed() {
+        assert!(!ApiKey::is_well_formed(""));
+        assert!(!ApiKey::is_well_formed("ak_1234"));
+        assert!(!ApiKey::is_well_formed(&format!("xx_12345678_{}", "a".repeat(40))));
+        assert!(ApiKey::is_well_formed(&format!("ak_12345678_{}", "a".repeat(40))));
+    }
+
+    #[test]
+    fn test_api_key_restrict_roles() {
+        let (mut api_key, _key) = ApiKey::new(
+            String::from("user"),
+            String::from("CI"),
+            String::from("ADMIN,USER"),
+            None,
+            10,
+        );
+
+        api_key.restrict_roles(&[Role::Admin, Role::User]);
+        assert_eq!(api_key.roles, String::from("ADMIN,USER"));
+
+        api_key.restrict_roles(&[Role::User]);
+        assert_eq!(api_key.roles, String::from("USER"));
+
+        api_key.restrict_roles(&[]);
+        assert!(api_key.claims().user_roles.is_empty());
+    }
+
+    #[test]
+    fn test_api_key_is_rate_limit_allowed() {
+        assert!(ApiKey::is_rate_limit_allowed(10, 0));
+        assert!(ApiKey::is_rate_limit_allowed(10, 10));
+        assert!(!ApiKey::is_rate_limit_allowed(10, 11));
+        assert!(!ApiKey::is_rate_limit_allowed(10, -1));
+
+        assert!(ApiKey::is_rate_limit_allowed(-1, -1));
+        assert!(ApiKey::is_rate_limit_allowed(-1, 1000));
+    }
+
+    #[test]
+    fn test_api_key_allows_route() {
+        let (mut api_key, _key) = ApiKey::new(String::from("user"), String::from("CI"), String::from("USER"), None, 10);
+        assert!(api_key.allows_route("/api/v1/users"));
+
+        api_key.routes = Some(String::from("/api/v1/users/,/api/v1/logout"));
+        assert!(api_key.allows_route("/api/v1/users"));
+        assert!(api_key.allows_route("/api/v1/users/123"));
+        assert!(api_key.allows_route("/api/v1/logout"));
+        assert!(!api_key.allows_route("/api/v1/users-export"));
+        assert!(!api_key.allows_route("/api/v1/api-keys"));
+    }
+
+    #[test]
+    fn test_api_key_roles_for() {
+        let admin = owner("ADMIN,USER");
+        assert_eq!(ApiKey::roles_for(&admin, None), Some(String::from("ADMIN,USER")));
+        assert_eq!(ApiKey::roles_for(&admin, Some("USER")), Some(String::from("USER")));
+        assert_eq!(ApiKey::roles_for(&admin, Some("MANAGER")), None);
+        assert_eq!(ApiKey::roles_for(&admin, Some("UNKNOWN")), None);
+    }
+
+    #[test]
+    fn test_api_key_routes_from() {
+        assert_eq!(ApiKey::routes_from(None), Some(None));
+        assert_eq!(
+            ApiKey::routes_from(Some(&[String::from("/api/v1/users"), String::from(" /api/v1/logout ")])),
+            Some(Some(String::from("/api/v1/logout,/api/v1/users")))
+        );
+        assert_eq!(ApiKey::routes_from(Some(&[String::from("users")])), None);
+    }
+}
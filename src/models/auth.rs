@@ -1,32 +1,62 @@
 //! Authentification module
 
+use super::{role::Permission, user::Role};
 use crate::{
     app_error,
-    utils::errors::{AppError, AppErrorCode, AppResult},
-};
-use axum::http::{header, HeaderMap};
-use chrono::Utc;
-use jsonwebtoken::{
-    decode, encode, errors::ErrorKind::ExpiredSignature, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+    utils::{
+        errors::{AppError, AppErrorCode, AppResult},
+        jwt::JwtKeys,
+    },
 };
+use axum::http::{header, request::Parts, HeaderMap};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, decode_header, encode, errors::ErrorKind::ExpiredSignature, Validation};
+use rand::distributions::{Alphanumeric, DistString};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::collections::HashSet;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Refresh token length
+const REFRESH_TOKEN_LENGTH: usize = 64;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: i64,
     pub iat: i64,
     pub nbf: i64,
+
+    /// Issue time in millisecond (`iat` is in second), compared with the revocation time of the user tokens
+    #[serde(default)]
+    pub iat_ms: i64,
+
+    /// Unique token ID (used for revocation)
+    pub jti: String,
+
     pub user_id: String,
-    pub user_roles: String,
+    pub user_roles: Vec<Role>,
+
+    /// Permissions of the roles, resolved at login
+    #[serde(default)]
+    pub user_permissions: Vec<Permission>,
 
     /// Max number of request by second (-1: unlimited)
     pub user_rate_limit: i32,
 }
 
 impl Claims {
+    /// Issue time in millisecond (tokens issued without `iat_ms` are considered issued at the end of their second)
+    pub fn issued_at_ms(&self) -> i64 {
+        match self.iat_ms {
+            0 => self.iat * 1000 + 999,
+            iat_ms => iat_ms,
+        }
+    }
+
     /// Extract claims from request headers
-    pub fn extract_from_request(headers: &HeaderMap, decoding_key: &DecodingKey) -> Option<AppResult<Self>> {
+    pub fn extract_from_request(headers: &HeaderMap, keys: &JwtKeys) -> Option<AppResult<Self>> {
         headers
             .get(header::AUTHORIZATION)
             .and_then(|h| h.to_str().ok())
@@ -34,7 +64,98 @@ impl Claims {
                 let words = h.split("Bearer").collect::<Vec<&str>>();
                 words.get(1).map(|w| w.trim())
             })
-            .map(|token| Jwt::parse(token, decoding_key))
+            .map(|token| Jwt::parse(token, keys))
+    }
+
+    /// Extract claims from request parts.
+    ///
+    /// The JWT is decoded only once by request: the result is cached in the request extensions
+    /// and reused by the next layers and extractors.
+    pub fn extract_from_parts(parts: &mut Parts, keys: &JwtKeys) -> Option<AppResult<Self>> {
+        let jwt = match parts.extensions.get::<RequestJwt>() {
+            Some(jwt) => jwt.clone(),
+            None => {
+                let jwt = RequestJwt::from(Self::extract_from_request(&parts.headers, keys));
+                parts.extensions.insert(jwt.clone());
+                jwt
+            }
+        };
+
+        jwt.into()
+    }
+
+    /// Return user roles
+    pub fn roles(&self) -> HashSet<Role> {
+        self.user_roles.iter().cloned().collect()
+    }
+
+    /// Check if the user has at least one of the roles
+    pub fn has_one_of_roles(&self, roles: &HashSet<Role>) -> bool {
+        !self.roles().is_disjoint(roles)
+    }
+
+    /// Check if the user has a permission
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.user_permissions.contains(&permission)
+    }
+}
+
+/// JWT of a request decoded by the first layer which needs it, cached in the request extensions
+#[derive(Debug, Clone)]
+pub enum RequestJwt {
+    /// No `Authorization: Bearer` header
+    Missing,
+
+    /// Invalid or expired token
+    Invalid,
+
+    Valid(Claims),
+}
+
+impl From<Option<AppResult<Claims>>> for RequestJwt {
+    fn from(claims: Option<AppResult<Claims>>) -> Self {
+        match claims {
+            None => Self::Missing,
+            Some(Err(_)) => Self::Invalid,
+            Some(Ok(claims)) => Self::Valid(claims),
+        }
+    }
+}
+
+impl From<RequestJwt> for Option<AppResult<Claims>> {
+    fn from(jwt: RequestJwt) -> Self {
+        match jwt {
+            RequestJwt::Missing => None,
+            RequestJwt::Invalid => Some(Err(app_error!(AppErrorCode::Unauthorized))),
+            RequestJwt::Valid(claims) => Some(Ok(claims)),
+        }
+    }
+}
+
+/// Roles of the authenticated request (JWT or API key), added to the request extensions by `JwtLayer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserRoles(pub HashSet<Role>);
+
+impl UserRoles {
+    /// Check if the user has at least one of the roles
+    pub fn has_one_of(&self, roles: &HashSet<Role>) -> bool {
+        !self.0.is_disjoint(roles)
+    }
+
+    /// Check if the user has at least one of the roles of a mask (see [`Role::bit`])
+    pub fn has_one_of_mask(&self, mask: u8) -> bool {
+        self.0.iter().any(|role| role.bit() & mask != 0)
+    }
+}
+
+/// Permissions of the authenticated request (JWT or API key), added to the request extensions by `JwtLayer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserPermissions(pub HashSet<Permission>);
+
+impl UserPermissions {
+    /// Check if the user has a permission
+    pub fn has(&self, permission: Permission) -> bool {
+        self.0.contains(&permission)
     }
 }
 
@@ -45,12 +166,14 @@ impl Jwt {
     pub fn generate(
         user_id: String,
         user_rate_limit: i32,
-        roles: String,
-        encoding_key: &EncodingKey,
+        roles: Vec<Role>,
+        permissions: Vec<Permission>,
+        keys: &JwtKeys,
         jwt_lifetime: i64,
     ) -> AppResult<(String, i64)> {
-        let header = Header::new(Algorithm::HS512);
-        let now = Utc::now().timestamp();
+        let header = keys.header();
+        let now_ms = Utc::now().timestamp_millis();
+        let now = now_ms / 1000;
         let expired_at = now + (jwt_lifetime * 3600);
 
         let payload = Claims {
@@ -58,12 +181,15 @@ impl Jwt {
             exp: expired_at,
             iat: now,
             nbf: now,
+            iat_ms: now_ms,
+            jti: Uuid::new_v4().to_string(),
             user_id,
             user_roles: roles,
+            user_permissions: permissions,
             user_rate_limit,
         };
 
-        let token = encode(&header, &payload, encoding_key).map_err(|err| {
+        let token = encode(&header, &payload, keys.encoding_key()).map_err(|err| {
             app_error!(
                 AppErrorCode::InternalError,
                 "error during JWT encoding",
@@ -75,8 +201,14 @@ impl Jwt {
     }
 
     /// Parse JWT
-    pub fn parse(token: &str, decoding_key: &DecodingKey) -> AppResult<Claims> {
-        let validation = Validation::new(Algorithm::HS512);
+    pub fn parse(token: &str, keys: &JwtKeys) -> AppResult<Claims> {
+        // The verification key is selected with the `kid` header
+        let header = decode_header(token).map_err(|_| app_error!(AppErrorCode::InternalError))?;
+        let decoding_key = keys
+            .decoding_key(header.kid.as_deref())
+            .ok_or_else(|| app_error!(AppErrorCode::InternalError))?;
+
+        let validation = Validation::new(keys.algorithm());
         let token = decode::<Claims>(token, decoding_key, &validation).map_err(|err| match err.kind() {
             ExpiredSignature => app_error!(
                 AppErrorCode::InternalError,
@@ -89,3 +221,144 @@ impl Jwt {
         Ok(token.claims)
     }
 }
+
+/// Refresh token used to get a new access token without sending credentials again.
+///
+/// Each use rotates the token: the used token is marked as used and a new one is created in the same family.
+/// Only a hash of the token is stored in database.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub id: String,
+    pub family_id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub expired_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RefreshToken {
+    /// Create a new refresh token and return it with its clear value.
+    ///
+    /// If `family_id` is `None`, a new family is started (login).
+    pub fn new(user_id: String, family_id: Option<String>, lifetime: i64) -> (Self, String) {
+        let now = Utc::now();
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), REFRESH_TOKEN_LENGTH);
+
+        (
+            Self {
+                id: Uuid::new_v4().to_string(),
+                family_id: family_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+                user_id,
+                token_hash: Self::hash(&token),
+                expired_at: now + Duration::hours(lifetime),
+                used_at: None,
+                revoked_at: None,
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    /// Hash a refresh token
+    pub fn hash(token: &str) -> String {
+        format!("{:x}", Sha512::digest(token.as_bytes()))
+    }
+
+    /// Check if the token is expired or revoked
+    pub fn is_valid(&self) -> bool {
+        self.revoked_at.is_none() && self.expired_at > Utc::now()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct RefreshTokenRequest {
+    #[validate(length(equal = 64))]
+    pub refresh_token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    fn parts(token: Option<&str>) -> Parts {
+        let mut request = Request::builder();
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_claims_extract_from_parts_is_cached() {
+        let keys = JwtKeys::from_secret("main", "mysecretjwtkey");
+        let (token, _) = Jwt::generate(
+            String::from("user"),
+            10,
+            vec![Role::Admin],
+            vec![Permission::UsersRead],
+            &keys,
+            1,
+        )
+        .unwrap();
+
+        let mut parts = parts(Some(&token));
+        let claims = Claims::extract_from_parts(&mut parts, &keys).unwrap().unwrap();
+        assert_eq!(claims.user_id, "user");
+        assert!(claims.has_permission(Permission::UsersRead));
+        assert!(!claims.has_permission(Permission::UsersDelete));
+        assert!(matches!(
+            parts.extensions.get::<RequestJwt>(),
+            Some(RequestJwt::Valid(_))
+        ));
+
+        // The header is not decoded again
+        parts.headers.remove(header::AUTHORIZATION);
+        assert!(matches!(Claims::extract_from_parts(&mut parts, &keys), Some(Ok(_))));
+
+        let mut parts = self::parts(Some("invalid"));
+        assert!(matches!(Claims::extract_from_parts(&mut parts, &keys), Some(Err(_))));
+        assert!(matches!(
+            parts.extensions.get::<RequestJwt>(),
+            Some(RequestJwt::Invalid)
+        ));
+
+        let mut parts = self::parts(None);
+        assert!(Claims::extract_from_parts(&mut parts, &keys).is_none());
+        assert!(matches!(
+            parts.extensions.get::<RequestJwt>(),
+            Some(RequestJwt::Missing)
+        ));
+    }
+
+    #[test]
+    fn test_claims_issued_at_ms() {
+        let keys = JwtKeys::from_secret("main", "mysecretjwtkey");
+        let (token, _) = Jwt::generate(String::from("user"), 10, vec![Role::User], vec![], &keys, 1).unwrap();
+
+        let mut claims = Claims::extract_from_parts(&mut parts(Some(&token)), &keys)
+            .unwrap()
+            .unwrap();
+        assert_eq!(claims.iat_ms / 1000, claims.iat);
+        assert_eq!(claims.issued_at_ms(), claims.iat_ms);
+
+        // Token issued without `iat_ms`
+        claims.iat_ms = 0;
+        assert_eq!(claims.issued_at_ms(), claims.iat * 1000 + 999);
+    }
+
+    #[test]
+    fn test_user_roles() {
+        let roles = UserRoles(Role::get_list("USER,MANAGER"));
+        assert!(roles.has_one_of(&HashSet::from([Role::Manager, Role::Admin])));
+        assert!(!roles.has_one_of(&HashSet::from([Role::Admin])));
+
+        assert!(roles.has_one_of_mask(Role::MANAGER_BIT | Role::ADMIN_BIT));
+        assert!(roles.has_one_of_mask(Role::USER_BIT));
+        assert!(!roles.has_one_of_mask(Role::ADMIN_BIT));
+        assert!(!roles.has_one_of_mask(0));
+    }
+}
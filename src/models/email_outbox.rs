@@ -0,0 +1,138 @@
+//! Email outbox model module
+
+use crate::emails::Message;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::fmt::Display;
+use uuid::Uuid;
+
+/// Maximum delay between two attempts
+const MAX_RETRY_DELAY_IN_SECONDS: i64 = 24 * 3600;
+
+/// Email outbox status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EmailOutboxStatus {
+    /// Waiting to be sent (first attempt or retry)
+    Pending,
+
+    /// Successfully sent
+    Sent,
+
+    /// Maximum number of attempts reached (dead letter)
+    Dead,
+}
+
+impl Display for EmailOutboxStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Pending => "PENDING",
+                Self::Sent => "SENT",
+                Self::Dead => "DEAD",
+            }
+        )
+    }
+}
+
+impl EmailOutboxStatus {
+    /// Parse a status stored in database
+    pub fn try_from_str(status: &str) -> Option<Self> {
+        match status {
+            "PENDING" => Some(Self::Pending),
+            "SENT" => Some(Self::Sent),
+            "DEAD" => Some(Self::Dead),
+            _ => None,
+        }
+    }
+}
+
+/// Email persisted in the outbox before being sent by the email worker
+#[derive(Debug, Clone)]
+pub struct OutboxEmail {
+    pub id: String,
+    pub from_email: String,
+    pub to_list: Vec<String>,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+    pub status: EmailOutboxStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OutboxEmail {
+    /// Create a new pending email
+    pub fn new(message: Message) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            from_email: message.from,
+            to_list: message.to_list,
+            subject: message.subject,
+            text_body: message.text_body,
+            html_body: message.html_body,
+            status: EmailOutboxStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            sent_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Message to send
+    pub fn message(&self) -> Message {
+        Message {
+            from: self.from_email.clone(),
+            to_list: self.to_list.clone(),
+            subject: self.subject.clone(),
+            text_body: self.text_body.clone(),
+            html_body: self.html_body.clone(),
+        }
+    }
+
+    /// Delay before the next attempt with an exponential backoff (`retry_delay` in second):
+    /// `retry_delay`, `2 * retry_delay`, `4 * retry_delay`, etc.
+    pub fn retry_delay(attempts: u32, retry_delay: i64) -> Duration {
+        let factor = 2_i64.saturating_pow(attempts.saturating_sub(1));
+
+        Duration::seconds(retry_delay.saturating_mul(factor).min(MAX_RETRY_DELAY_IN_SECONDS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_outbox_status() {
+        for status in [
+            EmailOutboxStatus::Pending,
+            EmailOutboxStatus::Sent,
+            EmailOutboxStatus::Dead,
+        ] {
+            assert_eq!(EmailOutboxStatus::try_from_str(&status.to_string()), Some(status));
+        }
+        assert_eq!(EmailOutboxStatus::try_from_str("UNKNOWN"), None);
+    }
+
+    #[test]
+    fn test_outbox_email_retry_delay() {
+        assert_eq!(OutboxEmail::retry_delay(1, 30), Duration::seconds(30));
+        assert_eq!(OutboxEmail::retry_delay(2, 30), Duration::seconds(60));
+        assert_eq!(OutboxEmail::retry_delay(3, 30), Duration::seconds(120));
+        assert_eq!(OutboxEmail::retry_delay(5, 30), Duration::seconds(480));
+        assert_eq!(
+            OutboxEmail::retry_delay(100, 30),
+            Duration::seconds(MAX_RETRY_DELAY_IN_SECONDS)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Login attempt model module
+//!
+//! Failed logins are recorded by username (existing or not) and by IP address.
+//! They are used to slow down attackers with a progressive delay and to temporarily lock accounts.
+
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha512};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Maximum delay before answering a failed login (in millisecond)
+const MAX_DELAY_IN_MILLISECONDS: u64 = 10_000;
+
+/// Length of the token of the unlock link
+const UNLOCK_TOKEN_LENGTH: usize = 64;
+
+/// Failed login
+#[derive(Debug, Clone)]
+pub struct LoginAttempt {
+    pub id: String,
+    pub username: String,
+    pub ip: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LoginAttempt {
+    /// Create a new failed login
+    pub fn new(username: &str, ip: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            username: Self::normalize_username(username),
+            ip: ip.to_owned(),
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Usernames are case insensitive
+    pub fn normalize_username(username: &str) -> String {
+        username.trim().to_lowercase()
+    }
+
+    /// Delay before answering the `failures`th consecutive failed login (`delay` in millisecond):
+    /// no delay, `delay`, `2 * delay`, `4 * delay`, etc.
+    pub fn delay(failures: u32, delay: u64) -> Duration {
+        let factor = match failures {
+            0 | 1 => 0,
+            failures => 2_u64.saturating_pow(failures - 2),
+        };
+
+        Duration::from_millis(delay.saturating_mul(factor).min(MAX_DELAY_IN_MILLISECONDS))
+    }
+
+    /// Generate the token of an unlock link and return it with its hash (only the hash is stored)
+    pub fn unlock_token() -> (String, String) {
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), UNLOCK_TOKEN_LENGTH);
+        let token_hash = Self::hash_unlock_token(&token);
+
+        (token, token_hash)
+    }
+
+    /// Hash an unlock token
+    pub fn hash_unlock_token(token: &str) -> String {
+        format!("{:x}", Sha512::digest(token.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_attempt_new() {
+        let attempt = LoginAttempt::new(" John@Test.com ", "127.0.0.1");
+        assert_eq!(attempt.username, String::from("john@test.com"));
+        assert_eq!(attempt.ip, String::from("127.0.0.1"));
+    }
+
+    #[test]
+    fn test_login_attempt_delay() {
+        assert_eq!(LoginAttempt::delay(0, 250), Duration::ZERO);
+        assert_eq!(LoginAttempt::delay(1, 250), Duration::ZERO);
+        assert_eq!(LoginAttempt::delay(2, 250), Duration::from_millis(250));
+        assert_eq!(LoginAttempt::delay(3, 250), Duration::from_millis(500));
+        assert_eq!(LoginAttempt::delay(5, 250), Duration::from_millis(2_000));
+        assert_eq!(
+            LoginAttempt::delay(50, 250),
+            Duration::from_millis(MAX_DELAY_IN_MILLISECONDS)
+        );
+        assert_eq!(LoginAttempt::delay(5, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_login_attempt_unlock_token() {
+        let (token, token_hash) = LoginAttempt::unlock_token();
+        assert_eq!(token.len(), UNLOCK_TOKEN_LENGTH);
+        assert_eq!(token_hash, LoginAttempt::hash_unlock_token(&token));
+        assert_ne!(token_hash, token);
+    }
+}
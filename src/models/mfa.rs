@@ -0,0 +1,245 @@
+//! Two-factor authentication model module
+//!
+//! Users can enroll a TOTP authenticator. The login then has two steps:
+//! the credentials are exchanged for a short-lived MFA challenge token,
+//! then the challenge token and a TOTP (or recovery) code are exchanged for the access and refresh tokens.
+
+use super::user::LoginResponse;
+use chrono::{DateTime, Duration, Utc};
+use rand::{distributions::Alphanumeric, distributions::DistString, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use uuid::Uuid;
+use validator::Validate;
+
+/// MFA challenge token length
+const CHALLENGE_TOKEN_LENGTH: usize = 64;
+
+/// Number of wrong codes before a challenge is deleted
+pub const CHALLENGE_MAX_ATTEMPTS: u32 = 5;
+
+/// Number of recovery codes generated at once
+const RECOVERY_CODES_NUMBER: usize = 10;
+
+/// Characters of the recovery codes (without ambiguous characters like `0`, `o`, `1` and `l`)
+const RECOVERY_CODE_CHARSET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+/// Length of each half of a recovery code (`xxxxx-xxxxx`)
+const RECOVERY_CODE_PART_LENGTH: usize = 5;
+
+/// TOTP settings of a user
+#[derive(Debug, Clone, Default)]
+pub struct UserTotp {
+    /// Base32 encoded secret (set before the enrollment is confirmed)
+    pub secret: Option<String>,
+    pub enabled_at: Option<DateTime<Utc>>,
+    /// Last accepted time step, a code cannot be used twice
+    pub last_used_step: Option<i64>,
+}
+
+impl UserTotp {
+    /// Is the enrollment confirmed?
+    pub fn is_enabled(&self) -> bool {
+        self.secret.is_some() && self.enabled_at.is_some()
+    }
+}
+
+/// Pending second step of a login
+#[derive(Debug, Clone)]
+pub struct MfaChallenge {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub attempts: u32,
+    pub expired_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MfaChallenge {
+    /// Create a new challenge (`lifetime` in second) and return it with its clear token
+    pub fn new(user_id: String, lifetime: i64) -> (Self, String) {
+        let now = Utc::now();
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), CHALLENGE_TOKEN_LENGTH);
+
+        (
+            Self {
+                id: Uuid::new_v4().to_string(),
+                user_id,
+                token_hash: Self::hash(&token),
+                attempts: 0,
+                expired_at: now + Duration::seconds(lifetime),
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    /// Hash a challenge token
+    pub fn hash(token: &str) -> String {
+        format!("{:x}", Sha512::digest(token.as_bytes()))
+    }
+
+    /// Check if the challenge is expired or has too many wrong attempts
+    pub fn is_valid(&self) -> bool {
+        self.attempts < CHALLENGE_MAX_ATTEMPTS && self.expired_at > Utc::now()
+    }
+}
+
+/// One-time recovery code, used when the authenticator is lost.
+///
+/// Only a hash of the code is stored in database.
+#[derive(Debug, Clone)]
+pub struct RecoveryCode {
+    pub id: String,
+    pub user_id: String,
+    pub code_hash: String,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RecoveryCode {
+    /// Generate a new set of recovery codes and return them with their clear values
+    pub fn generate(user_id: &str) -> (Vec<Self>, Vec<String>) {
+        let now = Utc::now();
+        let codes = (0..RECOVERY_CODES_NUMBER)
+            .map(|_| format!("{}-{}", random_part(), random_part()))
+            .collect::<Vec<_>>();
+
+        let recovery_codes = codes
+            .iter()
+            .map(|code| Self {
+                id: Uuid::new_v4().to_string(),
+                user_id: user_id.to_owned(),
+                code_hash: Self::hash(code),
+                used_at: None,
+                created_at: now,
+            })
+            .collect();
+
+        (recovery_codes, codes)
+    }
+
+    /// Hash a recovery code (case, spaces and dashes are ignored)
+    pub fn hash(code: &str) -> String {
+        let code = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        format!("{:x}", Sha512::digest(code.as_bytes()))
+    }
+}
+
+/// Random part of a recovery code
+fn random_part() -> String {
+    let mut rng = rand::thread_rng();
+
+    (0..RECOVERY_CODE_PART_LENGTH)
+        .map(|_| RECOVERY_CODE_CHARSET[rng.gen_range(0..RECOVERY_CODE_CHARSET.len())] as char)
+        .collect()
+}
+
+/// Answer of the first login step when a second factor is needed
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MfaChallengeResponse {
+    pub mfa_token: String,
+    pub expires_at: String,
+    /// The user must enroll TOTP before logging in (administrators when MFA is enforced)
+    pub enrollment_required: bool,
+}
+
+/// Answer of the login (tokens or MFA challenge)
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum LoginResult {
+    Authenticated(LoginResponse),
+    MfaRequired(MfaChallengeResponse),
+}
+
+/// Second login step
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct MfaLogin {
+    #[validate(length(equal = 64))]
+    pub mfa_token: String,
+    /// TOTP code or recovery code
+    #[validate(length(min = 6, max = 16))]
+    pub code: String,
+}
+
+/// TOTP enrollment during the login (the user is not authenticated yet)
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct MfaLoginEnrollment {
+    #[validate(length(equal = 64))]
+    pub mfa_token: String,
+}
+
+/// Secret to add in an authenticator application
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    /// `otpauth://` URI to display as a QR code
+    pub provisioning_uri: String,
+}
+
+/// Request confirmed with a TOTP code (or a recovery code to disable TOTP)
+#[derive(Deserialize, Serialize, Debug, Validate)]
+pub struct MfaCode {
+    #[validate(length(min = 6, max = 16))]
+    pub code: String,
+}
+
+/// New recovery codes, only shown once
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_totp_is_enabled() {
+        let mut totp = UserTotp {
+            secret: Some(String::from("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")),
+            ..UserTotp::default()
+        };
+        assert!(!totp.is_enabled());
+
+        totp.enabled_at = Some(Utc::now());
+        assert!(totp.is_enabled());
+    }
+
+    #[test]
+    fn test_mfa_challenge_is_valid() {
+        let (mut challenge, token) = MfaChallenge::new(String::from("user"), 300);
+        assert_eq!(token.len(), CHALLENGE_TOKEN_LENGTH);
+        assert_eq!(challenge.token_hash, MfaChallenge::hash(&token));
+        assert!(challenge.is_valid());
+
+        challenge.attempts = CHALLENGE_MAX_ATTEMPTS;
+        assert!(!challenge.is_valid());
+
+        let (challenge, _token) = MfaChallenge::new(String::from("user"), -1);
+        assert!(!challenge.is_valid());
+    }
+
+    #[test]
+    fn test_recovery_code_generate() {
+        let (recovery_codes, codes) = RecoveryCode::generate("user");
+        assert_eq!(recovery_codes.len(), RECOVERY_CODES_NUMBER);
+        assert_eq!(codes.len(), RECOVERY_CODES_NUMBER);
+
+        for (recovery_code, code) in recovery_codes.iter().zip(&codes) {
+            assert_eq!(code.len(), 2 * RECOVERY_CODE_PART_LENGTH + 1);
+            assert_eq!(recovery_code.code_hash, RecoveryCode::hash(code));
+        }
+    }
+
+    #[test]
+    fn test_recovery_code_hash() {
+        assert_eq!(RecoveryCode::hash("abcde-fghjk"), RecoveryCode::hash(" ABCDE FGHJK "));
+        assert_ne!(RecoveryCode::hash("abcde-fghjk"), RecoveryCode::hash("abcde-fghjm"));
+    }
+}
@@ -1,4 +1,10 @@
 //! Models module list
 
+pub mod api_key;
 pub mod auth;
+pub mod email_outbox;
+pub mod login_attempt;
+pub mod mfa;
+pub mod oidc;
+pub mod role;
 pub mod user;
@@ -0,0 +1,117 @@
+//! OpenID Connect model module
+//!
+//! Users can log in with an external provider (authorization code flow with PKCE).
+//! The `state` of the authorization request is returned to the callback: it is used to find
+//! the PKCE code verifier and the nonce expected in the ID token.
+//! An external identity is linked to a user by its verified email at the first login.
+
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use sha2::{Digest, Sha512};
+use uuid::Uuid;
+
+/// Length of the `state` parameter
+const STATE_LENGTH: usize = 64;
+
+/// Length of the `nonce` parameter
+const NONCE_LENGTH: usize = 32;
+
+/// Pending authorization request, from the redirection to the provider to the callback
+#[derive(Debug, Clone)]
+pub struct OidcAuthorization {
+    pub id: String,
+    pub state_hash: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expired_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OidcAuthorization {
+    /// Create a new authorization request (`lifetime` in second) and return it with its clear state
+    pub fn new(code_verifier: String, lifetime: i64) -> (Self, String) {
+        let now = Utc::now();
+        let mut rng = rand::thread_rng();
+        let state = Alphanumeric.sample_string(&mut rng, STATE_LENGTH);
+
+        (
+            Self {
+                id: Uuid::new_v4().to_string(),
+                state_hash: Self::hash(&state),
+                nonce: Alphanumeric.sample_string(&mut rng, NONCE_LENGTH),
+                code_verifier,
+                expired_at: now + Duration::seconds(lifetime),
+                created_at: now,
+            },
+            state,
+        )
+    }
+
+    /// Hash a state
+    pub fn hash(state: &str) -> String {
+        format!("{:x}", Sha512::digest(state.as_bytes()))
+    }
+
+    /// Check if the authorization request is expired
+    pub fn is_valid(&self) -> bool {
+        self.expired_at > Utc::now()
+    }
+}
+
+/// External identity of a user
+#[derive(Debug, Clone)]
+pub struct UserIdentity {
+    pub id: String,
+    pub user_id: String,
+    pub issuer: String,
+    /// User ID of the provider (`sub` claim)
+    pub subject: String,
+    /// Email used to link the identity
+    pub email: String,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserIdentity {
+    /// Create a new identity
+    pub fn new(user_id: String, issuer: String, subject: String, email: String) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            issuer,
+            subject,
+            email,
+            last_login_at: Some(now),
+            created_at: now,
+        }
+    }
+}
+
+/// Query parameters of the provider redirection
+#[derive(Deserialize, Debug)]
+pub struct OidcCallback {
+    pub code: Option<String>,
+    pub state: String,
+    /// Error returned by the provider (e.g. `access_denied`)
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oidc_authorization_new() {
+        let (authorization, state) = OidcAuthorization::new(String::from("verifier"), 600);
+        assert_eq!(state.len(), STATE_LENGTH);
+        assert_eq!(authorization.nonce.len(), NONCE_LENGTH);
+        assert_eq!(authorization.state_hash, OidcAuthorization::hash(&state));
+        assert!(authorization.is_valid());
+
+        let (authorization, _state) = OidcAuthorization::new(String::from("verifier"), -1);
+        assert!(!authorization.is_valid());
+    }
+}
@@ -0,0 +1,180 @@
+//! Role permissions module
+
+use super::user::Role;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use validator::{Validate, ValidationError};
+
+/// Permission granted to roles in the `role_permissions` table.
+///
+/// Permissions are resolved at login and added to the JWT claims, then checked by `RequirePermissionLayer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Permission {
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    #[serde(rename = "users:delete")]
+    UsersDelete,
+    #[serde(rename = "roles:manage")]
+    RolesManage,
+    #[serde(rename = "api-keys:manage")]
+    ApiKeysManage,
+}
+
+impl Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Permission {
+    /// All the permissions
+    pub const ALL: [Self; 5] = [
+        Self::UsersRead,
+        Self::UsersWrite,
+        Self::UsersDelete,
+        Self::RolesManage,
+        Self::ApiKeysManage,
+    ];
+
+    /// Name of the permission
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UsersRead => "users:read",
+            Self::UsersWrite => "users:write",
+            Self::UsersDelete => "users:delete",
+            Self::RolesManage => "roles:manage",
+            Self::ApiKeysManage => "api-keys:manage",
+        }
+    }
+
+    /// Try to return a `Permission` from its name
+    pub fn try_from_str(permission: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == permission)
+    }
+
+    /// Return the sorted `Permission` list of permission names, without duplicates and unknown permissions
+    pub fn from_names<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Vec<Self> {
+        names
+            .into_iter()
+            .filter_map(|name| Self::try_from_str(name.as_ref()))
+            .collect::<BTreeSet<Self>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Role with its permissions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RolePermissions {
+    pub name: Role,
+    pub permissions: Vec<Permission>,
+}
+
+impl RolePermissions {
+    /// Built-in roles with their permissions (as inserted by the migrations)
+    pub fn built_in() -> Vec<Self> {
+        vec![
+            Self {
+                name: Role::User,
+                permissions: vec![],
+            },
+            Self {
+                name: Role::Manager,
+                permissions: vec![Permission::UsersRead],
+            },
+            Self {
+                name: Role::Admin,
+                permissions: Permission::ALL.to_vec(),
+            },
+        ]
+    }
+}
+
+/// Custom role creation (`POST /api/v1/roles`)
+#[derive(Deserialize, Debug, Validate)]
+pub struct RoleCreation {
+    #[validate(custom = "validate_custom_role")]
+    pub name: String,
+    #[validate(custom = "validate_permissions")]
+    pub permissions: Vec<String>,
+}
+
+impl From<RoleCreation> for RolePermissions {
+    fn from(role: RoleCreation) -> Self {
+        Self {
+            name: Role::Custom(role.name),
+            permissions: Permission::from_names(role.permissions),
+        }
+    }
+}
+
+/// Permissions update of a custom role (`PUT /api/v1/roles/:name`)
+#[derive(Deserialize, Debug, Validate)]
+pub struct RoleUpdate {
+    #[validate(custom = "validate_permissions")]
+    pub permissions: Vec<String>,
+}
+
+/// Check that a name is valid for a custom role (built-in role names are refused)
+fn validate_custom_role(name: &str) -> Result<(), ValidationError> {
+    match Role::try_from_str(name) {
+        Some(Role::Custom(_)) => Ok(()),
+        _ => Err(ValidationError::new("invalid_role")),
+    }
+}
+
+/// Check that all the permissions exist
+fn validate_permissions(permissions: &[String]) -> Result<(), ValidationError> {
+    match permissions
+        .iter()
+        .all(|permission| Permission::try_from_str(permission).is_some())
+    {
+        true => Ok(()),
+        false => Err(ValidationError::new("unknown_permission")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_try_from_str() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::try_from_str(permission.as_str()), Some(permission));
+        }
+        assert_eq!(Permission::try_from_str("users:READ"), None);
+        assert_eq!(Permission::try_from_str(""), None);
+    }
+
+    #[test]
+    fn test_permission_serde() {
+        let json = serde_json::to_string(&[Permission::UsersDelete, Permission::ApiKeysManage]).unwrap();
+        assert_eq!(json, r#"["users:delete","api-keys:manage"]"#);
+        assert_eq!(
+            serde_json::from_str::<Vec<Permission>>(&json).unwrap(),
+            vec![Permission::UsersDelete, Permission::ApiKeysManage]
+        );
+    }
+
+    #[test]
+    fn test_role_creation_validation() {
+        let role = |name: &str, permissions: &[&str]| RoleCreation {
+            name: name.to_owned(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        };
+
+        assert!(role("SUPPORT", &["users:read", "users:write"]).validate().is_ok());
+        assert!(role("SUPPORT", &[]).validate().is_ok());
+        assert!(role("SUPPORT", &["users:read", "users:all"]).validate().is_err());
+        assert!(role("ADMIN", &["users:read"]).validate().is_err());
+        assert!(role("support", &["users:read"]).validate().is_err());
+
+        let role = RolePermissions::from(role("SUPPORT", &["users:write", "users:read", "users:write"]));
+        assert_eq!(role.name, Role::Custom(String::from("SUPPORT")));
+        assert_eq!(role.permissions, vec![Permission::UsersRead, Permission::UsersWrite]);
+    }
+}
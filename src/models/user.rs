@@ -1,17 +1,19 @@
 //! User model module
 
 use chrono::Duration;
+use rand::distributions::{Alphanumeric, DistString};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
 use sqlx::types::chrono::{DateTime, Utc};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Display,
     ops::Add,
 };
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub id: String,
     pub lastname: String,
@@ -19,7 +21,7 @@ pub struct User {
     pub username: String,
     #[serde(skip_serializing)]
     pub password: String,
-    pub roles: Option<String>,
+    pub roles: Vec<Role>,
     pub rate_limit: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -30,13 +32,15 @@ pub struct User {
 impl User {
     /// Create a new `User` from `UserCreation`
     pub fn new(user: UserCreation) -> Self {
+        let roles = user.roles();
+
         Self {
             id: Uuid::new_v4().to_string(),
             lastname: user.lastname,
             firstname: user.firstname,
             username: user.username,
             password: user.password,
-            roles: user.roles,
+            roles,
             rate_limit: user.rate_limit,
             created_at: Utc::now(),
             updated_at: Utc::now(),
@@ -60,9 +64,14 @@ pub struct LoginResponse {
     pub firstname: String,
     #[validate(email)]
     pub username: String,
-    pub roles: String,
+    pub roles: Vec<Role>,
     pub token: String,
     pub expires_at: String,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: String,
+    /// Recovery codes, only returned when TOTP is enrolled during the login
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Validate)]
@@ -73,22 +82,85 @@ pub struct UserCreation {
     pub username: String,
     #[validate(length(min = 8))]
     pub password: String,
-    pub roles: Option<String>,
+    #[serde(default)]
+    #[validate(custom = "validate_roles")]
+    pub roles: Vec<String>,
     pub rate_limit: i32,
 }
 
+impl UserCreation {
+    /// Roles of the user (invalid roles are rejected by the validation)
+    pub fn roles(&self) -> Vec<Role> {
+        Role::from_names(&self.roles)
+    }
+}
+
+/// Check that all the roles are valid (the existence of custom roles is checked by the handlers)
+fn validate_roles(roles: &[String]) -> Result<(), ValidationError> {
+    match roles.iter().all(|role| Role::try_from_str(role).is_some()) {
+        true => Ok(()),
+        false => Err(ValidationError::new("invalid_role")),
+    }
+}
+
+/// Self-service registration (`POST /api/v1/register`)
+#[derive(Deserialize, Debug, Validate)]
+pub struct UserRegistration {
+    #[validate(length(min = 1))]
+    pub lastname: String,
+    #[validate(length(min = 1))]
+    pub firstname: String,
+    #[validate(email)]
+    pub username: String,
+    #[validate(length(min = 8))]
+    pub password: String,
+}
+
+/// Profile update of the authenticated user (`PATCH /api/v1/me`)
+#[derive(Deserialize, Debug, Validate)]
+pub struct UserProfileUpdate {
+    #[validate(length(min = 1))]
+    pub lastname: Option<String>,
+    #[validate(length(min = 1))]
+    pub firstname: Option<String>,
+}
+
+/// Password change of the authenticated user (`POST /api/v1/me/password`)
+#[derive(Deserialize, Debug, Validate)]
+pub struct UserChangePassword {
+    pub current_password: String,
+    #[validate(length(min = 8))]
+    pub new_password: String,
+}
+
+/// Email change of the authenticated user (`POST /api/v1/me/email`)
+#[derive(Deserialize, Debug, Validate)]
+pub struct UserChangeEmail {
+    #[validate(email)]
+    pub email: String,
+    pub password: String,
+}
+
 #[derive(Deserialize, Debug, Validate)]
 pub struct UserUpdatePassword {
     #[validate(length(min = 8))]
     pub password: String,
 }
 
-/// Defines user roles. Be carefull, roles are case sensitive (uppercase)
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Maximum length of a role name
+const ROLE_NAME_MAX_LENGTH: usize = 63;
+
+/// Defines user roles, stored in the `roles` table. Be carefull, roles are case sensitive (uppercase)
+///
+/// Besides the built-in roles, administrators can create custom roles with their own permissions
+/// (see `models::role::RolePermissions`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum Role {
     User,
     Manager,
     Admin,
+    Custom(String),
 }
 
 impl Display for Role {
@@ -100,21 +172,73 @@ impl Display for Role {
                 Self::User => "USER",
                 Self::Manager => "MANAGER",
                 Self::Admin => "ADMIN",
+                Self::Custom(name) => name,
             }
         )
     }
 }
 
+impl TryFrom<String> for Role {
+    type Error = String;
+
+    fn try_from(role: String) -> Result<Self, Self::Error> {
+        Self::try_from_str(&role).ok_or_else(|| format!("invalid role: {role}"))
+    }
+}
+
+impl From<Role> for String {
+    fn from(role: Role) -> Self {
+        role.to_string()
+    }
+}
+
 impl Role {
-    /// Try to return a `Role` if string role is valid
-    /// TODO: Implement From trait instead!
-    fn try_from_str(role: &str) -> Option<Self> {
+    /// Bit of the `User` role in a roles mask
+    pub const USER_BIT: u8 = 1;
+    /// Bit of the `Manager` role in a roles mask
+    pub const MANAGER_BIT: u8 = 1 << 1;
+    /// Bit of the `Admin` role in a roles mask
+    pub const ADMIN_BIT: u8 = 1 << 2;
+
+    /// Bit of the role, used to require roles at compile time (e.g. `RequireRole<{ Role::ADMIN_BIT | Role::MANAGER_BIT }>`).
+    ///
+    /// Custom roles have no bit.
+    pub const fn bit(&self) -> u8 {
+        match self {
+            Self::User => Self::USER_BIT,
+            Self::Manager => Self::MANAGER_BIT,
+            Self::Admin => Self::ADMIN_BIT,
+            Self::Custom(_) => 0,
+        }
+    }
+
+    /// Try to return a `Role` if string role is valid: a built-in role, or a custom role
+    /// whose name is made of uppercase letters, digits and `_` (starting with a letter)
+    pub fn try_from_str(role: &str) -> Option<Self> {
         let mut roles = HashMap::with_capacity(3);
         roles.insert(format!("{}", Self::User), Self::User);
         roles.insert(format!("{}", Self::Manager), Self::Manager);
         roles.insert(format!("{}", Self::Admin), Self::Admin);
 
-        roles.get(role).cloned()
+        match roles.get(role) {
+            Some(role) => Some(role.clone()),
+            None if Self::is_valid_custom_name(role) => Some(Self::Custom(role.to_owned())),
+            None => None,
+        }
+    }
+
+    /// Check the name of a custom role
+    fn is_valid_custom_name(name: &str) -> bool {
+        name.len() <= ROLE_NAME_MAX_LENGTH
+            && name.starts_with(|c: char| c.is_ascii_uppercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    }
+
+    /// Is it a built-in role (which cannot be changed or deleted)?
+    pub fn is_built_in(&self) -> bool {
+        !matches!(self, Self::Custom(_))
     }
 
     /// Return `Role` list from string roles
@@ -127,6 +251,16 @@ impl Role {
             .filter_map(|r| Self::try_from_str(r))
             .collect()
     }
+
+    /// Return the sorted `Role` list of role names, without duplicates and invalid roles
+    pub fn from_names<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Vec<Self> {
+        names
+            .into_iter()
+            .filter_map(|name| Self::try_from_str(name.as_ref().trim()))
+            .collect::<BTreeSet<Self>>()
+            .into_iter()
+            .collect()
+    }
 }
 
 /// Password strength
@@ -167,24 +301,131 @@ impl PasswordScorer {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Length of the password reset token
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 64;
+
+/// Pending password reset, only the hash of its token is stored
+#[derive(Debug, Clone)]
 pub struct PasswordReset {
-    #[serde(skip_serializing)]
     pub user_id: String,
-    pub token: String,
+    pub token_hash: String,
     pub expired_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
 }
 
 impl PasswordReset {
-    /// Create a new password recovery
-    pub fn new(user_id: String, expiration_duration: i64) -> Self {
+    /// Create a new password recovery (`expiration_duration` in hour) and return it with its clear token
+    pub fn new(user_id: String, expiration_duration: i64) -> (Self, String) {
         let now = Utc::now();
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), PASSWORD_RESET_TOKEN_LENGTH);
 
-        Self {
-            user_id,
-            token: Uuid::new_v4().to_string(),
-            expired_at: now.add(Duration::hours(expiration_duration)),
-        }
+        (
+            Self {
+                user_id,
+                token_hash: Self::hash(&token),
+                expired_at: now.add(Duration::hours(expiration_duration)),
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    /// Hash a token
+    pub fn hash(token: &str) -> String {
+        format!("{:x}", Sha512::digest(token.as_bytes()))
+    }
+
+    /// Check if the password reset is expired
+    pub fn is_valid(&self) -> bool {
+        self.expired_at > Utc::now()
+    }
+
+    /// Check if a new email can be sent (`cooldown` in second)
+    pub fn can_be_renewed(&self, cooldown: i64) -> bool {
+        self.created_at.add(Duration::seconds(cooldown)) <= Utc::now()
+    }
+}
+
+/// Length of the email verification token
+const EMAIL_VERIFICATION_TOKEN_LENGTH: usize = 64;
+
+/// Pending verification of the email of a registered user
+#[derive(Debug, Clone)]
+pub struct EmailVerification {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub expired_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailVerification {
+    /// Create a new email verification (`lifetime` in hour) and return it with its clear token
+    pub fn new(user_id: String, lifetime: i64) -> (Self, String) {
+        let now = Utc::now();
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), EMAIL_VERIFICATION_TOKEN_LENGTH);
+
+        (
+            Self {
+                id: Uuid::new_v4().to_string(),
+                user_id,
+                token_hash: Self::hash(&token),
+                expired_at: now.add(Duration::hours(lifetime)),
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    /// Hash a token
+    pub fn hash(token: &str) -> String {
+        format!("{:x}", Sha512::digest(token.as_bytes()))
+    }
+
+    /// Check if the verification link is expired
+    pub fn is_valid(&self) -> bool {
+        self.expired_at > Utc::now()
+    }
+}
+
+/// Pending email change, confirmed with a link sent to the new email
+#[derive(Debug, Clone)]
+pub struct EmailChange {
+    pub id: String,
+    pub user_id: String,
+    pub new_email: String,
+    pub token_hash: String,
+    pub expired_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailChange {
+    /// Create a new email change (`lifetime` in hour) and return it with its clear token
+    pub fn new(user_id: String, new_email: String, lifetime: i64) -> (Self, String) {
+        let now = Utc::now();
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), EMAIL_VERIFICATION_TOKEN_LENGTH);
+
+        (
+            Self {
+                id: Uuid::new_v4().to_string(),
+                user_id,
+                new_email,
+                token_hash: EmailVerification::hash(&token),
+                expired_at: now.add(Duration::hours(lifetime)),
+                created_at: now,
+            },
+            token,
+        )
+    }
+
+    /// Hash a token
+    pub fn hash(token: &str) -> String {
+        EmailVerification::hash(token)
+    }
+
+    /// Check if the verification link is expired
+    pub fn is_valid(&self) -> bool {
+        self.expired_at > Utc::now()
     }
 }
 
@@ -199,6 +440,22 @@ mod tests {
         assert_eq!(Role::try_from_str("USER"), Some(Role::User));
         assert_eq!(Role::try_from_str("Admin"), None);
         assert_eq!(Role::try_from_str(""), None);
+        assert_eq!(
+            Role::try_from_str("SUPPORT_2"),
+            Some(Role::Custom(String::from("SUPPORT_2")))
+        );
+        assert_eq!(Role::try_from_str("2_SUPPORT"), None);
+        assert_eq!(Role::try_from_str("SUPPORT TEAM"), None);
+        assert_eq!(Role::try_from_str(&"A".repeat(64)), None);
+    }
+
+    #[test]
+    fn test_role_serde() {
+        let roles = vec![Role::Admin, Role::Custom(String::from("SUPPORT"))];
+        let json = serde_json::to_string(&roles).unwrap();
+        assert_eq!(json, r#"["ADMIN","SUPPORT"]"#);
+        assert_eq!(serde_json::from_str::<Vec<Role>>(&json).unwrap(), roles);
+        assert!(serde_json::from_str::<Role>(r#""admin""#).is_err());
     }
 
     #[test]
@@ -218,6 +475,72 @@ mod tests {
         assert_eq!(Role::get_list(" "), HashSet::new());
     }
 
+    #[test]
+    fn test_role_from_names() {
+        assert_eq!(
+            Role::from_names(["SUPPORT", "ADMIN", "USER", "ADMIN", "invalid"]),
+            vec![Role::User, Role::Admin, Role::Custom(String::from("SUPPORT"))]
+        );
+        assert_eq!(
+            Role::from_names(" MANAGER ,USER".split(',')),
+            vec![Role::User, Role::Manager]
+        );
+        assert_eq!(Role::from_names(Vec::<String>::new()), vec![]);
+    }
+
+    #[test]
+    fn test_user_creation_roles_validation() {
+        let user = |roles: &[&str]| UserCreation {
+            lastname: String::from("Doe"),
+            firstname: String::from("John"),
+            username: String::from("john@test.com"),
+            password: String::from("00000000"),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+            rate_limit: 10,
+        };
+
+        assert!(user(&[]).validate().is_ok());
+        assert!(user(&["ADMIN", "USER"]).validate().is_ok());
+        assert!(user(&["ADMIN", "SUPPORT"]).validate().is_ok());
+        assert!(user(&["ADMIN", "support"]).validate().is_err());
+        assert!(user(&[""]).validate().is_err());
+    }
+
+    #[test]
+    fn test_password_reset_new() {
+        let (password_reset, token) = PasswordReset::new(String::from("user"), 1);
+        assert_eq!(token.len(), PASSWORD_RESET_TOKEN_LENGTH);
+        assert_eq!(password_reset.token_hash, PasswordReset::hash(&token));
+        assert!(password_reset.is_valid());
+        assert!(!password_reset.can_be_renewed(60));
+        assert!(password_reset.can_be_renewed(0));
+
+        let (password_reset, _token) = PasswordReset::new(String::from("user"), -1);
+        assert!(!password_reset.is_valid());
+    }
+
+    #[test]
+    fn test_email_verification_new() {
+        let (verification, token) = EmailVerification::new(String::from("user"), 24);
+        assert_eq!(token.len(), EMAIL_VERIFICATION_TOKEN_LENGTH);
+        assert_eq!(verification.token_hash, EmailVerification::hash(&token));
+        assert!(verification.is_valid());
+
+        let (verification, _token) = EmailVerification::new(String::from("user"), -1);
+        assert!(!verification.is_valid());
+    }
+
+    #[test]
+    fn test_email_change_new() {
+        let (change, token) = EmailChange::new(String::from("user"), String::from("john@test.com"), 24);
+        assert_eq!(token.len(), EMAIL_VERIFICATION_TOKEN_LENGTH);
+        assert_eq!(change.token_hash, EmailChange::hash(&token));
+        assert!(change.is_valid());
+
+        let (change, _token) = EmailChange::new(String::from("user"), String::from("john@test.com"), -1);
+        assert!(!change.is_valid());
+    }
+
     #[test]
     fn test_passwords_score() {
         // Not valid
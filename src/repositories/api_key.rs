@@ -0,0 +1,181 @@
+use crate::database::{query, Database, DbRow};
+use crate::models::{api_key::ApiKey, user::Role};
+use crate::utils::errors::AppResult;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+
+/// Minimum interval between two updates of `last_used_at` (in second)
+pub(crate) const LAST_USED_AT_PRECISION: i64 = 60;
+
+#[async_trait]
+pub trait ApiKeyRepository: Send + Sync {
+    /// Add a new API key
+    async fn create(&self, api_key: &ApiKey) -> AppResult<()>;
+
+    /// Returns an API key of a not deleted user by its hash,
+    /// with its roles restricted to the current roles of the user
+    async fn get_by_hash(&self, key_hash: &str) -> AppResult<Option<ApiKey>>;
+
+    /// Returns an API key by its ID
+    async fn get_by_id(&self, id: &str) -> AppResult<Option<ApiKey>>;
+
+    /// Returns the API keys of a user
+    async fn get_all_by_user(&self, user_id: &str) -> AppResult<Vec<ApiKey>>;
+
+    /// Update an API key (not its value)
+    async fn update(&self, api_key: &ApiKey) -> AppResult<()>;
+
+    /// Delete an API key
+    async fn delete(&self, id: &str) -> AppResult<u64>;
+
+    /// Track the last use of an API key (at most once a minute to limit writes)
+    async fn touch(&self, id: &str) -> AppResult<()>;
+}
+
+#[async_trait]
+impl ApiKeyRepository for Database {
+    #[instrument(skip(self))]
+    async fn create(&self, api_key: &ApiKey) -> AppResult<()> {
+        query(
+            r#"
+                INSERT INTO api_keys (id, user_id, name, prefix, key_hash, roles, routes, rate_limit, expired_at, last_used_at, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&api_key.id)
+        .bind(&api_key.user_id)
+        .bind(&api_key.name)
+        .bind(&api_key.prefix)
+        .bind(&api_key.key_hash)
+        .bind(&api_key.roles)
+        .bind(&api_key.routes)
+        .bind(api_key.rate_limit)
+        .bind(api_key.expired_at)
+        .bind(api_key.last_used_at)
+        .bind(api_key.created_at)
+        .bind(api_key.updated_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_by_hash(&self, key_hash: &str) -> AppResult<Option<ApiKey>> {
+        let row = query(&format!(
+            r#"
+                SELECT k.*, (
+                    SELECT {}
+                    FROM user_roles
+                    WHERE user_roles.user_id = k.user_id
+                ) AS owner_roles
+                FROM api_keys k
+                    INNER JOIN users u ON u.id = k.user_id AND u.deleted_at IS NULL
+                WHERE k.key_hash = ?
+            "#,
+            self.backend().group_concat("user_roles.role")
+        ))
+        .bind(key_hash)
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => {
+                let mut api_key = from_row(&row)?;
+                let owner_roles = row.try_get::<Option<String>>("owner_roles")?.unwrap_or_default();
+                api_key.restrict_roles(&Role::from_names(owner_roles.split(',')));
+
+                Ok(Some(api_key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn get_by_id(&self, id: &str) -> AppResult<Option<ApiKey>> {
+        let row = query("SELECT * FROM api_keys WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self)
+            .await?;
+
+        row.as_ref().map(from_row).transpose()
+    }
+
+    #[instrument(skip(self))]
+    async fn get_all_by_user(&self, user_id: &str) -> AppResult<Vec<ApiKey>> {
+        let rows = query("SELECT * FROM api_keys WHERE user_id = ? ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(self)
+            .await?;
+
+        rows.iter().map(from_row).collect()
+    }
+
+    #[instrument(skip(self))]
+    async fn update(&self, api_key: &ApiKey) -> AppResult<()> {
+        query(
+            r#"
+                UPDATE api_keys
+                SET name = ?, roles = ?, routes = ?, rate_limit = ?, expired_at = ?, updated_at = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(&api_key.name)
+        .bind(&api_key.roles)
+        .bind(&api_key.routes)
+        .bind(api_key.rate_limit)
+        .bind(api_key.expired_at)
+        .bind(Utc::now())
+        .bind(&api_key.id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, id: &str) -> AppResult<u64> {
+        let result = query("DELETE FROM api_keys WHERE id = ?")
+            .bind(id)
+            .execute(self)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn touch(&self, id: &str) -> AppResult<()> {
+        let now = Utc::now();
+        query(
+            r#"
+                UPDATE api_keys
+                SET last_used_at = ?
+                WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)
+            "#,
+        )
+        .bind(now)
+        .bind(id)
+        .bind(now - Duration::seconds(LAST_USED_AT_PRECISION))
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn from_row(row: &DbRow) -> AppResult<ApiKey> {
+    Ok(ApiKey {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        prefix: row.try_get("prefix")?,
+        key_hash: row.try_get("key_hash")?,
+        roles: row.try_get("roles")?,
+        routes: row.try_get("routes")?,
+        rate_limit: row.try_get("rate_limit")?,
+        expired_at: row.try_get("expired_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
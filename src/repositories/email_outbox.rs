@@ -0,0 +1,249 @@
+use crate::app_error;
+use crate::database::{query, Database, DbRow};
+use crate::models::email_outbox::{EmailOutboxStatus, OutboxEmail};
+use crate::utils::errors::{AppError, AppErrorCode, AppResult};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[async_trait]
+pub trait EmailOutboxRepository: Send + Sync {
+    /// Add a new email in the outbox
+    async fn create(&self, email: &OutboxEmail) -> AppResult<()>;
+
+    /// Returns pending emails whose next attempt date is reached
+    async fn get_ready(&self, limit: u32) -> AppResult<Vec<OutboxEmail>>;
+
+    /// Returns emails with a status, the most recent first
+    async fn get_by_status(&self, status: EmailOutboxStatus) -> AppResult<Vec<OutboxEmail>>;
+
+    /// Lock a ready email until `locked_until` so that it is sent only once if several workers are running.
+    ///
+    /// Returns `false` if the email has already been locked by another worker.
+    async fn lock(&self, id: &str, locked_until: DateTime<Utc>) -> AppResult<bool>;
+
+    /// Mark an email as sent
+    async fn mark_as_sent(&self, id: &str, attempts: u32) -> AppResult<()>;
+
+    /// Save a failed attempt.
+    ///
+    /// The email is retried at `next_attempt_at` or moved to the dead letter state if `None`.
+    async fn mark_as_failed(
+        &self,
+        id: &str,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()>;
+
+    /// Move dead emails (all or only one) back to the pending state to send them again
+    async fn replay(&self, id: Option<&str>) -> AppResult<u64>;
+}
+
+#[async_trait]
+impl EmailOutboxRepository for Database {
+    #[instrument(skip(self, email), fields(id = %email.id))]
+    async fn create(&self, email: &OutboxEmail) -> AppResult<()> {
+        let to_list = serde_json::to_string(&email.to_list).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error when adding email in outbox",
+                format!("error when serializing email recipients: {err}")
+            )
+        })?;
+
+        query(
+            r#"
+                INSERT INTO email_outbox (id, from_email, to_list, subject, text_body, html_body, status, attempts, last_error, next_attempt_at, sent_at, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&email.id)
+        .bind(&email.from_email)
+        .bind(to_list)
+        .bind(&email.subject)
+        .bind(&email.text_body)
+        .bind(&email.html_body)
+        .bind(email.status.to_string())
+        .bind(email.attempts)
+        .bind(&email.last_error)
+        .bind(email.next_attempt_at)
+        .bind(email.sent_at)
+        .bind(email.created_at)
+        .bind(email.updated_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_ready(&self, limit: u32) -> AppResult<Vec<OutboxEmail>> {
+        let rows = query(
+            r#"
+                SELECT *
+                FROM email_outbox
+                WHERE status = ? AND next_attempt_at <= ?
+                ORDER BY next_attempt_at
+                LIMIT ?
+            "#,
+        )
+        .bind(EmailOutboxStatus::Pending.to_string())
+        .bind(Utc::now())
+        .bind(limit)
+        .fetch_all(self)
+        .await?;
+
+        rows.iter().map(from_row).collect()
+    }
+
+    #[instrument(skip(self))]
+    async fn get_by_status(&self, status: EmailOutboxStatus) -> AppResult<Vec<OutboxEmail>> {
+        let rows = query(
+            r#"
+                SELECT *
+                FROM email_outbox
+                WHERE status = ?
+                ORDER BY created_at DESC
+            "#,
+        )
+        .bind(status.to_string())
+        .fetch_all(self)
+        .await?;
+
+        rows.iter().map(from_row).collect()
+    }
+
+    #[instrument(skip(self))]
+    async fn lock(&self, id: &str, locked_until: DateTime<Utc>) -> AppResult<bool> {
+        let result = query(
+            r#"
+                UPDATE email_outbox
+                SET next_attempt_at = ?
+                WHERE id = ? AND status = ? AND next_attempt_at <= ?
+            "#,
+        )
+        .bind(locked_until)
+        .bind(id)
+        .bind(EmailOutboxStatus::Pending.to_string())
+        .bind(Utc::now())
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[instrument(skip(self))]
+    async fn mark_as_sent(&self, id: &str, attempts: u32) -> AppResult<()> {
+        let now = Utc::now();
+
+        query(
+            r#"
+                UPDATE email_outbox
+                SET status = ?, attempts = ?, sent_at = ?, updated_at = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(EmailOutboxStatus::Sent.to_string())
+        .bind(attempts)
+        .bind(now)
+        .bind(now)
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn mark_as_failed(
+        &self,
+        id: &str,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let now = Utc::now();
+        let status = match next_attempt_at {
+            Some(_) => EmailOutboxStatus::Pending,
+            None => EmailOutboxStatus::Dead,
+        };
+
+        query(
+            r#"
+                UPDATE email_outbox
+                SET status = ?, attempts = ?, last_error = ?, next_attempt_at = ?, updated_at = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(status.to_string())
+        .bind(attempts)
+        .bind(error)
+        .bind(next_attempt_at.unwrap_or(now))
+        .bind(now)
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn replay(&self, id: Option<&str>) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut sql = String::from(
+            r#"
+                UPDATE email_outbox
+                SET status = ?, attempts = 0, next_attempt_at = ?, updated_at = ?
+                WHERE status = ?
+            "#,
+        );
+        if id.is_some() {
+            sql.push_str(" AND id = ?");
+        }
+
+        let mut db_query = query(&sql)
+            .bind(EmailOutboxStatus::Pending.to_string())
+            .bind(now)
+            .bind(now)
+            .bind(EmailOutboxStatus::Dead.to_string());
+        if let Some(id) = id {
+            db_query = db_query.bind(id);
+        }
+
+        Ok(db_query.execute(self).await?.rows_affected())
+    }
+}
+
+/// Build an `OutboxEmail` from a database row
+fn from_row(row: &DbRow) -> AppResult<OutboxEmail> {
+    let to_list: String = row.try_get("to_list")?;
+    let status: String = row.try_get("status")?;
+
+    Ok(OutboxEmail {
+        id: row.try_get("id")?,
+        from_email: row.try_get("from_email")?,
+        to_list: serde_json::from_str(&to_list).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error when reading email outbox",
+                format!("invalid email recipients in outbox: {err}")
+            )
+        })?,
+        subject: row.try_get("subject")?,
+        text_body: row.try_get("text_body")?,
+        html_body: row.try_get("html_body")?,
+        status: EmailOutboxStatus::try_from_str(&status).ok_or_else(|| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error when reading email outbox",
+                format!("invalid email status in outbox: {status}")
+            )
+        })?,
+        attempts: row.try_get("attempts")?,
+        last_error: row.try_get("last_error")?,
+        next_attempt_at: row.try_get("next_attempt_at")?,
+        sent_at: row.try_get("sent_at")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
@@ -0,0 +1,90 @@
+use crate::database::{query, Database};
+use crate::models::login_attempt::LoginAttempt;
+use crate::utils::errors::AppResult;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    /// Record a failed login
+    async fn create(&self, attempt: &LoginAttempt) -> AppResult<()>;
+
+    /// Number of failed logins of a username since a date
+    async fn count_by_username(&self, username: &str, since: DateTime<Utc>) -> AppResult<u32>;
+
+    /// Number of failed logins from an IP address since a date
+    async fn count_by_ip(&self, ip: &str, since: DateTime<Utc>) -> AppResult<u32>;
+
+    /// Forget the failed logins of a username (successful login or unlock)
+    async fn delete_by_username(&self, username: &str) -> AppResult<u64>;
+}
+
+#[async_trait]
+impl LoginAttemptRepository for Database {
+    #[instrument(skip(self))]
+    async fn create(&self, attempt: &LoginAttempt) -> AppResult<()> {
+        query(
+            r#"
+                INSERT INTO login_attempts (id, username, ip, created_at)
+                VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(&attempt.id)
+        .bind(&attempt.username)
+        .bind(&attempt.ip)
+        .bind(attempt.created_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn count_by_username(&self, username: &str, since: DateTime<Utc>) -> AppResult<u32> {
+        let row = query(
+            r#"
+                SELECT COUNT(*) AS n
+                FROM login_attempts
+                WHERE username = ? AND created_at > ?
+            "#,
+        )
+        .bind(LoginAttempt::normalize_username(username))
+        .bind(since)
+        .fetch_one(self)
+        .await?;
+
+        Ok(row.try_get::<i64>("n")? as u32)
+    }
+
+    #[instrument(skip(self))]
+    async fn count_by_ip(&self, ip: &str, since: DateTime<Utc>) -> AppResult<u32> {
+        let row = query(
+            r#"
+                SELECT COUNT(*) AS n
+                FROM login_attempts
+                WHERE ip = ? AND created_at > ?
+            "#,
+        )
+        .bind(ip)
+        .bind(since)
+        .fetch_one(self)
+        .await?;
+
+        Ok(row.try_get::<i64>("n")? as u32)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_by_username(&self, username: &str) -> AppResult<u64> {
+        let result = query(
+            r#"
+                DELETE FROM login_attempts
+                WHERE username = ?
+            "#,
+        )
+        .bind(LoginAttempt::normalize_username(username))
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
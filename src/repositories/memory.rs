@@ -0,0 +1,1085 @@
+//! In-memory implementation of the stores, used to test the handlers without a database.
+//!
+//! All the stores share the same data behind a mutex, which is never held across an `.await`.
+
+use super::api_key::{ApiKeyRepository, LAST_USED_AT_PRECISION};
+use super::email_outbox::EmailOutboxRepository;
+use super::login_attempt::LoginAttemptRepository;
+use super::mfa::MfaRepository;
+use super::oidc::OidcRepository;
+use super::refresh_token::RefreshTokenRepository;
+use super::role::RoleRepository;
+use super::user::{
+    column_value, EmailChangeRepository, EmailVerificationRepository, PasswordResetStore, UserStore, USER_SORT_FIELDS,
+};
+use crate::app_error;
+use crate::models::api_key::ApiKey;
+use crate::models::auth::RefreshToken;
+use crate::models::email_outbox::{EmailOutboxStatus, OutboxEmail};
+use crate::models::login_attempt::LoginAttempt;
+use crate::models::mfa::{MfaChallenge, RecoveryCode, UserTotp};
+use crate::models::oidc::{OidcAuthorization, UserIdentity};
+use crate::models::role::{Permission, RolePermissions};
+use crate::models::user::{
+    EmailChange, EmailVerification, Login, PasswordReset, Role, User, UserCreation, UserProfileUpdate,
+};
+use crate::utils::errors::{AppError, AppErrorCode, AppResult};
+use crate::utils::password::{PasswordHasher, PasswordVerification};
+use crate::utils::query::{Filters, KeysetPagination, PaginateResponse, PaginateSort};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// User with the columns which are not part of the `User` model
+#[derive(Debug, Clone)]
+struct MemoryUser {
+    user: User,
+    email_verified_at: Option<DateTime<Utc>>,
+    locked_until: Option<DateTime<Utc>>,
+    unlock_token_hash: Option<String>,
+    totp: UserTotp,
+}
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    users: Vec<MemoryUser>,
+    password_resets: Vec<PasswordReset>,
+    email_verifications: Vec<EmailVerification>,
+    email_changes: Vec<EmailChange>,
+    refresh_tokens: Vec<RefreshToken>,
+    login_attempts: Vec<LoginAttempt>,
+    emails: Vec<OutboxEmail>,
+    recovery_codes: Vec<RecoveryCode>,
+    mfa_challenges: Vec<MfaChallenge>,
+    custom_roles: Vec<RolePermissions>,
+    api_keys: Vec<ApiKey>,
+    oidc_authorizations: Vec<OidcAuthorization>,
+    user_identities: Vec<UserIdentity>,
+}
+
+impl MemoryData {
+    /// Built-in and custom roles
+    fn roles(&self) -> Vec<RolePermissions> {
+        let mut roles = RolePermissions::built_in();
+        roles.extend(self.custom_roles.iter().cloned());
+
+        roles
+    }
+
+    /// Not deleted user
+    fn user(&self, id: &str) -> Option<&MemoryUser> {
+        self.users
+            .iter()
+            .find(|user| user.user.id == id && user.user.deleted_at.is_none())
+    }
+
+    /// User, even deleted
+    fn user_mut(&mut self, id: &str) -> Option<&mut MemoryUser> {
+        self.users.iter_mut().find(|user| user.user.id == id)
+    }
+
+    /// Not deleted users matching the filters
+    fn filtered_users(&self, filters: &Filters) -> Vec<User> {
+        self.users
+            .iter()
+            .filter(|user| user.user.deleted_at.is_none())
+            .map(|user| user.user.clone())
+            .filter(|user| filters.matches(user, column_value))
+            .collect()
+    }
+
+    /// Add a user, its username must be unique
+    fn insert_user(&mut self, user: &User, email_verified_at: Option<DateTime<Utc>>) -> AppResult<()> {
+        if self.users.iter().any(|u| u.user.username == user.username) {
+            return Err(app_error!(
+                AppErrorCode::InternalError,
+                "Database Error",
+                format!("duplicate username: {}", user.username)
+            ));
+        }
+
+        self.users.push(MemoryUser {
+            user: user.clone(),
+            email_verified_at,
+            locked_until: None,
+            unlock_token_hash: None,
+            totp: UserTotp::default(),
+        });
+
+        Ok(())
+    }
+}
+
+/// Thread-safe in-memory store (cloned stores share the same data)
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    data: Arc<Mutex<MemoryData>>,
+}
+
+impl InMemoryStore {
+    /// Lock the data
+    fn data(&self) -> AppResult<MutexGuard<'_, MemoryData>> {
+        self.data.lock().map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "Database Error",
+                format!("in-memory store lock error: {err}")
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl UserStore for InMemoryStore {
+    async fn login(&self, hasher: &PasswordHasher, input: Login) -> AppResult<Option<User>> {
+        let user = self.get_by_email(input.username).await?;
+
+        match user {
+            Some(mut user) => match hasher.verify_async(&input.password, &user.password).await? {
+                PasswordVerification::Invalid => Ok(None),
+                PasswordVerification::Valid => Ok(Some(user)),
+                PasswordVerification::ValidNeedsRehash => {
+                    user.password = hasher.hash_async(&input.password).await?;
+                    if let Some(stored) = self.data()?.user_mut(&user.id) {
+                        stored.user.password = user.password.clone();
+                    }
+
+                    Ok(Some(user))
+                }
+            },
+            None => {
+                // Hash anyway to answer in the same time as for an existing username
+                hasher.hash_async(&input.password).await?;
+
+                Ok(None)
+            }
+        }
+    }
+
+    async fn create(&self, hasher: &PasswordHasher, user: &mut User) -> AppResult<()> {
+        user.password = hasher.hash_async(&user.password).await?;
+
+        self.data()?.insert_user(user, Some(user.created_at))
+    }
+
+    async fn get_all<'a>(
+        &self,
+        paginate_sort: &'a PaginateSort,
+        filters: &'a Filters,
+        with_total: bool,
+    ) -> AppResult<PaginateResponse<Vec<User>>> {
+        let users = self.data()?.filtered_users(filters);
+        let total = with_total.then_some(users.len() as i64);
+
+        Ok(PaginateResponse {
+            data: paginate_sort.apply(users, USER_SORT_FIELDS, column_value),
+            total,
+            next_cursor: None,
+            prev_cursor: None,
+        })
+    }
+
+    async fn get_all_by_cursor<'a>(
+        &self,
+        pagination: &'a KeysetPagination,
+        filters: &'a Filters,
+        with_total: bool,
+        cursor_secret: &str,
+    ) -> AppResult<PaginateResponse<Vec<User>>> {
+        let users = self.data()?.filtered_users(filters);
+        let total = with_total.then_some(users.len() as i64);
+
+        let users = pagination.apply(users, column_value);
+        let mut response = pagination.paginate(users, column_value, cursor_secret)?;
+        response.total = total;
+
+        Ok(response)
+    }
+
+    async fn get_by_id(&self, id: String) -> AppResult<Option<User>> {
+        Ok(self.data()?.user(&id).map(|user| user.user.clone()))
+    }
+
+    async fn get_by_email(&self, email: String) -> AppResult<Option<User>> {
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| user.user.username == email && user.user.deleted_at.is_none())
+            .map(|user| user.user.clone()))
+    }
+
+    async fn delete(&self, id: String) -> AppResult<u64> {
+        let mut data = self.data()?;
+
+        match data.user_mut(&id) {
+            Some(user) if user.user.deleted_at.is_none() => {
+                user.user.deleted_at = Some(Utc::now());
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    async fn update(&self, hasher: &PasswordHasher, id: String, user: &UserCreation) -> AppResult<()> {
+        let hashed_password = hasher.hash_async(&user.password).await?;
+        let mut data = self.data()?;
+
+        if data
+            .users
+            .iter()
+            .any(|u| u.user.username == user.username && u.user.id != id)
+        {
+            return Err(app_error!(
+                AppErrorCode::InternalError,
+                "Database Error",
+                format!("duplicate username: {}", user.username)
+            ));
+        }
+
+        if let Some(stored) = data.user_mut(&id) {
+            stored.user.lastname = user.lastname.clone();
+            stored.user.firstname = user.firstname.clone();
+            stored.user.username = user.username.clone();
+            stored.user.password = hashed_password;
+            stored.user.roles = user.roles();
+            stored.user.rate_limit = user.rate_limit;
+            stored.user.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn update_profile(&self, id: &str, profile: &UserProfileUpdate) -> AppResult<()> {
+        if let Some(stored) = self.data()?.user_mut(id) {
+            if let Some(lastname) = &profile.lastname {
+                stored.user.lastname = lastname.clone();
+            }
+            if let Some(firstname) = &profile.firstname {
+                stored.user.firstname = firstname.clone();
+            }
+            stored.user.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn is_username_used(&self, username: &str) -> AppResult<bool> {
+        Ok(self.data()?.users.iter().any(|user| user.user.username == username))
+    }
+
+    async fn update_password(
+        &self,
+        hasher: &PasswordHasher,
+        id: String,
+        current_password: String,
+        new_password: String,
+    ) -> AppResult<()> {
+        if hasher.verify_async(&new_password, &current_password).await?.is_valid() {
+            return Err(app_error!(
+                AppErrorCode::BadRequest,
+                "new password cannot be the same as the current one"
+            ));
+        }
+
+        let hashed_password = hasher.hash_async(&new_password).await?;
+
+        if let Some(stored) = self.data()?.user_mut(&id) {
+            stored.user.password = hashed_password;
+            stored.user.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn get_locked_until(&self, id: &str) -> AppResult<Option<DateTime<Utc>>> {
+        let now = Utc::now();
+
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| user.user.id == id)
+            .and_then(|user| user.locked_until)
+            .filter(|locked_until| *locked_until > now))
+    }
+
+    async fn get_by_unlock_token(&self, token_hash: &str) -> AppResult<Option<User>> {
+        let now = Utc::now();
+
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .find(|user| {
+                user.unlock_token_hash.as_deref() == Some(token_hash)
+                    && user.locked_until.is_some_and(|locked_until| locked_until > now)
+                    && user.user.deleted_at.is_none()
+            })
+            .map(|user| user.user.clone()))
+    }
+
+    async fn lock(&self, id: &str, locked_until: DateTime<Utc>, unlock_token_hash: &str) -> AppResult<()> {
+        if let Some(stored) = self.data()?.user_mut(id) {
+            stored.locked_until = Some(locked_until);
+            stored.unlock_token_hash = Some(unlock_token_hash.to_owned());
+        }
+
+        Ok(())
+    }
+
+    async fn unlock(&self, id: &str) -> AppResult<()> {
+        if let Some(stored) = self.data()?.user_mut(id) {
+            stored.locked_until = None;
+            stored.unlock_token_hash = None;
+        }
+
+        Ok(())
+    }
+
+    async fn register(
+        &self,
+        hasher: &PasswordHasher,
+        user: &mut User,
+        verification: &EmailVerification,
+    ) -> AppResult<()> {
+        user.password = hasher.hash_async(&user.password).await?;
+
+        let mut data = self.data()?;
+        data.insert_user(user, None)?;
+        data.email_verifications.push(verification.clone());
+
+        Ok(())
+    }
+
+    async fn is_email_verified(&self, id: &str) -> AppResult<bool> {
+        Ok(self
+            .data()?
+            .users
+            .iter()
+            .any(|user| user.user.id == id && user.email_verified_at.is_some()))
+    }
+
+    async fn verify_email(&self, id: &str) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        if let Some(stored) = data.user_mut(id) {
+            stored.email_verified_at = Some(Utc::now());
+        }
+        data.email_verifications
+            .retain(|verification| verification.user_id != id);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RoleRepository for InMemoryStore {
+    async fn get_all(&self) -> AppResult<Vec<RolePermissions>> {
+        Ok(self.data()?.roles())
+    }
+
+    async fn get(&self, role: &Role) -> AppResult<Option<RolePermissions>> {
+        Ok(self.data()?.roles().into_iter().find(|r| r.name == *role))
+    }
+
+    async fn create(&self, role: &RolePermissions) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        if data.roles().iter().any(|r| r.name == role.name) {
+            return Err(app_error!(
+                AppErrorCode::InternalError,
+                "Database Error",
+                format!("duplicate role: {}", role.name)
+            ));
+        }
+        data.custom_roles.push(role.clone());
+
+        Ok(())
+    }
+
+    async fn update(&self, role: &RolePermissions) -> AppResult<()> {
+        if let Some(stored) = self.data()?.custom_roles.iter_mut().find(|r| r.name == role.name) {
+            stored.permissions = role.permissions.clone();
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, role: &Role) -> AppResult<()> {
+        self.data()?.custom_roles.retain(|r| r.name != *role);
+
+        Ok(())
+    }
+
+    async fn is_granted(&self, role: &Role) -> AppResult<bool> {
+        Ok(self.data()?.users.iter().any(|user| user.user.roles.contains(role)))
+    }
+
+    async fn get_permissions(&self, roles: &[Role]) -> AppResult<Vec<Permission>> {
+        let permissions = self
+            .data()?
+            .roles()
+            .into_iter()
+            .filter(|role| roles.contains(&role.name))
+            .flat_map(|role| role.permissions)
+            .collect::<BTreeSet<_>>();
+
+        Ok(permissions.into_iter().collect())
+    }
+
+    async fn grant(&self, user_id: &str, role: &Role) -> AppResult<bool> {
+        match self.data()?.user_mut(user_id) {
+            Some(stored) if !stored.user.roles.contains(role) => {
+                stored.user.roles.push(role.clone());
+                stored.user.roles.sort();
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke(&self, user_id: &str, role: &Role) -> AppResult<bool> {
+        match self.data()?.user_mut(user_id) {
+            Some(stored) if stored.user.roles.contains(role) => {
+                stored.user.roles.retain(|r| r != role);
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl PasswordResetStore for InMemoryStore {
+    async fn create_or_update(&self, password_reset: &PasswordReset) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        data.password_resets
+            .retain(|reset| reset.user_id != password_reset.user_id);
+        data.password_resets.push(password_reset.clone());
+
+        Ok(())
+    }
+
+    async fn get_by_user(&self, user_id: &str) -> AppResult<Option<PasswordReset>> {
+        Ok(self
+            .data()?
+            .password_resets
+            .iter()
+            .find(|reset| reset.user_id == user_id)
+            .cloned())
+    }
+
+    async fn get_user_id_from_hash(&self, token_hash: &str) -> AppResult<Option<(String, String)>> {
+        let now = Utc::now();
+        let data = self.data()?;
+
+        Ok(data
+            .password_resets
+            .iter()
+            .find(|reset| reset.token_hash == token_hash && reset.expired_at >= now)
+            .and_then(|reset| data.user(&reset.user_id))
+            .map(|user| (user.user.id.clone(), user.user.password.clone())))
+    }
+
+    async fn reset_password(&self, user_id: &str, token_hash: &str, password: &str) -> AppResult<bool> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        let count = data.password_resets.len();
+        data.password_resets
+            .retain(|reset| reset.user_id != user_id || reset.token_hash != token_hash || reset.expired_at < now);
+        if data.password_resets.len() == count {
+            return Ok(false);
+        }
+
+        if let Some(stored) = data.user_mut(user_id) {
+            stored.user.password = password.to_owned();
+            stored.user.updated_at = now;
+        }
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl EmailVerificationRepository for InMemoryStore {
+    async fn create(&self, verification: &EmailVerification) -> AppResult<()> {
+        self.data()?.email_verifications.push(verification.clone());
+
+        Ok(())
+    }
+
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<EmailVerification>> {
+        let data = self.data()?;
+
+        Ok(data
+            .email_verifications
+            .iter()
+            .find(|verification| verification.token_hash == token_hash && data.user(&verification.user_id).is_some())
+            .cloned())
+    }
+
+    async fn delete_by_user(&self, user_id: &str) -> AppResult<u64> {
+        let mut data = self.data()?;
+
+        let count = data.email_verifications.len();
+        data.email_verifications
+            .retain(|verification| verification.user_id != user_id);
+
+        Ok((count - data.email_verifications.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl EmailChangeRepository for InMemoryStore {
+    async fn create(&self, change: &EmailChange) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        data.email_changes.retain(|c| c.user_id != change.user_id);
+        data.email_changes.push(change.clone());
+
+        Ok(())
+    }
+
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<EmailChange>> {
+        let data = self.data()?;
+
+        Ok(data
+            .email_changes
+            .iter()
+            .find(|change| change.token_hash == token_hash && data.user(&change.user_id).is_some())
+            .cloned())
+    }
+
+    async fn confirm(&self, change: &EmailChange) -> AppResult<()> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        if let Some(stored) = data.user_mut(&change.user_id) {
+            stored.user.username = change.new_email.clone();
+            stored.user.updated_at = now;
+            stored.email_verified_at = Some(now);
+        }
+        data.email_changes.retain(|c| c.user_id != change.user_id);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for InMemoryStore {
+    async fn create(&self, refresh_token: &RefreshToken) -> AppResult<()> {
+        self.data()?.refresh_tokens.push(refresh_token.clone());
+
+        Ok(())
+    }
+
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<RefreshToken>> {
+        let data = self.data()?;
+
+        Ok(data
+            .refresh_tokens
+            .iter()
+            .find(|token| token.token_hash == token_hash && data.user(&token.user_id).is_some())
+            .cloned())
+    }
+
+    async fn use_token(&self, id: &str) -> AppResult<bool> {
+        let mut data = self.data()?;
+
+        match data
+            .refresh_tokens
+            .iter_mut()
+            .find(|token| token.id == id && token.used_at.is_none())
+        {
+            Some(token) => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        let mut count = 0;
+        for token in data
+            .refresh_tokens
+            .iter_mut()
+            .filter(|token| token.family_id == family_id && token.revoked_at.is_none())
+        {
+            token.revoked_at = Some(now);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    async fn revoke_user(&self, user_id: &str) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        let mut count = 0;
+        for token in data
+            .refresh_tokens
+            .iter_mut()
+            .filter(|token| token.user_id == user_id && token.revoked_at.is_none())
+        {
+            token.revoked_at = Some(now);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for InMemoryStore {
+    async fn create(&self, attempt: &LoginAttempt) -> AppResult<()> {
+        self.data()?.login_attempts.push(attempt.clone());
+
+        Ok(())
+    }
+
+    async fn count_by_username(&self, username: &str, since: DateTime<Utc>) -> AppResult<u32> {
+        let username = LoginAttempt::normalize_username(username);
+
+        Ok(self
+            .data()?
+            .login_attempts
+            .iter()
+            .filter(|attempt| attempt.username == username && attempt.created_at > since)
+            .count() as u32)
+    }
+
+    async fn count_by_ip(&self, ip: &str, since: DateTime<Utc>) -> AppResult<u32> {
+        Ok(self
+            .data()?
+            .login_attempts
+            .iter()
+            .filter(|attempt| attempt.ip == ip && attempt.created_at > since)
+            .count() as u32)
+    }
+
+    async fn delete_by_username(&self, username: &str) -> AppResult<u64> {
+        let username = LoginAttempt::normalize_username(username);
+        let mut data = self.data()?;
+
+        let count = data.login_attempts.len();
+        data.login_attempts.retain(|attempt| attempt.username != username);
+
+        Ok((count - data.login_attempts.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl EmailOutboxRepository for InMemoryStore {
+    async fn create(&self, email: &OutboxEmail) -> AppResult<()> {
+        self.data()?.emails.push(email.clone());
+
+        Ok(())
+    }
+
+    async fn get_ready(&self, limit: u32) -> AppResult<Vec<OutboxEmail>> {
+        let now = Utc::now();
+
+        let mut emails = self
+            .data()?
+            .emails
+            .iter()
+            .filter(|email| email.status == EmailOutboxStatus::Pending && email.next_attempt_at <= now)
+            .cloned()
+            .collect::<Vec<_>>();
+        emails.sort_by_key(|email| email.next_attempt_at);
+        emails.truncate(limit as usize);
+
+        Ok(emails)
+    }
+
+    async fn get_by_status(&self, status: EmailOutboxStatus) -> AppResult<Vec<OutboxEmail>> {
+        let mut emails = self
+            .data()?
+            .emails
+            .iter()
+            .filter(|email| email.status == status)
+            .cloned()
+            .collect::<Vec<_>>();
+        emails.sort_by_key(|email| Reverse(email.created_at));
+
+        Ok(emails)
+    }
+
+    async fn lock(&self, id: &str, locked_until: DateTime<Utc>) -> AppResult<bool> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        match data
+            .emails
+            .iter_mut()
+            .find(|email| email.id == id && email.status == EmailOutboxStatus::Pending && email.next_attempt_at <= now)
+        {
+            Some(email) => {
+                email.next_attempt_at = locked_until;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn mark_as_sent(&self, id: &str, attempts: u32) -> AppResult<()> {
+        let now = Utc::now();
+
+        if let Some(email) = self.data()?.emails.iter_mut().find(|email| email.id == id) {
+            email.status = EmailOutboxStatus::Sent;
+            email.attempts = attempts;
+            email.sent_at = Some(now);
+            email.updated_at = now;
+        }
+
+        Ok(())
+    }
+
+    async fn mark_as_failed(
+        &self,
+        id: &str,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let now = Utc::now();
+
+        if let Some(email) = self.data()?.emails.iter_mut().find(|email| email.id == id) {
+            email.status = match next_attempt_at {
+                Some(_) => EmailOutboxStatus::Pending,
+                None => EmailOutboxStatus::Dead,
+            };
+            email.attempts = attempts;
+            email.last_error = Some(error.to_owned());
+            email.next_attempt_at = next_attempt_at.unwrap_or(now);
+            email.updated_at = now;
+        }
+
+        Ok(())
+    }
+
+    async fn replay(&self, id: Option<&str>) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        let mut count = 0;
+        for email in data
+            .emails
+            .iter_mut()
+            .filter(|email| email.status == EmailOutboxStatus::Dead && id.is_none_or(|id| email.id == id))
+        {
+            email.status = EmailOutboxStatus::Pending;
+            email.attempts = 0;
+            email.next_attempt_at = now;
+            email.updated_at = now;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl MfaRepository for InMemoryStore {
+    async fn get_totp(&self, user_id: &str) -> AppResult<Option<UserTotp>> {
+        Ok(self.data()?.user(user_id).map(|user| user.totp.clone()))
+    }
+
+    async fn set_totp_secret(&self, user_id: &str, secret: &str) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        if let Some(stored) = data.user_mut(user_id).filter(|user| user.user.deleted_at.is_none()) {
+            stored.totp = UserTotp {
+                secret: Some(secret.to_owned()),
+                enabled_at: None,
+                last_used_step: None,
+            };
+            stored.user.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: &str, step: i64) -> AppResult<()> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        if let Some(stored) = data.user_mut(user_id).filter(|user| user.user.deleted_at.is_none()) {
+            stored.totp.enabled_at = Some(now);
+            stored.totp.last_used_step = Some(step);
+            stored.user.updated_at = now;
+        }
+
+        Ok(())
+    }
+
+    async fn disable_totp(&self, user_id: &str) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        if let Some(stored) = data.user_mut(user_id) {
+            stored.totp = UserTotp::default();
+            stored.user.updated_at = Utc::now();
+        }
+        data.recovery_codes.retain(|code| code.user_id != user_id);
+
+        Ok(())
+    }
+
+    async fn use_totp_step(&self, user_id: &str, step: i64) -> AppResult<bool> {
+        let mut data = self.data()?;
+
+        match data
+            .user_mut(user_id)
+            .filter(|user| user.totp.last_used_step.is_none_or(|last_step| last_step < step))
+        {
+            Some(stored) => {
+                stored.totp.last_used_step = Some(step);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn replace_recovery_codes(&self, user_id: &str, recovery_codes: &[RecoveryCode]) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        data.recovery_codes.retain(|code| code.user_id != user_id);
+        data.recovery_codes.extend_from_slice(recovery_codes);
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> AppResult<bool> {
+        let mut data = self.data()?;
+
+        match data
+            .recovery_codes
+            .iter_mut()
+            .find(|code| code.user_id == user_id && code.code_hash == code_hash && code.used_at.is_none())
+        {
+            Some(code) => {
+                code.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn create_challenge(&self, challenge: &MfaChallenge) -> AppResult<()> {
+        self.data()?.mfa_challenges.push(challenge.clone());
+
+        Ok(())
+    }
+
+    async fn get_challenge_by_hash(&self, token_hash: &str) -> AppResult<Option<MfaChallenge>> {
+        let data = self.data()?;
+
+        Ok(data
+            .mfa_challenges
+            .iter()
+            .find(|challenge| challenge.token_hash == token_hash && data.user(&challenge.user_id).is_some())
+            .cloned())
+    }
+
+    async fn increment_challenge_attempts(&self, id: &str) -> AppResult<()> {
+        if let Some(challenge) = self
+            .data()?
+            .mfa_challenges
+            .iter_mut()
+            .find(|challenge| challenge.id == id)
+        {
+            challenge.attempts += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_challenge(&self, id: &str) -> AppResult<bool> {
+        let mut data = self.data()?;
+
+        let count = data.mfa_challenges.len();
+        data.mfa_challenges.retain(|challenge| challenge.id != id);
+
+        Ok(data.mfa_challenges.len() < count)
+    }
+
+    async fn delete_expired_challenges(&self) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        let count = data.mfa_challenges.len();
+        data.mfa_challenges.retain(|challenge| challenge.expired_at >= now);
+
+        Ok((count - data.mfa_challenges.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for InMemoryStore {
+    async fn create(&self, api_key: &ApiKey) -> AppResult<()> {
+        self.data()?.api_keys.push(api_key.clone());
+
+        Ok(())
+    }
+
+    async fn get_by_hash(&self, key_hash: &str) -> AppResult<Option<ApiKey>> {
+        let data = self.data()?;
+
+        Ok(data
+            .api_keys
+            .iter()
+            .find(|api_key| api_key.key_hash == key_hash)
+            .and_then(|api_key| {
+                data.user(&api_key.user_id).map(|owner| {
+                    let mut api_key = api_key.clone();
+                    api_key.restrict_roles(&owner.user.roles);
+                    api_key
+                })
+            }))
+    }
+
+    async fn get_by_id(&self, id: &str) -> AppResult<Option<ApiKey>> {
+        Ok(self.data()?.api_keys.iter().find(|api_key| api_key.id == id).cloned())
+    }
+
+    async fn get_all_by_user(&self, user_id: &str) -> AppResult<Vec<ApiKey>> {
+        let mut api_keys: Vec<ApiKey> = self
+            .data()?
+            .api_keys
+            .iter()
+            .filter(|api_key| api_key.user_id == user_id)
+            .cloned()
+            .collect();
+        api_keys.sort_by_key(|api_key| api_key.created_at);
+
+        Ok(api_keys)
+    }
+
+    async fn update(&self, api_key: &ApiKey) -> AppResult<()> {
+        if let Some(stored) = self.data()?.api_keys.iter_mut().find(|stored| stored.id == api_key.id) {
+            stored.name = api_key.name.clone();
+            stored.roles = api_key.roles.clone();
+            stored.routes = api_key.routes.clone();
+            stored.rate_limit = api_key.rate_limit;
+            stored.expired_at = api_key.expired_at;
+            stored.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> AppResult<u64> {
+        let mut data = self.data()?;
+
+        let count = data.api_keys.len();
+        data.api_keys.retain(|api_key| api_key.id != id);
+
+        Ok((count - data.api_keys.len()) as u64)
+    }
+
+    async fn touch(&self, id: &str) -> AppResult<()> {
+        let now = Utc::now();
+
+        if let Some(api_key) = self.data()?.api_keys.iter_mut().find(|api_key| {
+            api_key.id == id
+                && api_key
+                    .last_used_at
+                    .is_none_or(|last_used_at| last_used_at < now - Duration::seconds(LAST_USED_AT_PRECISION))
+        }) {
+            api_key.last_used_at = Some(now);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OidcRepository for InMemoryStore {
+    async fn create_authorization(&self, authorization: &OidcAuthorization) -> AppResult<()> {
+        self.data()?.oidc_authorizations.push(authorization.clone());
+
+        Ok(())
+    }
+
+    async fn take_authorization(&self, state_hash: &str) -> AppResult<Option<OidcAuthorization>> {
+        let mut data = self.data()?;
+
+        Ok(data
+            .oidc_authorizations
+            .iter()
+            .position(|authorization| authorization.state_hash == state_hash)
+            .map(|index| data.oidc_authorizations.remove(index)))
+    }
+
+    async fn delete_expired_authorizations(&self) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut data = self.data()?;
+
+        let count = data.oidc_authorizations.len();
+        data.oidc_authorizations
+            .retain(|authorization| authorization.expired_at > now);
+
+        Ok((count - data.oidc_authorizations.len()) as u64)
+    }
+
+    async fn get_identity(&self, issuer: &str, subject: &str) -> AppResult<Option<UserIdentity>> {
+        Ok(self
+            .data()?
+            .user_identities
+            .iter()
+            .find(|identity| identity.issuer == issuer && identity.subject == subject)
+            .cloned())
+    }
+
+    async fn has_identity(&self, user_id: &str, issuer: &str) -> AppResult<bool> {
+        Ok(self
+            .data()?
+            .user_identities
+            .iter()
+            .any(|identity| identity.user_id == user_id && identity.issuer == issuer))
+    }
+
+    async fn create_identity(&self, identity: &UserIdentity) -> AppResult<()> {
+        let mut data = self.data()?;
+
+        if data
+            .user_identities
+            .iter()
+            .any(|stored| stored.issuer == identity.issuer && stored.subject == identity.subject)
+        {
+            return Err(app_error!(
+                AppErrorCode::InternalError,
+                "Database Error",
+                format!("duplicate identity: {}", identity.subject)
+            ));
+        }
+        data.user_identities.push(identity.clone());
+
+        Ok(())
+    }
+
+    async fn touch_identity(&self, id: &str) -> AppResult<()> {
+        if let Some(identity) = self
+            .data()?
+            .user_identities
+            .iter_mut()
+            .find(|identity| identity.id == id)
+        {
+            identity.last_login_at = Some(Utc::now());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use crate::database::{query, Database};
+use crate::models::mfa::{MfaChallenge, RecoveryCode, UserTotp};
+use crate::utils::errors::AppResult;
+use async_trait::async_trait;
+use chrono::Utc;
+
+#[async_trait]
+pub trait MfaRepository: Send + Sync {
+    /// Returns the TOTP settings of a not deleted user
+    async fn get_totp(&self, user_id: &str) -> AppResult<Option<UserTotp>>;
+
+    /// Start a TOTP enrollment with a new secret (not enabled until confirmed)
+    async fn set_totp_secret(&self, user_id: &str, secret: &str) -> AppResult<()>;
+
+    /// Confirm a TOTP enrollment, `step` is the time step of the confirmation code
+    async fn enable_totp(&self, user_id: &str, step: i64) -> AppResult<()>;
+
+    /// Disable TOTP and delete the recovery codes of a user
+    async fn disable_totp(&self, user_id: &str) -> AppResult<()>;
+
+    /// Mark a time step as used.
+    ///
+    /// Returns `false` if this step or a later one has already been used (replayed code).
+    async fn use_totp_step(&self, user_id: &str, step: i64) -> AppResult<bool>;
+
+    /// Replace all the recovery codes of a user
+    async fn replace_recovery_codes(&self, user_id: &str, recovery_codes: &[RecoveryCode]) -> AppResult<()>;
+
+    /// Use a recovery code of a user.
+    ///
+    /// Returns `false` if the code does not exist or has already been used.
+    async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> AppResult<bool>;
+
+    /// Add a new MFA challenge
+    async fn create_challenge(&self, challenge: &MfaChallenge) -> AppResult<()>;
+
+    /// Returns a MFA challenge of a not deleted user by its hash
+    async fn get_challenge_by_hash(&self, token_hash: &str) -> AppResult<Option<MfaChallenge>>;
+
+    /// Count a wrong code for a challenge
+    async fn increment_challenge_attempts(&self, id: &str) -> AppResult<()>;
+
+    /// Delete a challenge.
+    ///
+    /// Returns `false` if it has already been deleted (concurrent use of the same challenge).
+    async fn delete_challenge(&self, id: &str) -> AppResult<bool>;
+
+    /// Delete the expired challenges
+    async fn delete_expired_challenges(&self) -> AppResult<u64>;
+}
+
+#[async_trait]
+impl MfaRepository for Database {
+    #[instrument(skip(self))]
+    async fn get_totp(&self, user_id: &str) -> AppResult<Option<UserTotp>> {
+        let row = query(
+            r#"
+                SELECT totp_secret, totp_enabled_at, totp_last_used_step
+                FROM users
+                WHERE id = ? AND deleted_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(UserTotp {
+                secret: row.try_get("totp_secret")?,
+                enabled_at: row.try_get("totp_enabled_at")?,
+                last_used_step: row.try_get("totp_last_used_step")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self, secret))]
+    async fn set_totp_secret(&self, user_id: &str, secret: &str) -> AppResult<()> {
+        query(
+            r#"
+                UPDATE users
+                SET totp_secret = ?, totp_enabled_at = NULL, totp_last_used_step = NULL, updated_at = ?
+                WHERE id = ? AND deleted_at IS NULL
+            "#,
+        )
+        .bind(secret)
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn enable_totp(&self, user_id: &str, step: i64) -> AppResult<()> {
+        let now = Utc::now();
+        query(
+            r#"
+                UPDATE users
+                SET totp_enabled_at = ?, totp_last_used_step = ?, updated_at = ?
+                WHERE id = ? AND deleted_at IS NULL
+            "#,
+        )
+        .bind(now)
+        .bind(step)
+        .bind(now)
+        .bind(user_id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn disable_totp(&self, user_id: &str) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query(
+            r#"
+                UPDATE users
+                SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_used_step = NULL, updated_at = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+
+        query("DELETE FROM user_recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn use_totp_step(&self, user_id: &str, step: i64) -> AppResult<bool> {
+        let result = query(
+            r#"
+                UPDATE users
+                SET totp_last_used_step = ?
+                WHERE id = ? AND (totp_last_used_step IS NULL OR totp_last_used_step < ?)
+            "#,
+        )
+        .bind(step)
+        .bind(user_id)
+        .bind(step)
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[instrument(skip(self, recovery_codes))]
+    async fn replace_recovery_codes(&self, user_id: &str, recovery_codes: &[RecoveryCode]) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query("DELETE FROM user_recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        for recovery_code in recovery_codes {
+            query(
+                r#"
+                    INSERT INTO user_recovery_codes (id, user_id, code_hash, used_at, created_at)
+                    VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&recovery_code.id)
+            .bind(&recovery_code.user_id)
+            .bind(&recovery_code.code_hash)
+            .bind(recovery_code.used_at)
+            .bind(recovery_code.created_at)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, code_hash))]
+    async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> AppResult<bool> {
+        let result = query(
+            r#"
+                UPDATE user_recovery_codes
+                SET used_at = ?
+                WHERE user_id = ? AND code_hash = ? AND used_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[instrument(skip(self))]
+    async fn create_challenge(&self, challenge: &MfaChallenge) -> AppResult<()> {
+        query(
+            r#"
+                INSERT INTO mfa_challenges (id, user_id, token_hash, attempts, expired_at, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&challenge.id)
+        .bind(&challenge.user_id)
+        .bind(&challenge.token_hash)
+        .bind(challenge.attempts)
+        .bind(challenge.expired_at)
+        .bind(challenge.created_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_challenge_by_hash(&self, token_hash: &str) -> AppResult<Option<MfaChallenge>> {
+        let row = query(
+            r#"
+                SELECT c.id, c.user_id, c.token_hash, c.attempts, c.expired_at, c.created_at
+                FROM mfa_challenges c
+                    INNER JOIN users u ON u.id = c.user_id AND u.deleted_at IS NULL
+                WHERE c.token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(MfaChallenge {
+                id: row.try_get("id")?,
+                user_id: row.try_get("user_id")?,
+                token_hash: row.try_get("token_hash")?,
+                attempts: row.try_get("attempts")?,
+                expired_at: row.try_get("expired_at")?,
+                created_at: row.try_get("created_at")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn increment_challenge_attempts(&self, id: &str) -> AppResult<()> {
+        query("UPDATE mfa_challenges SET attempts = attempts + 1 WHERE id = ?")
+            .bind(id)
+            .execute(self)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_challenge(&self, id: &str) -> AppResult<bool> {
+        let result = query("DELETE FROM mfa_challenges WHERE id = ?")
+            .bind(id)
+            .execute(self)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_expired_challenges(&self) -> AppResult<u64> {
+        let result = query("DELETE FROM mfa_challenges WHERE expired_at < ?")
+            .bind(Utc::now())
+            .execute(self)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,3 +1,82 @@
 //! Repositories module
 
+pub mod api_key;
+pub mod email_outbox;
+pub mod login_attempt;
+pub mod memory;
+pub mod mfa;
+pub mod oidc;
+pub mod refresh_token;
+pub mod revoked_token;
+pub mod role;
 pub mod user;
+
+use crate::database::Database;
+use api_key::ApiKeyRepository;
+use email_outbox::EmailOutboxRepository;
+use login_attempt::LoginAttemptRepository;
+use memory::InMemoryStore;
+use mfa::MfaRepository;
+use oidc::OidcRepository;
+use refresh_token::RefreshTokenRepository;
+use role::RoleRepository;
+use std::sync::Arc;
+use user::{EmailChangeRepository, EmailVerificationRepository, PasswordResetStore, UserStore};
+
+/// Stores of the users and their accounts, injected in the application state.
+///
+/// They are backed by the database, or kept in memory to test the handlers without a database.
+#[derive(Clone)]
+pub struct Stores {
+    pub users: Arc<dyn UserStore>,
+    pub roles: Arc<dyn RoleRepository>,
+    pub password_resets: Arc<dyn PasswordResetStore>,
+    pub email_verifications: Arc<dyn EmailVerificationRepository>,
+    pub email_changes: Arc<dyn EmailChangeRepository>,
+    pub refresh_tokens: Arc<dyn RefreshTokenRepository>,
+    pub login_attempts: Arc<dyn LoginAttemptRepository>,
+    pub email_outbox: Arc<dyn EmailOutboxRepository>,
+    pub mfa: Arc<dyn MfaRepository>,
+    pub api_keys: Arc<dyn ApiKeyRepository>,
+    pub oidc: Arc<dyn OidcRepository>,
+}
+
+impl Stores {
+    /// Stores backed by the database
+    pub fn database(database: Database) -> Self {
+        let database = Arc::new(database);
+
+        Self {
+            users: database.clone(),
+            roles: database.clone(),
+            password_resets: database.clone(),
+            email_verifications: database.clone(),
+            email_changes: database.clone(),
+            refresh_tokens: database.clone(),
+            login_attempts: database.clone(),
+            email_outbox: database.clone(),
+            mfa: database.clone(),
+            api_keys: database.clone(),
+            oidc: database,
+        }
+    }
+
+    /// Stores sharing the same in-memory data
+    pub fn memory(store: InMemoryStore) -> Self {
+        let store = Arc::new(store);
+
+        Self {
+            users: store.clone(),
+            roles: store.clone(),
+            password_resets: store.clone(),
+            email_verifications: store.clone(),
+            email_changes: store.clone(),
+            refresh_tokens: store.clone(),
+            login_attempts: store.clone(),
+            email_outbox: store.clone(),
+            mfa: store.clone(),
+            api_keys: store.clone(),
+            oidc: store,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::database::{query, Database};
+use crate::models::oidc::{OidcAuthorization, UserIdentity};
+use crate::utils::errors::AppResult;
+use async_trait::async_trait;
+use chrono::Utc;
+
+#[async_trait]
+pub trait OidcRepository: Send + Sync {
+    /// Add a new authorization request
+    async fn create_authorization(&self, authorization: &OidcAuthorization) -> AppResult<()>;
+
+    /// Returns and deletes an authorization request by the hash of its state.
+    ///
+    /// A state can only be used once: `None` is returned if it has already been used by a concurrent request.
+    async fn take_authorization(&self, state_hash: &str) -> AppResult<Option<OidcAuthorization>>;
+
+    /// Delete expired authorization requests
+    async fn delete_expired_authorizations(&self) -> AppResult<u64>;
+
+    /// Returns an identity by its issuer and subject
+    async fn get_identity(&self, issuer: &str, subject: &str) -> AppResult<Option<UserIdentity>>;
+
+    /// Check if a user already has an identity of an issuer
+    async fn has_identity(&self, user_id: &str, issuer: &str) -> AppResult<bool>;
+
+    /// Link an identity to a user
+    async fn create_identity(&self, identity: &UserIdentity) -> AppResult<()>;
+
+    /// Track the last login with an identity
+    async fn touch_identity(&self, id: &str) -> AppResult<()>;
+}
+
+#[async_trait]
+impl OidcRepository for Database {
+    #[instrument(skip(self, authorization))]
+    async fn create_authorization(&self, authorization: &OidcAuthorization) -> AppResult<()> {
+        query(
+            r#"
+                INSERT INTO oidc_authorizations (id, state_hash, nonce, code_verifier, expired_at, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&authorization.id)
+        .bind(&authorization.state_hash)
+        .bind(&authorization.nonce)
+        .bind(&authorization.code_verifier)
+        .bind(authorization.expired_at)
+        .bind(authorization.created_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn take_authorization(&self, state_hash: &str) -> AppResult<Option<OidcAuthorization>> {
+        let row = query("SELECT * FROM oidc_authorizations WHERE state_hash = ?")
+            .bind(state_hash)
+            .fetch_optional(self)
+            .await?;
+
+        let authorization = match row {
+            Some(row) => OidcAuthorization {
+                id: row.try_get("id")?,
+                state_hash: row.try_get("state_hash")?,
+                nonce: row.try_get("nonce")?,
+                code_verifier: row.try_get("code_verifier")?,
+                expired_at: row.try_get("expired_at")?,
+                created_at: row.try_get("created_at")?,
+            },
+            None => return Ok(None),
+        };
+
+        let result = query("DELETE FROM oidc_authorizations WHERE id = ?")
+            .bind(&authorization.id)
+            .execute(self)
+            .await?;
+
+        Ok((result.rows_affected() == 1).then_some(authorization))
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_expired_authorizations(&self) -> AppResult<u64> {
+        let result = query("DELETE FROM oidc_authorizations WHERE expired_at <= ?")
+            .bind(Utc::now())
+            .execute(self)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_identity(&self, issuer: &str, subject: &str) -> AppResult<Option<UserIdentity>> {
+        let row = query("SELECT * FROM user_identities WHERE issuer = ? AND subject = ?")
+            .bind(issuer)
+            .bind(subject)
+            .fetch_optional(self)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(UserIdentity {
+                id: row.try_get("id")?,
+                user_id: row.try_get("user_id")?,
+                issuer: row.try_get("issuer")?,
+                subject: row.try_get("subject")?,
+                email: row.try_get("email")?,
+                last_login_at: row.try_get("last_login_at")?,
+                created_at: row.try_get("created_at")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn has_identity(&self, user_id: &str, issuer: &str) -> AppResult<bool> {
+        let row = query("SELECT COUNT(*) AS total FROM user_identities WHERE user_id = ? AND issuer = ?")
+            .bind(user_id)
+            .bind(issuer)
+            .fetch_one(self)
+            .await?;
+
+        Ok(row.try_get::<i64>("total")? > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn create_identity(&self, identity: &UserIdentity) -> AppResult<()> {
+        query(
+            r#"
+                INSERT INTO user_identities (id, user_id, issuer, subject, email, last_login_at, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&identity.id)
+        .bind(&identity.user_id)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .bind(identity.last_login_at)
+        .bind(identity.created_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn touch_identity(&self, id: &str) -> AppResult<()> {
+        query("UPDATE user_identities SET last_login_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(self)
+            .await?;
+
+        Ok(())
+    }
+}
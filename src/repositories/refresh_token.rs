@@ -0,0 +1,130 @@
+use crate::database::{query, Database};
+use crate::models::auth::RefreshToken;
+use crate::utils::errors::AppResult;
+use async_trait::async_trait;
+use chrono::Utc;
+
+#[async_trait]
+pub trait RefreshTokenRepository: Send + Sync {
+    /// Add a new refresh token
+    async fn create(&self, refresh_token: &RefreshToken) -> AppResult<()>;
+
+    /// Returns a refresh token of a not deleted user by its hash
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<RefreshToken>>;
+
+    /// Mark a refresh token as used.
+    ///
+    /// Returns `false` if the token has already been used (concurrent use of the same token).
+    async fn use_token(&self, id: &str) -> AppResult<bool>;
+
+    /// Revoke all the tokens of a family
+    async fn revoke_family(&self, family_id: &str) -> AppResult<u64>;
+
+    /// Revoke all the refresh tokens of a user
+    async fn revoke_user(&self, user_id: &str) -> AppResult<u64>;
+}
+
+#[async_trait]
+impl RefreshTokenRepository for Database {
+    #[instrument(skip(self))]
+    async fn create(&self, refresh_token: &RefreshToken) -> AppResult<()> {
+        query(
+            r#"
+                INSERT INTO refresh_tokens (id, family_id, user_id, token_hash, expired_at, used_at, revoked_at, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&refresh_token.id)
+        .bind(&refresh_token.family_id)
+        .bind(&refresh_token.user_id)
+        .bind(&refresh_token.token_hash)
+        .bind(refresh_token.expired_at)
+        .bind(refresh_token.used_at)
+        .bind(refresh_token.revoked_at)
+        .bind(refresh_token.created_at)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<RefreshToken>> {
+        let row = query(
+            r#"
+                SELECT rt.id, rt.family_id, rt.user_id, rt.token_hash, rt.expired_at, rt.used_at, rt.revoked_at, rt.created_at
+                FROM refresh_tokens rt
+                    INNER JOIN users u ON u.id = rt.user_id AND u.deleted_at IS NULL
+                WHERE rt.token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(RefreshToken {
+                id: row.try_get("id")?,
+                family_id: row.try_get("family_id")?,
+                user_id: row.try_get("user_id")?,
+                token_hash: row.try_get("token_hash")?,
+                expired_at: row.try_get("expired_at")?,
+                used_at: row.try_get("used_at")?,
+                revoked_at: row.try_get("revoked_at")?,
+                created_at: row.try_get("created_at")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn use_token(&self, id: &str) -> AppResult<bool> {
+        let result = query(
+            r#"
+                UPDATE refresh_tokens
+                SET used_at = ?
+                WHERE id = ? AND used_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[instrument(skip(self))]
+    async fn revoke_family(&self, family_id: &str) -> AppResult<u64> {
+        let result = query(
+            r#"
+                UPDATE refresh_tokens
+                SET revoked_at = ?
+                WHERE family_id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(family_id)
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn revoke_user(&self, user_id: &str) -> AppResult<u64> {
+        let result = query(
+            r#"
+                UPDATE refresh_tokens
+                SET revoked_at = ?
+                WHERE user_id = ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(self)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,215 @@
+//! Revoked JWT storage (in Redis if available, in the database otherwise, in memory for tests)
+
+use crate::app_error;
+use crate::database::{query, Database};
+use crate::models::auth::Claims;
+use crate::utils::errors::{AppError, AppErrorCode, AppResult};
+use chrono::{DateTime, Utc};
+use r2d2::{Pool, PooledConnection};
+use redis::{Client, Commands};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const REVOKED_TOKEN_PREFIX: &str = "jwt_revoked_";
+const REVOKED_USER_TOKENS_PREFIX: &str = "jwt_revoked_user_";
+
+/// Revoked tokens storage.
+///
+/// A token is revoked either by its `jti` (logout) or because all tokens of its user
+/// issued before a date have been revoked (user deletion, password change).
+/// Issue and revocation dates are compared in millisecond, so that a token issued right after
+/// a revocation (e.g. a login after a password change) is valid.
+#[derive(Clone)]
+pub enum RevokedTokenStore {
+    Redis { pool: Pool<Client>, prefix: String },
+    Database(Database),
+    Memory(Arc<Mutex<MemoryRevokedTokens>>),
+}
+
+/// Revoked tokens kept in memory
+#[derive(Debug, Default)]
+pub struct MemoryRevokedTokens {
+    /// Expiration timestamp of the revoked tokens by `jti`
+    tokens: HashMap<String, i64>,
+
+    /// Revocation timestamp (in millisecond) by user ID
+    users: HashMap<String, i64>,
+}
+
+impl RevokedTokenStore {
+    /// Create a Redis store
+    pub fn redis(pool: Pool<Client>, redis_prefix: &str) -> Self {
+        Self::Redis {
+            pool,
+            prefix: redis_prefix.to_owned(),
+        }
+    }
+
+    /// Create a database store
+    pub fn database(database: Database) -> Self {
+        Self::Database(database)
+    }
+
+    /// Create an in-memory store (used to test the handlers without Redis nor database)
+    pub fn memory() -> Self {
+        Self::Memory(Arc::default())
+    }
+
+    /// Lock the revoked tokens of an in-memory store
+    fn lock(tokens: &Mutex<MemoryRevokedTokens>) -> AppResult<MutexGuard<'_, MemoryRevokedTokens>> {
+        tokens.lock().map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "Database Error",
+                format!("revoked tokens lock error: {err}")
+            )
+        })
+    }
+
+    /// Run Redis commands on the blocking thread pool (the pool gives synchronous connections)
+    async fn run_redis<T, F>(pool: &Pool<Client>, commands: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut PooledConnection<Client>) -> AppResult<T> + Send + 'static,
+    {
+        let pool = pool.clone();
+
+        tokio::task::spawn_blocking(move || commands(&mut pool.get()?))
+            .await
+            .map_err(|err| {
+                app_error!(
+                    AppErrorCode::InternalError,
+                    "Redis Database Error",
+                    format!("revoked tokens task error: {err}")
+                )
+            })?
+    }
+
+    /// Revoke a token until its expiration
+    #[instrument(skip(self))]
+    pub async fn revoke(&self, jti: &str, expired_at: i64) -> AppResult<()> {
+        match self {
+            Self::Redis { pool, prefix } => {
+                let ttl = (expired_at - Utc::now().timestamp()).max(1) as u64;
+                let key = format!("{prefix}{REVOKED_TOKEN_PREFIX}{jti}");
+
+                Self::run_redis(pool, move |conn| Ok(conn.set_ex(key, 1, ttl)?)).await?;
+            }
+            Self::Database(database) => {
+                let now = Utc::now();
+
+                // Expired tokens no longer need to be kept
+                query("DELETE FROM revoked_tokens WHERE expired_at < ?")
+                    .bind(now)
+                    .execute(database)
+                    .await?;
+
+                let sql = database
+                    .backend()
+                    .upsert("revoked_tokens", &["jti", "expired_at"], "jti", &["expired_at"]);
+                query(&sql)
+                    .bind(jti)
+                    .bind(DateTime::from_timestamp(expired_at, 0).unwrap_or(now))
+                    .execute(database)
+                    .await?;
+            }
+            Self::Memory(tokens) => {
+                let mut tokens = Self::lock(tokens)?;
+                let now = Utc::now().timestamp();
+
+                tokens.tokens.retain(|_, expired_at| *expired_at >= now);
+                tokens.tokens.insert(jti.to_owned(), expired_at);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Revoke all the tokens of a user issued until now.
+    ///
+    /// `jwt_lifetime` (in hour) is used to know how long the revocation must be kept.
+    #[instrument(skip(self))]
+    pub async fn revoke_user(&self, user_id: &str, jwt_lifetime: i64) -> AppResult<()> {
+        let now = Utc::now();
+
+        match self {
+            Self::Redis { pool, prefix } => {
+                let ttl = (jwt_lifetime * 3600).max(1) as u64;
+                let key = format!("{prefix}{REVOKED_USER_TOKENS_PREFIX}{user_id}");
+                let revoked_at = now.timestamp_millis();
+
+                Self::run_redis(pool, move |conn| Ok(conn.set_ex(key, revoked_at, ttl)?)).await?;
+            }
+            Self::Database(database) => {
+                let sql = database.backend().upsert(
+                    "revoked_user_tokens",
+                    &["user_id", "revoked_at"],
+                    "user_id",
+                    &["revoked_at"],
+                );
+                query(&sql).bind(user_id).bind(now).execute(database).await?;
+            }
+            Self::Memory(tokens) => {
+                Self::lock(tokens)?
+                    .users
+                    .insert(user_id.to_owned(), now.timestamp_millis());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if a token has been revoked
+    #[instrument(skip(self))]
+    pub async fn is_revoked(&self, claims: &Claims) -> AppResult<bool> {
+        let issued_at = claims.issued_at_ms();
+
+        match self {
+            Self::Redis { pool, prefix } => {
+                let token_key = format!("{prefix}{REVOKED_TOKEN_PREFIX}{}", claims.jti);
+                let user_key = format!("{prefix}{REVOKED_USER_TOKENS_PREFIX}{}", claims.user_id);
+
+                let (revoked, revoked_at) = Self::run_redis(pool, move |conn| {
+                    let revoked: bool = conn.exists(token_key)?;
+                    let revoked_at: Option<i64> = match revoked {
+                        true => None,
+                        false => conn.get(user_key)?,
+                    };
+
+                    Ok((revoked, revoked_at))
+                })
+                .await?;
+
+                Ok(revoked || matches!(revoked_at, Some(revoked_at) if issued_at <= revoked_at))
+            }
+            Self::Database(database) => {
+                let revoked: i64 = query("SELECT COUNT(*) AS n FROM revoked_tokens WHERE jti = ?")
+                    .bind(&claims.jti)
+                    .fetch_one(database)
+                    .await?
+                    .try_get("n")?;
+                if revoked > 0 {
+                    return Ok(true);
+                }
+
+                // Dates are compared in millisecond (SQLite stores them as text)
+                let revoked_at: Option<DateTime<Utc>> =
+                    match query("SELECT revoked_at FROM revoked_user_tokens WHERE user_id = ?")
+                        .bind(&claims.user_id)
+                        .fetch_optional(database)
+                        .await?
+                    {
+                        Some(row) => Some(row.try_get("revoked_at")?),
+                        None => None,
+                    };
+                Ok(matches!(revoked_at, Some(revoked_at) if issued_at <= revoked_at.timestamp_millis()))
+            }
+            Self::Memory(tokens) => {
+                let tokens = Self::lock(tokens)?;
+
+                Ok(tokens.tokens.contains_key(&claims.jti)
+                    || matches!(tokens.users.get(&claims.user_id), Some(revoked_at) if issued_at <= *revoked_at))
+            }
+        }
+    }
+}
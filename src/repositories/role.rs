@@ -0,0 +1,212 @@
+use crate::database::{query, Database, DbRow, DbTransaction};
+use crate::models::role::{Permission, RolePermissions};
+use crate::models::user::Role;
+use crate::utils::errors::AppResult;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+
+#[async_trait]
+pub trait RoleRepository: Send + Sync {
+    /// Returns all the roles with their permissions
+    async fn get_all(&self) -> AppResult<Vec<RolePermissions>>;
+
+    /// Returns a role with its permissions
+    async fn get(&self, role: &Role) -> AppResult<Option<RolePermissions>>;
+
+    /// Add a new role with its permissions
+    async fn create(&self, role: &RolePermissions) -> AppResult<()>;
+
+    /// Replace the permissions of a role
+    async fn update(&self, role: &RolePermissions) -> AppResult<()>;
+
+    /// Delete a role and its permissions (it must not be granted to users)
+    async fn delete(&self, role: &Role) -> AppResult<()>;
+
+    /// Check if a role is granted to at least one user
+    async fn is_granted(&self, role: &Role) -> AppResult<bool>;
+
+    /// Returns the permissions of a list of roles
+    async fn get_permissions(&self, roles: &[Role]) -> AppResult<Vec<Permission>>;
+
+    /// Grant a role to a user.
+    ///
+    /// Returns `false` if the user already has the role.
+    async fn grant(&self, user_id: &str, role: &Role) -> AppResult<bool>;
+
+    /// Revoke a role of a user.
+    ///
+    /// Returns `false` if the user does not have the role.
+    async fn revoke(&self, user_id: &str, role: &Role) -> AppResult<bool>;
+}
+
+#[async_trait]
+impl RoleRepository for Database {
+    #[instrument(skip(self))]
+    async fn get_all(&self) -> AppResult<Vec<RolePermissions>> {
+        let rows = query(
+            r#"
+                SELECT r.name, rp.permission
+                FROM roles r
+                    LEFT JOIN role_permissions rp ON rp.role = r.name
+            "#,
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(from_rows(&rows)?)
+    }
+
+    #[instrument(skip(self))]
+    async fn get(&self, role: &Role) -> AppResult<Option<RolePermissions>> {
+        let rows = query(
+            r#"
+                SELECT r.name, rp.permission
+                FROM roles r
+                    LEFT JOIN role_permissions rp ON rp.role = r.name
+                WHERE r.name = ?
+            "#,
+        )
+        .bind(role.to_string())
+        .fetch_all(self)
+        .await?;
+
+        Ok(from_rows(&rows)?.into_iter().next())
+    }
+
+    #[instrument(skip(self))]
+    async fn create(&self, role: &RolePermissions) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query("INSERT INTO roles (name) VALUES (?)")
+            .bind(role.name.to_string())
+            .execute(&mut tx)
+            .await?;
+
+        insert_permissions(&mut tx, role).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn update(&self, role: &RolePermissions) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query("DELETE FROM role_permissions WHERE role = ?")
+            .bind(role.name.to_string())
+            .execute(&mut tx)
+            .await?;
+
+        insert_permissions(&mut tx, role).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, role: &Role) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query("DELETE FROM role_permissions WHERE role = ?")
+            .bind(role.to_string())
+            .execute(&mut tx)
+            .await?;
+
+        query("DELETE FROM roles WHERE name = ?")
+            .bind(role.to_string())
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn is_granted(&self, role: &Role) -> AppResult<bool> {
+        let row = query("SELECT COUNT(*) AS total FROM user_roles WHERE role = ?")
+            .bind(role.to_string())
+            .fetch_one(self)
+            .await?;
+
+        Ok(row.try_get::<i64>("total")? > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_permissions(&self, roles: &[Role]) -> AppResult<Vec<Permission>> {
+        if roles.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let sql = format!(
+            "SELECT DISTINCT permission FROM role_permissions WHERE role IN ({})",
+            vec!["?"; roles.len()].join(", ")
+        );
+        let permissions = roles
+            .iter()
+            .fold(query(&sql), |query, role| query.bind(role.to_string()))
+            .fetch_all(self)
+            .await?
+            .iter()
+            .map(|row| row.try_get::<String>("permission"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Permission::from_names(permissions))
+    }
+
+    #[instrument(skip(self))]
+    async fn grant(&self, user_id: &str, role: &Role) -> AppResult<bool> {
+        let sql = self.backend().insert_ignore("user_roles", &["user_id", "role"]);
+        let result = query(&sql).bind(user_id).bind(role.to_string()).execute(self).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn revoke(&self, user_id: &str, role: &Role) -> AppResult<bool> {
+        let result = query("DELETE FROM user_roles WHERE user_id = ? AND role = ?")
+            .bind(user_id)
+            .bind(role.to_string())
+            .execute(self)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Insert the permissions of a role
+async fn insert_permissions(tx: &mut DbTransaction, role: &RolePermissions) -> AppResult<()> {
+    for permission in &role.permissions {
+        query("INSERT INTO role_permissions (role, permission) VALUES (?, ?)")
+            .bind(role.name.to_string())
+            .bind(permission.as_str())
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Build roles from `(name, permission)` rows, sorted by role
+fn from_rows(rows: &[DbRow]) -> Result<Vec<RolePermissions>, sqlx::Error> {
+    let mut roles: BTreeMap<Role, Vec<String>> = BTreeMap::new();
+
+    for row in rows {
+        let name = row.try_get::<String>("name")?;
+        let permission = row.try_get::<Option<String>>("permission")?;
+
+        if let Some(role) = Role::try_from_str(&name) {
+            roles.entry(role).or_default().extend(permission);
+        }
+    }
+
+    Ok(roles
+        .into_iter()
+        .map(|(name, permissions)| RolePermissions {
+            name,
+            permissions: Permission::from_names(permissions),
+        })
+        .collect())
+}
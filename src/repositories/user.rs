@@ -1,343 +1,987 @@
 use crate::app_error;
-use crate::models::user::{Login, PasswordReset, User, UserCreation};
+use crate::database::{query, Database, DatabaseBackend, DbExecutor, DbRow, DbTransaction};
+use crate::models::user::{
+    EmailChange, EmailVerification, Login, PasswordReset, Role, User, UserCreation, UserProfileUpdate,
+};
 use crate::utils::query::PaginateResponse;
 use crate::utils::{
     errors::{AppError, AppErrorCode, AppResult},
-    query::PaginateSort,
+    password::{PasswordHasher, PasswordVerification},
+    query::{FilterField, FilterFieldType, FilterOperator, FilterValue, Filters, KeysetPagination, PaginateSort},
 };
-use chrono::{TimeZone, Utc};
-use futures::TryStreamExt;
-use sha2::{Digest, Sha512};
-use sqlx::{MySqlPool, Row};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Fields which can be used to filter users
+pub const USER_FILTER_FIELDS: &[FilterField] = &[
+    FilterField {
+        name: "lastname",
+        column: "lastname",
+        field_type: FilterFieldType::Text,
+        operators: FilterOperator::TEXT,
+    },
+    FilterField {
+        name: "firstname",
+        column: "firstname",
+        field_type: FilterFieldType::Text,
+        operators: FilterOperator::TEXT,
+    },
+    FilterField {
+        name: "role",
+        column: "roles",
+        field_type: FilterFieldType::List,
+        operators: FilterOperator::LIST,
+    },
+    FilterField {
+        name: "rate_limit",
+        column: "rate_limit",
+        field_type: FilterFieldType::Integer,
+        operators: FilterOperator::ALL,
+    },
+    FilterField {
+        name: "created_at",
+        column: "created_at",
+        field_type: FilterFieldType::DateTime,
+        operators: FilterOperator::ALL,
+    },
+];
+
+/// Fields which can be used to sort users
+pub const USER_SORT_FIELDS: &[&str] = &["id", "lastname", "firstname", "created_at", "updated_at", "deleted_at"];
+
+/// Fields which can be used to sort users with cursor pagination
+pub const USER_CURSOR_SORT_FIELDS: &[&str] = &["id", "lastname", "firstname", "created_at", "updated_at"];
+
+/// Users storage, injected in the application state (see `Stores`)
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    /// Returns a User if credentials are right
+    ///
+    /// Legacy SHA-512 hashes (and hashes generated with outdated parameters)
+    /// are transparently upgraded after a successful login.
+    async fn login(&self, hasher: &PasswordHasher, input: Login) -> AppResult<Option<User>>;
 
-pub struct UserRepository;
+    /// Add a new user
+    async fn create(&self, hasher: &PasswordHasher, user: &mut User) -> AppResult<()>;
 
-impl UserRepository {
-    /// Returns a User if credentials are right
+    /// Returns all not deleted users
+    async fn get_all<'a>(
+        &self,
+        paginate_sort: &'a PaginateSort,
+        filters: &'a Filters,
+        with_total: bool,
+    ) -> AppResult<PaginateResponse<Vec<User>>>;
+
+    /// Returns all not deleted users with cursor pagination
+    async fn get_all_by_cursor<'a>(
+        &self,
+        pagination: &'a KeysetPagination,
+        filters: &'a Filters,
+        with_total: bool,
+        cursor_secret: &str,
+    ) -> AppResult<PaginateResponse<Vec<User>>>;
+
+    /// Returns a user by its ID
+    async fn get_by_id(&self, id: String) -> AppResult<Option<User>>;
+
+    /// Returns a user by its email
+    async fn get_by_email(&self, email: String) -> AppResult<Option<User>>;
+
+    /// Delete a user
+    async fn delete(&self, id: String) -> AppResult<u64>;
+
+    /// Update a user
+    async fn update(&self, hasher: &PasswordHasher, id: String, user: &UserCreation) -> AppResult<()>;
+
+    /// Update the profile of a user (missing fields are not changed)
+    async fn update_profile(&self, id: &str, profile: &UserProfileUpdate) -> AppResult<()>;
+
+    /// Check if a username is used, even by a deleted user
+    async fn is_username_used(&self, username: &str) -> AppResult<bool>;
+
+    /// Update user password
+    async fn update_password(
+        &self,
+        hasher: &PasswordHasher,
+        id: String,
+        current_password: String,
+        new_password: String,
+    ) -> AppResult<()>;
+
+    /// Returns the end of the lockout of a user (`None` if the user is not locked)
+    async fn get_locked_until(&self, id: &str) -> AppResult<Option<DateTime<Utc>>>;
+
+    /// Returns a not deleted user by its unlock token
+    async fn get_by_unlock_token(&self, token_hash: &str) -> AppResult<Option<User>>;
+
+    /// Lock a user until a date, the lockout can be cancelled with the token of `unlock_token_hash`
+    async fn lock(&self, id: &str, locked_until: DateTime<Utc>, unlock_token_hash: &str) -> AppResult<()>;
+
+    /// Unlock a user
+    async fn unlock(&self, id: &str) -> AppResult<()>;
+
+    /// Add a new user whose email is not verified yet, with its email verification
+    async fn register(
+        &self,
+        hasher: &PasswordHasher,
+        user: &mut User,
+        verification: &EmailVerification,
+    ) -> AppResult<()>;
+
+    /// Check if the email of a user has been verified
+    async fn is_email_verified(&self, id: &str) -> AppResult<bool>;
+
+    /// Mark the email of a user as verified and delete its pending verifications
+    async fn verify_email(&self, id: &str) -> AppResult<()>;
+}
+
+#[async_trait]
+impl UserStore for Database {
     #[instrument(name = "Login repository", skip_all, level = "warn")]
-    pub async fn login(pool: &MySqlPool, input: Login) -> AppResult<Option<User>> {
+    async fn login(&self, hasher: &PasswordHasher, input: Login) -> AppResult<Option<User>> {
         // warn!("In Login repo");
-        let hashed_password = format!("{:x}", Sha512::digest(input.password.as_bytes()));
-        let result = sqlx::query!(
-            r#"
-                SELECT * 
-                FROM users 
-                WHERE username = ?
-                    AND password = ?
-                    AND deleted_at IS NULL
-            "#,
-            input.username,
-            hashed_password
-        )
-        .fetch_optional(pool)
-        .await?;
-
-        match result {
-            Some(result) => Ok(Some(User {
-                id: result.id,
-                lastname: result.lastname,
-                firstname: result.firstname,
-                username: result.username,
-                password: result.password,
-                roles: result.roles,
-                rate_limit: result.rate_limit,
-                created_at: Utc.from_utc_datetime(&result.created_at),
-                updated_at: Utc.from_utc_datetime(&result.updated_at),
-                deleted_at: result.deleted_at.map(|d| Utc.from_utc_datetime(&d)),
-            })),
-            None => Ok(None),
+        let user = self.get_by_email(input.username).await?;
+
+        match user {
+            Some(mut user) => match hasher.verify_async(&input.password, &user.password).await? {
+                PasswordVerification::Invalid => Ok(None),
+                PasswordVerification::Valid => Ok(Some(user)),
+                PasswordVerification::ValidNeedsRehash => {
+                    user.password = hasher.hash_async(&input.password).await?;
+                    update_password_hash(self, &user.id, &user.password).await?;
+
+                    Ok(Some(user))
+                }
+            },
+            None => {
+                // Hash anyway to answer in the same time as for an existing username
+                hasher.hash_async(&input.password).await?;
+
+                Ok(None)
+            }
         }
     }
 
-    /// Add a new user
-    #[tracing::instrument(skip(pool))]
-    pub async fn create(pool: &MySqlPool, user: &mut User) -> AppResult<()> {
-        user.password = format!("{:x}", Sha512::digest(user.password.as_bytes()));
+    #[tracing::instrument(skip(self, hasher))]
+    async fn create(&self, hasher: &PasswordHasher, user: &mut User) -> AppResult<()> {
+        user.password = hasher.hash_async(&user.password).await?;
+
+        let mut tx = self.begin().await?;
 
-        sqlx::query!(
+        query(
             r#"
-                INSERT INTO `users` (`id`, `lastname`, `firstname`, `username`, `password`, `roles`, `rate_limit`, `created_at`, `updated_at`, `deleted_at`)
+                INSERT INTO users (id, lastname, firstname, username, password, rate_limit, created_at, updated_at, deleted_at, email_verified_at)
                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
-            user.id,
-            user.lastname,
-            user.firstname,
-            user.username,
-            user.password,
-            user.roles,
-            user.rate_limit,
-            user.created_at,
-            user.updated_at,
-            user.deleted_at,
         )
-        .execute(pool)
+        .bind(&user.id)
+        .bind(&user.lastname)
+        .bind(&user.firstname)
+        .bind(&user.username)
+        .bind(&user.password)
+        .bind(user.rate_limit)
+        .bind(user.created_at)
+        .bind(user.updated_at)
+        .bind(user.deleted_at)
+        .bind(user.created_at)
+        .execute(&mut tx)
         .await?;
 
+        replace_user_roles(&mut tx, &user.id, &user.roles).await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
-    /// Returns all not deleted users
-    #[instrument(skip(pool))]
-    pub async fn get_all<'a>(
-        pool: &'a MySqlPool,
+    #[instrument(skip(self))]
+    async fn get_all<'a>(
+        &self,
         paginate_sort: &'a PaginateSort,
+        filters: &'a Filters,
+        with_total: bool,
     ) -> AppResult<PaginateResponse<Vec<User>>> {
-        let total = Self::get_total(pool).await?;
+        let total = match with_total {
+            true => Some(get_total(self, filters).await?),
+            false => None,
+        };
 
-        let mut query = String::from(
+        let mut sql = format!(
             "
-            SELECT id, username, password, lastname, firstname, roles, rate_limit, created_at, updated_at, deleted_at 
-            FROM users 
+            SELECT id, username, password, lastname, firstname, roles, rate_limit, created_at, updated_at, deleted_at
+            FROM {}
             WHERE deleted_at IS NULL
             ",
+            users_with_roles(self.backend())
         );
 
+        // Filters
+        sql.push_str(&filters.get_where_sql(self.backend()));
+
         // Sorts and pagination
-        query.push_str(&paginate_sort.get_sorts_sql(Some(&[
-            "id",
-            "lastname",
-            "firstname",
-            "created_at",
-            "updated_at",
-            "deleted_at",
-        ])));
-        query.push_str(&paginate_sort.get_pagination_sql());
-
-        let mut rows = sqlx::query(&query)
-            .bind(paginate_sort.limit)
-            .bind(paginate_sort.offset)
-            .fetch(pool);
-
-        let mut users = vec![];
-        while let Some(row) = rows.try_next().await? {
-            users.push(User {
-                id: row.try_get("id")?,
-                lastname: row.try_get("lastname")?,
-                firstname: row.try_get("firstname")?,
-                username: row.try_get("username")?,
-                password: row.try_get("password")?,
-                roles: row.try_get("roles")?,
-                rate_limit: row.try_get("rate_limit")?,
-                created_at: row.try_get("created_at")?,
-                updated_at: row.try_get("updated_at")?,
-                deleted_at: row.try_get("deleted_at")?,
-            });
-        }
-        Ok(PaginateResponse { data: users, total })
+        sql.push_str(&paginate_sort.get_sorts_sql(Some(USER_SORT_FIELDS)));
+        sql.push_str(&paginate_sort.get_pagination_sql());
+
+        let users = filters
+            .bind(query(&sql))
+            .fetch_all(self)
+            .await?
+            .iter()
+            .map(from_row)
+            .collect::<Result<Vec<User>, _>>()?;
+
+        Ok(PaginateResponse {
+            data: users,
+            total,
+            next_cursor: None,
+            prev_cursor: None,
+        })
     }
 
-    /// Returns a user by its ID
-    #[instrument(skip(pool))]
-    pub async fn get_by_id(pool: &MySqlPool, id: String) -> AppResult<Option<User>> {
-        let result = sqlx::query!(
-            r#"
-                SELECT * 
-                FROM users 
+    #[instrument(skip(self, cursor_secret))]
+    async fn get_all_by_cursor<'a>(
+        &self,
+        pagination: &'a KeysetPagination,
+        filters: &'a Filters,
+        with_total: bool,
+        cursor_secret: &str,
+    ) -> AppResult<PaginateResponse<Vec<User>>> {
+        let total = match with_total {
+            true => Some(get_total(self, filters).await?),
+            false => None,
+        };
+
+        let mut sql = format!(
+            "
+            SELECT id, username, password, lastname, firstname, roles, rate_limit, created_at, updated_at, deleted_at
+            FROM {}
+            WHERE deleted_at IS NULL
+            ",
+            users_with_roles(self.backend())
+        );
+
+        // Filters, cursor, sorts and limit
+        sql.push_str(&filters.get_where_sql(self.backend()));
+        sql.push_str(&pagination.get_where_sql());
+        sql.push_str(&pagination.get_sorts_sql());
+        sql.push_str(&pagination.get_limit_sql());
+
+        let users = pagination
+            .bind(filters.bind(query(&sql)))
+            .fetch_all(self)
+            .await?
+            .iter()
+            .map(from_row)
+            .collect::<Result<Vec<User>, _>>()?;
+
+        let mut response = pagination.paginate(users, column_value, cursor_secret)?;
+        response.total = total;
+
+        Ok(response)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_by_id(&self, id: String) -> AppResult<Option<User>> {
+        let sql = format!(
+            "
+                SELECT *
+                FROM {}
                 WHERE id = ?
                     AND deleted_at IS NULL
-            "#,
-            id
-        )
-        .fetch_optional(pool)
-        .await?;
+            ",
+            users_with_roles(self.backend())
+        );
+        let row = query(&sql).bind(id).fetch_optional(self).await?;
 
-        match result {
-            Some(result) => Ok(Some(User {
-                id: result.id,
-                lastname: result.lastname,
-                firstname: result.firstname,
-                username: result.username,
-                password: result.password,
-                roles: result.roles,
-                rate_limit: result.rate_limit,
-                created_at: Utc.from_utc_datetime(&result.created_at),
-                updated_at: Utc.from_utc_datetime(&result.updated_at),
-                deleted_at: result.deleted_at.map(|d| Utc.from_utc_datetime(&d)),
-            })),
+        match row {
+            Some(row) => Ok(Some(from_row(&row)?)),
             None => Ok(None),
         }
     }
 
-    /// Returns a user by its email
-    #[instrument(skip(pool))]
-    pub async fn get_by_email(pool: &MySqlPool, email: String) -> AppResult<Option<User>> {
-        let result = sqlx::query!(
-            r#"
-                SELECT * 
-                FROM users 
+    #[instrument(skip(self))]
+    async fn get_by_email(&self, email: String) -> AppResult<Option<User>> {
+        let sql = format!(
+            "
+                SELECT *
+                FROM {}
                 WHERE username = ?
                     AND deleted_at IS NULL
-            "#,
-            email
-        )
-        .fetch_optional(pool)
-        .await?;
+            ",
+            users_with_roles(self.backend())
+        );
+        let row = query(&sql).bind(email).fetch_optional(self).await?;
 
-        match result {
-            Some(result) => Ok(Some(User {
-                id: result.id,
-                lastname: result.lastname,
-                firstname: result.firstname,
-                username: result.username,
-                password: result.password,
-                roles: result.roles,
-                rate_limit: result.rate_limit,
-                created_at: Utc.from_utc_datetime(&result.created_at),
-                updated_at: Utc.from_utc_datetime(&result.updated_at),
-                deleted_at: result.deleted_at.map(|d| Utc.from_utc_datetime(&d)),
-            })),
+        match row {
+            Some(row) => Ok(Some(from_row(&row)?)),
             None => Ok(None),
         }
     }
 
-    /// Delete a user
-    #[instrument(skip(pool))]
-    pub async fn delete(pool: &MySqlPool, id: String) -> AppResult<u64> {
-        let result = sqlx::query!(
+    #[instrument(skip(self))]
+    async fn delete(&self, id: String) -> AppResult<u64> {
+        let result = query(
             r#"
                 UPDATE users
                 SET deleted_at = ?
                 WHERE id = ? AND deleted_at IS NULL
             "#,
-            Some(Utc::now()),
-            id
         )
-        .execute(pool)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(self)
         .await?;
 
         Ok(result.rows_affected())
     }
 
-    /// Update a user
-    // TODO: Check if roles, rate_limit, etc. are valid
-    #[instrument(skip(pool))]
-    pub async fn update(pool: &MySqlPool, id: String, user: &UserCreation) -> AppResult<()> {
-        let hashed_password = format!("{:x}", Sha512::digest(user.password.as_bytes()));
-        sqlx::query!(
+    // TODO: Check if rate_limit, etc. are valid
+    #[instrument(skip(self, hasher))]
+    async fn update(&self, hasher: &PasswordHasher, id: String, user: &UserCreation) -> AppResult<()> {
+        let hashed_password = hasher.hash_async(&user.password).await?;
+        let mut tx = self.begin().await?;
+
+        query(
             r#"
                 UPDATE users
-                SET lastname = ?, firstname = ?, username = ?, password = ?, roles = ?, rate_limit = ?, updated_at = ?
+                SET lastname = ?, firstname = ?, username = ?, password = ?, rate_limit = ?, updated_at = ?
                 WHERE id = ?
             "#,
-            user.lastname,
-            user.firstname,
-            user.username,
-            hashed_password,
-            user.roles,
-            user.rate_limit,
-            Some(Utc::now()),
-            id
         )
-        .execute(pool)
+        .bind(&user.lastname)
+        .bind(&user.firstname)
+        .bind(&user.username)
+        .bind(hashed_password)
+        .bind(user.rate_limit)
+        .bind(Utc::now())
+        .bind(&id)
+        .execute(&mut tx)
         .await?;
 
+        replace_user_roles(&mut tx, &id, &user.roles()).await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
-    #[instrument(skip(pool))]
-    pub async fn update_password(
-        pool: &MySqlPool,
+    #[instrument(skip(self))]
+    async fn update_profile(&self, id: &str, profile: &UserProfileUpdate) -> AppResult<()> {
+        query(
+            r#"
+                UPDATE users
+                SET lastname = COALESCE(?, lastname), firstname = COALESCE(?, firstname), updated_at = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(&profile.lastname)
+        .bind(&profile.firstname)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn is_username_used(&self, username: &str) -> AppResult<bool> {
+        let row = query("SELECT COUNT(*) AS total FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_one(self)
+            .await?;
+
+        Ok(row.try_get::<i64>("total")? > 0)
+    }
+
+    #[instrument(skip(self, hasher))]
+    async fn update_password(
+        &self,
+        hasher: &PasswordHasher,
         id: String,
         current_password: String,
         new_password: String,
     ) -> AppResult<()> {
-        let hashed_password = format!("{:x}", Sha512::digest(new_password.as_bytes()));
-
-        if hashed_password == current_password {
+        if hasher.verify_async(&new_password, &current_password).await?.is_valid() {
             return Err(app_error!(
                 AppErrorCode::BadRequest,
                 "new password cannot be the same as the current one"
             ));
         }
 
-        sqlx::query!(
+        let hashed_password = hasher.hash_async(&new_password).await?;
+
+        query(
             r#"
                 UPDATE users
                 SET password = ?, updated_at = ?
                 WHERE id = ?
             "#,
-            hashed_password,
-            Some(Utc::now()),
-            id
         )
-        .execute(pool)
+        .bind(hashed_password)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_locked_until(&self, id: &str) -> AppResult<Option<DateTime<Utc>>> {
+        let row = query(
+            r#"
+                SELECT locked_until
+                FROM users
+                WHERE id = ? AND locked_until > ?
+            "#,
+        )
+        .bind(id)
+        .bind(Utc::now())
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.try_get("locked_until")?),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn get_by_unlock_token(&self, token_hash: &str) -> AppResult<Option<User>> {
+        let sql = format!(
+            "
+                SELECT *
+                FROM {}
+                WHERE unlock_token_hash = ?
+                    AND locked_until > ?
+                    AND deleted_at IS NULL
+            ",
+            users_with_roles(self.backend())
+        );
+        let row = query(&sql)
+            .bind(token_hash)
+            .bind(Utc::now())
+            .fetch_optional(self)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self, unlock_token_hash))]
+    async fn lock(&self, id: &str, locked_until: DateTime<Utc>, unlock_token_hash: &str) -> AppResult<()> {
+        query(
+            r#"
+                UPDATE users
+                SET locked_until = ?, unlock_token_hash = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(locked_until)
+        .bind(unlock_token_hash)
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn unlock(&self, id: &str) -> AppResult<()> {
+        query(
+            r#"
+                UPDATE users
+                SET locked_until = NULL, unlock_token_hash = NULL
+                WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .execute(self)
         .await?;
 
         Ok(())
     }
 
-    // Get total lines number with pagination
-    #[instrument(skip(pool))]
-    async fn get_total(pool: &MySqlPool) -> Result<i64, sqlx::Error> {
-        let query = r#"
-            SELECT COUNT(id) AS n
+    #[instrument(skip(self, hasher, verification))]
+    async fn register(
+        &self,
+        hasher: &PasswordHasher,
+        user: &mut User,
+        verification: &EmailVerification,
+    ) -> AppResult<()> {
+        user.password = hasher.hash_async(&user.password).await?;
+
+        let mut tx = self.begin().await?;
+
+        query(
+            r#"
+                INSERT INTO users (id, lastname, firstname, username, password, rate_limit, created_at, updated_at, deleted_at, email_verified_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, NULL)
+            "#,
+        )
+        .bind(&user.id)
+        .bind(&user.lastname)
+        .bind(&user.firstname)
+        .bind(&user.username)
+        .bind(&user.password)
+        .bind(user.rate_limit)
+        .bind(user.created_at)
+        .bind(user.updated_at)
+        .bind(user.deleted_at)
+        .execute(&mut tx)
+        .await?;
+
+        replace_user_roles(&mut tx, &user.id, &user.roles).await?;
+
+        insert_email_verification(&mut tx, verification).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn is_email_verified(&self, id: &str) -> AppResult<bool> {
+        let row = query("SELECT email_verified_at FROM users WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self)
+            .await?;
+
+        match row {
+            Some(row) => Ok(row.try_get::<Option<DateTime<Utc>>>("email_verified_at")?.is_some()),
+            None => Ok(false),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn verify_email(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query("UPDATE users SET email_verified_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        query("DELETE FROM email_verifications WHERE user_id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Value of a column of a user (used to build cursors and to filter and sort users kept in memory)
+pub(super) fn column_value(user: &User, column: &str) -> Option<FilterValue> {
+    match column {
+        "id" => Some(FilterValue::Text(user.id.clone())),
+        "lastname" => Some(FilterValue::Text(user.lastname.clone())),
+        "firstname" => Some(FilterValue::Text(user.firstname.clone())),
+        "username" => Some(FilterValue::Text(user.username.clone())),
+        "roles" => match user.roles.is_empty() {
+            true => None,
+            false => Some(FilterValue::Text(
+                user.roles.iter().map(Role::to_string).collect::<Vec<_>>().join(","),
+            )),
+        },
+        "rate_limit" => Some(FilterValue::Integer(user.rate_limit.into())),
+        "created_at" => Some(FilterValue::DateTime(user.created_at)),
+        "updated_at" => Some(FilterValue::DateTime(user.updated_at)),
+        "deleted_at" => user.deleted_at.map(FilterValue::DateTime),
+        _ => None,
+    }
+}
+
+/// Build a `User` from a database row
+fn from_row(row: &DbRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        id: row.try_get("id")?,
+        lastname: row.try_get("lastname")?,
+        firstname: row.try_get("firstname")?,
+        username: row.try_get("username")?,
+        password: row.try_get("password")?,
+        roles: Role::from_names(row.try_get::<Option<String>>("roles")?.unwrap_or_default().split(',')),
+        rate_limit: row.try_get("rate_limit")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        deleted_at: row.try_get("deleted_at")?,
+    })
+}
+
+/// Users table with their roles joined with a comma in a `roles` column (used to filter users by role)
+fn users_with_roles(backend: DatabaseBackend) -> String {
+    format!(
+        "(
+            SELECT users.*, (
+                SELECT {}
+                FROM user_roles
+                WHERE user_roles.user_id = users.id
+            ) AS roles
             FROM users
-        "#;
+        ) users",
+        backend.group_concat("user_roles.role")
+    )
+}
+
+/// Replace the roles of a user
+async fn replace_user_roles(tx: &mut DbTransaction, user_id: &str, roles: &[Role]) -> AppResult<()> {
+    query("DELETE FROM user_roles WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
 
-        Ok(sqlx::query(query).fetch_one(pool).await?.get("n"))
+    for role in roles {
+        query("INSERT INTO user_roles (user_id, role) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(role.to_string())
+            .execute(&mut *tx)
+            .await?;
     }
+
+    Ok(())
 }
 
-pub struct PasswordResetRepository;
+/// Replace the password hash of a user (used to upgrade legacy hashes)
+#[instrument(skip(database, password))]
+async fn update_password_hash(database: &Database, id: &str, password: &str) -> AppResult<()> {
+    query(
+        r#"
+            UPDATE users
+            SET password = ?
+            WHERE id = ?
+        "#,
+    )
+    .bind(password)
+    .bind(id)
+    .execute(database)
+    .await?;
+
+    Ok(())
+}
+
+// Get total lines number with pagination
+#[instrument(skip(database))]
+async fn get_total(database: &Database, filters: &Filters) -> Result<i64, sqlx::Error> {
+    let mut sql = format!(
+        r#"
+        SELECT COUNT(id) AS n
+        FROM {}
+        WHERE deleted_at IS NULL
+    "#,
+        users_with_roles(database.backend())
+    );
+    sql.push_str(&filters.get_where_sql(database.backend()));
+
+    filters.bind(query(&sql)).fetch_one(database).await?.try_get("n")
+}
+
+/// Password resets storage, injected in the application state (see `Stores`)
+#[async_trait]
+pub trait PasswordResetStore: Send + Sync {
+    /// Add a new password reset (it replaces the previous one of the user)
+    async fn create_or_update(&self, password_reset: &PasswordReset) -> AppResult<()>;
+
+    /// Get the password reset of a user
+    async fn get_by_user(&self, user_id: &str) -> AppResult<Option<PasswordReset>>;
+
+    /// Get user ID and current password hash from the hash of a valid token
+    async fn get_user_id_from_hash(&self, token_hash: &str) -> AppResult<Option<(String, String)>>;
+
+    /// Consume a password reset and set the new password hash of its user.
+    ///
+    /// Return `false` if the token has already been used or is expired.
+    async fn reset_password(&self, user_id: &str, token_hash: &str, password: &str) -> AppResult<bool>;
+}
+
+#[async_trait]
+impl PasswordResetStore for Database {
+    #[instrument(skip(self, password_reset))]
+    async fn create_or_update(&self, password_reset: &PasswordReset) -> AppResult<()> {
+        let sql = self.backend().upsert(
+            "password_resets",
+            &["user_id", "token_hash", "expired_at", "created_at"],
+            "user_id",
+            &["token_hash", "expired_at", "created_at"],
+        );
+        query(&sql)
+            .bind(&password_reset.user_id)
+            .bind(&password_reset.token_hash)
+            .bind(password_reset.expired_at)
+            .bind(password_reset.created_at)
+            .execute(self)
+            .await?;
+
+        Ok(())
+    }
 
-impl PasswordResetRepository {
-    /// Add a new password reset
-    #[tracing::instrument(skip(pool))]
-    pub async fn create_or_update(pool: &MySqlPool, password_reset: &mut PasswordReset) -> AppResult<()> {
-        sqlx::query!(
+    #[instrument(skip(self))]
+    async fn get_by_user(&self, user_id: &str) -> AppResult<Option<PasswordReset>> {
+        let row = query(
             r#"
-                INSERT INTO password_resets (user_id, token, expired_at)
-                VALUES (?, ?, ?)
-                ON DUPLICATE KEY UPDATE token = ?, expired_at = ?
+                SELECT user_id, token_hash, expired_at, created_at
+                FROM password_resets
+                WHERE user_id = ?
             "#,
-            password_reset.user_id,
-            password_reset.token,
-            password_reset.expired_at,
-            password_reset.token,
-            password_reset.expired_at,
         )
-        .execute(pool)
+        .bind(user_id)
+        .fetch_optional(self)
         .await?;
 
-        Ok(())
+        Ok(match row {
+            Some(row) => Some(PasswordReset {
+                user_id: row.try_get("user_id")?,
+                token_hash: row.try_get("token_hash")?,
+                expired_at: row.try_get("expired_at")?,
+                created_at: row.try_get("created_at")?,
+            }),
+            None => None,
+        })
     }
 
-    /// Get user ID from token
-    #[tracing::instrument(skip(pool))]
-    pub async fn get_user_id_from_token(pool: &MySqlPool, token: String) -> AppResult<Option<(String, String)>> {
-        let result = sqlx::query!(
+    #[instrument(skip(self, token_hash))]
+    async fn get_user_id_from_hash(&self, token_hash: &str) -> AppResult<Option<(String, String)>> {
+        let row = query(
             r#"
                 SELECT u.id AS user_id, u.password AS password
                 FROM password_resets pr
                     INNER JOIN users u ON u.id = pr.user_id AND u.deleted_at IS NULL
-                WHERE pr.token = ?
+                WHERE pr.token_hash = ?
                     AND pr.expired_at >= ?
             "#,
-            token,
-            Utc::now(),
         )
-        .fetch_optional(pool)
+        .bind(token_hash)
+        .bind(Utc::now())
+        .fetch_optional(self)
         .await?;
 
-        match result {
-            Some(result) => Ok(Some((result.user_id, result.password))),
-            None => Ok(None),
-        }
+        Ok(match row {
+            Some(row) => Some((row.try_get("user_id")?, row.try_get("password")?)),
+            None => None,
+        })
     }
 
-    /// Delete password reset after successfull update
-    #[tracing::instrument(skip(pool))]
-    pub async fn delete(pool: &MySqlPool, user_id: String) -> AppResult<u64> {
-        let result = sqlx::query!(
+    #[instrument(skip(self, token_hash, password))]
+    async fn reset_password(&self, user_id: &str, token_hash: &str, password: &str) -> AppResult<bool> {
+        let mut tx = self.begin().await?;
+
+        let result = query(
             r#"
                 DELETE FROM password_resets
                 WHERE user_id = ?
+                    AND token_hash = ?
+                    AND expired_at >= ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(Utc::now())
+        .execute(&mut tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        query(
+            r#"
+                UPDATE users
+                SET password = ?, updated_at = ?
+                WHERE id = ?
             "#,
-            user_id
         )
-        .execute(pool)
+        .bind(password)
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&mut tx)
         .await?;
 
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+pub trait EmailVerificationRepository: Send + Sync {
+    /// Add a new email verification
+    async fn create(&self, verification: &EmailVerification) -> AppResult<()>;
+
+    /// Returns an email verification of a not deleted user by the hash of its token
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<EmailVerification>>;
+
+    /// Delete the email verifications of a user
+    async fn delete_by_user(&self, user_id: &str) -> AppResult<u64>;
+}
+
+#[async_trait]
+impl EmailVerificationRepository for Database {
+    #[instrument(skip(self, verification))]
+    async fn create(&self, verification: &EmailVerification) -> AppResult<()> {
+        insert_email_verification(self, verification).await
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<EmailVerification>> {
+        let row = query(
+            r#"
+                SELECT ev.*
+                FROM email_verifications ev
+                    INNER JOIN users u ON u.id = ev.user_id AND u.deleted_at IS NULL
+                WHERE ev.token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(EmailVerification {
+                id: row.try_get("id")?,
+                user_id: row.try_get("user_id")?,
+                token_hash: row.try_get("token_hash")?,
+                expired_at: row.try_get("expired_at")?,
+                created_at: row.try_get("created_at")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_by_user(&self, user_id: &str) -> AppResult<u64> {
+        let result = query("DELETE FROM email_verifications WHERE user_id = ?")
+            .bind(user_id)
+            .execute(self)
+            .await?;
+
         Ok(result.rows_affected())
     }
 }
+
+/// Insert an email verification with a connection or in a transaction
+async fn insert_email_verification<'e>(
+    executor: impl Into<DbExecutor<'e>> + Send,
+    verification: &EmailVerification,
+) -> AppResult<()> {
+    query(
+        r#"
+            INSERT INTO email_verifications (id, user_id, token_hash, expired_at, created_at)
+            VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&verification.id)
+    .bind(&verification.user_id)
+    .bind(&verification.token_hash)
+    .bind(verification.expired_at)
+    .bind(verification.created_at)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+pub trait EmailChangeRepository: Send + Sync {
+    /// Add a new email change, the previous ones of the user are deleted
+    async fn create(&self, change: &EmailChange) -> AppResult<()>;
+
+    /// Returns an email change of a not deleted user by the hash of its token
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<EmailChange>>;
+
+    /// Replace the email of the user by the verified one and delete its email changes
+    async fn confirm(&self, change: &EmailChange) -> AppResult<()>;
+}
+
+#[async_trait]
+impl EmailChangeRepository for Database {
+    #[instrument(skip(self, change))]
+    async fn create(&self, change: &EmailChange) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+
+        query("DELETE FROM email_changes WHERE user_id = ?")
+            .bind(&change.user_id)
+            .execute(&mut tx)
+            .await?;
+
+        query(
+            r#"
+                INSERT INTO email_changes (id, user_id, new_email, token_hash, expired_at, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&change.id)
+        .bind(&change.user_id)
+        .bind(&change.new_email)
+        .bind(&change.token_hash)
+        .bind(change.expired_at)
+        .bind(change.created_at)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn get_by_hash(&self, token_hash: &str) -> AppResult<Option<EmailChange>> {
+        let row = query(
+            r#"
+                SELECT ec.*
+                FROM email_changes ec
+                    INNER JOIN users u ON u.id = ec.user_id AND u.deleted_at IS NULL
+                WHERE ec.token_hash = ?
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(self)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(EmailChange {
+                id: row.try_get("id")?,
+                user_id: row.try_get("user_id")?,
+                new_email: row.try_get("new_email")?,
+                token_hash: row.try_get("token_hash")?,
+                expired_at: row.try_get("expired_at")?,
+                created_at: row.try_get("created_at")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument(skip(self, change))]
+    async fn confirm(&self, change: &EmailChange) -> AppResult<()> {
+        let mut tx = self.begin().await?;
+        let now = Utc::now();
+
+        query(
+            r#"
+                UPDATE users
+                SET username = ?, email_verified_at = ?, updated_at = ?
+                WHERE id = ?
+            "#,
+        )
+        .bind(&change.new_email)
+        .bind(now)
+        .bind(now)
+        .bind(&change.user_id)
+        .execute(&mut tx)
+        .await?;
+
+        query("DELETE FROM email_changes WHERE user_id = ?")
+            .bind(&change.user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
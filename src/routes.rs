@@ -2,14 +2,73 @@
 
 use crate::config::Config;
 use crate::handlers;
-use crate::layers::{self, basic_auth::BasicAuthLayer, SharedChatState, SharedState};
+use crate::layers::rate_limiter::{
+    policy::{RateLimitKey, RateLimitPolicy, RateLimitPolicyLayer},
+    strategy::RateLimitStrategy,
+};
+use crate::layers::{self, basic_auth::BasicAuthLayer, permissions::require_permission, SharedChatState, SharedState};
 use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
 
+/// Login attempts: 5 per minute for an IP address and a username
+const LOGIN_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "login",
+    strategy: RateLimitStrategy::SlidingWindow,
+    limit: 5,
+    window_in_seconds: 60,
+    keys: &[RateLimitKey::Ip, RateLimitKey::BodyField("username")],
+};
+
+/// Forgotten password requests: 3 per hour for an email
+const FORGOTTEN_PASSWORD_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "forgotten_password",
+    strategy: RateLimitStrategy::SlidingWindow,
+    limit: 3,
+    window_in_seconds: 3_600,
+    keys: &[RateLimitKey::PathParam("email")],
+};
+
+/// Registrations: 10 per hour for an IP address
+const REGISTER_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "register",
+    strategy: RateLimitStrategy::SlidingWindow,
+    limit: 10,
+    window_in_seconds: 3_600,
+    keys: &[RateLimitKey::Ip],
+};
+
+/// Second login step: 10 codes per minute for an IP address
+const MFA_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "mfa",
+    strategy: RateLimitStrategy::SlidingWindow,
+    limit: 10,
+    window_in_seconds: 60,
+    keys: &[RateLimitKey::Ip],
+};
+
+/// Password and email changes: 5 per 15 minutes for a user
+const ACCOUNT_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "account",
+    strategy: RateLimitStrategy::SlidingWindow,
+    limit: 5,
+    window_in_seconds: 900,
+    keys: &[RateLimitKey::User],
+};
+
+/// OpenID Connect login: 20 requests per minute for an IP address
+const OIDC_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "oidc",
+    strategy: RateLimitStrategy::SlidingWindow,
+    limit: 20,
+    window_in_seconds: 60,
+    keys: &[RateLimitKey::Ip],
+};
+
 /// Return web routes list
 pub fn web(settings: &Config) -> Router<SharedState> {
     Router::new()
         .route("/health-check", get(handlers::web::health_check))
+        .route("/.well-known/jwks.json", get(handlers::web::jwks))
         .route("/timeout", get(handlers::web::timeout))
         .route("/spawn", get(handlers::web::spawn))
         // Test of streams and large data
@@ -37,26 +96,138 @@ pub fn ws(state: SharedChatState) -> Router<SharedState> {
 
 /// Return API routes list
 pub fn api(state: SharedState) -> Router<SharedState> {
+    let login = RateLimitPolicyLayer::new(state.clone(), LOGIN_POLICY);
+    let forgotten_password = RateLimitPolicyLayer::new(state.clone(), FORGOTTEN_PASSWORD_POLICY);
+    let register = RateLimitPolicyLayer::new(state.clone(), REGISTER_POLICY);
+    let mfa = RateLimitPolicyLayer::new(state.clone(), MFA_POLICY);
+    let oidc = RateLimitPolicyLayer::new(state.clone(), OIDC_POLICY);
+
     Router::new()
         // Public routes
-        .route("/login", post(handlers::users::login))
-        .route("/forgotten-password/:email", post(handlers::users::forgotten_password))
+        .route("/login", post(handlers::users::login).route_layer(login))
+        .route("/login/mfa", post(handlers::mfa::login).route_layer(mfa.clone()))
+        .route("/login/mfa/enroll", post(handlers::mfa::login_enroll).route_layer(mfa))
+        .route(
+            "/oidc/authorize",
+            get(handlers::oidc::authorize).route_layer(oidc.clone()),
+        )
+        .route("/oidc/callback", get(handlers::oidc::callback).route_layer(oidc))
+        .route("/token/refresh", post(handlers::users::refresh_token))
+        .route(
+            "/forgotten-password/:email",
+            post(handlers::users::forgotten_password).route_layer(forgotten_password),
+        )
         .route("/update-password/:token", patch(handlers::users::update_password))
+        .route("/unlock/:token", post(handlers::users::unlock_account))
+        .route("/register", post(handlers::users::register).route_layer(register))
+        .route(
+            "/verify-email/:token",
+            get(handlers::users::verify_email).post(handlers::users::verify_email),
+        )
+        .route(
+            "/verify-email-change/:token",
+            get(handlers::me::verify_email_change).post(handlers::me::verify_email_change),
+        )
         // Protected routes
-        .nest("/", api_protected().layer(layers::jwt::JwtLayer { state }))
+        .nest("/", api_protected(state.clone()).layer(layers::jwt::JwtLayer { state }))
 }
 
 /// Protected API routes
-fn api_protected() -> Router<SharedState> {
-    Router::new().nest("/users", api_users())
+fn api_protected(state: SharedState) -> Router<SharedState> {
+    Router::new()
+        .route("/logout", post(handlers::users::logout))
+        .nest("/api-keys", api_keys())
+        .nest("/me", api_me(state))
+        .nest("/mfa", api_mfa())
+        .nest("/roles", api_roles())
+        .nest("/users", api_users())
+}
+
+/// Authenticated user account routes
+fn api_me(state: SharedState) -> Router<SharedState> {
+    let account = RateLimitPolicyLayer::new(state, ACCOUNT_POLICY);
+
+    Router::new()
+        .route("/", get(handlers::me::get))
+        .route("/", patch(handlers::me::update))
+        .route(
+            "/password",
+            post(handlers::me::change_password).route_layer(account.clone()),
+        )
+        .route("/email", post(handlers::me::change_email).route_layer(account))
+}
+
+/// API keys routes
+fn api_keys() -> Router<SharedState> {
+    Router::new()
+        .route("/", post(handlers::api_keys::create))
+        .route("/", get(handlers::api_keys::get_all))
+        .route("/:id", get(handlers::api_keys::get_by_id))
+        .route("/:id", put(handlers::api_keys::update))
+        .route("/:id", delete(handlers::api_keys::delete))
+}
+
+/// Two-factor authentication API routes
+fn api_mfa() -> Router<SharedState> {
+    Router::new()
+        .route("/totp", post(handlers::mfa::enroll))
+        .route("/totp", delete(handlers::mfa::disable))
+        .route("/totp/confirm", post(handlers::mfa::confirm))
+        .route("/recovery-codes", post(handlers::mfa::regenerate_recovery_codes))
 }
 
 /// Users API routes
 fn api_users() -> Router<SharedState> {
+    let read = require_permission("users:read");
+    let write = require_permission("users:write");
+    let roles = require_permission("roles:manage");
+    let api_keys = require_permission("api-keys:manage");
+
+    Router::new()
+        .route("/", post(handlers::users::create).route_layer(write.clone()))
+        .route("/", get(handlers::users::get_all).route_layer(read.clone()))
+        .route("/:id", get(handlers::users::get_by_id).route_layer(read))
+        .route(
+            "/:id",
+            delete(handlers::users::delete).route_layer(require_permission("users:delete")),
+        )
+        .route("/:id", put(handlers::users::update).route_layer(write.clone()))
+        .route("/:id/unlock", post(handlers::users::unlock).route_layer(write))
+        .route(
+            "/:id/roles/:role",
+            put(handlers::roles::grant).route_layer(roles.clone()),
+        )
+        .route("/:id/roles/:role", delete(handlers::roles::revoke).route_layer(roles))
+        .route(
+            "/:id/api-keys",
+            post(handlers::api_keys::create_for_user).route_layer(api_keys.clone()),
+        )
+        .route(
+            "/:id/api-keys",
+            get(handlers::api_keys::get_all_for_user).route_layer(api_keys.clone()),
+        )
+        .route(
+            "/:id/api-keys/:key_id",
+            get(handlers::api_keys::get_by_id_for_user).route_layer(api_keys.clone()),
+        )
+        .route(
+            "/:id/api-keys/:key_id",
+            put(handlers::api_keys::update_for_user).route_layer(api_keys.clone()),
+        )
+        .route(
+            "/:id/api-keys/:key_id",
+            delete(handlers::api_keys::delete_for_user).route_layer(api_keys),
+        )
+}
+
+/// Roles API routes
+fn api_roles() -> Router<SharedState> {
+    let roles = require_permission("roles:manage");
+
     Router::new()
-        .route("/", post(handlers::users::create))
-        .route("/", get(handlers::users::get_all))
-        .route("/:id", get(handlers::users::get_by_id))
-        .route("/:id", delete(handlers::users::delete))
-        .route("/:id", put(handlers::users::update))
+        .route("/", get(handlers::roles::get_all).route_layer(roles.clone()))
+        .route("/", post(handlers::roles::create).route_layer(roles.clone()))
+        .route("/:name", get(handlers::roles::get).route_layer(roles.clone()))
+        .route("/:name", put(handlers::roles::update).route_layer(roles.clone()))
+        .route("/:name", delete(handlers::roles::delete).route_layer(roles))
 }
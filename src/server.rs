@@ -1,15 +1,26 @@
 use crate::{
     config::{databases, logger, Config},
+    emails::{transport, worker::EmailWorker},
     handlers,
     layers::{
-        self, basic_auth::BasicAuthLayer, prometheus::PrometheusMetric, rate_limiter::RateLimiterLayer, ChatState,
-        MakeRequestUuid, SharedChatState, SharedState, State,
+        self,
+        basic_auth::BasicAuthLayer,
+        prometheus::PrometheusMetric,
+        rate_limiter::{
+            store::{MemoryStore, RateLimiterStore, RedisStore},
+            RateLimiter, RateLimiterConfig, RateLimiterLayer,
+        },
+        ChatState, MakeRequestUuid, SharedChatState, SharedState, State,
     },
+    repositories::{revoked_token::RevokedTokenStore, Stores},
     routes,
+    utils::errors::CliError,
 };
 use axum::{error_handling::HandleErrorLayer, middleware, routing::get, Extension, Router};
 use color_eyre::Result;
 use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use std::{future::ready, sync::Mutex};
 use tokio::net::TcpListener;
@@ -36,7 +47,7 @@ pub async fn start_server() -> Result<()> {
     let listener = TcpListener::bind(&addr).await.unwrap();
     info!("Starting server on {}...", &addr);
 
-    let server = axum::serve(listener, app);
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>());
 
     // No graceful shutdown in development environment
     if &settings.environment == "development" {
@@ -51,9 +62,14 @@ pub async fn get_app(settings: &Config) -> Result<Router> {
     // -------
     logger::init(&settings.environment, &settings.logs_path, &settings.logs_file)?;
 
-    // MySQL database
-    // --------------
-    let pool = databases::init_mysql(settings).await?;
+    // Database
+    // --------
+    let pool = databases::init_database(settings).await?;
+    let stores = Stores::database(pool.clone());
+
+    // Email outbox worker
+    // -------------------
+    EmailWorker::new(stores.email_outbox.clone(), transport::init(settings)?, settings).start();
 
     // CORS
     // ----
@@ -68,9 +84,41 @@ pub async fn get_app(settings: &Config) -> Result<Router> {
         .timeout(Duration::from_secs(settings.request_timeout))
         .propagate_x_request_id();
 
+    // Redis
+    // -----
+    let limiter_redis_store = settings.limiter_enabled && settings.limiter_store != "memory";
+    let redis_pool = match settings.redis_enabled || limiter_redis_store {
+        true => Some(databases::init_redis(settings).await?),
+        false => None,
+    };
+
+    // Rate limiter store
+    // ------------------
+    let rate_limiter = match settings.limiter_enabled {
+        true => {
+            let store: Arc<dyn RateLimiterStore> = match (settings.limiter_store.as_str(), &redis_pool) {
+                ("memory", _) => Arc::new(MemoryStore::default()),
+                ("" | "redis", Some(redis_pool)) => Arc::new(RedisStore::new(redis_pool.clone())),
+                (store, _) => {
+                    return Err(CliError::ConfigError(format!("invalid rate limiter store: {store}")).into());
+                }
+            };
+
+            Some(RateLimiter {
+                store,
+                config: RateLimiterConfig::try_from(settings)?,
+            })
+        }
+        false => None,
+    };
+
     // Global state
     // ------------
-    let global_state = SharedState::new(State::init(settings));
+    let revoked_tokens = match (&redis_pool, settings.redis_enabled) {
+        (Some(redis_pool), true) => RevokedTokenStore::redis(redis_pool.clone(), &settings.redis_prefix),
+        _ => RevokedTokenStore::database(pool.clone()),
+    };
+    let global_state = SharedState::new(State::init(settings, stores, revoked_tokens, rate_limiter.clone())?);
 
     // Routing - API
     // -------------
@@ -107,27 +155,20 @@ pub async fn get_app(settings: &Config) -> Result<Router> {
 
     // Rate limiter
     // ------------
-    if settings.limiter_enabled {
-        // Redis
-        // -----
-        let redis_pool = databases::init_redis(settings).await?;
-
-        app = app
-            .layer(RateLimiterLayer::new(
-                global_state.clone(),
-                redis_pool.clone(),
-                settings.redis_prefix.clone(),
-                settings.limiter_requests_by_second,
-                settings.limiter_expire_in_seconds,
-                settings.limiter_white_list.clone(),
-            ))
-            .layer(Extension(redis_pool));
+    if let Some(rate_limiter) = rate_limiter {
+        app = app.layer(RateLimiterLayer::new(
+            global_state.clone(),
+            rate_limiter.store,
+            rate_limiter.config,
+        ));
+    }
+    if let Some(redis_pool) = redis_pool {
+        app = app.layer(Extension(redis_pool));
     }
 
     app = app
         .fallback_service(ServeDir::new("assets").append_index_html_on_directories(true)) // FIXME: static_file_error not work this Axum 0.6.9!
         .layer(middleware::from_fn(layers::override_http_errors))
-        .layer(Extension(pool))
         .layer(layers);
 
     let app = app.with_state(global_state);
@@ -28,6 +28,7 @@ pub enum AppErrorCode {
     UnprocessableEntity,
     Timeout,
     Unauthorized,
+    Forbidden,
     TooManyRequests,
     MethodNotAllowed,
 }
@@ -53,6 +54,9 @@ pub enum AppError {
     #[display(fmt = "Unauthorized")]
     Unauthorized,
 
+    #[display(fmt = "Forbidden")]
+    Forbidden,
+
     #[display(fmt = "Too Many Requests")]
     TooManyRequests,
 
@@ -68,6 +72,7 @@ impl IntoResponse for AppError {
             AppError::InternalError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound { .. } => StatusCode::NOT_FOUND,
             AppError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
             AppError::BadRequest { .. } => StatusCode::BAD_REQUEST,
             AppError::Timeout { .. } => StatusCode::REQUEST_TIMEOUT,
             AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
@@ -181,6 +186,7 @@ macro_rules! app_error {
         match $error {
             AppErrorCode::Timeout => AppError::Timeout,
             AppErrorCode::Unauthorized => AppError::Unauthorized,
+            AppErrorCode::Forbidden => AppError::Forbidden,
             AppErrorCode::TooManyRequests => AppError::TooManyRequests,
             AppErrorCode::MethodNotAllowed => AppError::MethodNotAllowed,
             AppErrorCode::InternalError => AppError::InternalError {
@@ -202,6 +208,7 @@ macro_rules! app_error {
         match $error {
             AppErrorCode::Timeout => AppError::Timeout,
             AppErrorCode::Unauthorized => AppError::Unauthorized,
+            AppErrorCode::Forbidden => AppError::Forbidden,
             AppErrorCode::TooManyRequests => AppError::TooManyRequests,
             AppErrorCode::MethodNotAllowed => AppError::MethodNotAllowed,
             AppErrorCode::InternalError => {
@@ -226,6 +233,7 @@ macro_rules! app_error {
         match $error {
             AppErrorCode::Timeout => AppError::Timeout,
             AppErrorCode::Unauthorized => AppError::Unauthorized,
+            AppErrorCode::Forbidden => AppError::Forbidden,
             AppErrorCode::TooManyRequests => AppError::TooManyRequests,
             AppErrorCode::MethodNotAllowed => AppError::MethodNotAllowed,
             AppErrorCode::InternalError => {
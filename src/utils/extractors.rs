@@ -2,6 +2,8 @@
 
 use super::errors::{AppError, AppErrorCode};
 use crate::app_error;
+use crate::layers::SharedState;
+use crate::models::auth::{Claims, RequestJwt, UserRoles};
 use axum::http::{header::HeaderValue, request::Parts};
 use axum::{
     async_trait,
@@ -28,6 +30,92 @@ where
     }
 }
 
+/// Authenticated user extractor, from the claims added to the request extensions by `JwtLayer`
+pub struct AuthUser(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Claims>()
+            .cloned()
+            .map(Self)
+            .ok_or_else(|| app_error!(AppErrorCode::Unauthorized))
+    }
+}
+
+/// Authenticated user extractor which only accepts requests authenticated with a JWT (API keys are rejected)
+pub struct JwtAuthUser(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for JwtAuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // `JwtLayer` uses the JWT when it is valid, the API key otherwise
+        let AuthUser(claims) = AuthUser::from_request_parts(parts, state).await?;
+
+        match parts.extensions.get::<RequestJwt>() {
+            Some(RequestJwt::Valid(_)) => Ok(Self(claims)),
+            _ => Err(app_error!(AppErrorCode::Unauthorized)),
+        }
+    }
+}
+
+/// Optional authenticated user extractor, for routes which are also open to anonymous requests.
+///
+/// Behind `JwtLayer`, the claims of the request are used. Otherwise the JWT is decoded (once by request)
+/// and an invalid, expired or revoked token is considered as an anonymous request.
+pub struct OptionalAuthUser(pub Option<Claims>);
+
+#[async_trait]
+impl FromRequestParts<SharedState> for OptionalAuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &SharedState) -> Result<Self, Self::Rejection> {
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(Self(Some(claims.clone())));
+        }
+
+        match Claims::extract_from_parts(parts, &state.config.jwt_keys) {
+            Some(Ok(claims)) if !state.revoked_tokens.is_revoked(&claims).await? => Ok(Self(Some(claims))),
+            _ => Ok(Self(None)),
+        }
+    }
+}
+
+/// Extractor which only accepts requests with at least one of the roles of the `R` mask
+/// (e.g. `RequireRole<{ Role::ADMIN_BIT | Role::MANAGER_BIT }>`).
+///
+/// It uses the roles added to the request extensions by `JwtLayer`.
+pub struct RequireRole<const R: u8>(pub Claims);
+
+#[async_trait]
+impl<S, const R: u8> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthUser(claims) = AuthUser::from_request_parts(parts, state).await?;
+
+        match parts.extensions.get::<UserRoles>() {
+            Some(roles) if roles.has_one_of_mask(R) => Ok(Self(claims)),
+            _ => Err(app_error!(AppErrorCode::Forbidden)),
+        }
+    }
+}
+
 // We define our own `Path` extractor that customizes the error from `axum::extract::Path`
 pub struct Path<T>(pub T);
 
@@ -124,3 +212,88 @@ where
         Ok(Query(value))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::Role;
+    use axum::http::Request;
+
+    fn claims(roles: &str) -> Claims {
+        Claims {
+            sub: String::from("user"),
+            exp: 0,
+            iat: 0,
+            nbf: 0,
+            iat_ms: 0,
+            jti: String::from("jti"),
+            user_id: String::from("user"),
+            user_roles: Role::from_names(roles.split(',')),
+            user_permissions: vec![],
+            user_rate_limit: -1,
+        }
+    }
+
+    /// Request parts as given by `JwtLayer` to the handlers
+    fn parts(claims: Option<Claims>, jwt: bool) -> Parts {
+        let mut parts = Request::builder().body(()).unwrap().into_parts().0;
+        if let Some(claims) = claims {
+            if jwt {
+                parts.extensions.insert(RequestJwt::Valid(claims.clone()));
+            }
+            parts.extensions.insert(UserRoles(claims.roles()));
+            parts.extensions.insert(claims);
+        }
+
+        parts
+    }
+
+    #[tokio::test]
+    async fn test_auth_user() {
+        let mut parts = self::parts(Some(claims("USER")), false);
+        assert!(AuthUser::from_request_parts(&mut parts, &()).await.is_ok());
+
+        let mut parts = self::parts(None, false);
+        assert_eq!(
+            AuthUser::from_request_parts(&mut parts, &()).await.err(),
+            Some(AppError::Unauthorized)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jwt_auth_user() {
+        let mut parts = self::parts(Some(claims("USER")), true);
+        assert!(JwtAuthUser::from_request_parts(&mut parts, &()).await.is_ok());
+
+        // Request authenticated with an API key
+        let mut parts = self::parts(Some(claims("USER")), false);
+        assert_eq!(
+            JwtAuthUser::from_request_parts(&mut parts, &()).await.err(),
+            Some(AppError::Unauthorized)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_require_role() {
+        const ADMIN_OR_MANAGER: u8 = Role::ADMIN_BIT | Role::MANAGER_BIT;
+
+        let mut parts = self::parts(Some(claims("MANAGER")), true);
+        assert!(RequireRole::<ADMIN_OR_MANAGER>::from_request_parts(&mut parts, &())
+            .await
+            .is_ok());
+        assert_eq!(
+            RequireRole::<{ Role::ADMIN_BIT }>::from_request_parts(&mut parts, &())
+                .await
+                .err(),
+            Some(AppError::Forbidden)
+        );
+
+        let mut parts = self::parts(None, false);
+        assert_eq!(
+            RequireRole::<ADMIN_OR_MANAGER>::from_request_parts(&mut parts, &())
+                .await
+                .err(),
+            Some(AppError::Unauthorized)
+        );
+    }
+}
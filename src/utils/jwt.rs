@@ -0,0 +1,413 @@
+//! JWT signing and verification keys module
+
+use super::errors::{AppError, AppErrorCode, AppResult};
+use crate::app_error;
+use crate::config::Config;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, PublicKeyUse, RSAKeyParameters,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use simple_asn1::ASN1Block;
+use std::collections::HashMap;
+use std::fs;
+
+/// Supported algorithms
+const SUPPORTED_ALGORITHMS: [Algorithm; 4] = [Algorithm::HS512, Algorithm::RS256, Algorithm::ES256, Algorithm::EdDSA];
+
+/// Create an error for invalid keys
+fn key_error(details: String) -> AppError {
+    app_error!(AppErrorCode::InternalError, "error during JWT keys loading", details)
+}
+
+/// JWT keys: the current signing key and all the verification keys indexed by their ID (`kid`).
+///
+/// Verification keys of previous signing keys can be added to allow key rotation:
+/// tokens signed with them stay valid until their expiration.
+/// Only public keys are published in the JWKS, so it is empty with HS512.
+#[derive(Clone)]
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    kid: String,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    /// Create keys from a secret (HS512)
+    pub fn from_secret(kid: &str, secret: &str) -> Self {
+        Self {
+            algorithm: Algorithm::HS512,
+            kid: kid.to_owned(),
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_keys: HashMap::from([(kid.to_owned(), DecodingKey::from_secret(secret.as_bytes()))]),
+            jwks: JwkSet { keys: vec![] },
+        }
+    }
+
+    /// Create keys from PEM encoded private and public keys (RS256, ES256 or EdDSA)
+    pub fn from_pem(algorithm: Algorithm, kid: &str, private_key: &[u8], public_key: &[u8]) -> AppResult<Self> {
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_key),
+            Algorithm::ES256 => EncodingKey::from_ec_pem(private_key),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(private_key),
+            _ => return Err(key_error(format!("algorithm {algorithm:?} does not use PEM keys"))),
+        }
+        .map_err(|err| key_error(format!("invalid private key: {err}")))?;
+
+        let mut keys = Self {
+            algorithm,
+            kid: kid.to_owned(),
+            encoding_key,
+            decoding_keys: HashMap::new(),
+            jwks: JwkSet { keys: vec![] },
+        };
+        keys.add_verification_key(kid, public_key)?;
+
+        Ok(keys)
+    }
+
+    /// Create keys from configuration (`.env`)
+    pub fn from_config(config: &Config) -> AppResult<Self> {
+        let algorithm = Self::parse_algorithm(&config.jwt_algorithm)?;
+        if algorithm == Algorithm::HS512 {
+            return Ok(Self::from_secret(&config.jwt_kid, &config.jwt_secret_key));
+        }
+
+        let mut keys = Self::from_pem(
+            algorithm,
+            &config.jwt_kid,
+            &Self::read_file(&config.jwt_private_key)?,
+            &Self::read_file(&config.jwt_public_key)?,
+        )?;
+
+        // Keys of previous rotations (format: kid=path,kid=path)
+        for rotated_key in config
+            .jwt_rotated_public_keys
+            .split(',')
+            .filter(|k| !k.trim().is_empty())
+        {
+            let (kid, path) = rotated_key
+                .split_once('=')
+                .ok_or_else(|| key_error(format!("invalid rotated public key: {rotated_key}")))?;
+            keys.add_verification_key(kid.trim(), &Self::read_file(path.trim())?)?;
+        }
+
+        Ok(keys)
+    }
+
+    /// Add a PEM encoded public key used to verify tokens
+    pub fn add_verification_key(&mut self, kid: &str, public_key: &[u8]) -> AppResult<()> {
+        let decoding_key = match self.algorithm {
+            Algorithm::RS256 => DecodingKey::from_rsa_pem(public_key),
+            Algorithm::ES256 => DecodingKey::from_ec_pem(public_key),
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(public_key),
+            _ => {
+                return Err(key_error(format!(
+                    "algorithm {:?} does not use PEM keys",
+                    self.algorithm
+                )))
+            }
+        }
+        .map_err(|err| key_error(format!("invalid public key {kid}: {err}")))?;
+
+        self.jwks
+            .keys
+            .push(Self::jwk_from_pem(self.algorithm, kid, public_key)?);
+        self.decoding_keys.insert(kid.to_owned(), decoding_key);
+
+        Ok(())
+    }
+
+    /// Signing algorithm
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Header of the generated tokens
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+
+        header
+    }
+
+    /// Current signing key
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    /// Verification key of a token (current key if the token has no `kid`)
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<&DecodingKey> {
+        self.decoding_keys.get(kid.unwrap_or(&self.kid))
+    }
+
+    /// Public keys set
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    /// Parse algorithm name
+    fn parse_algorithm(algorithm: &str) -> AppResult<Algorithm> {
+        algorithm
+            .parse::<Algorithm>()
+            .ok()
+            .filter(|algorithm| SUPPORTED_ALGORITHMS.contains(algorithm))
+            .ok_or_else(|| key_error(format!("unsupported JWT algorithm: {algorithm}")))
+    }
+
+    /// Read a key file
+    fn read_file(path: &str) -> AppResult<Vec<u8>> {
+        fs::read(path).map_err(|err| key_error(format!("cannot read key file {path}: {err}")))
+    }
+
+    /// Convert a PEM encoded public key to JWK
+    fn jwk_from_pem(algorithm: Algorithm, kid: &str, public_key: &[u8]) -> AppResult<Jwk> {
+        let pem = pem::parse(public_key).map_err(|err| key_error(format!("invalid public key {kid}: {err}")))?;
+
+        let (key_algorithm, parameters) = match (algorithm, pem.tag()) {
+            (Algorithm::RS256, "RSA PUBLIC KEY") => (KeyAlgorithm::RS256, Self::rsa_parameters(pem.contents())?),
+            (Algorithm::RS256, "PUBLIC KEY") => (
+                KeyAlgorithm::RS256,
+                Self::rsa_parameters(&Self::subject_public_key(pem.contents())?)?,
+            ),
+            (Algorithm::ES256, "PUBLIC KEY") => (
+                KeyAlgorithm::ES256,
+                Self::ec_parameters(&Self::subject_public_key(pem.contents())?)?,
+            ),
+            (Algorithm::EdDSA, "PUBLIC KEY") => (
+                KeyAlgorithm::EdDSA,
+                Self::ed_parameters(&Self::subject_public_key(pem.contents())?)?,
+            ),
+            _ => return Err(key_error(format!("invalid public key {kid}: unsupported format"))),
+        };
+
+        Ok(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_owned()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        })
+    }
+
+    /// Extract the key from a DER encoded `SubjectPublicKeyInfo`
+    fn subject_public_key(der: &[u8]) -> AppResult<Vec<u8>> {
+        let blocks = simple_asn1::from_der(der).map_err(|err| key_error(format!("invalid public key: {err}")))?;
+
+        match blocks.first() {
+            Some(ASN1Block::Sequence(_, items)) => match items.get(1) {
+                Some(ASN1Block::BitString(_, _, key)) => Ok(key.clone()),
+                _ => Err(key_error(String::from("invalid public key: missing key"))),
+            },
+            _ => Err(key_error(String::from(
+                "invalid public key: not a SubjectPublicKeyInfo",
+            ))),
+        }
+    }
+
+    /// RSA JWK parameters from a DER encoded `RSAPublicKey`
+    fn rsa_parameters(der: &[u8]) -> AppResult<AlgorithmParameters> {
+        let blocks = simple_asn1::from_der(der).map_err(|err| key_error(format!("invalid RSA public key: {err}")))?;
+
+        match blocks.first() {
+            Some(ASN1Block::Sequence(_, items)) => match (items.first(), items.get(1)) {
+                (Some(ASN1Block::Integer(_, n)), Some(ASN1Block::Integer(_, e))) => {
+                    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+                        n: URL_SAFE_NO_PAD.encode(n.to_bytes_be().1),
+                        e: URL_SAFE_NO_PAD.encode(e.to_bytes_be().1),
+                        ..Default::default()
+                    }))
+                }
+                _ => Err(key_error(String::from(
+                    "invalid RSA public key: missing modulus or exponent",
+                ))),
+            },
+            _ => Err(key_error(String::from("invalid RSA public key"))),
+        }
+    }
+
+    /// P-256 JWK parameters from an uncompressed EC point
+    fn ec_parameters(point: &[u8]) -> AppResult<AlgorithmParameters> {
+        match point {
+            [0x04, coordinates @ ..] if coordinates.len() == 64 => {
+                Ok(AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    curve: EllipticCurve::P256,
+                    x: URL_SAFE_NO_PAD.encode(&coordinates[..32]),
+                    y: URL_SAFE_NO_PAD.encode(&coordinates[32..]),
+                    ..Default::default()
+                }))
+            }
+            _ => Err(key_error(String::from(
+                "invalid EC public key: only uncompressed P-256 keys are supported",
+            ))),
+        }
+    }
+
+    /// Ed25519 JWK parameters
+    fn ed_parameters(key: &[u8]) -> AppResult<AlgorithmParameters> {
+        match key.len() {
+            32 => Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key),
+                ..Default::default()
+            })),
+            _ => Err(key_error(String::from("invalid Ed25519 public key"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{decode, decode_header, encode, Validation};
+    use serde::{Deserialize, Serialize};
+
+    const RSA_PRIVATE_KEY: &str = include_str!("../../tests/keys/rsa_private.pem");
+    const RSA_PUBLIC_KEY: &str = include_str!("../../tests/keys/rsa_public.pem");
+    const EC_PRIVATE_KEY: &str = include_str!("../../tests/keys/ec_private.pem");
+    const EC_PUBLIC_KEY: &str = include_str!("../../tests/keys/ec_public.pem");
+    const ED_PRIVATE_KEY: &str = include_str!("../../tests/keys/ed25519_private.pem");
+    const ED_PUBLIC_KEY: &str = include_str!("../../tests/keys/ed25519_public.pem");
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        sub: String,
+        exp: i64,
+    }
+
+    /// Sign a token with `keys` and verify it with the JWK published for its `kid`
+    fn assert_round_trip_with_jwk(keys: &JwtKeys) {
+        let claims = TestClaims {
+            sub: String::from("user"),
+            exp: 10_000_000_000,
+        };
+        let token = encode(&keys.header(), &claims, keys.encoding_key()).unwrap();
+
+        let kid = decode_header(&token).unwrap().kid.unwrap();
+        let jwk = keys.jwks().find(&kid).unwrap();
+        let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+        let decoded = decode::<TestClaims>(&token, &decoding_key, &Validation::new(keys.algorithm())).unwrap();
+
+        assert_eq!(decoded.claims, claims);
+    }
+
+    #[test]
+    fn test_jwt_keys_parse_algorithm() {
+        assert_eq!(JwtKeys::parse_algorithm("HS512").unwrap(), Algorithm::HS512);
+        assert_eq!(JwtKeys::parse_algorithm("RS256").unwrap(), Algorithm::RS256);
+        assert_eq!(JwtKeys::parse_algorithm("ES256").unwrap(), Algorithm::ES256);
+        assert_eq!(JwtKeys::parse_algorithm("EdDSA").unwrap(), Algorithm::EdDSA);
+        assert!(JwtKeys::parse_algorithm("HS256").is_err());
+        assert!(JwtKeys::parse_algorithm("").is_err());
+    }
+
+    #[test]
+    fn test_jwt_keys_from_secret() {
+        let keys = JwtKeys::from_secret("main", "secret");
+
+        assert_eq!(keys.algorithm(), Algorithm::HS512);
+        assert_eq!(keys.header().kid, Some(String::from("main")));
+        assert!(keys.decoding_key(None).is_some());
+        assert!(keys.decoding_key(Some("main")).is_some());
+        assert!(keys.decoding_key(Some("other")).is_none());
+        assert!(keys.jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_jwt_keys_rs256() {
+        let keys = JwtKeys::from_pem(
+            Algorithm::RS256,
+            "rsa",
+            RSA_PRIVATE_KEY.as_bytes(),
+            RSA_PUBLIC_KEY.as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(keys.jwks().keys.len(), 1);
+        assert_round_trip_with_jwk(&keys);
+    }
+
+    #[test]
+    fn test_jwt_keys_es256() {
+        let keys = JwtKeys::from_pem(
+            Algorithm::ES256,
+            "ec",
+            EC_PRIVATE_KEY.as_bytes(),
+            EC_PUBLIC_KEY.as_bytes(),
+        )
+        .unwrap();
+
+        assert_round_trip_with_jwk(&keys);
+    }
+
+    #[test]
+    fn test_jwt_keys_eddsa() {
+        let keys = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "ed",
+            ED_PRIVATE_KEY.as_bytes(),
+            ED_PUBLIC_KEY.as_bytes(),
+        )
+        .unwrap();
+
+        assert_round_trip_with_jwk(&keys);
+    }
+
+    #[test]
+    fn test_jwt_keys_rotation() {
+        let old_keys = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "old",
+            ED_PRIVATE_KEY.as_bytes(),
+            ED_PUBLIC_KEY.as_bytes(),
+        )
+        .unwrap();
+        let mut keys = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "new",
+            ED_PRIVATE_KEY.as_bytes(),
+            ED_PUBLIC_KEY.as_bytes(),
+        )
+        .unwrap();
+        keys.add_verification_key("old", ED_PUBLIC_KEY.as_bytes()).unwrap();
+
+        assert_eq!(keys.header().kid, Some(String::from("new")));
+        assert!(keys.decoding_key(Some("old")).is_some());
+        assert!(keys.jwks().find("old").is_some());
+        assert!(keys.jwks().find("new").is_some());
+
+        // Tokens signed with the old key are still verified
+        let claims = TestClaims {
+            sub: String::from("user"),
+            exp: 10_000_000_000,
+        };
+        let token = encode(&old_keys.header(), &claims, old_keys.encoding_key()).unwrap();
+        let kid = decode_header(&token).unwrap().kid;
+        let decoding_key = keys.decoding_key(kid.as_deref()).unwrap();
+        assert!(decode::<TestClaims>(&token, decoding_key, &Validation::new(Algorithm::EdDSA)).is_ok());
+    }
+
+    #[test]
+    fn test_jwt_keys_invalid_keys() {
+        assert!(JwtKeys::from_pem(
+            Algorithm::RS256,
+            "rsa",
+            EC_PRIVATE_KEY.as_bytes(),
+            EC_PUBLIC_KEY.as_bytes()
+        )
+        .is_err());
+        assert!(JwtKeys::from_pem(
+            Algorithm::ES256,
+            "ec",
+            EC_PRIVATE_KEY.as_bytes(),
+            ED_PUBLIC_KEY.as_bytes()
+        )
+        .is_err());
+        assert!(JwtKeys::from_pem(Algorithm::HS512, "hs", b"secret", b"secret").is_err());
+    }
+}
@@ -1,4 +1,8 @@
 pub mod errors;
 pub mod extractors;
+pub mod jwt;
+pub mod oidc;
+pub mod password;
 pub mod query;
+pub mod totp;
 pub mod validation;
@@ -0,0 +1,309 @@
+//! OpenID Connect client module
+//!
+//! Authorization code flow with PKCE (RFC 7636): the provider endpoints are discovered from the issuer URL
+//! and the ID token returned by the token endpoint is verified with the provider public keys (JWKS).
+
+use super::errors::{AppError, AppErrorCode, AppResult};
+use crate::app_error;
+use crate::config::Config;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::distributions::{Alphanumeric, DistString};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// Length of the PKCE code verifier (43 to 128 characters)
+const CODE_VERIFIER_LENGTH: usize = 64;
+
+/// Timeout of the requests to the provider (in second)
+const REQUEST_TIMEOUT: u64 = 10;
+
+/// Algorithms accepted for ID tokens (symmetric algorithms are rejected)
+const ID_TOKEN_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::EdDSA,
+];
+
+/// Create an error for provider failures
+fn provider_error(details: String) -> AppError {
+    app_error!(AppErrorCode::InternalError, "error during OIDC login", details)
+}
+
+/// PKCE code verifier and its `S256` challenge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    /// Generate a new random code verifier
+    pub fn generate() -> Self {
+        let verifier = Alphanumeric.sample_string(&mut rand::thread_rng(), CODE_VERIFIER_LENGTH);
+
+        Self {
+            challenge: Self::challenge(&verifier),
+            verifier,
+        }
+    }
+
+    /// `S256` challenge of a code verifier
+    pub fn challenge(verifier: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+    }
+}
+
+/// OpenID Connect client configuration
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+    /// Scopes delimited by a space
+    pub scopes: String,
+}
+
+/// Provider metadata (`/.well-known/openid-configuration`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Token endpoint response (only the ID token is used)
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of an ID token used to find the user (`aud` and `exp` are checked during the validation)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+}
+
+impl IdTokenClaims {
+    /// Verified email of the user
+    pub fn verified_email(&self) -> Option<&str> {
+        self.email.as_deref().filter(|_| self.email_verified)
+    }
+}
+
+/// OpenID Connect client
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    /// Provider metadata, discovered at the first login
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+impl OidcClient {
+    /// Create a new client
+    pub fn new(config: OidcConfig) -> AppResult<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT))
+            .build()
+            .map_err(|err| provider_error(format!("cannot create HTTP client: {err}")))?;
+
+        Ok(Self {
+            config,
+            http,
+            metadata: OnceCell::new(),
+        })
+    }
+
+    /// Create a client from configuration (`.env`), `None` if OIDC login is disabled
+    pub fn from_config(config: &Config) -> AppResult<Option<Self>> {
+        if config.oidc_issuer_url.is_empty() {
+            return Ok(None);
+        }
+
+        Self::new(OidcConfig {
+            issuer_url: config.oidc_issuer_url.clone(),
+            client_id: config.oidc_client_id.clone(),
+            client_secret: config.oidc_client_secret.clone(),
+            redirect_url: config.oidc_redirect_url.clone(),
+            scopes: config.oidc_scopes.clone(),
+        })
+        .map(Some)
+    }
+
+    /// Provider metadata
+    pub async fn metadata(&self) -> AppResult<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let issuer_url = self.config.issuer_url.trim_end_matches('/');
+                let metadata: ProviderMetadata = self
+                    .get_json(&format!("{issuer_url}/.well-known/openid-configuration"))
+                    .await?;
+
+                if metadata.issuer.trim_end_matches('/') != issuer_url {
+                    return Err(provider_error(format!(
+                        "issuer mismatch: expected {issuer_url}, got {}",
+                        metadata.issuer
+                    )));
+                }
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// URL of the provider login page
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> AppResult<String> {
+        let metadata = self.metadata().await?;
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|err| provider_error(format!("invalid authorization endpoint: {err}")))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.to_string())
+    }
+
+    /// Exchange an authorization code for an ID token
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str) -> AppResult<String> {
+        let metadata = self.metadata().await?;
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await
+            .map_err(|err| provider_error(format!("token request failed: {err}")))?;
+
+        // An invalid or already used code is rejected by the provider with a 400 error
+        if response.status().is_client_error() {
+            warn!("OIDC code rejected by the provider: {}", response.status());
+            return Err(app_error!(AppErrorCode::Unauthorized));
+        }
+
+        let response: TokenResponse = response
+            .error_for_status()
+            .map_err(|err| provider_error(format!("token request failed: {err}")))?
+            .json()
+            .await
+            .map_err(|err| provider_error(format!("invalid token response: {err}")))?;
+
+        Ok(response.id_token)
+    }
+
+    /// Verify an ID token (signature, issuer, audience, expiration and nonce) and return its claims
+    pub async fn verify_id_token(&self, id_token: &str, nonce: &str) -> AppResult<IdTokenClaims> {
+        let unauthorized = |details: String| {
+            warn!("invalid OIDC ID token: {details}");
+            app_error!(AppErrorCode::Unauthorized)
+        };
+
+        let header = jsonwebtoken::decode_header(id_token).map_err(|err| unauthorized(err.to_string()))?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(unauthorized(format!("unsupported algorithm {:?}", header.alg)));
+        }
+
+        // Keys are fetched at each login to follow the provider key rotations
+        let metadata = self.metadata().await?;
+        let jwks: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| unauthorized(format!("unknown key {:?}", header.kid)))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|err| unauthorized(err.to_string()))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|err| unauthorized(err.to_string()))?
+            .claims;
+
+        // The nonce binds the ID token to the authorization request
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(unauthorized(String::from("nonce mismatch")));
+        }
+
+        Ok(claims)
+    }
+
+    /// Get a JSON document from the provider
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> AppResult<T> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| provider_error(format!("request to {url} failed: {err}")))?
+            .json()
+            .await
+            .map_err(|err| provider_error(format!("invalid response from {url}: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pkce() {
+        let pkce = Pkce::generate();
+        assert_eq!(pkce.verifier.len(), CODE_VERIFIER_LENGTH);
+        assert_eq!(pkce.challenge, Pkce::challenge(&pkce.verifier));
+        assert_ne!(pkce, Pkce::generate());
+
+        // Example of RFC 7636 (appendix B)
+        assert_eq!(
+            Pkce::challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_id_token_claims_verified_email() {
+        let mut claims = IdTokenClaims {
+            iss: String::from("https://idp.test"),
+            sub: String::from("123"),
+            nonce: None,
+            email: Some(String::from("john@test.com")),
+            email_verified: false,
+        };
+        assert_eq!(claims.verified_email(), None);
+
+        claims.email_verified = true;
+        assert_eq!(claims.verified_email(), Some("john@test.com"));
+
+        claims.email = None;
+        assert_eq!(claims.verified_email(), None);
+    }
+}
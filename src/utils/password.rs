@@ -0,0 +1,259 @@
+//! Password hashing module
+
+use super::errors::{AppError, AppErrorCode, AppResult};
+use crate::app_error;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha512};
+
+/// Length of a legacy SHA-512 hexadecimal hash
+const LEGACY_SHA512_HASH_LENGTH: usize = 128;
+
+/// Result of a password verification
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordVerification {
+    /// Password does not match
+    Invalid,
+
+    /// Password matches and the hash is up to date
+    Valid,
+
+    /// Password matches but the hash must be upgraded
+    /// (legacy SHA-512 hash or outdated Argon2 parameters)
+    ValidNeedsRehash,
+}
+
+impl PasswordVerification {
+    /// Return `true` if the password matches
+    pub fn is_valid(&self) -> bool {
+        *self != Self::Invalid
+    }
+}
+
+/// Argon2id password hasher producing PHC strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHasher {
+    /// Memory size (in KiB)
+    pub memory_cost: u32,
+
+    /// Number of iterations
+    pub time_cost: u32,
+
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self {
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl PasswordHasher {
+    /// Create a new `PasswordHasher`
+    pub fn new(memory_cost: u32, time_cost: u32, parallelism: u32) -> Self {
+        Self {
+            memory_cost,
+            time_cost,
+            parallelism,
+        }
+    }
+
+    /// Argon2id context
+    fn argon2(&self) -> AppResult<Argon2<'static>> {
+        let params = Params::new(self.memory_cost, self.time_cost, self.parallelism, None).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error during password hashing",
+                format!("invalid Argon2 parameters: {err}")
+            )
+        })?;
+
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Hash a password and return its PHC string
+    pub fn hash(&self, password: &str) -> AppResult<String> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Ok(self
+            .argon2()?
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|err| {
+                app_error!(
+                    AppErrorCode::InternalError,
+                    "error during password hashing",
+                    format!("error during password hashing: {err}")
+                )
+            })?
+            .to_string())
+    }
+
+    /// Verify a password against a stored hash (PHC string or legacy SHA-512)
+    pub fn verify(&self, password: &str, hash: &str) -> AppResult<PasswordVerification> {
+        if Self::is_legacy_hash(hash) {
+            let legacy_hash = format!("{:x}", Sha512::digest(password.as_bytes()));
+
+            return Ok(match legacy_hash == hash {
+                true => PasswordVerification::ValidNeedsRehash,
+                false => PasswordVerification::Invalid,
+            });
+        }
+
+        let parsed_hash = PasswordHash::new(hash).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error during password verification",
+                format!("invalid password hash in database: {err}")
+            )
+        })?;
+
+        if self
+            .argon2()?
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_err()
+        {
+            return Ok(PasswordVerification::Invalid);
+        }
+
+        Ok(match self.is_outdated(&parsed_hash) {
+            true => PasswordVerification::ValidNeedsRehash,
+            false => PasswordVerification::Valid,
+        })
+    }
+
+    /// Hash a password on the blocking thread pool (Argon2id would block the async runtime)
+    pub async fn hash_async(&self, password: &str) -> AppResult<String> {
+        let (hasher, password) = (*self, password.to_owned());
+
+        tokio::task::spawn_blocking(move || hasher.hash(&password))
+            .await
+            .map_err(join_error)?
+    }
+
+    /// Verify a password on the blocking thread pool (Argon2id would block the async runtime)
+    pub async fn verify_async(&self, password: &str, hash: &str) -> AppResult<PasswordVerification> {
+        let (hasher, password, hash) = (*self, password.to_owned(), hash.to_owned());
+
+        tokio::task::spawn_blocking(move || hasher.verify(&password, &hash))
+            .await
+            .map_err(join_error)?
+    }
+
+    /// Check if the hash has been generated with other algorithm or parameters than the current ones
+    fn is_outdated(&self, hash: &PasswordHash) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+
+        match Params::try_from(hash) {
+            Ok(params) => {
+                params.m_cost() != self.memory_cost
+                    || params.t_cost() != self.time_cost
+                    || params.p_cost() != self.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Check if the hash is a legacy unsalted SHA-512 hash
+    fn is_legacy_hash(hash: &str) -> bool {
+        hash.len() == LEGACY_SHA512_HASH_LENGTH && hash.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
+
+/// Error of a panicked or cancelled hashing task
+fn join_error(err: tokio::task::JoinError) -> AppError {
+    app_error!(
+        AppErrorCode::InternalError,
+        "error during password hashing",
+        format!("error during password hashing task: {err}")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fast parameters for tests
+    fn hasher() -> PasswordHasher {
+        PasswordHasher::new(Params::MIN_M_COST, 1, 1)
+    }
+
+    #[test]
+    fn test_password_hash_and_verify() {
+        let hasher = hasher();
+        let hash = hasher.hash("00000000").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert_eq!(hasher.verify("00000000", &hash).unwrap(), PasswordVerification::Valid);
+        assert_eq!(hasher.verify("11111111", &hash).unwrap(), PasswordVerification::Invalid);
+    }
+
+    #[test]
+    fn test_password_hash_is_salted() {
+        let hasher = hasher();
+
+        assert_ne!(hasher.hash("00000000").unwrap(), hasher.hash("00000000").unwrap());
+    }
+
+    #[test]
+    fn test_password_verify_legacy_hash() {
+        let hasher = hasher();
+        let legacy_hash = format!("{:x}", Sha512::digest("00000000".as_bytes()));
+
+        assert_eq!(
+            hasher.verify("00000000", &legacy_hash).unwrap(),
+            PasswordVerification::ValidNeedsRehash
+        );
+        assert_eq!(
+            hasher.verify("11111111", &legacy_hash).unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[test]
+    fn test_password_verify_outdated_parameters() {
+        let old_hasher = hasher();
+        let new_hasher = PasswordHasher::new(Params::MIN_M_COST, 2, 1);
+        let hash = old_hasher.hash("00000000").unwrap();
+
+        assert_eq!(
+            new_hasher.verify("00000000", &hash).unwrap(),
+            PasswordVerification::ValidNeedsRehash
+        );
+        assert_eq!(
+            new_hasher.verify("11111111", &hash).unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[test]
+    fn test_password_verify_invalid_hash() {
+        assert!(hasher().verify("00000000", "not a hash").is_err());
+    }
+
+    #[test]
+    fn test_password_hasher_invalid_parameters() {
+        assert!(PasswordHasher::new(0, 0, 0).hash("00000000").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_and_verify() {
+        let hasher = hasher();
+        let hash = hasher.hash_async("00000000").await.unwrap();
+
+        assert_eq!(
+            hasher.verify_async("00000000", &hash).await.unwrap(),
+            PasswordVerification::Valid
+        );
+        assert_eq!(
+            hasher.verify_async("11111111", &hash).await.unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+}
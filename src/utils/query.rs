@@ -2,7 +2,15 @@
 
 // https://www.moesif.com/blog/technical/api-design/REST-API-Design-Filtering-Sorting-and-Pagination/
 
+use super::errors::{AppError, AppErrorCode, AppResult};
+use crate::app_error;
+use crate::database::{DatabaseBackend, DbQuery};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, NaiveDate, Utc};
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use std::cmp::Ordering;
 use std::fmt::Display;
 
 const PAGINATION_MAX_LIMIT: u32 = 500;
@@ -10,7 +18,18 @@ const PAGINATION_MAX_LIMIT: u32 = 500;
 #[derive(Serialize)]
 pub struct PaginateResponse<T: Serialize> {
     pub data: T,
-    pub total: i64,
+
+    /// Total number of results (skipped with `?total=false`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+
+    /// Cursor of the next page (cursor pagination only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+
+    /// Cursor of the previous page (cursor pagination only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev_cursor: Option<String>,
 }
 
 /// Query parameters used to paginate API
@@ -26,7 +45,7 @@ pub struct PaginateSortQuery {
     pub sort: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
 pub enum Sort {
     /// Ascending sort (`'+'` prefix)
     /// Example: ?sort=+id
@@ -150,6 +169,696 @@ impl PaginateSort {
 
         s
     }
+
+    /// Sort and paginate items which are not stored in a database,
+    /// like `get_sorts_sql` and `get_pagination_sql` do in SQL.
+    ///
+    /// `value` returns the value of a field for an item.
+    pub fn apply<T, F>(&self, mut items: Vec<T>, valid_fields: &[&str], value: F) -> Vec<T>
+    where
+        F: Fn(&T, &str) -> Option<FilterValue>,
+    {
+        let sorts = self
+            .sorts
+            .iter()
+            .filter(|(field, _)| valid_fields.contains(&field.as_str()))
+            .map(|(field, sort)| (field.as_str(), *sort == Sort::Asc))
+            .collect::<Vec<_>>();
+        items.sort_by(|item, other| compare_items(item, other, sorts.iter().copied(), &value));
+
+        items
+            .into_iter()
+            .skip(self.offset as usize)
+            .take(self.limit as usize)
+            .collect()
+    }
+}
+
+/// Query parameters used to filter API
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct FilterQuery {
+    /// Filters delimited by a comma (Ex.: `?f=lastname:eq:Doe,created_at:gte:2024-01-01`)
+    #[serde(rename(deserialize = "f"))]
+    pub filter: Option<String>,
+}
+
+/// Filter operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOperator {
+    /// Equal (`eq`)
+    Eq,
+
+    /// Not equal (`ne`)
+    Ne,
+
+    /// Greater than (`gt`)
+    Gt,
+
+    /// Greater than or equal (`gte`)
+    Gte,
+
+    /// Less than (`lt`)
+    Lt,
+
+    /// Less than or equal (`lte`)
+    Lte,
+
+    /// Contains (`like`)
+    Like,
+}
+
+impl FilterOperator {
+    /// Operators usable with all types of fields except lists
+    pub const ALL: &'static [Self] = &[Self::Eq, Self::Ne, Self::Gt, Self::Gte, Self::Lt, Self::Lte];
+
+    /// Operators usable with text fields
+    pub const TEXT: &'static [Self] = &[Self::Eq, Self::Ne, Self::Like];
+
+    /// Operators usable with list fields
+    pub const LIST: &'static [Self] = &[Self::Eq, Self::Ne];
+
+    /// Parse an operator from the query
+    fn try_from_str(value: &str) -> Option<Self> {
+        match value {
+            "eq" => Some(Self::Eq),
+            "ne" => Some(Self::Ne),
+            "gt" => Some(Self::Gt),
+            "gte" => Some(Self::Gte),
+            "lt" => Some(Self::Lt),
+            "lte" => Some(Self::Lte),
+            "like" => Some(Self::Like),
+            _ => None,
+        }
+    }
+}
+
+/// Filter field types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterFieldType {
+    Text,
+    Integer,
+    DateTime,
+
+    /// Values delimited by a comma (Ex.: roles)
+    List,
+}
+
+/// Filterable field of a resource
+#[derive(Debug, PartialEq, Eq)]
+pub struct FilterField {
+    /// Name used in the query
+    pub name: &'static str,
+
+    /// Database column
+    pub column: &'static str,
+
+    pub field_type: FilterFieldType,
+    pub operators: &'static [FilterOperator],
+}
+
+/// Typed filter value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterValue {
+    Text(String),
+    Integer(i64),
+    DateTime(DateTime<Utc>),
+}
+
+impl FilterValue {
+    /// Bind the value to a query
+    fn bind(&self, query: DbQuery) -> DbQuery {
+        match self {
+            Self::Text(value) => query.bind(value),
+            Self::Integer(value) => query.bind(*value),
+            Self::DateTime(value) => query.bind(*value),
+        }
+    }
+
+    /// Compare with a value of the same type (`None` for values of different types)
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Text(value), Self::Text(other)) => Some(value.cmp(other)),
+            (Self::Integer(value), Self::Integer(other)) => Some(value.cmp(other)),
+            (Self::DateTime(value), Self::DateTime(other)) => Some(value.cmp(other)),
+            _ => None,
+        }
+    }
+}
+
+/// Compare two values of a field, `NULL` values being the smallest
+fn compare_values(value: Option<&FilterValue>, other: Option<&FilterValue>) -> Ordering {
+    match (value, other) {
+        (Some(value), Some(other)) => value.compare(other).unwrap_or(Ordering::Equal),
+        (value, other) => value.is_some().cmp(&other.is_some()),
+    }
+}
+
+/// Compare two items with sorts (`value` returns the value of a field for an item)
+fn compare_items<'a, T, F>(item: &T, other: &T, sorts: impl Iterator<Item = (&'a str, bool)>, value: &F) -> Ordering
+where
+    F: Fn(&T, &str) -> Option<FilterValue>,
+{
+    for (field, ascending) in sorts {
+        let ordering = compare_values(value(item, field).as_ref(), value(other, field).as_ref());
+        match (ordering, ascending) {
+            (Ordering::Equal, _) => continue,
+            (ordering, true) => return ordering,
+            (ordering, false) => return ordering.reverse(),
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Case insensitive SQL `LIKE` (`%` and `_` wildcards can be escaped with `\`)
+fn like(value: &str, pattern: &str) -> bool {
+    enum Token {
+        Any,
+        One,
+        Char(char),
+    }
+
+    let pattern = pattern.to_lowercase();
+    let mut chars = pattern.chars();
+    let mut tokens = vec![];
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Any,
+            '_' => Token::One,
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            c => Token::Char(c),
+        });
+    }
+
+    // Wildcard matching, backtracking to the last `%` on mismatch
+    let value = value.to_lowercase().chars().collect::<Vec<char>>();
+    let (mut v, mut t) = (0, 0);
+    let mut last_any: Option<(usize, usize)> = None;
+    while v < value.len() {
+        match tokens.get(t) {
+            Some(Token::Any) => {
+                last_any = Some((t, v));
+                t += 1;
+            }
+            Some(Token::One) => {
+                v += 1;
+                t += 1;
+            }
+            Some(Token::Char(c)) if *c == value[v] => {
+                v += 1;
+                t += 1;
+            }
+            _ => match last_any {
+                Some((any_t, any_v)) => {
+                    last_any = Some((any_t, any_v + 1));
+                    t = any_t + 1;
+                    v = any_v + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[t..].iter().all(|token| matches!(token, Token::Any))
+}
+
+/// Filter validated against a resource whitelist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub column: &'static str,
+    pub field_type: FilterFieldType,
+    pub operator: FilterOperator,
+    pub value: FilterValue,
+}
+
+impl Filter {
+    /// SQL condition with a placeholder for the value
+    fn get_sql(&self, backend: DatabaseBackend) -> String {
+        let column = self.column;
+
+        match (self.field_type, self.operator) {
+            (FilterFieldType::List, operator) => {
+                let comparison = match operator {
+                    FilterOperator::Ne => "= 0",
+                    _ => "> 0",
+                };
+                match backend {
+                    DatabaseBackend::MySql => format!("FIND_IN_SET(?, {column}) {comparison}"),
+                    DatabaseBackend::Postgres => {
+                        format!("strpos(',' || {column} || ',', ',' || ? || ',') {comparison}")
+                    }
+                    DatabaseBackend::Sqlite => format!("instr(',' || {column} || ',', ',' || ? || ',') {comparison}"),
+                }
+            }
+            (_, FilterOperator::Eq) => format!("{column} = ?"),
+            (_, FilterOperator::Ne) => format!("{column} <> ?"),
+            (_, FilterOperator::Gt) => format!("{column} > ?"),
+            (_, FilterOperator::Gte) => format!("{column} >= ?"),
+            (_, FilterOperator::Lt) => format!("{column} < ?"),
+            (_, FilterOperator::Lte) => format!("{column} <= ?"),
+            (_, FilterOperator::Like) => match backend {
+                DatabaseBackend::MySql => format!("{column} LIKE ?"),
+                DatabaseBackend::Postgres => format!("{column} ILIKE ?"),
+                DatabaseBackend::Sqlite => format!("{column} LIKE ? ESCAPE '\\'"),
+            },
+        }
+    }
+
+    /// Check the condition against the value of the column (`None` for `NULL`, which never matches)
+    pub fn matches(&self, value: Option<&FilterValue>) -> bool {
+        let value = match value {
+            Some(value) => value,
+            None => return false,
+        };
+
+        match (self.field_type, self.operator, value, &self.value) {
+            (FilterFieldType::List, operator, FilterValue::Text(list), FilterValue::Text(item)) => {
+                let contains = list.split(',').any(|value| value == item);
+                match operator {
+                    FilterOperator::Ne => !contains,
+                    _ => contains,
+                }
+            }
+            (_, FilterOperator::Like, FilterValue::Text(value), FilterValue::Text(pattern)) => like(value, pattern),
+            (_, operator, value, filter_value) => match value.compare(filter_value) {
+                Some(ordering) => match operator {
+                    FilterOperator::Eq => ordering == Ordering::Equal,
+                    FilterOperator::Ne => ordering != Ordering::Equal,
+                    FilterOperator::Gt => ordering == Ordering::Greater,
+                    FilterOperator::Gte => ordering != Ordering::Less,
+                    FilterOperator::Lt => ordering == Ordering::Less,
+                    FilterOperator::Lte => ordering != Ordering::Greater,
+                    FilterOperator::Like => false,
+                },
+                None => false,
+            },
+        }
+    }
+}
+
+/// List of filters used to filter database results
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Filters(pub Vec<Filter>);
+
+impl Filters {
+    /// Parse filters (`field:operator:value` delimited by a comma) and validate them
+    /// against the fields whitelist of the resource
+    pub fn parse(query: &str, valid_fields: &[FilterField]) -> AppResult<Self> {
+        let mut filters = vec![];
+
+        for part in query.split(',').filter(|part| !part.is_empty()) {
+            let invalid_filter = || app_error!(AppErrorCode::BadRequest, format!("invalid filter: {part}"));
+
+            let mut items = part.splitn(3, ':');
+            let (name, operator, value) = match (items.next(), items.next(), items.next()) {
+                (Some(name), Some(operator), Some(value)) => (name, operator, value),
+                _ => return Err(invalid_filter()),
+            };
+
+            let field = valid_fields
+                .iter()
+                .find(|field| field.name == name)
+                .ok_or_else(invalid_filter)?;
+            let operator = FilterOperator::try_from_str(operator)
+                .filter(|operator| field.operators.contains(operator))
+                .ok_or_else(invalid_filter)?;
+            let value = Self::parse_value(field.field_type, operator, value).ok_or_else(invalid_filter)?;
+
+            filters.push(Filter {
+                column: field.column,
+                field_type: field.field_type,
+                operator,
+                value,
+            });
+        }
+
+        Ok(Self(filters))
+    }
+
+    /// Parse a filter value according to the field type
+    fn parse_value(field_type: FilterFieldType, operator: FilterOperator, value: &str) -> Option<FilterValue> {
+        match field_type {
+            FilterFieldType::Text if operator == FilterOperator::Like => {
+                // Escape LIKE wildcards to search the value as is
+                let value = value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+                Some(FilterValue::Text(format!("%{value}%")))
+            }
+            FilterFieldType::Text | FilterFieldType::List => Some(FilterValue::Text(value.to_owned())),
+            FilterFieldType::Integer => value.parse().ok().map(FilterValue::Integer),
+            FilterFieldType::DateTime => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                Ok(date) => date.and_hms_opt(0, 0, 0).map(|d| FilterValue::DateTime(d.and_utc())),
+                Err(_) => DateTime::parse_from_rfc3339(value)
+                    .ok()
+                    .map(|d| FilterValue::DateTime(d.with_timezone(&Utc))),
+            },
+        }
+    }
+
+    /// SQL code for filters (conditions to add to a `WHERE` clause, values are bound with `bind`)
+    pub fn get_where_sql(&self, backend: DatabaseBackend) -> String {
+        self.0
+            .iter()
+            .map(|filter| format!(" AND {}", filter.get_sql(backend)))
+            .collect()
+    }
+
+    /// Bind filters values to the query built with `get_where_sql`
+    pub fn bind(&self, query: DbQuery) -> DbQuery {
+        self.0.iter().fold(query, |query, filter| filter.value.bind(query))
+    }
+
+    /// Check filters against an item which is not stored in a database.
+    ///
+    /// `value` returns the value of a column for the item.
+    pub fn matches<T, F>(&self, item: &T, value: F) -> bool
+    where
+        F: Fn(&T, &str) -> Option<FilterValue>,
+    {
+        self.0
+            .iter()
+            .all(|filter| filter.matches(value(item, filter.column).as_ref()))
+    }
+}
+
+/// Query parameters used for cursor pagination
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct CursorQuery {
+    /// Opaque cursor returned by the previous request (empty for the first page)
+    pub cursor: Option<String>,
+
+    /// Return the total number of results (`true` by default)
+    pub total: Option<bool>,
+}
+
+/// Cursor pagination direction
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CursorDirection {
+    Next,
+    Prev,
+}
+
+/// Cursor content: the sort-key values of the first or last row of a page
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Cursor {
+    #[serde(rename = "d")]
+    pub direction: CursorDirection,
+
+    /// Sorts used to generate the cursor (Ex.: `+lastname,-id`)
+    #[serde(rename = "s")]
+    pub sorts: String,
+
+    #[serde(rename = "v")]
+    pub values: Vec<FilterValue>,
+}
+
+impl Cursor {
+    /// Encode and sign the cursor (`payload.signature` in base64)
+    pub fn encode(&self, secret: &str) -> AppResult<String> {
+        let payload = serde_json::to_vec(self).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error during cursor encoding",
+                format!("error during cursor encoding: {err}")
+            )
+        })?;
+        let payload = URL_SAFE_NO_PAD.encode(payload);
+        let signature = URL_SAFE_NO_PAD.encode(Self::mac(secret, &payload)?.finalize().into_bytes());
+
+        Ok(format!("{payload}.{signature}"))
+    }
+
+    /// Check the cursor signature and decode it
+    pub fn decode(cursor: &str, secret: &str) -> AppResult<Self> {
+        let invalid_cursor = || app_error!(AppErrorCode::BadRequest, "invalid cursor");
+
+        let (payload, signature) = cursor.split_once('.').ok_or_else(invalid_cursor)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid_cursor())?;
+        Self::mac(secret, payload)?
+            .verify_slice(&signature)
+            .map_err(|_| invalid_cursor())?;
+
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid_cursor())?;
+        serde_json::from_slice(&payload).map_err(|_| invalid_cursor())
+    }
+
+    /// HMAC-SHA512 of the payload
+    fn mac(secret: &str, payload: &str) -> AppResult<Hmac<Sha512>> {
+        let mut mac = Hmac::<Sha512>::new_from_slice(secret.as_bytes()).map_err(|err| {
+            app_error!(
+                AppErrorCode::InternalError,
+                "error during cursor signature",
+                format!("error during cursor signature: {err}")
+            )
+        })?;
+        mac.update(payload.as_bytes());
+
+        Ok(mac)
+    }
+}
+
+/// Parameters used to paginate database results with a cursor (keyset pagination)
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeysetPagination {
+    pub limit: u32,
+
+    /// Valid sorts ending with a unique field to have a total order
+    pub sorts: Vec<(String, Sort)>,
+
+    /// `None` for the first page
+    pub cursor: Option<Cursor>,
+}
+
+impl KeysetPagination {
+    /// Create keyset pagination from the sorts of `PaginateSort` and a cursor (empty for the first page).
+    ///
+    /// `unique_field` is added to the sorts if missing.
+    pub fn new(
+        paginate_sort: &PaginateSort,
+        valid_fields: &[&str],
+        unique_field: &str,
+        cursor: &str,
+        secret: &str,
+    ) -> AppResult<Self> {
+        let mut sorts: Vec<(String, Sort)> = vec![];
+        for (field, sort) in paginate_sort.sorts.iter() {
+            if valid_fields.contains(&field.as_str()) && !sorts.iter().any(|(f, _)| f == field) {
+                sorts.push((field.clone(), *sort));
+            }
+        }
+        if !sorts.iter().any(|(field, _)| field == unique_field) {
+            sorts.push((unique_field.to_owned(), Sort::Asc));
+        }
+
+        let cursor = match cursor.is_empty() {
+            true => None,
+            false => {
+                let cursor = Cursor::decode(cursor, secret)?;
+
+                // The cursor can only be used with the sorts used to generate it
+                if cursor.sorts != Self::sorts_signature(&sorts) || cursor.values.len() != sorts.len() {
+                    return Err(app_error!(AppErrorCode::BadRequest, "invalid cursor"));
+                }
+
+                Some(cursor)
+            }
+        };
+
+        Ok(Self {
+            limit: paginate_sort.limit,
+            sorts,
+            cursor,
+        })
+    }
+
+    /// Sorts representation stored in the cursor
+    fn sorts_signature(sorts: &[(String, Sort)]) -> String {
+        sorts
+            .iter()
+            .map(|(field, sort)| match sort {
+                Sort::Asc => format!("+{field}"),
+                Sort::Desc => format!("-{field}"),
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    /// Rows are read in reverse order to get the previous page
+    fn is_backward(&self) -> bool {
+        matches!(
+            self.cursor,
+            Some(Cursor {
+                direction: CursorDirection::Prev,
+                ..
+            })
+        )
+    }
+
+    /// SQL code for the cursor condition (to add to a `WHERE` clause, values are bound with `bind`).
+    ///
+    /// Example with `+lastname,+id`: ` AND ((lastname > ?) OR (lastname = ? AND id > ?))`
+    pub fn get_where_sql(&self) -> String {
+        if self.cursor.is_none() {
+            return String::new();
+        }
+
+        let backward = self.is_backward();
+        let conditions = (0..self.sorts.len())
+            .map(|i| {
+                let mut parts: Vec<String> = self.sorts[..i]
+                    .iter()
+                    .map(|(field, _)| format!("{field} = ?"))
+                    .collect();
+
+                let (field, sort) = &self.sorts[i];
+                let operator = match (sort, backward) {
+                    (Sort::Asc, false) | (Sort::Desc, true) => ">",
+                    _ => "<",
+                };
+                parts.push(format!("{field} {operator} ?"));
+
+                format!("({})", parts.join(" AND "))
+            })
+            .collect::<Vec<String>>();
+
+        format!(" AND ({})", conditions.join(" OR "))
+    }
+
+    /// Bind cursor values to the query built with `get_where_sql`
+    pub fn bind(&self, query: DbQuery) -> DbQuery {
+        match &self.cursor {
+            None => query,
+            Some(cursor) => (0..cursor.values.len()).fold(query, |query, i| {
+                cursor.values[..=i].iter().fold(query, |query, value| value.bind(query))
+            }),
+        }
+    }
+
+    /// SQL code for sorts (ORDER BY)
+    pub fn get_sorts_sql(&self) -> String {
+        let backward = self.is_backward();
+        let sorts = self
+            .sorts
+            .iter()
+            .map(|(field, sort)| match (sort, backward) {
+                (Sort::Asc, false) | (Sort::Desc, true) => format!("{field} ASC"),
+                _ => format!("{field} DESC"),
+            })
+            .collect::<Vec<String>>();
+
+        format!(" ORDER BY {}", sorts.join(", "))
+    }
+
+    /// SQL code for limit (one more row is read to know if there is another page)
+    pub fn get_limit_sql(&self) -> String {
+        format!(" LIMIT {}", self.limit + 1)
+    }
+
+    /// Filter with the cursor, sort and limit items which are not stored in a database,
+    /// like `get_where_sql`, `get_sorts_sql` and `get_limit_sql` do in SQL (the page is then built with `paginate`).
+    ///
+    /// `value` returns the value of a sort field for an item.
+    pub fn apply<T, F>(&self, mut items: Vec<T>, value: F) -> Vec<T>
+    where
+        F: Fn(&T, &str) -> Option<FilterValue>,
+    {
+        let backward = self.is_backward();
+        let sorts = self
+            .sorts
+            .iter()
+            .map(|(field, sort)| {
+                (
+                    field.as_str(),
+                    matches!((sort, backward), (Sort::Asc, false) | (Sort::Desc, true)),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(cursor) = &self.cursor {
+            items.retain(|item| {
+                for ((field, ascending), cursor_value) in sorts.iter().zip(cursor.values.iter()) {
+                    match value(item, field).and_then(|value| value.compare(cursor_value)) {
+                        Some(Ordering::Equal) => continue,
+                        Some(ordering) => return (ordering == Ordering::Greater) == *ascending,
+                        None => return false,
+                    }
+                }
+
+                false
+            });
+        }
+
+        items.sort_by(|item, other| compare_items(item, other, sorts.iter().copied(), &value));
+        items.truncate(self.limit as usize + 1);
+
+        items
+    }
+
+    /// Build the page from the rows read with this pagination.
+    ///
+    /// `value` returns the value of a sort field for a row.
+    pub fn paginate<T, F>(&self, mut rows: Vec<T>, value: F, secret: &str) -> AppResult<PaginateResponse<Vec<T>>>
+    where
+        T: Serialize,
+        F: Fn(&T, &str) -> Option<FilterValue>,
+    {
+        let has_more = rows.len() > self.limit as usize;
+        rows.truncate(self.limit as usize);
+        if self.is_backward() {
+            rows.reverse();
+        }
+
+        let cursor = |row: Option<&T>, direction: CursorDirection| -> AppResult<Option<String>> {
+            let row = match row {
+                Some(row) => row,
+                None => return Ok(None),
+            };
+            let values = self
+                .sorts
+                .iter()
+                .map(|(field, _)| value(row, field))
+                .collect::<Option<Vec<FilterValue>>>()
+                .ok_or_else(|| {
+                    app_error!(
+                        AppErrorCode::InternalError,
+                        "error during cursor generation",
+                        "error during cursor generation: missing sort field value"
+                    )
+                })?;
+
+            Cursor {
+                direction,
+                sorts: Self::sorts_signature(&self.sorts),
+                values,
+            }
+            .encode(secret)
+            .map(Some)
+        };
+
+        let (has_next, has_prev) = match &self.cursor {
+            None => (has_more, false),
+            Some(Cursor {
+                direction: CursorDirection::Next,
+                ..
+            }) => (has_more, true),
+            Some(Cursor {
+                direction: CursorDirection::Prev,
+                ..
+            }) => (true, has_more),
+        };
+
+        Ok(PaginateResponse {
+            next_cursor: match has_next {
+                true => cursor(rows.last(), CursorDirection::Next)?,
+                false => None,
+            },
+            prev_cursor: match has_prev {
+                true => cursor(rows.first(), CursorDirection::Prev)?,
+                false => None,
+            },
+            data: rows,
+            total: None,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -372,4 +1081,384 @@ mod test {
             paginate_sort.get_sorts_sql(valid_fields)
         );
     }
+
+    const FILTER_FIELDS: &[FilterField] = &[
+        FilterField {
+            name: "lastname",
+            column: "lastname",
+            field_type: FilterFieldType::Text,
+            operators: FilterOperator::TEXT,
+        },
+        FilterField {
+            name: "rate_limit",
+            column: "rate_limit",
+            field_type: FilterFieldType::Integer,
+            operators: FilterOperator::ALL,
+        },
+        FilterField {
+            name: "created_at",
+            column: "u.created_at",
+            field_type: FilterFieldType::DateTime,
+            operators: FilterOperator::ALL,
+        },
+        FilterField {
+            name: "role",
+            column: "roles",
+            field_type: FilterFieldType::List,
+            operators: FilterOperator::LIST,
+        },
+    ];
+
+    #[test]
+    fn test_filters_parse() {
+        assert_eq!(Filters::parse("", FILTER_FIELDS).unwrap(), Filters::default());
+
+        let filters = Filters::parse(
+            "lastname:eq:Doe,rate_limit:gte:10,created_at:lt:2024-01-01,role:eq:ADMIN",
+            FILTER_FIELDS,
+        )
+        .unwrap();
+        assert_eq!(
+            filters.0,
+            vec![
+                Filter {
+                    column: "lastname",
+                    field_type: FilterFieldType::Text,
+                    operator: FilterOperator::Eq,
+                    value: FilterValue::Text(String::from("Doe")),
+                },
+                Filter {
+                    column: "rate_limit",
+                    field_type: FilterFieldType::Integer,
+                    operator: FilterOperator::Gte,
+                    value: FilterValue::Integer(10),
+                },
+                Filter {
+                    column: "u.created_at",
+                    field_type: FilterFieldType::DateTime,
+                    operator: FilterOperator::Lt,
+                    value: FilterValue::DateTime(
+                        NaiveDate::from_ymd_opt(2024, 1, 1)
+                            .unwrap()
+                            .and_hms_opt(0, 0, 0)
+                            .unwrap()
+                            .and_utc()
+                    ),
+                },
+                Filter {
+                    column: "roles",
+                    field_type: FilterFieldType::List,
+                    operator: FilterOperator::Eq,
+                    value: FilterValue::Text(String::from("ADMIN")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filters_parse_values() {
+        let filters = Filters::parse("created_at:gte:2024-01-01T10:30:00+02:00", FILTER_FIELDS).unwrap();
+        assert_eq!(
+            filters.0[0].value,
+            FilterValue::DateTime(
+                NaiveDate::from_ymd_opt(2024, 1, 1)
+                    .unwrap()
+                    .and_hms_opt(8, 30, 0)
+                    .unwrap()
+                    .and_utc()
+            )
+        );
+
+        let filters = Filters::parse("lastname:like:50%_off", FILTER_FIELDS).unwrap();
+        assert_eq!(filters.0[0].value, FilterValue::Text(String::from("%50\\%\\_off%")));
+
+        let filters = Filters::parse("lastname:eq:a:b", FILTER_FIELDS).unwrap();
+        assert_eq!(filters.0[0].value, FilterValue::Text(String::from("a:b")));
+    }
+
+    #[test]
+    fn test_filters_parse_invalid() {
+        // Unknown field
+        assert!(Filters::parse("password:eq:0000", FILTER_FIELDS).is_err());
+
+        // Unknown or not allowed operator
+        assert!(Filters::parse("lastname:in:Doe", FILTER_FIELDS).is_err());
+        assert!(Filters::parse("lastname:gt:Doe", FILTER_FIELDS).is_err());
+        assert!(Filters::parse("role:like:ADMIN", FILTER_FIELDS).is_err());
+
+        // Invalid value
+        assert!(Filters::parse("rate_limit:eq:ten", FILTER_FIELDS).is_err());
+        assert!(Filters::parse("created_at:eq:yesterday", FILTER_FIELDS).is_err());
+
+        // Invalid format
+        assert!(Filters::parse("lastname:eq", FILTER_FIELDS).is_err());
+        assert!(Filters::parse("lastname", FILTER_FIELDS).is_err());
+    }
+
+    #[test]
+    fn test_filters_get_where_sql() {
+        assert_eq!(Filters::default().get_where_sql(DatabaseBackend::MySql), String::new());
+
+        let filters = Filters::parse(
+            "lastname:like:Doe,rate_limit:ne:10,created_at:lte:2024-01-01,role:eq:ADMIN,role:ne:USER",
+            FILTER_FIELDS,
+        )
+        .unwrap();
+        assert_eq!(
+            filters.get_where_sql(DatabaseBackend::MySql),
+            String::from(
+                " AND lastname LIKE ? AND rate_limit <> ? AND u.created_at <= ? AND FIND_IN_SET(?, roles) > 0 AND FIND_IN_SET(?, roles) = 0"
+            )
+        );
+        assert_eq!(
+            filters.get_where_sql(DatabaseBackend::Postgres),
+            String::from(
+                " AND lastname ILIKE ? AND rate_limit <> ? AND u.created_at <= ? AND strpos(',' || roles || ',', ',' || ? || ',') > 0 AND strpos(',' || roles || ',', ',' || ? || ',') = 0"
+            )
+        );
+        assert_eq!(
+            filters.get_where_sql(DatabaseBackend::Sqlite),
+            String::from(
+                " AND lastname LIKE ? ESCAPE '\\' AND rate_limit <> ? AND u.created_at <= ? AND instr(',' || roles || ',', ',' || ? || ',') > 0 AND instr(',' || roles || ',', ',' || ? || ',') = 0"
+            )
+        );
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like("Doe", "%o%"));
+        assert!(like("Doe", "d_e"));
+        assert!(like("Doe", "%"));
+        assert!(like("john.doe@example.com", "%DOE@%.com"));
+        assert!(!like("Doe", "%a%"));
+        assert!(!like("Doe", "do"));
+
+        // Escaped wildcards
+        assert!(like("100%", "%0\\%"));
+        assert!(!like("1000", "%0\\%"));
+        assert!(like("a_b", "a\\_b"));
+        assert!(!like("acb", "a\\_b"));
+    }
+
+    #[test]
+    fn test_filters_matches() {
+        let value = |item: &(&str, i64, Option<&str>), column: &str| match column {
+            "lastname" => Some(FilterValue::Text(item.0.to_owned())),
+            "rate_limit" => Some(FilterValue::Integer(item.1)),
+            "roles" => item.2.map(|roles| FilterValue::Text(roles.to_owned())),
+            _ => None,
+        };
+
+        let filters = Filters::parse("lastname:like:do,rate_limit:gte:10,role:eq:ADMIN", FILTER_FIELDS).unwrap();
+        assert!(filters.matches(&("Doe", 10, Some("ADMIN,USER")), value));
+        assert!(!filters.matches(&("Doe", 9, Some("ADMIN,USER")), value));
+        assert!(!filters.matches(&("Smith", 10, Some("ADMIN")), value));
+        assert!(!filters.matches(&("Doe", 10, Some("USER")), value));
+
+        // `NULL` never matches
+        let filters = Filters::parse("role:ne:ADMIN", FILTER_FIELDS).unwrap();
+        assert!(filters.matches(&("Doe", 10, Some("USER")), value));
+        assert!(!filters.matches(&("Doe", 10, None), value));
+
+        // Filters on unknown columns never match
+        let filters = Filters::parse("created_at:lte:2024-01-01", FILTER_FIELDS).unwrap();
+        assert!(!filters.matches(&("Doe", 10, None), value));
+        assert!(Filters::default().matches(&("Doe", 10, None), value));
+    }
+
+    #[test]
+    fn test_paginate_sort_apply() {
+        let value = |item: &(&str, i64), field: &str| match field {
+            "lastname" => Some(FilterValue::Text(item.0.to_owned())),
+            "id" => Some(FilterValue::Integer(item.1)),
+            _ => None,
+        };
+        let items = || vec![("Doe", 1), ("Smith", 2), ("Doe", 3), ("Adams", 4)];
+
+        let paginate_sort: PaginateSort = PaginateSortQuery {
+            page: Some(1),
+            limit: Some(3),
+            sort: Some(String::from("+lastname,-id,+unknown")),
+        }
+        .into();
+        assert_eq!(
+            paginate_sort.apply(items(), &["id", "lastname"], value),
+            vec![("Adams", 4), ("Doe", 3), ("Doe", 1)]
+        );
+
+        let paginate_sort = PaginateSort {
+            page: 2,
+            offset: 3,
+            ..paginate_sort
+        };
+        assert_eq!(
+            paginate_sort.apply(items(), &["id", "lastname"], value),
+            vec![("Smith", 2)]
+        );
+    }
+
+    fn keyset_pagination(sorts: &str, cursor: &str) -> AppResult<KeysetPagination> {
+        let paginate_sort: PaginateSort = PaginateSortQuery {
+            page: None,
+            limit: Some(2),
+            sort: Some(sorts.to_owned()),
+        }
+        .into();
+
+        KeysetPagination::new(&paginate_sort, &["id", "lastname"], "id", cursor, "secret")
+    }
+
+    #[test]
+    fn test_cursor_encode_decode() {
+        let cursor = Cursor {
+            direction: CursorDirection::Next,
+            sorts: String::from("+lastname,+id"),
+            values: vec![
+                FilterValue::Text(String::from("Doe")),
+                FilterValue::Text(String::from("1")),
+            ],
+        };
+        let encoded = cursor.encode("secret").unwrap();
+
+        assert_eq!(Cursor::decode(&encoded, "secret").unwrap(), cursor);
+
+        // Invalid secret or tampered cursor
+        assert!(Cursor::decode(&encoded, "other").is_err());
+        let (payload, signature) = encoded.split_once('.').unwrap();
+        let other_payload = Cursor {
+            direction: CursorDirection::Prev,
+            ..cursor
+        }
+        .encode("secret")
+        .unwrap();
+        let (other_payload, _) = other_payload.split_once('.').unwrap();
+        assert!(Cursor::decode(&format!("{other_payload}.{signature}"), "secret").is_err());
+        assert!(Cursor::decode(payload, "secret").is_err());
+        assert!(Cursor::decode("", "secret").is_err());
+    }
+
+    #[test]
+    fn test_keyset_pagination_first_page() {
+        let pagination = keyset_pagination("-lastname,+created_at", "").unwrap();
+
+        assert_eq!(
+            pagination.sorts,
+            vec![("lastname".to_owned(), Sort::Desc), ("id".to_owned(), Sort::Asc)]
+        );
+        assert_eq!(pagination.get_where_sql(), String::new());
+        assert_eq!(
+            pagination.get_sorts_sql(),
+            String::from(" ORDER BY lastname DESC, id ASC")
+        );
+        assert_eq!(pagination.get_limit_sql(), String::from(" LIMIT 3"));
+    }
+
+    #[test]
+    fn test_keyset_pagination_with_cursor() {
+        let cursor = Cursor {
+            direction: CursorDirection::Next,
+            sorts: String::from("-lastname,+id"),
+            values: vec![
+                FilterValue::Text(String::from("Doe")),
+                FilterValue::Text(String::from("1")),
+            ],
+        };
+        let pagination = keyset_pagination("-lastname", &cursor.encode("secret").unwrap()).unwrap();
+        assert_eq!(
+            pagination.get_where_sql(),
+            String::from(" AND ((lastname < ?) OR (lastname = ? AND id > ?))")
+        );
+        assert_eq!(
+            pagination.get_sorts_sql(),
+            String::from(" ORDER BY lastname DESC, id ASC")
+        );
+
+        // Previous page: conditions and sorts are reversed
+        let cursor = Cursor {
+            direction: CursorDirection::Prev,
+            ..cursor
+        };
+        let pagination = keyset_pagination("-lastname", &cursor.encode("secret").unwrap()).unwrap();
+        assert_eq!(
+            pagination.get_where_sql(),
+            String::from(" AND ((lastname > ?) OR (lastname = ? AND id < ?))")
+        );
+        assert_eq!(
+            pagination.get_sorts_sql(),
+            String::from(" ORDER BY lastname ASC, id DESC")
+        );
+
+        // Cursor generated with other sorts
+        assert!(keyset_pagination("+lastname", &cursor.encode("secret").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_keyset_pagination_paginate() {
+        let value = |row: &String, _field: &str| Some(FilterValue::Text(row.clone()));
+        let rows = || vec![String::from("a"), String::from("b"), String::from("c")];
+
+        // First page with more results
+        let pagination = keyset_pagination("+id", "").unwrap();
+        let page = pagination.paginate(rows(), value, "secret").unwrap();
+        assert_eq!(page.data, vec![String::from("a"), String::from("b")]);
+        assert!(page.prev_cursor.is_none());
+        let next_cursor = Cursor::decode(&page.next_cursor.unwrap(), "secret").unwrap();
+        assert_eq!(next_cursor.direction, CursorDirection::Next);
+        assert_eq!(next_cursor.values, vec![FilterValue::Text(String::from("b"))]);
+
+        // Last page
+        let pagination = keyset_pagination("+id", &next_cursor.encode("secret").unwrap()).unwrap();
+        let page = pagination.paginate(vec![String::from("c")], value, "secret").unwrap();
+        assert_eq!(page.data, vec![String::from("c")]);
+        assert!(page.next_cursor.is_none());
+        let prev_cursor = Cursor::decode(&page.prev_cursor.unwrap(), "secret").unwrap();
+        assert_eq!(prev_cursor.direction, CursorDirection::Prev);
+        assert_eq!(prev_cursor.values, vec![FilterValue::Text(String::from("c"))]);
+
+        // Previous page: rows are read in reverse order
+        let pagination = keyset_pagination("+id", &prev_cursor.encode("secret").unwrap()).unwrap();
+        let page = pagination
+            .paginate(vec![String::from("b"), String::from("a")], value, "secret")
+            .unwrap();
+        assert_eq!(page.data, vec![String::from("a"), String::from("b")]);
+        assert!(page.prev_cursor.is_none());
+        assert!(page.next_cursor.is_some());
+    }
+
+    #[test]
+    fn test_keyset_pagination_apply() {
+        let value = |item: &(&str, &str), field: &str| match field {
+            "lastname" => Some(FilterValue::Text(item.0.to_owned())),
+            "id" => Some(FilterValue::Text(item.1.to_owned())),
+            _ => None,
+        };
+        let items = || vec![("Doe", "1"), ("Smith", "2"), ("Doe", "3"), ("Adams", "4")];
+
+        // First page: one more item is kept to know if there is another page
+        let pagination = keyset_pagination("-lastname", "").unwrap();
+        assert_eq!(
+            pagination.apply(items(), value),
+            vec![("Smith", "2"), ("Doe", "1"), ("Doe", "3")]
+        );
+
+        // Next page
+        let cursor = Cursor {
+            direction: CursorDirection::Next,
+            sorts: String::from("-lastname,+id"),
+            values: vec![
+                FilterValue::Text(String::from("Doe")),
+                FilterValue::Text(String::from("1")),
+            ],
+        };
+        let pagination = keyset_pagination("-lastname", &cursor.encode("secret").unwrap()).unwrap();
+        assert_eq!(pagination.apply(items(), value), vec![("Doe", "3"), ("Adams", "4")]);
+
+        // Previous page: items are read in reverse order
+        let cursor = Cursor {
+            direction: CursorDirection::Prev,
+            ..cursor
+        };
+        let pagination = keyset_pagination("-lastname", &cursor.encode("secret").unwrap()).unwrap();
+        assert_eq!(pagination.apply(items(), value), vec![("Smith", "2")]);
+    }
 }
@@ -0,0 +1,161 @@
+//! TOTP module (RFC 6238)
+//!
+//! Codes are compatible with authenticator applications: HMAC-SHA1, 6 digits and a 30 seconds period.
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+/// Secret length (in byte)
+const SECRET_LENGTH: usize = 20;
+
+/// Number of digits of a code
+const DIGITS: u32 = 6;
+
+/// Validity period of a code (in second)
+const PERIOD: i64 = 30;
+
+/// Number of previous and next periods accepted to tolerate clock drift
+const SKEW: i64 = 1;
+
+/// TOTP generator and verifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Totp {
+    secret: Vec<u8>,
+}
+
+impl Totp {
+    /// Create a TOTP with a new random secret
+    pub fn generate() -> Self {
+        let mut secret = vec![0; SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        Self { secret }
+    }
+
+    /// Create a TOTP from a base32 encoded secret
+    pub fn from_base32(secret: &str) -> Option<Self> {
+        BASE32_NOPAD
+            .decode(secret.trim_end_matches('=').as_bytes())
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| Self { secret })
+    }
+
+    /// Base32 encoded secret (to store it or to enter it manually in an application)
+    pub fn secret(&self) -> String {
+        BASE32_NOPAD.encode(&self.secret)
+    }
+
+    /// URI used to generate the QR code scanned by authenticator applications
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        let label = urlencoding(&format!("{issuer}:{account}"));
+
+        format!(
+            "otpauth://totp/{label}?secret={}&issuer={}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}",
+            self.secret(),
+            urlencoding(issuer)
+        )
+    }
+
+    /// Time step of a timestamp (in second)
+    pub fn step(timestamp: i64) -> i64 {
+        timestamp.div_euclid(PERIOD)
+    }
+
+    /// Code of a time step
+    pub fn code(&self, step: i64) -> String {
+        // The key length is not limited with HMAC
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret).expect("HMAC can take key of any size");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        // Dynamic truncation
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary =
+            u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+
+        format!("{:0width$}", binary % 10_u32.pow(DIGITS), width = DIGITS as usize)
+    }
+
+    /// Verify a code at a timestamp (in second) and returns the matching time step
+    pub fn verify(&self, code: &str, timestamp: i64) -> Option<i64> {
+        let code = code.trim();
+        let current_step = Self::step(timestamp);
+
+        (current_step - SKEW..=current_step + SKEW)
+            .find(|step| constant_time_eq(self.code(*step).as_bytes(), code.as_bytes()))
+    }
+}
+
+/// Compare two byte slices in a constant time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Percent-encode a URI component
+fn urlencoding(value: &str) -> String {
+    serde_urlencoded::to_string([("", value)])
+        .unwrap_or_default()
+        .trim_start_matches('=')
+        .replace('+', "%20")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 SHA1 secret
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_totp_code_rfc_6238() {
+        let totp = Totp {
+            secret: RFC_SECRET.to_vec(),
+        };
+
+        // RFC test vectors truncated to 6 digits
+        assert_eq!(totp.code(Totp::step(59)), "287082");
+        assert_eq!(totp.code(Totp::step(1111111109)), "081804");
+        assert_eq!(totp.code(Totp::step(1111111111)), "050471");
+        assert_eq!(totp.code(Totp::step(1234567890)), "005924");
+        assert_eq!(totp.code(Totp::step(2000000000)), "279037");
+    }
+
+    #[test]
+    fn test_totp_verify() {
+        let totp = Totp::generate();
+        let now = 1_700_000_000;
+        let code = totp.code(Totp::step(now));
+
+        assert_eq!(totp.verify(&code, now), Some(Totp::step(now)));
+        assert_eq!(totp.verify(&code, now + PERIOD), Some(Totp::step(now)));
+        assert_eq!(totp.verify(&code, now - PERIOD), Some(Totp::step(now)));
+        assert_eq!(totp.verify(&code, now + 3 * PERIOD), None);
+        assert_eq!(totp.verify("", now), None);
+    }
+
+    #[test]
+    fn test_totp_secret() {
+        let totp = Totp::generate();
+        assert_eq!(totp.secret().len(), 32);
+        assert_eq!(Totp::from_base32(&totp.secret()), Some(totp));
+        assert_eq!(Totp::from_base32("invalid secret"), None);
+        assert_eq!(Totp::from_base32(""), None);
+    }
+
+    #[test]
+    fn test_totp_provisioning_uri() {
+        let totp = Totp {
+            secret: RFC_SECRET.to_vec(),
+        };
+
+        assert_eq!(
+            totp.provisioning_uri("My App", "john@test.com"),
+            String::from(
+                "otpauth://totp/My%20App%3Ajohn%40test.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=My%20App&algorithm=SHA1&digits=6&period=30"
+            )
+        );
+    }
+}
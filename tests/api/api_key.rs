@@ -0,0 +1,329 @@
+use super::helpers::{
+    api_key::{
+        create_api_key, create_user_api_key, delete_api_key, delete_user_api_key, get_api_key, get_api_keys,
+        get_user_api_keys, update_api_key, update_user_api_key,
+    },
+    role::revoke_role,
+    user::{create_and_authenticate, create_and_authenticate_with_role, create_and_authenticate_with_roles},
+    TestResponse,
+};
+use crate::helper::{TestApp, TestAppBuilder};
+use axum::http::StatusCode;
+use axum_boilerplate::models::{
+    api_key::{ApiKey, ApiKeyCreated},
+    user::{LoginResponse, Role},
+};
+use uuid::Uuid;
+
+/// Create an API key and return it
+async fn create(app: &TestApp, token: &str, body: serde_json::Value) -> ApiKeyCreated {
+    let response = create_api_key(app, token, body.to_string()).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    serde_json::from_str(&response.body.to_string()).expect("error when deserializing API key")
+}
+
+/// Get the users with an API key
+async fn get_users(app: &TestApp, key: &str) -> TestResponse {
+    TestResponse::with_api_key(app, "/api/v1/users", "GET", None, key).await
+}
+
+#[tokio::test]
+async fn test_api_key_authentication() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    let created = create(&app, &token, serde_json::json!({ "name": "CI" })).await;
+    assert!(created.key.starts_with(&format!("ak_{}_", created.api_key.prefix)));
+    assert_eq!(created.api_key.roles, Role::Admin.to_string());
+
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // The key is not returned again, only its prefix
+    let response = get_api_keys(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert!(response.body[0].get("key").is_none());
+    assert!(response.body[0].get("key_hash").is_none());
+    let api_keys: Vec<ApiKey> = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(api_keys.len(), 1);
+    assert!(api_keys[0].last_used_at.is_some());
+
+    // Unknown key
+    let response = get_users(&app, &format!("ak_{}_{}", "a".repeat(8), "b".repeat(40))).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // A key cannot manage keys
+    let response = TestResponse::with_api_key(&app, "/api/v1/api-keys", "GET", None, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let response = delete_api_key(&app, &token, &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_key_authentication_in_memory() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    let created = create(&app, &token, serde_json::json!({ "name": "CI" })).await;
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = get_api_key(&app, &token, &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let api_key: ApiKey = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert!(api_key.last_used_at.is_some());
+
+    let response = delete_api_key(&app, &token, &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_key_scopes() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) =
+        create_and_authenticate_with_roles(&app, "john.doe@test.com", &[Role::Admin, Role::User]).await;
+
+    // Roles
+    let created = create(
+        &app,
+        &token,
+        serde_json::json!({ "name": "Read only", "roles": "USER" }),
+    )
+    .await;
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    // Routes
+    let created = create(
+        &app,
+        &token,
+        serde_json::json!({ "name": "Users", "routes": ["/api/v1/users"] }),
+    )
+    .await;
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = TestResponse::with_api_key(&app, "/api/v1/logout", "POST", None, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    // Update
+    let response = update_api_key(
+        &app,
+        &token,
+        &created.api_key.id,
+        serde_json::json!({ "name": "Users", "roles": "USER", "rate_limit": 10 }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let api_key: ApiKey = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(api_key.roles, Role::User.to_string());
+    assert!(api_key.routes.is_none());
+
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_key_creation_rules() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (response, admin_token) = create_and_authenticate(&app).await;
+    let admin: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    let (response, token) = create_and_authenticate_with_role(&app, "user@test.com", Role::User).await;
+    let user: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    // Roles are a subset of the owner roles
+    let response = create_api_key(
+        &app,
+        &token,
+        serde_json::json!({ "name": "Admin", "roles": "ADMIN" }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    // Only users with the `api-keys:manage` permission can create keys for other users
+    let response = create_user_api_key(
+        &app,
+        &token,
+        &admin.id,
+        serde_json::json!({ "name": "Admin" }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    let response = create_user_api_key(
+        &app,
+        &admin_token,
+        &user.id,
+        serde_json::json!({ "name": "Service" }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let created: ApiKeyCreated = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(created.api_key.user_id, user.id);
+    assert_eq!(created.api_key.roles, Role::User.to_string());
+
+    // Expiration date in the past
+    let response = create_api_key(
+        &app,
+        &token,
+        serde_json::json!({ "name": "Expired", "expired_at": "2020-01-01T00:00:00Z" }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    // Keys of other users are not visible
+    let response = get_api_key(&app, &token, &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let other = create(&app, &admin_token, serde_json::json!({ "name": "Admin" })).await;
+    let response = get_api_key(&app, &token, &other.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+    let response = get_user_api_keys(&app, &token, &admin.id).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_key_management_of_other_users() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, admin_token) = create_and_authenticate(&app).await;
+    let (response, token) = create_and_authenticate_with_role(&app, "user@test.com", Role::User).await;
+    let user: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    let created = create(&app, &token, serde_json::json!({ "name": "CI" })).await;
+
+    // Owner routes only give access to the keys of the authenticated user
+    let response = get_api_key(&app, &admin_token, &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = get_user_api_keys(&app, &admin_token, &user.id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let api_keys: Vec<ApiKey> = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(api_keys.len(), 1);
+
+    let response = update_user_api_key(
+        &app,
+        &admin_token,
+        &user.id,
+        &created.api_key.id,
+        serde_json::json!({ "name": "Renamed", "rate_limit": 20 }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let api_key: ApiKey = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(api_key.name, "Renamed");
+    assert_eq!(api_key.rate_limit, 20);
+
+    // The key must belong to the user of the path
+    let response = delete_user_api_key(&app, &admin_token, &Uuid::new_v4().to_string(), &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = delete_user_api_key(&app, &admin_token, &user.id, &created.api_key.id).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_api_key_rate_limit_rules() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, admin_token) = create_and_authenticate(&app).await;
+    let (response, token) = create_and_authenticate_with_role(&app, "user@test.com", Role::User).await;
+    let user: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    // Unlimited or above the owner rate limit (30)
+    for rate_limit in [-1, 31] {
+        let response = create_api_key(
+            &app,
+            &token,
+            serde_json::json!({ "name": "CI", "rate_limit": rate_limit }).to_string(),
+        )
+        .await;
+        assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    let created = create(&app, &token, serde_json::json!({ "name": "CI", "rate_limit": 30 })).await;
+    for rate_limit in [-1, 31] {
+        let response = update_api_key(
+            &app,
+            &token,
+            &created.api_key.id,
+            serde_json::json!({ "name": "CI", "rate_limit": rate_limit }).to_string(),
+        )
+        .await;
+        assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    // With the `api-keys:manage` permission, bounded by the rate limit of the manager (30)
+    for rate_limit in [-1, 31] {
+        let response = create_user_api_key(
+            &app,
+            &admin_token,
+            &user.id,
+            serde_json::json!({ "name": "Service", "rate_limit": rate_limit }).to_string(),
+        )
+        .await;
+        assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+        let response = update_user_api_key(
+            &app,
+            &admin_token,
+            &user.id,
+            &created.api_key.id,
+            serde_json::json!({ "name": "Service", "rate_limit": rate_limit }).to_string(),
+        )
+        .await;
+        assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    let response = create_user_api_key(
+        &app,
+        &admin_token,
+        &user.id,
+        serde_json::json!({ "name": "Service", "rate_limit": 30 }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let created: ApiKeyCreated = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(created.api_key.rate_limit, 30);
+}
+
+#[tokio::test]
+async fn test_api_key_roles_follow_owner_roles() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, admin_token) = create_and_authenticate(&app).await;
+    let (response, token) =
+        create_and_authenticate_with_roles(&app, "owner@test.com", &[Role::Admin, Role::User]).await;
+    let owner: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    let created = create(&app, &token, serde_json::json!({ "name": "CI" })).await;
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // The owner is demoted: the key loses the revoked role
+    let response = revoke_role(&app, &admin_token, &owner.id, &Role::Admin.to_string()).await;
+    assert!(response.status_code.is_success());
+
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_key_rate_limit() {
+    let app: TestApp = TestAppBuilder::with_rate_limiter().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    let created = create(&app, &token, serde_json::json!({ "name": "CI", "rate_limit": 2 })).await;
+
+    for _ in 0..2 {
+        assert_eq!(get_users(&app, &created.key).await.status_code, StatusCode::OK);
+    }
+
+    let response = get_users(&app, &created.key).await;
+    assert_eq!(response.status_code, StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers.contains_key("retry-after"));
+}
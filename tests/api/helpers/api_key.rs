@@ -0,0 +1,77 @@
+//! Helpers for API keys tests
+
+use super::TestResponse;
+use crate::helper::TestApp;
+
+/// Create an API key
+pub async fn create_api_key(app: &TestApp, token: &str, body: String) -> TestResponse {
+    TestResponse::new(app, "/api/v1/api-keys", "POST", Some(body), Some(token)).await
+}
+
+/// Return the API keys of the user
+pub async fn get_api_keys(app: &TestApp, token: &str) -> TestResponse {
+    TestResponse::new(app, "/api/v1/api-keys", "GET", None, Some(token)).await
+}
+
+/// Return an API key
+pub async fn get_api_key(app: &TestApp, token: &str, id: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/api-keys/{id}"), "GET", None, Some(token)).await
+}
+
+/// Update an API key
+pub async fn update_api_key(app: &TestApp, token: &str, id: &str, body: String) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/api-keys/{id}"), "PUT", Some(body), Some(token)).await
+}
+
+/// Delete an API key
+pub async fn delete_api_key(app: &TestApp, token: &str, id: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/api-keys/{id}"), "DELETE", None, Some(token)).await
+}
+
+/// Create an API key of a user
+pub async fn create_user_api_key(app: &TestApp, token: &str, user_id: &str, body: String) -> TestResponse {
+    TestResponse::new(
+        app,
+        &format!("/api/v1/users/{user_id}/api-keys"),
+        "POST",
+        Some(body),
+        Some(token),
+    )
+    .await
+}
+
+/// Return the API keys of a user
+pub async fn get_user_api_keys(app: &TestApp, token: &str, user_id: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        &format!("/api/v1/users/{user_id}/api-keys"),
+        "GET",
+        None,
+        Some(token),
+    )
+    .await
+}
+
+/// Update an API key of a user
+pub async fn update_user_api_key(app: &TestApp, token: &str, user_id: &str, id: &str, body: String) -> TestResponse {
+    TestResponse::new(
+        app,
+        &format!("/api/v1/users/{user_id}/api-keys/{id}"),
+        "PUT",
+        Some(body),
+        Some(token),
+    )
+    .await
+}
+
+/// Delete an API key of a user
+pub async fn delete_user_api_key(app: &TestApp, token: &str, user_id: &str, id: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        &format!("/api/v1/users/{user_id}/api-keys/{id}"),
+        "DELETE",
+        None,
+        Some(token),
+    )
+    .await
+}
@@ -1,3 +1,6 @@
+pub mod api_key;
+pub mod oidc;
+pub mod role;
 pub mod user;
 
 use crate::helper::TestApp;
@@ -22,12 +25,29 @@ pub struct TestResponse {
 impl TestResponse {
     /// Create a new `TestResponse`
     pub async fn new(app: &TestApp, url: &str, method: &str, body: Option<String>, token: Option<&str>) -> Self {
+        let headers = token.map(|token| ("Authorization", format!("Bearer {token}")));
+        Self::send(app, url, method, body, headers).await
+    }
+
+    /// Create a new `TestResponse` for a request authenticated with an API key
+    #[allow(unused)]
+    pub async fn with_api_key(app: &TestApp, url: &str, method: &str, body: Option<String>, api_key: &str) -> Self {
+        Self::send(app, url, method, body, Some(("X-API-Key", api_key.to_owned()))).await
+    }
+
+    async fn send(
+        app: &TestApp,
+        url: &str,
+        method: &str,
+        body: Option<String>,
+        header: Option<(&str, String)>,
+    ) -> Self {
         let mut request = Request::builder()
             .uri(url)
             .method(method)
             .header("Content-Type", "application/json");
-        if let Some(token) = token {
-            request = request.header("Authorization", format!("Bearer {token}"));
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
         }
 
         let request = request.body(match body {
@@ -38,6 +58,11 @@ impl TestResponse {
         let response = app.router.clone().oneshot(request.unwrap()).await.unwrap();
 
         let status_code = response.status();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_str().unwrap_or_default().to_owned()))
+            .collect();
         let body = response
             .into_body()
             .collect()
@@ -49,7 +74,7 @@ impl TestResponse {
         TestResponse {
             status_code,
             body,
-            headers: HashMap::new(),
+            headers,
         }
     }
 }
@@ -67,3 +92,11 @@ pub struct TestPaginateResponse<T> {
     pub data: T,
     pub total: i64,
 }
+
+#[derive(Deserialize)]
+pub struct TestCursorPaginateResponse<T> {
+    pub data: T,
+    pub total: Option<i64>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
@@ -0,0 +1,25 @@
+//! Helpers for OpenID Connect login tests
+
+use super::TestResponse;
+use crate::helper::{TestApp, TestIdentityProvider, TestIdpUser};
+use axum::http::StatusCode;
+
+/// Start an OIDC login and return the URL of the provider login page
+pub async fn authorize(app: &TestApp) -> String {
+    let response = TestResponse::new(app, "/api/v1/oidc/authorize", "GET", None, None).await;
+    assert_eq!(response.status_code, StatusCode::SEE_OTHER);
+
+    response.headers.get("location").expect("no redirection").to_owned()
+}
+
+/// Provider redirection to the application
+pub async fn callback(app: &TestApp, query: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/oidc/callback?{query}"), "GET", None, None).await
+}
+
+/// Full OIDC login of a user of the provider
+pub async fn oidc_login(app: &TestApp, provider: &TestIdentityProvider, user: TestIdpUser) -> TestResponse {
+    let (code, state) = provider.authorize(&authorize(app).await, user);
+
+    callback(app, &format!("code={code}&state={state}")).await
+}
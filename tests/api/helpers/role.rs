@@ -0,0 +1,53 @@
+//! Helpers for roles tests
+
+use super::TestResponse;
+use crate::helper::TestApp;
+
+/// Return all the roles
+pub async fn get_roles(app: &TestApp, token: &str) -> TestResponse {
+    TestResponse::new(app, "/api/v1/roles", "GET", None, Some(token)).await
+}
+
+/// Create a custom role
+pub async fn create_role(app: &TestApp, token: &str, body: String) -> TestResponse {
+    TestResponse::new(app, "/api/v1/roles", "POST", Some(body), Some(token)).await
+}
+
+/// Return a role
+pub async fn get_role(app: &TestApp, token: &str, name: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/roles/{name}"), "GET", None, Some(token)).await
+}
+
+/// Update the permissions of a custom role
+pub async fn update_role(app: &TestApp, token: &str, name: &str, body: String) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/roles/{name}"), "PUT", Some(body), Some(token)).await
+}
+
+/// Delete a custom role
+pub async fn delete_role(app: &TestApp, token: &str, name: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/roles/{name}"), "DELETE", None, Some(token)).await
+}
+
+/// Grant a role to a user
+pub async fn grant_role(app: &TestApp, token: &str, id: &str, role: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        &format!("/api/v1/users/{id}/roles/{role}"),
+        "PUT",
+        None,
+        Some(token),
+    )
+    .await
+}
+
+/// Revoke a role of a user
+pub async fn revoke_role(app: &TestApp, token: &str, id: &str, role: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        &format!("/api/v1/users/{id}/roles/{role}"),
+        "DELETE",
+        None,
+        Some(token),
+    )
+    .await
+}
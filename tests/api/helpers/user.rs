@@ -1,13 +1,21 @@
 //! Helpers for user API tests
 
 use super::TestResponse;
-use crate::helper::{TestApp, TestDatabase};
+use crate::helper::TestApp;
 use axum_boilerplate::{
-    models::user::{LoginResponse, Role, User},
-    repositories::user::{PasswordResetRepository, UserRepository},
+    config::Config,
+    database::query,
+    emails::{transport::MemoryTransport, worker::EmailWorker, Message},
+    models::{
+        email_outbox::{EmailOutboxStatus, OutboxEmail},
+        user::{LoginResponse, PasswordReset, Role, User},
+    },
+    utils::{password::PasswordHasher, totp::Totp},
 };
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use sha2::{Digest, Sha512};
+use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Deserialize, Debug)]
@@ -16,7 +24,7 @@ pub struct TestUser {
     pub lastname: String,
     pub firstname: String,
     pub username: String,
-    pub roles: Option<String>,
+    pub roles: Vec<Role>,
     pub rate_limit: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -28,36 +36,30 @@ impl TestUser {
     }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct TestPasswordReset {
-    pub token: String,
-    pub expired_at: DateTime<Utc>,
-}
-
-impl TestPasswordReset {
-    pub fn from_body(body: &str) -> Self {
-        serde_json::from_str(body).expect("error when deserializing password reset body")
-    }
+/// Create a user for authentication
+pub async fn create_user(app: &TestApp, username: &str, role: Role) -> User {
+    create_user_with_roles(app, username, &[role]).await
 }
 
-/// Create a user for authentication
-async fn create_user(db: &TestDatabase) -> User {
+/// Create a user with several roles for authentication
+pub async fn create_user_with_roles(app: &TestApp, username: &str, roles: &[Role]) -> User {
     let password = String::from("00000000");
     let mut user = User {
         id: Uuid::new_v4().to_string(),
         lastname: String::from("Doe"),
         firstname: String::from("John"),
-        username: String::from("john.doe@test.com"),
+        username: username.to_string(),
         password: password.clone(),
-        roles: Some(Role::User.to_string()),
+        roles: Role::from_names(roles.iter().map(Role::to_string)),
         rate_limit: 30,
         created_at: Utc::now(),
         updated_at: Utc::now(),
         deleted_at: None,
     };
 
-    let pool = db.database().await;
-    UserRepository::create(&pool, &mut user)
+    app.stores()
+        .users
+        .create(&PasswordHasher::default(), &mut user)
         .await
         .expect("error during user creation");
 
@@ -66,10 +68,48 @@ async fn create_user(db: &TestDatabase) -> User {
     user
 }
 
+/// Create a user whose password is stored with the legacy unsalted SHA-512 hash
+pub async fn create_user_with_legacy_password(app: &TestApp, username: &str, password: &str) {
+    let user = create_user(app, username, Role::User).await;
+
+    // The hash is replaced as is, like the reset of a password
+    let (password_reset, token) = PasswordReset::new(user.id.clone(), 1);
+    let stores = app.stores();
+    stores
+        .password_resets
+        .create_or_update(&password_reset)
+        .await
+        .expect("error during password reset creation");
+    stores
+        .password_resets
+        .reset_password(
+            &user.id,
+            &PasswordReset::hash(&token),
+            &format!("{:x}", Sha512::digest(password.as_bytes())),
+        )
+        .await
+        .expect("error during legacy user creation");
+}
+
+/// Return the password hash stored in database for a user
+pub async fn get_password_hash(app: &TestApp, username: &str) -> String {
+    app.stores()
+        .users
+        .get_by_email(username.to_string())
+        .await
+        .expect("error when getting user")
+        .expect("user not found")
+        .password
+}
+
 /// Is password reset token already in database?
-pub async fn is_password_reset_token_still_in_database(db: &TestDatabase, token: &str) -> bool {
-    let pool = db.database().await;
-    if let Ok(result) = PasswordResetRepository::get_user_id_from_token(&pool, token.to_string()).await {
+pub async fn is_password_reset_token_still_in_database(app: &TestApp, token: &str) -> bool {
+    if let Ok(result) = app
+        .stores()
+        .password_resets
+        .get_user_id_from_hash(&PasswordReset::hash(token))
+        .await
+    {
         return match result {
             Some(_) => true,
             None => false,
@@ -78,10 +118,112 @@ pub async fn is_password_reset_token_still_in_database(db: &TestDatabase, token:
     false
 }
 
-/// Create, authenticate a user and return `TestResponse` and the generated JWT
+/// Is a user locked?
+pub async fn is_locked(app: &TestApp, username: &str) -> bool {
+    let stores = app.stores();
+    let user = stores
+        .users
+        .get_by_email(username.to_string())
+        .await
+        .expect("error when getting user")
+        .expect("user not found");
+
+    stores
+        .users
+        .get_locked_until(&user.id)
+        .await
+        .expect("error when getting lockout")
+        .is_some()
+}
+
+/// Mark the email of a user as not verified (registered user)
+pub async fn set_email_unverified(app: &TestApp, username: &str) {
+    let pool = app.database().database().await;
+    query("UPDATE users SET email_verified_at = NULL WHERE username = ?")
+        .bind(username)
+        .execute(&pool)
+        .await
+        .expect("error when updating email verification");
+}
+
+/// Return the email verification token of a sent email
+pub fn get_verification_token(email: &Message) -> Option<String> {
+    get_link_token(email, "http://localhost/verify-email/")
+}
+
+/// Return the email change token of a sent email
+pub fn get_email_change_token(email: &Message) -> Option<String> {
+    get_link_token(email, "http://localhost/verify-email-change/")
+}
+
+/// Return the password reset token of a sent email
+pub fn get_password_reset_token(email: &Message) -> Option<String> {
+    if email.subject != "[Axum Boilerplate] Forgotten password" {
+        return None;
+    }
+
+    get_link_token(email, "http://localhost/")
+}
+
+/// Return the unlock token of a sent account locked email
+pub fn get_unlock_token(email: &Message) -> Option<String> {
+    if !email.subject.contains("locked") {
+        return None;
+    }
+
+    get_link_token(email, "http://localhost/")
+}
+
+fn get_link_token(email: &Message, base_url: &str) -> Option<String> {
+    let (_, token) = email.text_body.split_once(base_url)?;
+
+    Some(
+        token
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect(),
+    )
+}
+
+/// Get emails waiting in the outbox
+pub async fn get_pending_emails(app: &TestApp) -> Vec<OutboxEmail> {
+    app.stores()
+        .email_outbox
+        .get_by_status(EmailOutboxStatus::Pending)
+        .await
+        .expect("error when getting outbox emails")
+}
+
+/// Send emails waiting in the outbox with an in-memory transport and return them
+pub async fn send_pending_emails(app: &TestApp) -> Vec<Message> {
+    let transport = MemoryTransport::new();
+    let worker = EmailWorker::new(
+        app.stores().email_outbox.clone(),
+        Arc::new(transport.clone()),
+        &Config::default(),
+    );
+    while worker.process().await.expect("error when sending outbox emails") > 0 {}
+
+    transport.emails()
+}
+
+/// Create, authenticate an administrator and return `TestResponse` and the generated JWT
 pub async fn create_and_authenticate(app: &TestApp) -> (TestResponse, String) {
-    let db = app.database();
-    let user = create_user(db).await;
+    create_and_authenticate_with_role(app, "john.doe@test.com", Role::Admin).await
+}
+
+/// Create, authenticate a user with a specific role and return `TestResponse` and the generated JWT
+pub async fn create_and_authenticate_with_role(app: &TestApp, username: &str, role: Role) -> (TestResponse, String) {
+    create_and_authenticate_with_roles(app, username, &[role]).await
+}
+
+/// Create, authenticate a user with several roles and return `TestResponse` and the generated JWT
+pub async fn create_and_authenticate_with_roles(
+    app: &TestApp,
+    username: &str,
+    roles: &[Role],
+) -> (TestResponse, String) {
+    let user = create_user_with_roles(app, username, roles).await;
     let response = login_request(
         &app,
         serde_json::json!({
@@ -102,6 +244,24 @@ pub async fn login_request(app: &TestApp, body: String) -> TestResponse {
     TestResponse::new(app, "/api/v1/login", "POST", Some(body), None).await
 }
 
+/// Refresh token request helper
+pub async fn refresh_token_request(app: &TestApp, refresh_token: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        "/api/v1/token/refresh",
+        "POST",
+        Some(serde_json::json!({ "refresh_token": refresh_token }).to_string()),
+        None,
+    )
+    .await
+}
+
+/// Logout request helper
+pub async fn logout_request(app: &TestApp, token: &str, refresh_token: Option<&str>) -> TestResponse {
+    let body = refresh_token.map(|refresh_token| serde_json::json!({ "refresh_token": refresh_token }).to_string());
+    TestResponse::new(app, "/api/v1/logout", "POST", body, Some(token)).await
+}
+
 /// User creation request helper
 pub async fn create_user_request(app: &TestApp, body: String, token: &str) -> TestResponse {
     TestResponse::new(app, "/api/v1/users", "POST", Some(body), Some(token)).await
@@ -112,6 +272,16 @@ pub async fn get_all(app: &TestApp, token: &str) -> TestResponse {
     TestResponse::new(app, "/api/v1/users", "GET", None, Some(token)).await
 }
 
+/// Return users matching filters
+pub async fn get_all_filtered(app: &TestApp, token: &str, filter: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/users?f={filter}"), "GET", None, Some(token)).await
+}
+
+/// Return users with cursor pagination
+pub async fn get_all_by_cursor(app: &TestApp, token: &str, query: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/users?{query}"), "GET", None, Some(token)).await
+}
+
 /// Return a user
 pub async fn get_one(app: &TestApp, token: &str, id: &str) -> TestResponse {
     TestResponse::new(app, &format!("/api/v1/users/{id}"), "GET", None, Some(token)).await
@@ -143,3 +313,101 @@ pub async fn update_password(app: &TestApp, token: &str, body: String) -> TestRe
     )
     .await
 }
+
+/// Unlock a user (administrator)
+pub async fn unlock(app: &TestApp, token: &str, id: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/users/{id}/unlock"), "POST", None, Some(token)).await
+}
+
+/// Unlock an account with the token sent by email
+pub async fn unlock_account(app: &TestApp, token: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/unlock/{token}"), "POST", None, None).await
+}
+
+/// Register a new user
+pub async fn register(app: &TestApp, body: String) -> TestResponse {
+    TestResponse::new(app, "/api/v1/register", "POST", Some(body), None).await
+}
+
+/// Verify an email with the token sent by email
+pub async fn verify_email(app: &TestApp, token: &str, method: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/verify-email/{token}"), method, None, None).await
+}
+
+/// Get the authenticated user
+pub async fn get_me(app: &TestApp, token: &str) -> TestResponse {
+    TestResponse::new(app, "/api/v1/me", "GET", None, Some(token)).await
+}
+
+/// Update the profile of the authenticated user
+pub async fn update_me(app: &TestApp, token: &str, body: String) -> TestResponse {
+    TestResponse::new(app, "/api/v1/me", "PATCH", Some(body), Some(token)).await
+}
+
+/// Change the password of the authenticated user
+pub async fn change_password(app: &TestApp, token: &str, body: String) -> TestResponse {
+    TestResponse::new(app, "/api/v1/me/password", "POST", Some(body), Some(token)).await
+}
+
+/// Change the email of the authenticated user
+pub async fn change_email(app: &TestApp, token: &str, body: String) -> TestResponse {
+    TestResponse::new(app, "/api/v1/me/email", "POST", Some(body), Some(token)).await
+}
+
+/// Confirm an email change with the token sent by email
+pub async fn verify_email_change(app: &TestApp, token: &str) -> TestResponse {
+    TestResponse::new(app, &format!("/api/v1/verify-email-change/{token}"), "POST", None, None).await
+}
+
+/// TOTP code of a secret, `offset` periods after the current one
+pub fn totp_code(secret: &str, offset: i64) -> String {
+    let totp = Totp::from_base32(secret).expect("invalid TOTP secret");
+    totp.code(Totp::step(Utc::now().timestamp()) + offset)
+}
+
+/// Second login step helper
+pub async fn login_mfa_request(app: &TestApp, mfa_token: &str, code: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        "/api/v1/login/mfa",
+        "POST",
+        Some(serde_json::json!({ "mfa_token": mfa_token, "code": code }).to_string()),
+        None,
+    )
+    .await
+}
+
+/// TOTP enrollment during the login helper
+pub async fn login_mfa_enroll_request(app: &TestApp, mfa_token: &str) -> TestResponse {
+    TestResponse::new(
+        app,
+        "/api/v1/login/mfa/enroll",
+        "POST",
+        Some(serde_json::json!({ "mfa_token": mfa_token }).to_string()),
+        None,
+    )
+    .await
+}
+
+/// Start a TOTP enrollment
+pub async fn enroll_totp(app: &TestApp, token: &str) -> TestResponse {
+    TestResponse::new(app, "/api/v1/mfa/totp", "POST", None, Some(token)).await
+}
+
+/// Confirm a TOTP enrollment
+pub async fn confirm_totp(app: &TestApp, token: &str, code: &str) -> TestResponse {
+    let body = serde_json::json!({ "code": code }).to_string();
+    TestResponse::new(app, "/api/v1/mfa/totp/confirm", "POST", Some(body), Some(token)).await
+}
+
+/// Disable TOTP
+pub async fn disable_totp(app: &TestApp, token: &str, code: &str) -> TestResponse {
+    let body = serde_json::json!({ "code": code }).to_string();
+    TestResponse::new(app, "/api/v1/mfa/totp", "DELETE", Some(body), Some(token)).await
+}
+
+/// Generate new recovery codes
+pub async fn regenerate_recovery_codes(app: &TestApp, token: &str, code: &str) -> TestResponse {
+    let body = serde_json::json!({ "code": code }).to_string();
+    TestResponse::new(app, "/api/v1/mfa/recovery-codes", "POST", Some(body), Some(token)).await
+}
@@ -0,0 +1,153 @@
+use super::helpers::user::{
+    change_email, change_password, create_and_authenticate_with_role, create_user, get_email_change_token, get_me,
+    get_pending_emails, login_request, send_pending_emails, update_me, verify_email_change,
+};
+use crate::helper::{TestApp, TestAppBuilder};
+use axum::http::StatusCode;
+use axum_boilerplate::models::user::{LoginResponse, Role};
+
+const NEW_PASSWORD: &str = "Wl6,Ak4;6a";
+
+fn credentials(username: &str, password: &str) -> String {
+    serde_json::json!({
+        "username": username,
+        "password": password
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_api_me_get() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "john.doe@example.com", Role::User).await;
+
+    let response = get_me(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["username"], "john.doe@example.com");
+    assert!(response.body.get("password").is_none());
+
+    let response = get_me(&app, "invalid").await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_me_update() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "john.doe@example.com", Role::User).await;
+
+    let response = update_me(&app, &token, serde_json::json!({ "firstname": "Johnny" }).to_string()).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["firstname"], "Johnny");
+    assert_eq!(response.body["lastname"], "Doe");
+    assert_eq!(response.body["roles"], serde_json::json!(["USER"]));
+
+    let response = update_me(&app, &token, serde_json::json!({ "lastname": "" }).to_string()).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_me_change_password() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "john.doe@example.com", Role::User).await;
+    let body = |current_password: &str, new_password: &str| {
+        serde_json::json!({
+            "current_password": current_password,
+            "new_password": new_password
+        })
+        .to_string()
+    };
+
+    let response = change_password(&app, &token, body("11111111", NEW_PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = change_password(&app, &token, body("00000000", "azertyuiop")).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = change_password(&app, &token, body("00000000", NEW_PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    // Tokens are revoked
+    let response = get_me(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let sent_emails = send_pending_emails(&app).await;
+    assert!(sent_emails
+        .iter()
+        .any(|email| email.to_list == vec![String::from("john.doe@example.com")]));
+
+    let response = login_request(&app, credentials("john.doe@example.com", "00000000")).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // A token issued right after the revocation is valid
+    let response = login_request(&app, credentials("john.doe@example.com", NEW_PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    let response = get_me(&app, &login.token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_me_change_email() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "john.doe@example.com", Role::User).await;
+    let body = |email: &str, password: &str| {
+        serde_json::json!({
+            "email": email,
+            "password": password
+        })
+        .to_string()
+    };
+
+    let response = change_email(&app, &token, body("johnny@example.com", "11111111")).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = change_email(&app, &token, body("john.doe@example.com", "00000000")).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = change_email(&app, &token, body("johnny@example.com", "00000000")).await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+
+    // The link is sent to the new email
+    let sent_emails = send_pending_emails(&app).await;
+    assert_eq!(sent_emails.len(), 1);
+    assert_eq!(sent_emails[0].to_list, vec![String::from("johnny@example.com")]);
+    let change_token = get_email_change_token(&sent_emails[0]).expect("no email change link");
+
+    // The email is not changed before the verification
+    let response = get_me(&app, &token).await;
+    assert_eq!(response.body["username"], "john.doe@example.com");
+
+    let response = verify_email_change(&app, &change_token).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = get_me(&app, &token).await;
+    assert_eq!(response.body["username"], "johnny@example.com");
+
+    let response = login_request(&app, credentials("johnny@example.com", "00000000")).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // Token can be used only once
+    let response = verify_email_change(&app, &change_token).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_me_change_email_already_used() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "john.doe@example.com", Role::User).await;
+    create_user(&app, "jane.doe@example.com", Role::User).await;
+
+    // Same answer as for an unused email, but nothing is sent
+    let response = change_email(
+        &app,
+        &token,
+        serde_json::json!({
+            "email": "jane.doe@example.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    assert!(get_pending_emails(&app).await.is_empty());
+}
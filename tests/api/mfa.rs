@@ -0,0 +1,194 @@
+use super::helpers::user::{
+    confirm_totp, create_and_authenticate_with_role, create_user, disable_totp, enroll_totp, login_mfa_enroll_request,
+    login_mfa_request, login_request, regenerate_recovery_codes, totp_code,
+};
+use crate::helper::{TestApp, TestAppBuilder};
+use axum::http::StatusCode;
+use axum_boilerplate::models::{
+    mfa::{MfaChallengeResponse, RecoveryCodes, TotpEnrollment},
+    user::{LoginResponse, Role},
+};
+
+/// Enroll TOTP for an authenticated user and return the secret and the recovery codes
+async fn enroll_and_confirm(app: &TestApp, token: &str) -> (String, Vec<String>) {
+    let response = enroll_totp(app, token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let enrollment: TotpEnrollment = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    let response = confirm_totp(app, token, &totp_code(&enrollment.secret, 0)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let recovery_codes: RecoveryCodes = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    (enrollment.secret, recovery_codes.recovery_codes)
+}
+
+/// First login step expecting a MFA challenge
+async fn login_challenge(app: &TestApp, username: &str) -> MfaChallengeResponse {
+    let response = login_request(
+        app,
+        serde_json::json!({
+            "username": username,
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    serde_json::from_str(&response.body.to_string()).expect("a MFA challenge was expected")
+}
+
+#[tokio::test]
+async fn test_api_mfa_totp_enrollment_and_login() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "mfa@test.com", Role::User).await;
+
+    let response = enroll_totp(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let enrollment: TotpEnrollment = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert!(enrollment.provisioning_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment.provisioning_uri.contains(&enrollment.secret));
+
+    // Wrong confirmation code
+    let response = confirm_totp(&app, &token, &totp_code(&enrollment.secret, 5)).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = confirm_totp(&app, &token, &totp_code(&enrollment.secret, 0)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let recovery_codes: RecoveryCodes = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(recovery_codes.recovery_codes.len(), 10);
+
+    // Enrollment cannot be restarted once confirmed
+    let response = enroll_totp(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let challenge = login_challenge(&app, "mfa@test.com").await;
+    assert!(!challenge.enrollment_required);
+
+    // The confirmation code cannot be replayed
+    let response = login_mfa_request(&app, &challenge.mfa_token, &totp_code(&enrollment.secret, 0)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let response = login_mfa_request(&app, &challenge.mfa_token, &totp_code(&enrollment.secret, 1)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(login.username, String::from("mfa@test.com"));
+    assert!(login.recovery_codes.is_none());
+
+    // The challenge can only be used once
+    let response = login_mfa_request(&app, &challenge.mfa_token, &totp_code(&enrollment.secret, 1)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_mfa_recovery_code_used_once() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "mfa@test.com", Role::User).await;
+    let (_secret, recovery_codes) = enroll_and_confirm(&app, &token).await;
+
+    let challenge = login_challenge(&app, "mfa@test.com").await;
+    let response = login_mfa_request(&app, &challenge.mfa_token, &recovery_codes[0]).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let challenge = login_challenge(&app, "mfa@test.com").await;
+    let response = login_mfa_request(&app, &challenge.mfa_token, &recovery_codes[0]).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // Recovery codes are case insensitive
+    let response = login_mfa_request(&app, &challenge.mfa_token, &recovery_codes[1].to_uppercase()).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_mfa_regenerate_recovery_codes() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "mfa@test.com", Role::User).await;
+    let (secret, recovery_codes) = enroll_and_confirm(&app, &token).await;
+
+    let response = regenerate_recovery_codes(&app, &token, &totp_code(&secret, 1)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let new_recovery_codes: RecoveryCodes = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(new_recovery_codes.recovery_codes.len(), 10);
+
+    // Old codes are no longer valid
+    let challenge = login_challenge(&app, "mfa@test.com").await;
+    let response = login_mfa_request(&app, &challenge.mfa_token, &recovery_codes[0]).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let response = login_mfa_request(&app, &challenge.mfa_token, &new_recovery_codes.recovery_codes[0]).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_mfa_challenge_max_attempts() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "mfa@test.com", Role::User).await;
+    let (secret, _recovery_codes) = enroll_and_confirm(&app, &token).await;
+
+    let challenge = login_challenge(&app, "mfa@test.com").await;
+    for _ in 0..5 {
+        let response = login_mfa_request(&app, &challenge.mfa_token, &totp_code(&secret, 5)).await;
+        assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+    }
+
+    // The challenge is deleted after 5 wrong codes
+    let response = login_mfa_request(&app, &challenge.mfa_token, &totp_code(&secret, 1)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_mfa_disable() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "mfa@test.com", Role::User).await;
+    let (secret, _recovery_codes) = enroll_and_confirm(&app, &token).await;
+
+    let response = disable_totp(&app, &token, &totp_code(&secret, 5)).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = disable_totp(&app, &token, &totp_code(&secret, 1)).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    // Login with the password only
+    let response = login_request(
+        &app,
+        serde_json::json!({
+            "username": "mfa@test.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: Result<LoginResponse, _> = serde_json::from_str(&response.body.to_string());
+    assert!(login.is_ok());
+}
+
+#[tokio::test]
+async fn test_api_mfa_required_for_admin() {
+    let app: TestApp = TestAppBuilder::with_admin_mfa().await.build();
+    create_user(&app, "admin@test.com", Role::Admin).await;
+
+    let challenge = login_challenge(&app, "admin@test.com").await;
+    assert!(challenge.enrollment_required);
+
+    // A code is needed to finish the login
+    let response = login_mfa_request(&app, &challenge.mfa_token, "123456").await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = login_mfa_enroll_request(&app, &challenge.mfa_token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let enrollment: TotpEnrollment = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    let response = login_mfa_request(&app, &challenge.mfa_token, &totp_code(&enrollment.secret, 0)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(login.recovery_codes.map(|codes| codes.len()), Some(10));
+
+    // Administrators cannot disable TOTP
+    let response = disable_totp(&app, &login.token, &totp_code(&enrollment.secret, 1)).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    // Next logins use TOTP
+    let challenge = login_challenge(&app, "admin@test.com").await;
+    assert!(!challenge.enrollment_required);
+}
@@ -1,2 +1,8 @@
+mod api_key;
 mod helpers;
+mod me;
+mod mfa;
+mod oidc;
+mod registration;
+mod role;
 mod user;
@@ -0,0 +1,126 @@
+use super::helpers::{
+    oidc::{authorize, callback, oidc_login},
+    user::{create_user, get_all, set_email_unverified},
+    TestResponse,
+};
+use crate::helper::{TestApp, TestAppBuilder, TestIdentityProvider, TestIdpUser};
+use axum::http::StatusCode;
+use axum_boilerplate::models::user::{LoginResponse, Role};
+
+fn idp_user(subject: &str, email: &str, email_verified: bool) -> TestIdpUser {
+    TestIdpUser {
+        subject: subject.to_owned(),
+        email: email.to_owned(),
+        email_verified,
+    }
+}
+
+#[tokio::test]
+async fn test_oidc_login_disabled() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+
+    let response = TestResponse::new(&app, "/api/v1/oidc/authorize", "GET", None, None).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_oidc_login_links_verified_email() {
+    let provider = TestIdentityProvider::start().await;
+    let app: TestApp = TestAppBuilder::with_oidc(&provider).await.build();
+    let user = create_user(&app, "john.doe@test.com", Role::Admin).await;
+
+    let response = oidc_login(&app, &provider, idp_user("idp-123", "john.doe@test.com", true)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(login.id, user.id);
+
+    // The token is issued by the application
+    let response = get_all(&app, &login.token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // The identity is linked: the email of the provider can change
+    let response = oidc_login(&app, &provider, idp_user("idp-123", "john@other.com", false)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(login.id, user.id);
+
+    // Another identity of the same provider cannot be linked to the user
+    let response = oidc_login(&app, &provider, idp_user("idp-456", "john.doe@test.com", true)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_oidc_login_in_memory() {
+    let provider = TestIdentityProvider::start().await;
+    let app: TestApp = TestAppBuilder::in_memory_with_oidc(&provider).await.build();
+    let user = create_user(&app, "john.doe@test.com", Role::Admin).await;
+
+    let response = oidc_login(&app, &provider, idp_user("idp-123", "john.doe@test.com", true)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+    assert_eq!(login.id, user.id);
+
+    // The linked identity is found again
+    let response = oidc_login(&app, &provider, idp_user("idp-123", "john@other.com", false)).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = oidc_login(&app, &provider, idp_user("idp-456", "john.doe@test.com", true)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_oidc_login_rejects_unknown_users() {
+    let provider = TestIdentityProvider::start().await;
+    let app: TestApp = TestAppBuilder::with_oidc(&provider).await.build();
+    create_user(&app, "john.doe@test.com", Role::User).await;
+
+    // Unverified email
+    let response = oidc_login(&app, &provider, idp_user("idp-123", "john.doe@test.com", false)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // No account with this email
+    let response = oidc_login(&app, &provider, idp_user("idp-456", "jane.doe@test.com", true)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_oidc_login_does_not_link_unverified_account() {
+    let provider = TestIdentityProvider::start().await;
+    let app: TestApp = TestAppBuilder::with_oidc(&provider).await.build();
+    create_user(&app, "john.doe@test.com", Role::User).await;
+    set_email_unverified(&app, "john.doe@test.com").await;
+
+    // The account may have been registered by someone else than the owner of the email
+    let response = oidc_login(&app, &provider, idp_user("idp-123", "john.doe@test.com", true)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_oidc_callback_state() {
+    let provider = TestIdentityProvider::start().await;
+    let app: TestApp = TestAppBuilder::with_oidc(&provider).await.build();
+    create_user(&app, "john.doe@test.com", Role::User).await;
+
+    let (code, state) = provider.authorize(&authorize(&app).await, idp_user("idp-123", "john.doe@test.com", true));
+
+    // Unknown state
+    let response = callback(&app, &format!("code={code}&state=unknown")).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let response = callback(&app, &format!("code={code}&state={state}")).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // A state cannot be used twice
+    let response = callback(&app, &format!("code={code}&state={state}")).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // Login refused by the user
+    let (_code, state) = provider.authorize(&authorize(&app).await, idp_user("idp-123", "john.doe@test.com", true));
+    let response = callback(&app, &format!("error=access_denied&state={state}")).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // Unknown code
+    let (_code, state) = provider.authorize(&authorize(&app).await, idp_user("idp-123", "john.doe@test.com", true));
+    let response = callback(&app, &format!("code=unknown&state={state}")).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
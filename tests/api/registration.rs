@@ -0,0 +1,110 @@
+use super::helpers::user::{
+    create_user, get_pending_emails, get_verification_token, login_request, register, send_pending_emails, verify_email,
+};
+use crate::helper::{TestApp, TestAppBuilder};
+use axum::http::StatusCode;
+use axum_boilerplate::models::user::Role;
+
+const PASSWORD: &str = "Wl6,Ak4;6a";
+
+fn registration(username: &str, password: &str) -> String {
+    serde_json::json!({
+        "lastname": "Doe",
+        "firstname": "Jane",
+        "username": username,
+        "password": password
+    })
+    .to_string()
+}
+
+fn credentials(username: &str) -> String {
+    serde_json::json!({
+        "username": username,
+        "password": PASSWORD
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_api_register_disabled() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+
+    let response = register(&app, registration("jane.doe@example.com", PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_register_and_verify_email() {
+    let app: TestApp = TestAppBuilder::with_registration().await.build();
+
+    let response = register(&app, registration("jane.doe@example.com", PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+
+    // Login is refused until the email is verified
+    let response = login_request(&app, credentials("jane.doe@example.com")).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    let sent_emails = send_pending_emails(&app).await;
+    assert_eq!(sent_emails.len(), 1);
+    assert_eq!(sent_emails[0].to_list, vec![String::from("jane.doe@example.com")]);
+    let token = get_verification_token(&sent_emails[0]).expect("no verification link");
+
+    let response = verify_email(&app, "unknown", "GET").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = verify_email(&app, &token, "GET").await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    // Welcome email
+    let sent_emails = send_pending_emails(&app).await;
+    assert!(sent_emails.iter().any(|email| email.subject.contains("Welcome")));
+
+    let response = login_request(&app, credentials("jane.doe@example.com")).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER"]));
+
+    // Token can be used only once
+    let response = verify_email(&app, &token, "POST").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_register_invalid_data() {
+    let app: TestApp = TestAppBuilder::with_registration().await.build();
+
+    let response = register(&app, registration("jane.doe", PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = register(&app, registration("jane.doe@yopmail.com", PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = register(&app, registration("jane.doe@example.com", "azertyuiop")).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    assert!(get_pending_emails(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_api_register_existing_email() {
+    let app: TestApp = TestAppBuilder::with_registration().await.build();
+    create_user(&app, "john.doe@example.com", Role::User).await;
+
+    // Same answer as for a new email, but nothing is sent for a verified account
+    let response = register(&app, registration("john.doe@example.com", PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    assert!(get_pending_emails(&app).await.is_empty());
+
+    // The verification link is sent again for a registered account, the previous one is invalidated
+    register(&app, registration("jane.doe@example.com", PASSWORD)).await;
+    let first_token = get_verification_token(&send_pending_emails(&app).await[0]).unwrap();
+
+    let response = register(&app, registration("jane.doe@example.com", PASSWORD)).await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    let second_token = get_verification_token(&send_pending_emails(&app).await[0]).unwrap();
+
+    let response = verify_email(&app, &first_token, "POST").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = verify_email(&app, &second_token, "POST").await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+}
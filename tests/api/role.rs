@@ -0,0 +1,301 @@
+use super::helpers::{
+    role::{create_role, delete_role, get_role, get_roles, grant_role, revoke_role, update_role},
+    user::{
+        create_and_authenticate, create_and_authenticate_with_role, create_and_authenticate_with_roles, create_user,
+        create_user_request, delete, get_all, get_all_filtered, get_me, login_request, update,
+    },
+};
+use crate::helper::{TestApp, TestAppBuilder};
+use axum::http::StatusCode;
+use axum_boilerplate::models::user::{LoginResponse, Role};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_api_role_list() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    let response = get_roles(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        response.body,
+        serde_json::json!([
+            { "name": "USER", "permissions": [] },
+            { "name": "MANAGER", "permissions": ["users:read"] },
+            {
+                "name": "ADMIN",
+                "permissions": ["users:read", "users:write", "users:delete", "roles:manage", "api-keys:manage"],
+            },
+        ])
+    );
+
+    let (_response, token) = create_and_authenticate_with_role(&app, "user@test.com", Role::User).await;
+    let response = get_roles(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_role_grant_and_revoke() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+    let user = create_user(&app, "user@test.com", Role::User).await;
+    let credentials = serde_json::json!({ "username": user.username, "password": user.password }).to_string();
+
+    // Grant
+    let response = grant_role(&app, &token, &user.id, "MANAGER").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER", "MANAGER"]));
+
+    let response = grant_role(&app, &token, &user.id, "MANAGER").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER", "MANAGER"]));
+
+    let response = get_all_filtered(&app, &token, "role:eq:MANAGER").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["total"], 1);
+    assert_eq!(response.body["data"][0]["id"], user.id);
+
+    // The roles of the JWT are an array
+    let response = login_request(&app, credentials.clone()).await;
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+    assert_eq!(login.roles, vec![Role::User, Role::Manager]);
+
+    // Revoke (the tokens of the user are revoked)
+    let response = revoke_role(&app, &token, &user.id, "MANAGER").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER"]));
+
+    let response = get_me(&app, &login.token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let response = get_all_filtered(&app, &token, "role:eq:MANAGER").await;
+    assert_eq!(response.body["total"], 0);
+
+    let response = revoke_role(&app, &token, &user.id, "MANAGER").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER"]));
+}
+
+#[tokio::test]
+async fn test_api_role_grant_and_revoke_errors() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (response, token) = create_and_authenticate(&app).await;
+    let admin: LoginResponse = serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+    let user = create_user(&app, "user@test.com", Role::User).await;
+
+    let response = grant_role(&app, &token, &user.id, "UNKNOWN").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = grant_role(&app, &token, &Uuid::new_v4().to_string(), "ADMIN").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    // Administrators cannot revoke their own ADMIN role
+    let response = revoke_role(&app, &token, &admin.id, "ADMIN").await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    // Only administrators can manage roles
+    let (_response, user_token) = create_and_authenticate_with_role(&app, "manager@test.com", Role::Manager).await;
+    let response = grant_role(&app, &user_token, &user.id, "ADMIN").await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_user_creation_with_unknown_role() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+    let body = |roles: serde_json::Value| {
+        serde_json::json!({
+            "username": "new-user@test.com",
+            "password": "00000000",
+            "lastname": "Doe",
+            "firstname": "John",
+            "roles": roles,
+            "rate_limit": 10,
+        })
+        .to_string()
+    };
+
+    let response = create_user_request(&app, body(serde_json::json!(["USER", "SUPERADMIN"])), &token).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = create_user_request(&app, body(serde_json::json!(["USER", "super-admin"])), &token).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = create_user_request(&app, body(serde_json::json!(["MANAGER", "USER"])), &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER", "MANAGER"]));
+}
+
+#[tokio::test]
+async fn test_api_role_custom_role_management() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+    let body = |name: &str, permissions: serde_json::Value| {
+        serde_json::json!({ "name": name, "permissions": permissions }).to_string()
+    };
+
+    // Creation
+    let response = create_role(
+        &app,
+        &token,
+        body("SUPPORT", serde_json::json!(["users:write", "users:read"])),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::CREATED);
+    assert_eq!(
+        response.body,
+        serde_json::json!({ "name": "SUPPORT", "permissions": ["users:read", "users:write"] })
+    );
+
+    let response = create_role(&app, &token, body("SUPPORT", serde_json::json!([]))).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = create_role(&app, &token, body("ADMIN", serde_json::json!([]))).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = create_role(&app, &token, body("AUDIT", serde_json::json!(["users:all"]))).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = get_roles(&app, &token).await;
+    assert_eq!(response.body.as_array().map(Vec::len), Some(4));
+    assert_eq!(response.body[3]["name"], "SUPPORT");
+
+    // Update
+    let permissions = serde_json::json!({ "permissions": ["users:read"] }).to_string();
+    let response = update_role(&app, &token, "SUPPORT", permissions.clone()).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = get_role(&app, &token, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["permissions"], serde_json::json!(["users:read"]));
+
+    let response = update_role(&app, &token, "AUDIT", permissions.clone()).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    // Built-in roles cannot be changed
+    let response = update_role(&app, &token, "MANAGER", permissions).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = delete_role(&app, &token, "USER").await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    // A granted role cannot be deleted
+    let user = create_user(&app, "user@test.com", Role::User).await;
+    let response = grant_role(&app, &token, &user.id, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["USER", "SUPPORT"]));
+
+    let response = delete_role(&app, &token, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    let response = revoke_role(&app, &token, &user.id, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = delete_role(&app, &token, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = get_role(&app, &token, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = grant_role(&app, &token, &user.id, "SUPPORT").await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_role_permissions() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, admin_token) = create_and_authenticate(&app).await;
+    let role = serde_json::json!({ "name": "SUPPORT", "permissions": ["users:read", "users:write"] }).to_string();
+    let response = create_role(&app, &admin_token, role).await;
+    assert_eq!(response.status_code, StatusCode::CREATED);
+
+    // Permissions are resolved at login
+    let support = Role::Custom(String::from("SUPPORT"));
+    let (_response, token) = create_and_authenticate_with_roles(&app, "support@test.com", &[support]).await;
+    let user = create_user(&app, "user@test.com", Role::User).await;
+
+    let response = get_all(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = delete(&app, &token, &user.id).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    let response = get_roles(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    // Changing the roles of a user requires the `roles:manage` permission
+    let body = |roles: serde_json::Value| {
+        serde_json::json!({
+            "username": user.username,
+            "password": user.password,
+            "lastname": "Doe",
+            "firstname": "Jane",
+            "roles": roles,
+            "rate_limit": 10,
+        })
+        .to_string()
+    };
+    let response = update(&app, body(serde_json::json!(["USER"])), &token, &user.id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["firstname"], "Jane");
+
+    let response = update(&app, body(serde_json::json!(["ADMIN"])), &token, &user.id).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    let response = update(&app, body(serde_json::json!(["ADMIN"])), &admin_token, &user.id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body["roles"], serde_json::json!(["ADMIN"]));
+
+    // Current tokens keep their permissions until they expire
+    let permissions = serde_json::json!({ "permissions": [] }).to_string();
+    let response = update_role(&app, &admin_token, "SUPPORT", permissions).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = get_all(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = login_request(
+        &app,
+        serde_json::json!({ "username": "support@test.com", "password": "00000000" }).to_string(),
+    )
+    .await;
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+    let response = get_all(&app, &login.token).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_api_role_cannot_update_more_privileged_user() {
+    let app: TestApp = TestAppBuilder::new().await.build();
+    let (_response, admin_token) = create_and_authenticate(&app).await;
+    let role = serde_json::json!({ "name": "SUPPORT", "permissions": ["users:read", "users:write"] }).to_string();
+    let response = create_role(&app, &admin_token, role).await;
+    assert_eq!(response.status_code, StatusCode::CREATED);
+
+    let support = Role::Custom(String::from("SUPPORT"));
+    let (_response, token) = create_and_authenticate_with_roles(&app, "support@test.com", &[support]).await;
+    let admin = create_user(&app, "admin@test.com", Role::Admin).await;
+
+    // The password of an administrator cannot be changed without all its permissions
+    let body = serde_json::json!({
+        "username": admin.username,
+        "password": "11111111",
+        "lastname": "Doe",
+        "firstname": "John",
+        "roles": ["ADMIN"],
+        "rate_limit": 10,
+    })
+    .to_string();
+    let response = update(&app, body.clone(), &token, &admin.id).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+
+    let response = login_request(
+        &app,
+        serde_json::json!({ "username": admin.username, "password": admin.password }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = update(&app, body, &admin_token, &admin.id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
@@ -1,17 +1,29 @@
 use super::helpers::user::{
-    create_and_authenticate, create_user_request, delete, forgotten_password, get_all, get_one,
-    is_password_reset_token_still_in_database, login_request, update, update_password, TestPasswordReset, TestUser,
+    create_and_authenticate, create_and_authenticate_with_role, create_user_request, create_user_with_legacy_password,
+    delete, forgotten_password, get_all, get_all_by_cursor, get_all_filtered, get_one, get_password_hash,
+    get_password_reset_token, get_pending_emails, get_unlock_token, is_locked,
+    is_password_reset_token_still_in_database, login_request, logout_request, refresh_token_request,
+    send_pending_emails, unlock, unlock_account, update, update_password, TestUser,
 };
 use crate::{
-    api::helpers::TestPaginateResponse,
+    api::helpers::{TestCursorPaginateResponse, TestPaginateResponse},
     helper::{TestApp, TestAppBuilder},
 };
 use axum::http::StatusCode;
+use axum_boilerplate::{
+    handlers::users::FORGOTTEN_PASSWORD_MIN_RESPONSE_TIME,
+    models::{
+        login_attempt::LoginAttempt,
+        user::{LoginResponse, Role},
+    },
+};
+use chrono::{Duration, Utc};
+use std::time::Instant;
 use uuid::Uuid;
 
 #[tokio::test]
 async fn test_api_login_unauthorized_user() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
 
     let response = login_request(
         &app,
@@ -35,15 +47,137 @@ async fn test_api_login_unauthorized_user() {
 
 #[tokio::test]
 async fn test_api_login_authorized_user() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (response, _token) = create_and_authenticate(&app).await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_login_upgrades_legacy_password_hash() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    create_user_with_legacy_password(&app, "legacy@test.com", "00000000").await;
+
+    let response = login_request(
+        &app,
+        serde_json::json!({
+            "username": "legacy@test.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert!(get_password_hash(&app, "legacy@test.com")
+        .await
+        .starts_with("$argon2id$"));
+
+    // Login again with the upgraded hash
+    let response = login_request(
+        &app,
+        serde_json::json!({
+            "username": "legacy@test.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_refresh_token_rotation() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (response, _token) = create_and_authenticate(&app).await;
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+
+    // First use: a new refresh token is returned
+    let response = refresh_token_request(&app, &login.refresh_token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let refreshed: LoginResponse =
+        serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+    assert_ne!(refreshed.refresh_token, login.refresh_token);
+    assert_eq!(refreshed.id, login.id);
+
+    // New access token is valid
+    let response = get_all(&app, &refreshed.token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // Rotated refresh token is still usable once
+    let response = refresh_token_request(&app, &refreshed.refresh_token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_refresh_token_reuse_revokes_family() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (response, _token) = create_and_authenticate(&app).await;
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
 
+    let response = refresh_token_request(&app, &login.refresh_token).await;
     assert_eq!(response.status_code, StatusCode::OK);
+    let refreshed: LoginResponse =
+        serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+
+    // Reuse of an already used refresh token
+    let response = refresh_token_request(&app, &login.refresh_token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // The whole family has been revoked
+    let response = refresh_token_request(&app, &refreshed.refresh_token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_refresh_token_invalid() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+
+    let response = refresh_token_request(&app, &"a".repeat(64)).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    let response = refresh_token_request(&app, "invalid").await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_logout_revokes_tokens() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (response, token) = create_and_authenticate(&app).await;
+    let login: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    let response = get_all(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = logout_request(&app, &token, Some(&login.refresh_token)).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    // Access token is revoked
+    let response = get_all(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // Refresh token is revoked too
+    let response = refresh_token_request(&app, &login.refresh_token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_logout_without_refresh_token() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    let response = logout_request(&app, &token, None).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = logout_request(&app, &token, None).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
 }
 
 #[tokio::test]
 async fn test_api_user_creation_success() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     let response = create_user_request(
@@ -65,7 +199,7 @@ async fn test_api_user_creation_success() {
 
 #[tokio::test]
 async fn test_api_user_creation_invalid_password() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     let response = create_user_request(
@@ -85,9 +219,70 @@ async fn test_api_user_creation_invalid_password() {
     assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
 }
 
+#[tokio::test]
+async fn test_api_user_creation_forbidden_for_user_role() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate_with_role(&app, "user@test.com", Role::User).await;
+
+    let response = create_user_request(
+        &app,
+        serde_json::json!({
+            "username": "test-user-creation@test.com",
+            "password": "00000000",
+            "lastname": "Test",
+            "firstname": "Toto",
+            "rate_limit": 10,
+        })
+        .to_string(),
+        &token,
+    )
+    .await;
+
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.body,
+        serde_json::json!({
+            "code": 403,
+            "message": "Forbidden"
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_api_user_manager_role_can_only_read() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, admin_token) = create_and_authenticate(&app).await;
+    let (_response, token) = create_and_authenticate_with_role(&app, "manager@test.com", Role::Manager).await;
+
+    let response = get_all(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // Create a user
+    let response = create_user_request(
+        &app,
+        serde_json::json!({
+            "username": "test-user-creation@test.com",
+            "password": "00000000",
+            "lastname": "Test",
+            "firstname": "Toto",
+            "rate_limit": 10,
+        })
+        .to_string(),
+        &admin_token,
+    )
+    .await;
+    let user_id = TestUser::from_body(&response.body.to_string()).id;
+
+    let response = get_one(&app, &token, &user_id).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = delete(&app, &token, &user_id).await;
+    assert_eq!(response.status_code, StatusCode::FORBIDDEN);
+}
+
 #[tokio::test]
 async fn test_api_user_list_all() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create 2 users
@@ -117,9 +312,158 @@ async fn test_api_user_list_all() {
     assert_eq!(users.total, 3);
 }
 
+#[tokio::test]
+async fn test_api_user_list_filtered() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    // Create 2 users
+    for i in 1..3 {
+        create_user_request(
+            &app,
+            serde_json::json!({
+                "username": format!("test-user-filter-{i}@test.com"),
+                "password": "00000000",
+                "lastname": "Filter",
+                "firstname": format!("Toto {i}"),
+                "roles": ["USER"],
+                "rate_limit": i * 10,
+            })
+            .to_string(),
+            &token,
+        )
+        .await;
+    }
+
+    let cases = [
+        ("lastname:eq:Filter", 2),
+        ("lastname:like:ilt", 2),
+        ("lastname:eq:Filter,rate_limit:gt:10", 1),
+        ("role:eq:USER", 2),
+        ("role:ne:USER", 1),
+        ("created_at:gte:2000-01-01", 3),
+        ("created_at:lt:2000-01-01", 0),
+    ];
+    for (filter, expected) in cases {
+        let response = get_all_filtered(&app, &token, filter).await;
+        assert_eq!(response.status_code, StatusCode::OK, "filter: {filter}");
+
+        let users: TestPaginateResponse<Vec<TestUser>> =
+            serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+        assert_eq!(users.data.len(), expected, "filter: {filter}");
+        assert_eq!(users.total, expected as i64, "filter: {filter}");
+    }
+}
+
+#[tokio::test]
+async fn test_api_user_list_invalid_filter() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    for filter in ["password:eq:00000000", "lastname:gt:Doe", "rate_limit:eq:ten"] {
+        let response = get_all_filtered(&app, &token, filter).await;
+        assert_eq!(response.status_code, StatusCode::BAD_REQUEST, "filter: {filter}");
+    }
+}
+
+#[tokio::test]
+async fn test_api_user_list_by_cursor() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    // Create 4 users (5 with the authenticated one)
+    for i in 1..5 {
+        create_user_request(
+            &app,
+            serde_json::json!({
+                "username": format!("test-user-cursor-{i}@test.com"),
+                "password": "00000000",
+                "lastname": format!("Cursor {i}"),
+                "firstname": "Toto",
+                "rate_limit": 10,
+            })
+            .to_string(),
+            &token,
+        )
+        .await;
+    }
+
+    let get_page = |query: String| {
+        let app = &app;
+        let token = &token;
+        async move {
+            let response = get_all_by_cursor(app, token, &query).await;
+            assert_eq!(response.status_code, StatusCode::OK);
+
+            let page: TestCursorPaginateResponse<Vec<TestUser>> =
+                serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+            page
+        }
+    };
+    let lastnames = |page: &TestCursorPaginateResponse<Vec<TestUser>>| {
+        page.data.iter().map(|u| u.lastname.clone()).collect::<Vec<String>>()
+    };
+
+    // First page
+    let page = get_page(String::from("cursor=&l=2&s=-lastname")).await;
+    assert_eq!(lastnames(&page), vec!["Doe", "Cursor 4"]);
+    assert_eq!(page.total, Some(5));
+    assert!(page.prev_cursor.is_none());
+
+    // Next pages
+    let page = get_page(format!("cursor={}&l=2&s=-lastname", page.next_cursor.unwrap())).await;
+    assert_eq!(lastnames(&page), vec!["Cursor 3", "Cursor 2"]);
+    let page = get_page(format!(
+        "cursor={}&l=2&s=-lastname&total=false",
+        page.next_cursor.unwrap()
+    ))
+    .await;
+    assert_eq!(lastnames(&page), vec!["Cursor 1"]);
+    assert!(page.total.is_none());
+    assert!(page.next_cursor.is_none());
+
+    // Previous page
+    let page = get_page(format!("cursor={}&l=2&s=-lastname", page.prev_cursor.unwrap())).await;
+    assert_eq!(lastnames(&page), vec!["Cursor 3", "Cursor 2"]);
+    assert!(page.next_cursor.is_some());
+    let page = get_page(format!("cursor={}&l=2&s=-lastname", page.prev_cursor.unwrap())).await;
+    assert_eq!(lastnames(&page), vec!["Doe", "Cursor 4"]);
+    assert!(page.prev_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_api_user_list_by_cursor_invalid() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+
+    let response = get_all_by_cursor(&app, &token, "cursor=invalid").await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+
+    // A cursor cannot be used with other sorts
+    create_user_request(
+        &app,
+        serde_json::json!({
+            "username": "test-user-cursor@test.com",
+            "password": "00000000",
+            "lastname": "Cursor",
+            "firstname": "Toto",
+            "rate_limit": 10,
+        })
+        .to_string(),
+        &token,
+    )
+    .await;
+    let response = get_all_by_cursor(&app, &token, "cursor=&l=1&s=%2Blastname").await;
+    let page: TestCursorPaginateResponse<Vec<TestUser>> =
+        serde_json::from_str(&response.body.to_string()).expect("error when deserializing body");
+    let next_cursor = page.next_cursor.expect("next cursor");
+    let response = get_all_by_cursor(&app, &token, &format!("cursor={next_cursor}&l=1&s=-lastname")).await;
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_api_user_list_one() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -149,7 +493,7 @@ async fn test_api_user_list_one() {
 
 #[tokio::test]
 async fn test_api_user_get_one_bad_parameter() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -178,7 +522,7 @@ async fn test_api_user_get_one_bad_parameter() {
 
 #[tokio::test]
 async fn test_api_user_delete() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -205,9 +549,30 @@ async fn test_api_user_delete() {
     assert_eq!(response.status_code, StatusCode::NO_CONTENT);
 }
 
+#[tokio::test]
+async fn test_api_user_delete_revokes_user_tokens() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+    let (response, manager_token) = create_and_authenticate_with_role(&app, "manager@test.com", Role::Manager).await;
+    let manager: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    let response = get_all(&app, &manager_token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    let response = delete(&app, &token, &manager.id).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = get_all(&app, &manager_token).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // Other users tokens are still valid
+    let response = get_all(&app, &token).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
 #[tokio::test]
 async fn test_api_user_update() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -255,7 +620,7 @@ async fn test_api_user_update() {
 
 #[tokio::test]
 async fn test_api_user_forgotten_password() {
-    let app: TestApp = TestAppBuilder::new().await.with_logger().build();
+    let app: TestApp = TestAppBuilder::in_memory().await.with_logger().build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -273,21 +638,48 @@ async fn test_api_user_forgotten_password() {
     )
     .await;
 
+    // Welcome email
+    let sent_emails = send_pending_emails(&app).await;
+    assert_eq!(sent_emails.len(), 1);
+    assert_eq!(sent_emails[0].subject, "[Axum Boilerplate] Welcome");
+
     let response = forgotten_password(&app, "test-user-creation@test.com").await;
 
-    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    assert!(response.body.is_null());
+
+    // Email is not sent during the request but added in the outbox
+    let emails = get_pending_emails(&app).await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to_list, vec![String::from("test-user-creation@test.com")]);
+    assert_eq!(emails[0].attempts, 0);
 
-    let body = TestPasswordReset::from_body(&response.body.to_string());
-    let uuid = Uuid::parse_str(&body.token).expect("invalid uuid");
-    assert_eq!(uuid.get_version_num(), 4);
+    // Email is sent by the worker
+    let sent_emails = send_pending_emails(&app).await;
+    assert_eq!(sent_emails.len(), 1);
+    assert_eq!(
+        sent_emails[0].to_list,
+        vec![String::from("test-user-creation@test.com")]
+    );
+    assert_eq!(sent_emails[0].subject, "[Axum Boilerplate] Forgotten password");
+    assert!(get_pending_emails(&app).await.is_empty());
+
+    // Only the hash of the token is stored
+    let token = get_password_reset_token(&sent_emails[0]).expect("no token in email");
+    assert_eq!(token.len(), 64);
+    assert!(sent_emails[0].html_body.contains(&token));
+    assert!(is_password_reset_token_still_in_database(&app, &token).await);
 
-    let now = chrono::Utc::now();
-    assert!(body.expired_at > now);
+    // No new email during the cooldown
+    let response = forgotten_password(&app, "test-user-creation@test.com").await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    assert!(get_pending_emails(&app).await.is_empty());
+    assert!(is_password_reset_token_still_in_database(&app, &token).await);
 }
 
 #[tokio::test]
 async fn test_api_user_forgotten_password_email_not_found() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -305,14 +697,22 @@ async fn test_api_user_forgotten_password_email_not_found() {
     )
     .await;
 
+    let started_at = Instant::now();
     let response = forgotten_password(&app, "test-user-creation_1@test.com").await;
 
-    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+    // Same answer and minimum response time as for an existing email
+    assert!(started_at.elapsed() >= FORGOTTEN_PASSWORD_MIN_RESPONSE_TIME);
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    assert!(response.body.is_null());
+    assert!(get_pending_emails(&app)
+        .await
+        .iter()
+        .all(|email| email.to_list != vec![String::from("test-user-creation_1@test.com")]));
 }
 
 #[tokio::test]
 async fn test_api_user_update_password() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -331,8 +731,12 @@ async fn test_api_user_update_password() {
     .await;
 
     // Get a reset password token
-    let response = forgotten_password(&app, "test-user-creation@test.com").await;
-    let token = TestPasswordReset::from_body(&response.body.to_string()).token;
+    forgotten_password(&app, "test-user-creation@test.com").await;
+    let token = send_pending_emails(&app)
+        .await
+        .iter()
+        .find_map(get_password_reset_token)
+        .expect("no password reset email");
 
     let response = update_password(
         &app,
@@ -346,6 +750,12 @@ async fn test_api_user_update_password() {
 
     assert_eq!(response.status_code, StatusCode::OK);
 
+    // Password changed confirmation email
+    let sent_emails = send_pending_emails(&app).await;
+    assert!(sent_emails
+        .iter()
+        .any(|email| email.subject == "[Axum Boilerplate] Your password has been changed"));
+
     // Try to login with new password
     let response = login_request(
         &app,
@@ -360,14 +770,27 @@ async fn test_api_user_update_password() {
     assert_eq!(response.status_code, StatusCode::OK);
 
     // Is token still in database?
-    let still_in_db = is_password_reset_token_still_in_database(app.database(), &token).await;
+    let still_in_db = is_password_reset_token_still_in_database(&app, &token).await;
 
     assert!(!still_in_db);
+
+    // A token is only used once
+    let response = update_password(
+        &app,
+        &token,
+        serde_json::json!({
+            "password": "22222222",
+        })
+        .to_string(),
+    )
+    .await;
+
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
 }
 
 #[tokio::test]
 async fn test_api_user_update_password_with_old_password() {
-    let app: TestApp = TestAppBuilder::new().await.build();
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
     let (_response, token) = create_and_authenticate(&app).await;
 
     // Create a user
@@ -386,8 +809,12 @@ async fn test_api_user_update_password_with_old_password() {
     .await;
 
     // Get a reset password token
-    let response = forgotten_password(&app, "test-user-creation@test.com").await;
-    let token = TestPasswordReset::from_body(&response.body.to_string()).token;
+    forgotten_password(&app, "test-user-creation@test.com").await;
+    let token = send_pending_emails(&app)
+        .await
+        .iter()
+        .find_map(get_password_reset_token)
+        .expect("no password reset email");
 
     let response = update_password(
         &app,
@@ -402,7 +829,214 @@ async fn test_api_user_update_password_with_old_password() {
     assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
 
     // Is token still in database?
-    let still_in_db = is_password_reset_token_still_in_database(app.database(), &token).await;
+    let still_in_db = is_password_reset_token_still_in_database(&app, &token).await;
 
     assert!(still_in_db);
 }
+
+#[tokio::test]
+async fn test_api_login_rate_limit_policy() {
+    let app: TestApp = TestAppBuilder::in_memory_with_rate_limiter().await.build();
+    let body = serde_json::json!({
+        "username": "test@gmail.com",
+        "password": "00000000"
+    })
+    .to_string();
+
+    for _ in 0..5 {
+        let response = login_request(&app, body.clone()).await;
+        assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+    }
+
+    let response = login_request(&app, body).await;
+    assert_eq!(response.status_code, StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers.contains_key("retry-after"));
+
+    // Usernames are case insensitive
+    let response = login_request(
+        &app,
+        serde_json::json!({
+            "username": "Test@Gmail.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::TOO_MANY_REQUESTS);
+
+    // Another username has its own budget
+    let response = login_request(
+        &app,
+        serde_json::json!({
+            "username": "other@gmail.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_forgotten_password_rate_limit_policy() {
+    let app: TestApp = TestAppBuilder::in_memory_with_rate_limiter().await.build();
+
+    for _ in 0..3 {
+        let response = forgotten_password(&app, "unknown@test.com").await;
+        assert_eq!(response.status_code, StatusCode::ACCEPTED);
+    }
+
+    let response = forgotten_password(&app, "unknown@test.com").await;
+    assert_eq!(response.status_code, StatusCode::TOO_MANY_REQUESTS);
+
+    let response = forgotten_password(&app, "other@test.com").await;
+    assert_eq!(response.status_code, StatusCode::ACCEPTED);
+}
+
+/// Login with a wrong password and returns the status code
+async fn failed_login(app: &TestApp, username: &str) -> StatusCode {
+    login_request(
+        app,
+        serde_json::json!({
+            "username": username,
+            "password": "wrong-password"
+        })
+        .to_string(),
+    )
+    .await
+    .status_code
+}
+
+#[tokio::test]
+async fn test_api_login_lockout() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, _token) = create_and_authenticate_with_role(&app, "locked@test.com", Role::User).await;
+    let credentials = serde_json::json!({
+        "username": "locked@test.com",
+        "password": "00000000"
+    })
+    .to_string();
+
+    // The account is locked after 3 failures (see `login_max_failures` in test state)
+    for _ in 0..3 {
+        assert_eq!(failed_login(&app, "locked@test.com").await, StatusCode::UNAUTHORIZED);
+    }
+
+    let response = login_request(&app, credentials.clone()).await;
+    assert_eq!(response.status_code, StatusCode::UNAUTHORIZED);
+
+    // Unlock email
+    let sent_emails = send_pending_emails(&app).await;
+    assert!(sent_emails
+        .iter()
+        .any(|email| email.to_list == vec![String::from("locked@test.com")] && email.subject.contains("locked")));
+
+    assert!(is_locked(&app, "locked@test.com").await);
+    let unlock_token = sent_emails
+        .iter()
+        .find_map(get_unlock_token)
+        .expect("unlock token should be sent");
+
+    let response = unlock_account(&app, &unlock_token).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+    assert!(!is_locked(&app, "locked@test.com").await);
+
+    let response = login_request(&app, credentials).await;
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // Token can be used only once
+    let response = unlock_account(&app, &unlock_token).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_login_lockout_unlock_token_expired() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (response, _token) = create_and_authenticate_with_role(&app, "locked@test.com", Role::User).await;
+    let user: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    for _ in 0..3 {
+        failed_login(&app, "locked@test.com").await;
+    }
+    let unlock_token = send_pending_emails(&app)
+        .await
+        .iter()
+        .find_map(get_unlock_token)
+        .expect("unlock token should be sent");
+
+    // Only the hash of the token is stored
+    let users = &app.stores().users;
+    assert!(users.get_by_unlock_token(&unlock_token).await.unwrap().is_none());
+
+    // The lockout is over
+    let token_hash = LoginAttempt::hash_unlock_token(&unlock_token);
+    assert!(users.get_by_unlock_token(&token_hash).await.unwrap().is_some());
+    users
+        .lock(&user.id, Utc::now() - Duration::seconds(1), &token_hash)
+        .await
+        .unwrap();
+
+    let response = unlock_account(&app, &unlock_token).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_login_lockout_does_not_leak_usernames() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, _token) = create_and_authenticate_with_role(&app, "existing@test.com", Role::User).await;
+
+    for _ in 0..5 {
+        assert_eq!(
+            failed_login(&app, "existing@test.com").await,
+            failed_login(&app, "unknown@test.com").await
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_api_admin_unlock_user() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+    let (_response, token) = create_and_authenticate(&app).await;
+    let (response, _token) = create_and_authenticate_with_role(&app, "locked@test.com", Role::User).await;
+    let user: LoginResponse = serde_json::from_str(&response.body.to_string()).unwrap();
+
+    for _ in 0..3 {
+        failed_login(&app, "locked@test.com").await;
+    }
+    assert!(is_locked(&app, "locked@test.com").await);
+
+    let response = unlock(&app, &token, &Uuid::new_v4().to_string()).await;
+    assert_eq!(response.status_code, StatusCode::NOT_FOUND);
+
+    let response = unlock(&app, &token, &user.id).await;
+    assert_eq!(response.status_code, StatusCode::NO_CONTENT);
+
+    let response = login_request(
+        &app,
+        serde_json::json!({
+            "username": "locked@test.com",
+            "password": "00000000"
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_login_too_many_failures_from_ip() {
+    let app: TestApp = TestAppBuilder::in_memory().await.build();
+
+    // 10 failures by IP address (see `login_ip_max_failures` in test state)
+    for i in 0..10 {
+        assert_eq!(
+            failed_login(&app, &format!("user-{i}@test.com")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    assert_eq!(
+        failed_login(&app, "another-user@test.com").await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
@@ -1,15 +1,45 @@
 //! Test helper for unit tests
 
-use axum::{Extension, Router};
+use axum::{
+    extract::{ConnectInfo, State as AxumState},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Form, Json, Router,
+};
 use axum_boilerplate::{
     config::{logger, Config},
-    layers::{self, ConfigState, MakeRequestUuid, SharedState, State},
+    database::{Database, DatabaseBackend},
+    layers::{
+        self,
+        rate_limiter::{
+            store::MemoryStore, strategy::RateLimitStrategy, RateLimiter, RateLimiterConfig, RateLimiterFailureMode,
+        },
+        ConfigState, MakeRequestUuid, SharedState, State,
+    },
+    repositories::{memory::InMemoryStore, revoked_token::RevokedTokenStore, Stores},
     routes,
+    utils::{
+        jwt::JwtKeys,
+        oidc::{OidcClient, OidcConfig, Pkce},
+        password::PasswordHasher,
+    },
 };
-use jsonwebtoken::{DecodingKey, EncodingKey};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jsonwebtoken::Algorithm;
 use rand::distributions::{Alphanumeric, DistString};
-use sqlx::{mysql::MySqlPoolOptions, Connection, MySql, MySqlConnection, MySqlPool};
+use reqwest::Url;
+use serde::Deserialize;
+use sqlx::{
+    mysql::MySqlPoolOptions, postgres::PgPoolOptions, sqlite::SqliteConnectOptions, Connection, MySql, MySqlConnection,
+    MySqlPool, PgConnection, PgPool, Postgres, SqlitePool,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::net::TcpListener;
 use tower::ServiceBuilder;
 use tower_http::ServiceBuilderExt;
 
@@ -22,33 +52,136 @@ use tower_http::ServiceBuilderExt;
 
 pub struct TestApp {
     pub router: Router,
-    pub database: TestDatabase,
+    pub stores: Stores,
+    pub database: Option<TestDatabase>,
 }
 
 impl TestApp {
+    /// Database of the application (not available with in-memory stores)
     pub fn database(&self) -> &TestDatabase {
-        &self.database
+        self.database.as_ref().expect("the application uses in-memory stores")
+    }
+
+    /// Stores injected in the application state
+    pub fn stores(&self) -> &Stores {
+        &self.stores
     }
 }
 
 pub struct TestAppBuilder {
     router: Router,
-    database: TestDatabase,
+    stores: Stores,
+    database: Option<TestDatabase>,
 }
 
 impl TestAppBuilder {
     pub async fn new() -> Self {
-        let state = Self::get_state();
-        let db = TestDatabase::new().await;
+        Self::init(false, None, false, None, false).await
+    }
+
+    /// Application with in-memory stores, without database
+    #[allow(unused)]
+    pub async fn in_memory() -> Self {
+        Self::init(true, None, false, None, false).await
+    }
+
+    /// Application with the route rate limit policies (in-memory store)
+    #[allow(unused)]
+    pub async fn with_rate_limiter() -> Self {
+        Self::init(false, Some(Self::rate_limiter()), false, None, false).await
+    }
+
+    /// Application with in-memory stores and the route rate limit policies
+    #[allow(unused)]
+    pub async fn in_memory_with_rate_limiter() -> Self {
+        Self::init(true, Some(Self::rate_limiter()), false, None, false).await
+    }
+
+    /// Application requiring two-factor authentication for administrators
+    #[allow(unused)]
+    pub async fn with_admin_mfa() -> Self {
+        Self::init(false, None, true, None, false).await
+    }
+
+    /// Application with OIDC login using a test identity provider
+    #[allow(unused)]
+    pub async fn with_oidc(provider: &TestIdentityProvider) -> Self {
+        Self::init(false, None, false, Some(provider.client()), false).await
+    }
+
+    /// Application with in-memory stores and OIDC login using a test identity provider
+    #[allow(unused)]
+    pub async fn in_memory_with_oidc(provider: &TestIdentityProvider) -> Self {
+        Self::init(true, None, false, Some(provider.client()), false).await
+    }
+
+    /// Application with self-service registration
+    #[allow(unused)]
+    pub async fn with_registration() -> Self {
+        Self::init(false, None, false, None, true).await
+    }
+
+    async fn init(
+        in_memory: bool,
+        rate_limiter: Option<RateLimiter>,
+        mfa_required_for_admin: bool,
+        oidc: Option<OidcClient>,
+        registration_enabled: bool,
+    ) -> Self {
+        let (stores, revoked_tokens, db) = match in_memory {
+            true => (
+                Stores::memory(InMemoryStore::default()),
+                RevokedTokenStore::memory(),
+                None,
+            ),
+            false => {
+                let db = TestDatabase::new().await;
+                (
+                    Stores::database(db.database().await),
+                    RevokedTokenStore::database(db.database().await),
+                    Some(db),
+                )
+            }
+        };
+        let state = Self::get_state(
+            stores.clone(),
+            revoked_tokens,
+            rate_limiter,
+            mfa_required_for_admin,
+            oidc,
+            registration_enabled,
+        );
         let settings = Config::default();
 
         let mut router = Router::new().nest("/api/v1", routes::api(state.clone()));
         router = router.nest("/", routes::web(&settings));
-        router = router.layer(Extension(db.database().await));
+
+        // Client address usually given by `into_make_service_with_connect_info`
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+        router = router.layer(Extension(ConnectInfo(addr)));
 
         let router = router.with_state(state);
 
-        Self { router, database: db }
+        Self {
+            router,
+            stores,
+            database: db,
+        }
+    }
+
+    /// Rate limiter with an in-memory store
+    fn rate_limiter() -> RateLimiter {
+        RateLimiter {
+            store: Arc::new(MemoryStore::default()),
+            config: RateLimiterConfig {
+                prefix: String::from("axum_test_"),
+                strategy: RateLimitStrategy::FixedWindow,
+                failure_mode: RateLimiterFailureMode::Open,
+                requests_by_second: -1,
+                expire_in_seconds: 60,
+                white_list: String::new(),
+            },
+        }
     }
 
     #[allow(unused)]
@@ -61,24 +194,53 @@ impl TestAppBuilder {
 
         Self {
             router: self.router.layer(layers),
-            database: self.database,
+            ..self
         }
     }
 
-    fn get_state() -> SharedState {
-        let jwt_secret_key = "mysecretjwtkey";
+    fn get_state(
+        stores: Stores,
+        revoked_tokens: RevokedTokenStore,
+        rate_limiter: Option<RateLimiter>,
+        mfa_required_for_admin: bool,
+        oidc: Option<OidcClient>,
+        registration_enabled: bool,
+    ) -> SharedState {
         let state = State {
             config: ConfigState {
-                jwt_encoding_key: EncodingKey::from_secret(jwt_secret_key.as_bytes()),
-                jwt_decoding_key: DecodingKey::from_secret(jwt_secret_key.as_bytes()),
+                jwt_keys: JwtKeys::from_secret("main", "mysecretjwtkey"),
                 jwt_lifetime: 1025,
+                jwt_refresh_lifetime: 24,
+                password_hasher: PasswordHasher::default(),
+                cursor_secret_key: String::from("mysecretcursorkey"),
                 smtp_host: String::from("127.0.0.1"),
                 smtp_port: 1025,
                 smtp_timeout: 30,
                 forgotten_password_expiration_duration: 1,
                 forgotten_password_base_url: String::from("http://localhost"),
                 forgotten_password_email_from: String::from("contact@test.com"),
+                forgotten_password_cooldown: 60,
+                email_from: String::from("contact@test.com"),
+                email_locale: String::from("en"),
+                registration_enabled,
+                registration_rate_limit: 100,
+                email_verification_base_url: String::from("http://localhost/verify-email"),
+                email_verification_lifetime: 24,
+                email_change_base_url: String::from("http://localhost/verify-email-change"),
+                login_max_failures: 3,
+                login_ip_max_failures: 10,
+                login_failures_window: 900,
+                login_lockout_duration: 900,
+                login_delay: 0,
+                login_unlock_base_url: String::from("http://localhost"),
+                mfa_required_for_admin,
+                mfa_challenge_lifetime: 300,
+                oidc_authorization_lifetime: 600,
             },
+            stores,
+            revoked_tokens,
+            rate_limiter,
+            oidc,
         };
 
         SharedState::new(state)
@@ -87,15 +249,203 @@ impl TestAppBuilder {
     pub fn build(self) -> TestApp {
         TestApp {
             router: self.router,
+            stores: self.stores,
             database: self.database,
         }
     }
 }
 
+/// Client ID registered in the test identity provider
+const IDP_CLIENT_ID: &str = "axum-test";
+
+/// Client secret registered in the test identity provider
+const IDP_CLIENT_SECRET: &str = "axum-test-secret";
+
+/// Redirect URL registered in the test identity provider
+const IDP_REDIRECT_URL: &str = "http://localhost/api/v1/oidc/callback";
+
+/// User logged in the test identity provider
+#[derive(Debug, Clone)]
+pub struct TestIdpUser {
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+}
+
+/// Authorization code waiting to be exchanged
+#[derive(Debug, Clone)]
+struct TestIdpCode {
+    user: TestIdpUser,
+    nonce: String,
+    code_challenge: String,
+    redirect_uri: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TestIdpTokenRequest {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    code_verifier: String,
+}
+
+/// Local OpenID Connect provider started on a random port.
+///
+/// The login page is not served: `authorize` simulates a user who logs in and accepts the authorization request.
+/// The discovery document, the JWKS and the token endpoint (with PKCE verification) are served over HTTP.
+#[derive(Clone)]
+pub struct TestIdentityProvider {
+    pub issuer: String,
+    keys: JwtKeys,
+    codes: Arc<Mutex<HashMap<String, TestIdpCode>>>,
+}
+
+impl TestIdentityProvider {
+    /// Start the provider
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let provider = Self {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            keys: JwtKeys::from_pem(
+                Algorithm::RS256,
+                "idp",
+                include_bytes!("keys/rsa_private.pem"),
+                include_bytes!("keys/rsa_public.pem"),
+            )
+            .unwrap(),
+            codes: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(Self::discovery))
+            .route("/jwks", get(Self::jwks))
+            .route("/token", post(Self::token))
+            .with_state(provider.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        provider
+    }
+
+    /// Client of the application
+    pub fn client(&self) -> OidcClient {
+        OidcClient::new(OidcConfig {
+            issuer_url: self.issuer.clone(),
+            client_id: IDP_CLIENT_ID.to_owned(),
+            client_secret: IDP_CLIENT_SECRET.to_owned(),
+            redirect_url: IDP_REDIRECT_URL.to_owned(),
+            scopes: String::from("openid email profile"),
+        })
+        .unwrap()
+    }
+
+    /// Log in a user with the authorization URL returned by the application and return the code and the state
+    /// sent back to the application callback
+    pub fn authorize(&self, authorization_url: &str, user: TestIdpUser) -> (String, String) {
+        let url = Url::parse(authorization_url).expect("invalid authorization URL");
+        assert_eq!(url.origin().ascii_serialization(), self.issuer);
+        assert_eq!(url.path(), "/authorize");
+
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], IDP_CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(params["scope"].split(' ').any(|scope| scope == "openid"));
+
+        let code = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        self.codes.lock().unwrap().insert(
+            code.clone(),
+            TestIdpCode {
+                user,
+                nonce: params["nonce"].clone(),
+                code_challenge: params["code_challenge"].clone(),
+                redirect_uri: params["redirect_uri"].clone(),
+            },
+        );
+
+        (code, params["state"].clone())
+    }
+
+    async fn discovery(AxumState(provider): AxumState<Self>) -> impl IntoResponse {
+        Json(serde_json::json!({
+            "issuer": provider.issuer,
+            "authorization_endpoint": format!("{}/authorize", provider.issuer),
+            "token_endpoint": format!("{}/token", provider.issuer),
+            "jwks_uri": format!("{}/jwks", provider.issuer),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        }))
+    }
+
+    async fn jwks(AxumState(provider): AxumState<Self>) -> impl IntoResponse {
+        Json(provider.keys.jwks().clone())
+    }
+
+    async fn token(
+        AxumState(provider): AxumState<Self>,
+        headers: HeaderMap,
+        Form(request): Form<TestIdpTokenRequest>,
+    ) -> impl IntoResponse {
+        let invalid_grant = (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid_grant" })),
+        );
+
+        let credentials = format!(
+            "Basic {}",
+            STANDARD.encode(format!("{IDP_CLIENT_ID}:{IDP_CLIENT_SECRET}"))
+        );
+        if headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok()) != Some(credentials.as_str()) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "invalid_client" })),
+            );
+        }
+
+        // A code can only be used once
+        let code = match provider.codes.lock().unwrap().remove(&request.code) {
+            Some(code) => code,
+            None => return invalid_grant,
+        };
+        if request.grant_type != "authorization_code"
+            || request.redirect_uri != code.redirect_uri
+            || Pkce::challenge(&request.code_verifier) != code.code_challenge
+        {
+            return invalid_grant;
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = serde_json::json!({
+            "iss": provider.issuer,
+            "sub": code.user.subject,
+            "aud": IDP_CLIENT_ID,
+            "exp": now + 300,
+            "iat": now,
+            "nonce": code.nonce,
+            "email": code.user.email,
+            "email_verified": code.user.email_verified,
+        });
+        let id_token = jsonwebtoken::encode(&provider.keys.header(), &claims, provider.keys.encoding_key()).unwrap();
+
+        (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "access_token": Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
+                "token_type": "Bearer",
+                "expires_in": 300,
+                "id_token": id_token,
+            })),
+        )
+    }
+}
+
+/// Database of a test, with a random name.
+///
+/// The backend is selected by the scheme of `DATABASE_URL` (a temporary SQLite file is used if it is not set).
 #[derive(Debug)]
 pub struct TestDatabase {
     url: String,
-    pool: MySqlPool,
+    database: Database,
 }
 
 /// Sets up a new DB for running tests with.
@@ -103,20 +453,46 @@ impl TestDatabase {
     pub async fn new() -> Self {
         let db_url = Self::url();
 
-        Self::create_database(&db_url).await;
-        Self::run_migrations(&db_url).await;
+        let database = match DatabaseBackend::from_url(&db_url).expect("unsupported DATABASE_URL scheme") {
+            DatabaseBackend::MySql => {
+                Self::create_mysql_database(&db_url).await;
+                Database::MySql(MySqlPool::connect(&db_url).await.unwrap())
+            }
+            DatabaseBackend::Postgres => {
+                Self::create_postgres_database(&db_url).await;
+                Database::Postgres(PgPool::connect(&db_url).await.unwrap())
+            }
+            DatabaseBackend::Sqlite => {
+                let options = SqliteConnectOptions::from_str(&db_url).unwrap().create_if_missing(true);
+                Database::Sqlite(SqlitePool::connect_with(options).await.unwrap())
+            }
+        };
 
-        let pool = MySqlPool::connect(&db_url).await.unwrap();
+        // Run the migrations
+        database.migrate().await.expect("Failed to migrate the database");
 
-        Self { url: db_url, pool }
+        Self { url: db_url, database }
     }
 
-    pub async fn database(&self) -> MySqlPool {
-        self.pool.clone()
+    pub async fn database(&self) -> Database {
+        self.database.clone()
     }
 
     /// Drop database after the test
     pub async fn drop_database(&self) {
+        match self.database.backend() {
+            DatabaseBackend::MySql => self.drop_mysql_database().await,
+            DatabaseBackend::Postgres => self.drop_postgres_database().await,
+            DatabaseBackend::Sqlite => {
+                let path = self.url.trim_start_matches("sqlite://");
+                for suffix in ["", "-wal", "-shm"] {
+                    let _ = std::fs::remove_file(format!("{path}{suffix}"));
+                }
+            }
+        }
+    }
+
+    async fn drop_mysql_database(&self) {
         let (_conn, db_name) = Self::parse_url(&self.url);
 
         let pool = MySqlPoolOptions::new()
@@ -150,15 +526,40 @@ impl TestDatabase {
             .expect("error when dropping database");
     }
 
+    async fn drop_postgres_database(&self) {
+        let (conn, db_name) = Self::parse_url(&self.url);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect(&format!("{conn}postgres"))
+            .await
+            .expect("error during PostgreSQL pool creation");
+
+        // Connections of the test are closed by `WITH (FORCE)`
+        let sql = format!(r#"DROP DATABASE "{db_name}" WITH (FORCE)"#);
+        sqlx::query::<Postgres>(&sql)
+            .execute(&pool)
+            .await
+            .expect("error when dropping database");
+    }
+
     /// Generate url with a random database name
     fn url() -> String {
         dotenvy::dotenv().ok();
 
         // Set up the database per tests
         let suffix: String = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-        let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL missing from environment.");
 
-        format!("{}_{}", db_url, suffix)
+        match std::env::var("DATABASE_URL") {
+            Ok(db_url) if DatabaseBackend::from_url(&db_url) != Some(DatabaseBackend::Sqlite) => {
+                format!("{}_{}", db_url, suffix)
+            }
+            _ => {
+                let path = std::env::temp_dir().join(format!("axum_test_{}.db", suffix.to_lowercase()));
+                format!("sqlite://{}", path.display())
+            }
+        }
     }
 
     /// Parse database URL and return the database name in a separate variable
@@ -170,8 +571,8 @@ impl TestDatabase {
         (conn, name)
     }
 
-    /// Create the test database
-    async fn create_database(url: &str) {
+    /// Create the MySQL test database
+    async fn create_mysql_database(url: &str) {
         let (conn, db_name) = Self::parse_url(url);
 
         let mut pool = MySqlConnection::connect(conn).await.unwrap();
@@ -180,18 +581,14 @@ impl TestDatabase {
         sqlx::query::<MySql>(&sql).execute(&mut pool).await.unwrap();
     }
 
-    /// Launch migrations
-    async fn run_migrations(url: &str) {
+    /// Create the PostgreSQL test database
+    async fn create_postgres_database(url: &str) {
         let (conn, db_name) = Self::parse_url(url);
-        let mut pool = MySqlConnection::connect(&format!("{}/{}", conn, db_name))
-            .await
-            .unwrap();
 
-        // Run the migrations
-        sqlx::migrate!("./migrations")
-            .run(&mut pool)
-            .await
-            .expect("Failed to migrate the database");
+        let mut pool = PgConnection::connect(&format!("{conn}postgres")).await.unwrap();
+
+        let sql = format!(r#"CREATE DATABASE "{}""#, &db_name);
+        sqlx::query::<Postgres>(&sql).execute(&mut pool).await.unwrap();
     }
 }
 
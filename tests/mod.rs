@@ -1,3 +1,4 @@
 mod api;
 mod helper;
+mod rate_limiter;
 mod web;
@@ -0,0 +1,126 @@
+//! Rate limiter stores and strategies tests
+//!
+//! Redis store tests are ignored by default: they need a Redis server (see `REDIS_URL`) and are run with `--ignored`.
+
+use axum_boilerplate::layers::rate_limiter::{
+    store::{MemoryStore, RateLimiterStore, RedisStore},
+    strategy::RateLimitStrategy,
+};
+use r2d2::Pool;
+use redis::Client;
+use std::sync::Arc;
+use std::thread;
+use uuid::Uuid;
+
+const STRATEGIES: [RateLimitStrategy; 3] = [
+    RateLimitStrategy::FixedWindow,
+    RateLimitStrategy::SlidingWindow,
+    RateLimitStrategy::TokenBucket,
+];
+
+/// Redis store
+fn redis_store() -> Arc<dyn RateLimiterStore> {
+    dotenvy::dotenv().ok();
+    let url = std::env::var("REDIS_URL").expect("REDIS_URL missing from environment.");
+    let client = Client::open(url).expect("invalid Redis URL");
+    let pool = Pool::builder()
+        .max_size(20)
+        .build(client)
+        .expect("error during Redis pool creation");
+
+    Arc::new(RedisStore::new(pool))
+}
+
+/// Unique key for each test
+fn key(strategy: RateLimitStrategy) -> String {
+    format!("axum_test_rl_{}{}", strategy.key_prefix(), Uuid::new_v4())
+}
+
+/// Requests are rejected once the limit is reached
+fn assert_limit(store: Arc<dyn RateLimiterStore>) {
+    for strategy in STRATEGIES {
+        let key = key(strategy);
+
+        for i in 0..5 {
+            let result = store.check(strategy, &key, 5, 60).unwrap();
+            assert_eq!(result.remaining, 4 - i, "{strategy:?}");
+            assert!(result.reset > 0 && result.reset <= 60, "{strategy:?}");
+        }
+
+        let result = store.check(strategy, &key, 5, 60).unwrap();
+        assert_eq!(result.remaining, -1, "{strategy:?}");
+        assert!(result.reset > 0 && result.reset <= 60, "{strategy:?}");
+    }
+}
+
+/// Concurrent requests never exceed the limit
+fn assert_concurrency(store: Arc<dyn RateLimiterStore>) {
+    let limit = 30;
+
+    for strategy in STRATEGIES {
+        let key = key(strategy);
+
+        // 20 clients send 5 requests at the same time
+        let handles: Vec<_> = (0..20)
+            .map(|_| {
+                let store = store.clone();
+                let key = key.clone();
+
+                thread::spawn(move || {
+                    (0..5)
+                        .filter(|_| store.check(strategy, &key, limit, 60).unwrap().remaining >= 0)
+                        .count()
+                })
+            })
+            .collect();
+
+        let accepted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(accepted, limit as usize, "{strategy:?}");
+    }
+}
+
+/// 2 tokens are refilled every second
+fn assert_token_bucket_refill(store: Arc<dyn RateLimiterStore>) {
+    let strategy = RateLimitStrategy::TokenBucket;
+    let key = key(strategy);
+
+    assert_eq!(store.check(strategy, &key, 2, 1).unwrap().remaining, 1);
+    assert_eq!(store.check(strategy, &key, 2, 1).unwrap().remaining, 0);
+    assert_eq!(store.check(strategy, &key, 2, 1).unwrap().remaining, -1);
+
+    thread::sleep(std::time::Duration::from_millis(600));
+    assert!(store.check(strategy, &key, 2, 1).unwrap().remaining >= 0);
+}
+
+#[test]
+#[ignore = "needs a Redis server (REDIS_URL)"]
+fn test_redis_store_limit() {
+    assert_limit(redis_store());
+}
+
+#[test]
+#[ignore = "needs a Redis server (REDIS_URL)"]
+fn test_redis_store_concurrency() {
+    assert_concurrency(redis_store());
+}
+
+#[test]
+#[ignore = "needs a Redis server (REDIS_URL)"]
+fn test_redis_store_token_bucket_refill() {
+    assert_token_bucket_refill(redis_store());
+}
+
+#[test]
+fn test_memory_store_limit() {
+    assert_limit(Arc::new(MemoryStore::default()));
+}
+
+#[test]
+fn test_memory_store_concurrency() {
+    assert_concurrency(Arc::new(MemoryStore::default()));
+}
+
+#[test]
+fn test_memory_store_token_bucket_refill() {
+    assert_token_bucket_refill(Arc::new(MemoryStore::default()));
+}
@@ -1,6 +1,7 @@
 use super::helper::TestAppBuilder;
 use axum::http::StatusCode;
 use axum::{body::Body, http::Request};
+use http_body_util::BodyExt;
 use tower::ServiceExt;
 
 #[tokio::test]
@@ -13,3 +14,24 @@ async fn test_health_check() {
 
     assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_jwks() {
+    let router = TestAppBuilder::new().await.build().router;
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri("/.well-known/jwks.json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // No public key with HS512
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body, serde_json::json!({ "keys": [] }));
+}